@@ -0,0 +1,42 @@
+use std::time::SystemTime;
+use certs::{verify_certificate_chain, KeyPurpose};
+use cose::{decode_cose_sign, HEADER_KID};
+use policy::{evaluate_cose_sign, VerificationPolicy, VerificationReport};
+use serialize::CborType;
+use verify::VerifyError;
+
+/// Returns the intermediate certificates carried in the kid header of the COSE_Sign body. In this
+/// profile the body's kid is an array of DER certificates rather than a key identifier. It may be
+/// absent if the end-entity certificates are issued directly by the root.
+fn intermediates(body_kid: Option<&CborType>) -> Result<Vec<&[u8]>, VerifyError> {
+    let certs = match body_kid {
+        Some(CborType::Arr(certs)) => certs,
+        Some(_) => return Err(VerifyError::DecodingCOSEFailed),
+        None => return Ok(Vec::new()),
+    };
+    let mut intermediates = Vec::with_capacity(certs.len());
+    for cert in certs {
+        match *cert {
+            CborType::BStr(ref cert) => intermediates.push(cert.as_slice()),
+            _ => return Err(VerifyError::DecodingCOSEFailed),
+        }
+    }
+    Ok(intermediates)
+}
+
+/// Verifies a COSE_Sign in the profile used to sign Firefox add-ons. Each COSE_Signature's kid is
+/// the DER encoding of the signer's end-entity certificate, and the kid header of the COSE_Sign
+/// body is an array of DER intermediate certificates. Each end-entity certificate must chain to the
+/// given root and be valid for object signing now; its key is then used to verify the signature.
+/// As with verify_cose_sign, the outcome for each signature is recorded in the returned report.
+pub fn verify_addon_signature(cose_sign: &[u8], detached_payload: Option<&[u8]>, root: &[u8],
+                              policy: VerificationPolicy)
+                              -> Result<VerificationReport, VerifyError> {
+    let cose_sign = decode_cose_sign(cose_sign, detached_payload)?;
+    let intermediates = intermediates(cose_sign.headers.get(HEADER_KID))?;
+    Ok(evaluate_cose_sign(&cose_sign, policy, |cose_signature| {
+        let end_entity = cose_signature.headers.kid()?.ok_or(VerifyError::UnknownKeyId)?;
+        verify_certificate_chain(end_entity, &intermediates, &[root], SystemTime::now(),
+                                 KeyPurpose::CodeSigning)
+    }))
+}
@@ -0,0 +1,134 @@
+use std::mem;
+use std::os::raw;
+use std::slice;
+use nss::{secure_compare, with_sym_key, CkAesCtrParams, CkAttributeType, CkMechanismType,
+          SECItem, CKA_ENCRYPT, CKM_AES_CBC, CKM_AES_CTR, SEC_SUCCESS, PK11_Encrypt};
+use verify::VerifyError;
+
+// AES-CCM (RFC 3610), which NSS does not implement, built from AES-CBC for the CBC-MAC and AES-CTR
+// for the encryption. The nonce is 15 - L bytes long, where L is the size of the length field.
+
+const BLOCK_LENGTH: usize = 16;
+
+/// Encrypts the input with AES in the given mode (CBC or CTR) and the given parameters.
+fn aes_encrypt(mechanism: CkMechanismType, params: &SECItem, key: &[u8], input: &[u8])
+               -> Result<Vec<u8>, VerifyError> {
+    if input.len() > raw::c_uint::MAX as usize {
+        return Err(VerifyError::InputTooLarge);
+    }
+    let mut output = vec![0; input.len()];
+    let mut written: raw::c_uint = 0;
+    with_sym_key(mechanism, CKA_ENCRYPT, key, |sym_key| {
+        let result = unsafe {
+            PK11_Encrypt(sym_key, mechanism, params, output.as_mut_ptr(), &mut written,
+                         input.len() as raw::c_uint, input.as_ptr(), input.len() as raw::c_uint)
+        };
+        match result {
+            SEC_SUCCESS => Ok(()),
+            _ => Err(VerifyError::LibraryFailure),
+        }
+    })?;
+    output.truncate(written as usize);
+    Ok(output)
+}
+
+/// Appends zeros to the data up to a multiple of the block length.
+fn pad(data: &mut Vec<u8>) {
+    let padded_length = data.len().div_ceil(BLOCK_LENGTH) * BLOCK_LENGTH;
+    data.resize(padded_length, 0);
+}
+
+/// Returns a block consisting of the flags, the nonce and the value in the length field.
+fn block(flags: u8, nonce: &[u8], value: usize) -> [u8; BLOCK_LENGTH] {
+    let mut block = [0; BLOCK_LENGTH];
+    block[0] = flags;
+    block[1..1 + nonce.len()].copy_from_slice(nonce);
+    let value_bytes = (value as u64).to_be_bytes();
+    let length_field_length = BLOCK_LENGTH - 1 - nonce.len();
+    let skipped = value_bytes.len().saturating_sub(length_field_length);
+    block[BLOCK_LENGTH - value_bytes.len() + skipped..].copy_from_slice(&value_bytes[skipped..]);
+    block
+}
+
+/// Computes the CBC-MAC of the message and the additional data, truncated to the tag length.
+fn cbc_mac(key: &[u8], nonce: &[u8], aad: &[u8], message: &[u8], tag_length: usize)
+           -> Result<Vec<u8>, VerifyError> {
+    let length_field_length = BLOCK_LENGTH - 1 - nonce.len();
+    let flags = if aad.is_empty() { 0 } else { 0x40 } | (((tag_length - 2) / 2) << 3) as u8 |
+                (length_field_length - 1) as u8;
+    let mut input = block(flags, nonce, message.len()).to_vec();
+    if !aad.is_empty() {
+        if aad.len() < 0xff00 {
+            input.extend_from_slice(&(aad.len() as u16).to_be_bytes());
+        } else if aad.len() <= u32::MAX as usize {
+            input.extend_from_slice(&[0xff, 0xfe]);
+            input.extend_from_slice(&(aad.len() as u32).to_be_bytes());
+        } else {
+            input.extend_from_slice(&[0xff, 0xff]);
+            input.extend_from_slice(&(aad.len() as u64).to_be_bytes());
+        }
+        input.extend_from_slice(aad);
+        pad(&mut input);
+    }
+    input.extend_from_slice(message);
+    pad(&mut input);
+    let iv = [0; BLOCK_LENGTH];
+    let iv_item = SECItem::maybe_new(&iv)?;
+    let output = aes_encrypt(CKM_AES_CBC, &iv_item, key, &input)?;
+    Ok(output[output.len() - BLOCK_LENGTH..][..tag_length].to_vec())
+}
+
+/// Applies the key stream to the input, returning the first block of the key stream, which
+/// encrypts the tag, and the output.
+fn ctr(key: &[u8], nonce: &[u8], input: &[u8]) -> Result<(Vec<u8>, Vec<u8>), VerifyError> {
+    let length_field_length = BLOCK_LENGTH - 1 - nonce.len();
+    let params = CkAesCtrParams::new(length_field_length * 8,
+                                     block((length_field_length - 1) as u8, nonce, 0));
+    let params_ptr: *const CkAesCtrParams = &params;
+    let params_bytes = unsafe {
+        slice::from_raw_parts(params_ptr as *const u8, mem::size_of::<CkAesCtrParams>())
+    };
+    let params_item = SECItem::maybe_new(params_bytes)?;
+    let mut counter_input = vec![0; BLOCK_LENGTH];
+    counter_input.extend_from_slice(input);
+    let mut output = aes_encrypt(CKM_AES_CTR, &params_item, key, &counter_input)?;
+    let output_tail = output.split_off(BLOCK_LENGTH);
+    Ok((output, output_tail))
+}
+
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    a.iter().zip(b).map(|(a, b)| a ^ b).collect()
+}
+
+/// Encrypts (with CKA_ENCRYPT) the input and appends the tag, or decrypts (with CKA_DECRYPT) the
+/// input with an appended tag after checking the tag. The caller checks the lengths of the key,
+/// nonce and tag and that the length of the message fits into the length field.
+pub fn aes_ccm(operation: CkAttributeType, key: &[u8], nonce: &[u8], aad: &[u8], input: &[u8],
+               tag_length: usize) -> Result<Vec<u8>, VerifyError> {
+    if operation == CKA_ENCRYPT {
+        let tag = cbc_mac(key, nonce, aad, input, tag_length)?;
+        let (tag_key_stream, mut output) = ctr(key, nonce, input)?;
+        output.extend(xor(&tag, &tag_key_stream));
+        Ok(output)
+    } else {
+        let (ciphertext, encrypted_tag) = input.split_at(input.len() - tag_length);
+        let (tag_key_stream, message) = ctr(key, nonce, ciphertext)?;
+        let tag = cbc_mac(key, nonce, aad, &message, tag_length)?;
+        if !secure_compare(&xor(&tag, &tag_key_stream), encrypted_tag) {
+            return Err(VerifyError::DecryptionFailed);
+        }
+        Ok(message)
+    }
+}
+
+#[test]
+fn test_block() {
+    let nonce = [0xaa; 13];
+    let expected = [0x19, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa,
+                    0xaa, 0x01, 0x02];
+    assert_eq!(block(0x19, &nonce, 0x0102), expected);
+    let nonce = [0xaa; 7];
+    let expected = [0x07, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0x00, 0x00, 0x00, 0x00, 0x00,
+                    0x00, 0x00, 0x05];
+    assert_eq!(block(0x07, &nonce, 5), expected);
+}
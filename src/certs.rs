@@ -0,0 +1,157 @@
+use std::cmp;
+use std::ptr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use nss::{CertValInParam, CERTCertificate, CERTIFICATE_USAGE_EMAIL_SIGNER,
+          CERTIFICATE_USAGE_OBJECT_SIGNER, CERTIFICATE_USAGE_SSL_CLIENT,
+          CERTIFICATE_USAGE_SSL_SERVER, CERTIFICATE_USAGE_STATUS_RESPONDER, CERT_PI_DATE,
+          CERT_PI_REVOCATION_FLAGS, CERT_PI_TRUST_ANCHORS, PRErrorCode, PRTime, PR_FALSE, PR_TRUE,
+          SECCertificateUsage, SECItem, SECKEYPublicKey, SEC_ERROR_BAD_SIGNATURE,
+          SEC_ERROR_EXPIRED_CERTIFICATE, SEC_ERROR_EXPIRED_ISSUER_CERTIFICATE,
+          SEC_ERROR_INADEQUATE_CERT_TYPE, SEC_ERROR_INADEQUATE_KEY_USAGE, SEC_ERROR_UNKNOWN_ISSUER,
+          SEC_ERROR_UNTRUSTED_CERT, SEC_ERROR_UNTRUSTED_ISSUER, SEC_SUCCESS, CERT_AddCertToListTail,
+          CERT_DestroyCertList, CERT_DestroyCertificate, CERT_DupCertificate, CERT_ExtractPublicKey,
+          CERT_GetClassicOCSPDisabledPolicy, CERT_GetDefaultCertDB, CERT_NewCertList,
+          CERT_NewTempCertificate, CERT_PKIXVerifyCert, PORT_GetError, SECKEY_DestroyPublicKey,
+          encode_spki};
+use verify::VerifyError;
+
+/// An owned reference to an NSS temporary certificate. Temporary certificates are visible to NSS
+/// path building for as long as a reference to them is held.
+pub struct Certificate {
+    cert: *const CERTCertificate,
+}
+
+impl Certificate {
+    /// Decodes the given DER certificate.
+    pub fn from_der(der: &[u8]) -> Result<Certificate, VerifyError> {
+        let der_item = SECItem::maybe_new(der)?;
+        let cert = unsafe {
+            CERT_NewTempCertificate(CERT_GetDefaultCertDB(), &der_item, ptr::null(), PR_FALSE,
+                                    PR_TRUE)
+        };
+        if cert.is_null() {
+            return Err(VerifyError::DecodingCertificateFailed);
+        }
+        Ok(Certificate { cert })
+    }
+
+    /// Calls the given function with the certificate's public key. The key is destroyed afterwards.
+    pub fn with_public_key<T, F>(&self, f: F) -> Result<T, VerifyError>
+        where F: FnOnce(*const SECKEYPublicKey) -> Result<T, VerifyError>
+    {
+        let key = unsafe { CERT_ExtractPublicKey(self.cert) };
+        if key.is_null() {
+            return Err(VerifyError::DecodingSPKIFailed);
+        }
+        defer!(unsafe { SECKEY_DestroyPublicKey(key); });
+        f(key)
+    }
+
+    /// Returns the DER encoding of the certificate's subject public key info.
+    pub fn spki(&self) -> Result<Vec<u8>, VerifyError> {
+        self.with_public_key(encode_spki)
+    }
+}
+
+impl Drop for Certificate {
+    fn drop(&mut self) {
+        unsafe { CERT_DestroyCertificate(self.cert); }
+    }
+}
+
+/// The extended key usage an end-entity certificate is required to be valid for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeyPurpose {
+    ClientAuth,
+    CodeSigning,
+    EmailProtection,
+    OCSPSigning,
+    ServerAuth,
+}
+
+impl KeyPurpose {
+    /// NSS expresses the required extended key usage as a certificate usage.
+    fn certificate_usage(self) -> SECCertificateUsage {
+        match self {
+            KeyPurpose::ClientAuth => CERTIFICATE_USAGE_SSL_CLIENT,
+            KeyPurpose::CodeSigning => CERTIFICATE_USAGE_OBJECT_SIGNER,
+            KeyPurpose::EmailProtection => CERTIFICATE_USAGE_EMAIL_SIGNER,
+            KeyPurpose::OCSPSigning => CERTIFICATE_USAGE_STATUS_RESPONDER,
+            KeyPurpose::ServerAuth => CERTIFICATE_USAGE_SSL_SERVER,
+        }
+    }
+}
+
+/// Converts the given time to a PRTime. Times before the epoch are clamped to it.
+fn pr_time(time: SystemTime) -> PRTime {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => {
+            let seconds = cmp::min(duration.as_secs(), (PRTime::MAX / 1_000_000) as u64 - 1);
+            seconds as PRTime * 1_000_000 + PRTime::from(duration.subsec_micros())
+        }
+        Err(_) => 0,
+    }
+}
+
+/// Maps the error NSS reports for a failed path validation to the corresponding VerifyError.
+fn chain_error(error: PRErrorCode) -> VerifyError {
+    match error {
+        SEC_ERROR_EXPIRED_CERTIFICATE | SEC_ERROR_EXPIRED_ISSUER_CERTIFICATE => {
+            VerifyError::CertificateExpired
+        }
+        SEC_ERROR_UNKNOWN_ISSUER | SEC_ERROR_UNTRUSTED_ISSUER | SEC_ERROR_UNTRUSTED_CERT => {
+            VerifyError::UntrustedCertificate
+        }
+        SEC_ERROR_BAD_SIGNATURE => VerifyError::CertificateSignatureInvalid,
+        SEC_ERROR_INADEQUATE_KEY_USAGE | SEC_ERROR_INADEQUATE_CERT_TYPE => {
+            VerifyError::InadequateCertificateUsage
+        }
+        _ => VerifyError::CertificateChainInvalid,
+    }
+}
+
+/// Given an end-entity certificate, any number of intermediate certificates (in no particular
+/// order), and any number of trust anchors, validates that the end-entity certificate chains to one
+/// of the trust anchors and is valid for the given purpose at the given time. Returns the
+/// end-entity certificate's subject public key info on success. Revocation is not checked.
+/// Failures are reported as CertificateExpired (which includes certificates that are not yet
+/// valid), UntrustedCertificate, CertificateSignatureInvalid, InadequateCertificateUsage, or
+/// CertificateChainInvalid for any other problem with the path.
+pub fn verify_certificate_chain(end_entity: &[u8], intermediates: &[&[u8]],
+                                trust_anchors: &[&[u8]], time: SystemTime, purpose: KeyPurpose)
+                                -> Result<Vec<u8>, VerifyError> {
+    let end_entity = Certificate::from_der(end_entity)?;
+    // These have to be kept alive until path building is done so NSS can find them.
+    let mut _intermediates = Vec::with_capacity(intermediates.len());
+    for intermediate in intermediates {
+        _intermediates.push(Certificate::from_der(intermediate)?);
+    }
+    let trust_anchor_list = unsafe { CERT_NewCertList() };
+    if trust_anchor_list.is_null() {
+        return Err(VerifyError::LibraryFailure);
+    }
+    defer!(unsafe { CERT_DestroyCertList(trust_anchor_list); });
+    for trust_anchor in trust_anchors {
+        let trust_anchor = Certificate::from_der(trust_anchor)?;
+        let reference = unsafe { CERT_DupCertificate(trust_anchor.cert) };
+        if unsafe { CERT_AddCertToListTail(trust_anchor_list, reference) } != SEC_SUCCESS {
+            unsafe { CERT_DestroyCertificate(reference); }
+            return Err(VerifyError::LibraryFailure);
+        }
+    }
+    let params = [
+        CertValInParam::new_pointer(CERT_PI_TRUST_ANCHORS, trust_anchor_list),
+        CertValInParam::new_pointer(CERT_PI_REVOCATION_FLAGS,
+                                    unsafe { CERT_GetClassicOCSPDisabledPolicy() }),
+        CertValInParam::new_time(CERT_PI_DATE, pr_time(time)),
+        CertValInParam::end(),
+    ];
+    let result = unsafe {
+        CERT_PKIXVerifyCert(end_entity.cert, purpose.certificate_usage(), params.as_ptr(),
+                            ptr::null_mut(), ptr::null())
+    };
+    if result != SEC_SUCCESS {
+        return Err(chain_error(unsafe { PORT_GetError() }));
+    }
+    end_entity.spki()
+}
@@ -0,0 +1,208 @@
+use cose::{decode_cose_sign1, Headers, HEADER_CONTENT_TYPE};
+use policy::{verify_cose_sign1, TrustedKey};
+use serialize::CborType;
+use verify::VerifyError;
+
+// Well-known entries of the IANA "CoAP Content-Formats" registry and their media types.
+const COAP_CONTENT_FORMATS: &[(u16, &str)] = &[
+    (0, "text/plain; charset=utf-8"),
+    (16, "application/cose; cose-type=\"cose-encrypt0\""),
+    (17, "application/cose; cose-type=\"cose-mac0\""),
+    (18, "application/cose; cose-type=\"cose-sign1\""),
+    (40, "application/link-format"),
+    (41, "application/xml"),
+    (42, "application/octet-stream"),
+    (47, "application/exi"),
+    (50, "application/json"),
+    (51, "application/json-patch+json"),
+    (52, "application/merge-patch+json"),
+    (60, "application/cbor"),
+    (61, "application/cwt"),
+    (62, "application/multipart-core"),
+    (63, "application/cbor-seq"),
+    (96, "application/cose; cose-type=\"cose-encrypt\""),
+    (97, "application/cose; cose-type=\"cose-mac\""),
+    (98, "application/cose; cose-type=\"cose-sign\""),
+    (101, "application/cose-key"),
+    (102, "application/cose-key-set"),
+    (110, "application/senml+json"),
+    (112, "application/senml+cbor"),
+    (256, "application/coap-group+json"),
+];
+
+/// The content type of a COSE payload (RFC 9052, Section 3.1), which is either a CoAP
+/// Content-Format or a media type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ContentType {
+    CoapContentFormat(u16),
+    /// A media type, possibly with parameters, such as "text/plain; charset=utf-8".
+    MediaType(String),
+}
+
+/// Lower-cases a media type and removes optional whitespace around its parameters, so that media
+/// types can be compared.
+fn normalize_media_type(media_type: &str) -> String {
+    media_type.split(';')
+              .map(|part| part.trim().to_ascii_lowercase())
+              .collect::<Vec<_>>()
+              .join(";")
+}
+
+impl ContentType {
+    /// Decodes the value of a content type header: an unsigned integer that fits in 16 bits, or a
+    /// text string that looks like a media type.
+    pub fn from_cbor(value: &CborType) -> Result<ContentType, VerifyError> {
+        match *value {
+            CborType::UInt(content_format) if content_format <= u16::MAX as u64 => {
+                Ok(ContentType::CoapContentFormat(content_format as u16))
+            }
+            CborType::TStr(ref media_type) if media_type.contains('/') => {
+                Ok(ContentType::MediaType(media_type.clone()))
+            }
+            _ => Err(VerifyError::DecodingCOSEFailed),
+        }
+    }
+
+    /// Returns the value of a content type header for this content type.
+    pub fn to_cbor(&self) -> CborType {
+        match *self {
+            ContentType::CoapContentFormat(content_format) => {
+                CborType::UInt(u64::from(content_format))
+            }
+            ContentType::MediaType(ref media_type) => CborType::TStr(media_type.clone()),
+        }
+    }
+
+    /// Returns the media type of this content type, if it is a media type or a well-known CoAP
+    /// Content-Format.
+    pub fn media_type(&self) -> Option<&str> {
+        match *self {
+            ContentType::CoapContentFormat(content_format) => {
+                COAP_CONTENT_FORMATS.iter()
+                                    .find(|&&(known_format, _)| known_format == content_format)
+                                    .map(|&(_, media_type)| media_type)
+            }
+            ContentType::MediaType(ref media_type) => Some(media_type),
+        }
+    }
+
+    /// Returns the CoAP Content-Format of this content type, if it is one or it is the media type
+    /// of a well-known one.
+    pub fn coap_content_format(&self) -> Option<u16> {
+        match *self {
+            ContentType::CoapContentFormat(content_format) => Some(content_format),
+            ContentType::MediaType(ref media_type) => {
+                let media_type = normalize_media_type(media_type);
+                COAP_CONTENT_FORMATS.iter()
+                                    .find(|&&(_, known_type)| {
+                                        normalize_media_type(known_type) == media_type
+                                    })
+                                    .map(|&(content_format, _)| content_format)
+            }
+        }
+    }
+
+    /// Whether the two content types are the same, in either form.
+    pub fn matches(&self, other: &ContentType) -> bool {
+        if let (Some(format), Some(other_format)) = (self.coap_content_format(),
+                                                     other.coap_content_format()) {
+            return format == other_format;
+        }
+        match (self.media_type(), other.media_type()) {
+            (Some(media_type), Some(other_media_type)) => {
+                normalize_media_type(media_type) == normalize_media_type(other_media_type)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Headers {
+    /// Returns the value of the content type header, if present.
+    pub fn content_type(&self) -> Result<Option<ContentType>, VerifyError> {
+        match self.get(HEADER_CONTENT_TYPE) {
+            Some(value) => Ok(Some(ContentType::from_cbor(value)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+type PayloadDecoder<T> = Box<dyn Fn(&[u8]) -> Result<T, VerifyError>>;
+
+/// Decoders for payloads, keyed by their content type.
+pub struct PayloadDecoders<T> {
+    decoders: Vec<(ContentType, PayloadDecoder<T>)>,
+}
+
+impl<T> Default for PayloadDecoders<T> {
+    fn default() -> PayloadDecoders<T> {
+        PayloadDecoders { decoders: Vec::new() }
+    }
+}
+
+impl<T> PayloadDecoders<T> {
+    pub fn new() -> PayloadDecoders<T> {
+        PayloadDecoders::default()
+    }
+
+    /// Registers a decoder for payloads of the given content type, in either form. A decoder
+    /// registered later for the same content type takes precedence.
+    pub fn register<F>(&mut self, content_type: ContentType, decoder: F)
+        where F: Fn(&[u8]) -> Result<T, VerifyError> + 'static
+    {
+        self.decoders.insert(0, (content_type, Box::new(decoder)));
+    }
+
+    /// Decodes a payload of the given content type. It is an error for there to be no decoder
+    /// for it.
+    pub fn decode(&self, content_type: &ContentType, payload: &[u8]) -> Result<T, VerifyError> {
+        match self.decoders.iter().find(|&(registered, _)| registered.matches(content_type)) {
+            Some((_, decoder)) => decoder(payload),
+            None => Err(VerifyError::UnsupportedContentType),
+        }
+    }
+}
+
+/// Verifies a COSE_Sign1 as verify_cose_sign1 does and decodes its payload with the decoder for the
+/// content type in its headers, which it must have.
+pub fn verify_cose_sign1_decoded<T>(cose_sign1: &[u8], detached_payload: Option<&[u8]>,
+                                    trusted_keys: &[TrustedKey], decoders: &PayloadDecoders<T>)
+                                    -> Result<T, VerifyError> {
+    let payload = verify_cose_sign1(cose_sign1, detached_payload, trusted_keys)?;
+    let headers = decode_cose_sign1(cose_sign1, detached_payload)?.headers;
+    let content_type = headers.content_type()?.ok_or(VerifyError::UnsupportedContentType)?;
+    decoders.decode(&content_type, &payload)
+}
+
+#[test]
+fn test_content_type() {
+    assert_eq!(ContentType::from_cbor(&CborType::UInt(60)),
+               Ok(ContentType::CoapContentFormat(60)));
+    assert_eq!(ContentType::from_cbor(&CborType::UInt(65536)),
+               Err(VerifyError::DecodingCOSEFailed));
+    assert_eq!(ContentType::from_cbor(&CborType::TStr(String::from("cbor"))),
+               Err(VerifyError::DecodingCOSEFailed));
+    assert_eq!(ContentType::CoapContentFormat(0).media_type(), Some("text/plain; charset=utf-8"));
+    assert_eq!(ContentType::CoapContentFormat(65000).media_type(), None);
+    assert_eq!(ContentType::MediaType(String::from("Text/Plain;charset=UTF-8"))
+                   .coap_content_format(),
+               Some(0));
+    assert!(ContentType::CoapContentFormat(50)
+                .matches(&ContentType::MediaType(String::from("application/json"))));
+    assert!(!ContentType::CoapContentFormat(50).matches(&ContentType::CoapContentFormat(60)));
+    assert!(ContentType::MediaType(String::from("application/x-firmware"))
+                .matches(&ContentType::MediaType(String::from("Application/X-Firmware"))));
+    assert!(!ContentType::CoapContentFormat(65000)
+                 .matches(&ContentType::MediaType(String::from("application/x-firmware"))));
+}
+
+#[test]
+fn test_payload_decoders() {
+    let mut decoders = PayloadDecoders::new();
+    decoders.register(ContentType::MediaType(String::from("application/octet-stream")),
+                      |payload: &[u8]| Ok(payload.len()));
+    decoders.register(ContentType::CoapContentFormat(42), |_: &[u8]| Ok(0));
+    assert_eq!(decoders.decode(&ContentType::CoapContentFormat(42), &[1, 2]), Ok(0));
+    assert_eq!(decoders.decode(&ContentType::CoapContentFormat(60), &[1, 2]),
+               Err(VerifyError::UnsupportedContentType));
+}
@@ -0,0 +1,268 @@
+use std::collections::BTreeMap;
+use decode::decode;
+use serialize::CborType;
+use verify::{SignatureAlgorithm, VerifyError};
+
+// See RFC 9052, Section 2 and the IANA "CBOR Tags" registry.
+pub const COSE_ENCRYPT0_TAG: u64 = 16;
+pub const COSE_MAC0_TAG: u64 = 17;
+pub const COSE_SIGN1_TAG: u64 = 18;
+pub const COSE_ENCRYPT_TAG: u64 = 96;
+pub const COSE_MAC_TAG: u64 = 97;
+pub const COSE_SIGN_TAG: u64 = 98;
+
+// See RFC 9052, Section 3.1 and the IANA "COSE Header Parameters" registry.
+pub const HEADER_ALG: i64 = 1;
+pub const HEADER_CONTENT_TYPE: i64 = 3;
+pub const HEADER_KID: i64 = 4;
+pub const HEADER_IV: i64 = 5;
+pub const HEADER_PARTIAL_IV: i64 = 6;
+
+/// Returns the CBOR representation of a header label.
+pub fn label(label: i64) -> CborType {
+    if label < 0 {
+        CborType::NInt(label)
+    } else {
+        CborType::UInt(label as u64)
+    }
+}
+
+/// The protected and unprotected header buckets of a COSE structure. The protected bucket is kept
+/// in its serialized form as well, because that is what gets signed.
+pub struct Headers {
+    pub protected_bytes: Vec<u8>,
+    pub protected: BTreeMap<CborType, CborType>,
+    pub unprotected: BTreeMap<CborType, CborType>,
+}
+
+impl Headers {
+    /// Decodes the protected bucket (a bstr wrapping a map, where an empty bstr means an empty map)
+    /// and the unprotected bucket (a map). A label may not appear in both buckets.
+    pub fn decode(protected: &CborType, unprotected: &CborType) -> Result<Headers, VerifyError> {
+        let protected_bytes = match *protected {
+            CborType::BStr(ref bytes) => bytes.clone(),
+            _ => return Err(VerifyError::DecodingCOSEFailed),
+        };
+        let protected = if protected_bytes.is_empty() {
+            BTreeMap::new()
+        } else {
+            match decode(&protected_bytes) {
+                Ok(CborType::Map(map)) => map,
+                Ok(_) => return Err(VerifyError::DecodingCOSEFailed),
+                Err(_) => return Err(VerifyError::DecodingCBORFailed),
+            }
+        };
+        let unprotected = match *unprotected {
+            CborType::Map(ref map) => map.clone(),
+            _ => return Err(VerifyError::DecodingCOSEFailed),
+        };
+        if protected.keys().any(|key| unprotected.contains_key(key)) {
+            return Err(VerifyError::DecodingCOSEFailed);
+        }
+        Ok(Headers {
+            protected_bytes,
+            protected,
+            unprotected,
+        })
+    }
+
+    /// Creates headers with the given buckets, serializing the protected one.
+    pub fn new(protected: BTreeMap<CborType, CborType>, unprotected: BTreeMap<CborType, CborType>)
+               -> Headers {
+        Headers {
+            protected_bytes: encode_protected(&protected),
+            protected,
+            unprotected,
+        }
+    }
+
+    /// Looks up the given label in the protected bucket and then in the unprotected bucket.
+    pub fn get(&self, header_label: i64) -> Option<&CborType> {
+        let key = label(header_label);
+        self.protected.get(&key).or_else(|| self.unprotected.get(&key))
+    }
+
+    /// Returns the value of the alg header. It is an error for it to be absent or to not be an
+    /// integer.
+    pub fn algorithm(&self) -> Result<i64, VerifyError> {
+        match self.get(HEADER_ALG) {
+            Some(&CborType::NInt(id)) => Ok(id),
+            Some(&CborType::UInt(id)) if id <= i64::MAX as u64 => Ok(id as i64),
+            _ => Err(VerifyError::DecodingCOSEFailed),
+        }
+    }
+
+    /// Returns the signature algorithm identified by the alg header.
+    pub fn signature_algorithm(&self) -> Result<SignatureAlgorithm, VerifyError> {
+        SignatureAlgorithm::from_cose_id(self.algorithm()?).ok_or(VerifyError::UnsupportedAlgorithm)
+    }
+
+    /// Returns the value of the kid header, if present.
+    pub fn kid(&self) -> Result<Option<&[u8]>, VerifyError> {
+        match self.get(HEADER_KID) {
+            Some(CborType::BStr(kid)) => Ok(Some(kid)),
+            Some(_) => Err(VerifyError::DecodingCOSEFailed),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Serializes a protected header bucket. An empty bucket is encoded as zero bytes rather than as
+/// an empty map, as RFC 9052, Section 3 recommends.
+pub fn encode_protected(protected: &BTreeMap<CborType, CborType>) -> Vec<u8> {
+    if protected.is_empty() {
+        Vec::new()
+    } else {
+        CborType::Map(protected.clone()).serialize()
+    }
+}
+
+/// Returns the serialized protected bucket of a structure that only identifies its algorithm.
+pub fn encode_protected_algorithm(algorithm: i64) -> Vec<u8> {
+    let mut protected = BTreeMap::new();
+    protected.insert(label(HEADER_ALG), label(algorithm));
+    encode_protected(&protected)
+}
+
+/// Returns an unprotected bucket holding the kid header, if there is one.
+pub fn unprotected_kid(kid: Option<&[u8]>) -> BTreeMap<CborType, CborType> {
+    let mut unprotected = BTreeMap::new();
+    if let Some(kid) = kid {
+        unprotected.insert(label(HEADER_KID), CborType::BStr(kid.to_vec()));
+    }
+    unprotected
+}
+
+/// Builds the serialized Sig_structure (RFC 9052, Section 4.4) that a signature is computed over.
+/// sign_protected is omitted for the "Signature1" context.
+pub fn sig_structure(context: &str, body_protected: &[u8], sign_protected: Option<&[u8]>,
+                     external_aad: &[u8], payload: &[u8]) -> Vec<u8> {
+    let mut elements = vec![CborType::TStr(String::from(context)),
+                            CborType::BStr(body_protected.to_vec())];
+    if let Some(sign_protected) = sign_protected {
+        elements.push(CborType::BStr(sign_protected.to_vec()));
+    }
+    elements.push(CborType::BStr(external_aad.to_vec()));
+    elements.push(CborType::BStr(payload.to_vec()));
+    CborType::Arr(elements).serialize()
+}
+
+/// A single COSE_Signature from a COSE_Sign, along with the Sig_structure it signs.
+pub struct CoseSignature {
+    pub headers: Headers,
+    pub signature: Vec<u8>,
+    pub to_verify: Vec<u8>,
+}
+
+/// A decoded COSE_Sign structure.
+pub struct CoseSign {
+    pub headers: Headers,
+    pub payload: Vec<u8>,
+    pub signatures: Vec<CoseSignature>,
+}
+
+/// A decoded COSE_Sign1 structure.
+pub struct CoseSign1 {
+    pub headers: Headers,
+    pub payload: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+/// Removes the given tag, if present. Anything else is left alone, since COSE structures may be
+/// untagged when the application knows what to expect.
+pub fn strip_tag(item: CborType, expected_tag: u64) -> Result<CborType, VerifyError> {
+    match item {
+        CborType::Tag(tag, value) => {
+            if tag != expected_tag {
+                return Err(VerifyError::DecodingCOSEFailed);
+            }
+            Ok(*value)
+        }
+        item => Ok(item),
+    }
+}
+
+/// Decodes the payload element of a COSE structure. A nil payload means it is detached and must
+/// be supplied by the caller. Supplying a payload when one is present in the structure is an
+/// error.
+pub fn decode_payload(payload: &CborType, detached_payload: Option<&[u8]>)
+                      -> Result<Vec<u8>, VerifyError> {
+    match (payload, detached_payload) {
+        (CborType::BStr(payload), None) => Ok(payload.clone()),
+        (&CborType::Null, Some(detached_payload)) => Ok(detached_payload.to_vec()),
+        (&CborType::Null, None) => Err(VerifyError::MissingPayload),
+        _ => Err(VerifyError::DecodingCOSEFailed),
+    }
+}
+
+/// Decodes a (possibly tagged) COSE_Sign structure:
+///
+/// COSE_Sign = [ protected: bstr, unprotected: map, payload: bstr / nil,
+///               signatures: [+ COSE_Signature] ]
+/// COSE_Signature = [ protected: bstr, unprotected: map, signature: bstr ]
+pub fn decode_cose_sign(cose_sign: &[u8], detached_payload: Option<&[u8]>)
+                        -> Result<CoseSign, VerifyError> {
+    let item = decode(cose_sign).map_err(|_| VerifyError::DecodingCBORFailed)?;
+    let elements = match strip_tag(item, COSE_SIGN_TAG)? {
+        CborType::Arr(elements) => elements,
+        _ => return Err(VerifyError::DecodingCOSEFailed),
+    };
+    if elements.len() != 4 {
+        return Err(VerifyError::DecodingCOSEFailed);
+    }
+    let headers = Headers::decode(&elements[0], &elements[1])?;
+    let payload = decode_payload(&elements[2], detached_payload)?;
+    let cose_signatures = match elements[3] {
+        CborType::Arr(ref cose_signatures) if !cose_signatures.is_empty() => cose_signatures,
+        _ => return Err(VerifyError::DecodingCOSEFailed),
+    };
+    let mut signatures = Vec::with_capacity(cose_signatures.len());
+    for cose_signature in cose_signatures {
+        let signature_elements = match *cose_signature {
+            CborType::Arr(ref signature_elements) if signature_elements.len() == 3 => {
+                signature_elements
+            }
+            _ => return Err(VerifyError::DecodingCOSEFailed),
+        };
+        let signature_headers = Headers::decode(&signature_elements[0], &signature_elements[1])?;
+        let signature = match signature_elements[2] {
+            CborType::BStr(ref signature) => signature.clone(),
+            _ => return Err(VerifyError::DecodingCOSEFailed),
+        };
+        let to_verify = sig_structure("Signature", &headers.protected_bytes,
+                                      Some(&signature_headers.protected_bytes), &[], &payload);
+        signatures.push(CoseSignature {
+            headers: signature_headers,
+            signature,
+            to_verify,
+        });
+    }
+    Ok(CoseSign {
+        headers,
+        payload,
+        signatures,
+    })
+}
+
+/// Decodes a (possibly tagged) COSE_Sign1 structure:
+///
+/// COSE_Sign1 = [ protected: bstr, unprotected: map, payload: bstr / nil, signature: bstr ]
+pub fn decode_cose_sign1(cose_sign1: &[u8], detached_payload: Option<&[u8]>)
+                         -> Result<CoseSign1, VerifyError> {
+    let item = decode(cose_sign1).map_err(|_| VerifyError::DecodingCBORFailed)?;
+    let elements = match strip_tag(item, COSE_SIGN1_TAG)? {
+        CborType::Arr(elements) if elements.len() == 4 => elements,
+        _ => return Err(VerifyError::DecodingCOSEFailed),
+    };
+    let headers = Headers::decode(&elements[0], &elements[1])?;
+    let payload = decode_payload(&elements[2], detached_payload)?;
+    let signature = match elements[3] {
+        CborType::BStr(ref signature) => signature.clone(),
+        _ => return Err(VerifyError::DecodingCOSEFailed),
+    };
+    Ok(CoseSign1 {
+        headers,
+        payload,
+        signature,
+    })
+}
@@ -0,0 +1,247 @@
+use cose::{decode_cose_sign, decode_cose_sign1, CoseSign, CoseSign1, Headers, COSE_SIGN1_TAG};
+use decode::decode;
+use policy::{SignerReport, TrustedKey};
+use serialize::CborType;
+use verify::{verify_signature, SignatureAlgorithm, VerifyError};
+
+// See RFC 9338, Section 3 and the IANA "COSE Header Parameters" registry.
+pub const HEADER_COUNTERSIGNATURE: i64 = 11;
+pub const HEADER_COUNTERSIGNATURE0: i64 = 12;
+
+/// The layer of a COSE_Sign or COSE_Sign1 that a countersignature is on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CountersignatureTarget {
+    /// The COSE_Sign or COSE_Sign1 itself.
+    Message,
+    /// The COSE_Signature with the given index in the signatures of a COSE_Sign.
+    Signature(usize),
+}
+
+/// The key that abbreviated countersignatures (label 12) are verified with. These carry no
+/// headers, so the algorithm and the key must be known from the context.
+pub struct AbbreviatedCountersigner<'a> {
+    pub algorithm: SignatureAlgorithm,
+    pub spki: &'a [u8],
+}
+
+/// The outcome of verifying one countersignature on the given layer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CountersignatureReport {
+    pub target: CountersignatureTarget,
+    pub abbreviated: bool,
+    pub signer: SignerReport,
+}
+
+/// Builds the serialized Countersign_structure (RFC 9338, Section 3.3) that a countersignature is
+/// computed over. sign_protected is omitted for abbreviated countersignatures. body_protected and
+/// payload are the first two bstr fields of the countersigned structure and other_fields are any
+/// further ones, so for a COSE_Signature the payload is its signature.
+///
+/// Countersign_structure = [ context: "CounterSignatureV2" / "CounterSignature0V2",
+///                           body_protected: bstr, ? sign_protected: bstr, external_aad: bstr,
+///                           payload: bstr, ? other_fields: [+ bstr] ]
+pub fn countersignature_structure(body_protected: &[u8], sign_protected: Option<&[u8]>,
+                                  external_aad: &[u8], payload: &[u8], other_fields: &[Vec<u8>])
+                                  -> Vec<u8> {
+    let context = if sign_protected.is_some() {
+        "CounterSignatureV2"
+    } else {
+        "CounterSignature0V2"
+    };
+    let mut elements = vec![CborType::TStr(String::from(context)),
+                            CborType::BStr(body_protected.to_vec())];
+    if let Some(sign_protected) = sign_protected {
+        elements.push(CborType::BStr(sign_protected.to_vec()));
+    }
+    elements.push(CborType::BStr(external_aad.to_vec()));
+    elements.push(CborType::BStr(payload.to_vec()));
+    if !other_fields.is_empty() {
+        elements.push(CborType::Arr(other_fields.iter()
+                                                .map(|field| CborType::BStr(field.clone()))
+                                                .collect()));
+    }
+    CborType::Arr(elements).serialize()
+}
+
+enum SignedMessage {
+    Sign(CoseSign),
+    Sign1(CoseSign1),
+}
+
+/// A layer that may be countersigned, with the fields a countersignature on it covers.
+struct Countersigned<'a> {
+    target: CountersignatureTarget,
+    headers: &'a Headers,
+    payload: &'a [u8],
+    other_fields: Vec<Vec<u8>>,
+}
+
+impl<'a> Countersigned<'a> {
+    fn to_be_signed(&self, sign_protected: Option<&[u8]>, external_aad: &[u8]) -> Vec<u8> {
+        countersignature_structure(&self.headers.protected_bytes, sign_protected, external_aad,
+                                   self.payload, &self.other_fields)
+    }
+}
+
+/// Decodes a COSE_Sign or a COSE_Sign1, telling them apart by their tag or, if untagged, by
+/// whether the last element is a signature or an array of signatures.
+fn decode_signed_message(message: &[u8], detached_payload: Option<&[u8]>)
+                         -> Result<SignedMessage, VerifyError> {
+    let is_sign1 = match decode(message) {
+        Ok(CborType::Tag(tag, _)) => tag == COSE_SIGN1_TAG,
+        Ok(CborType::Arr(ref elements)) => {
+            elements.len() == 4 && matches!(elements[3], CborType::BStr(_))
+        }
+        _ => false,
+    };
+    if is_sign1 {
+        Ok(SignedMessage::Sign1(decode_cose_sign1(message, detached_payload)?))
+    } else {
+        Ok(SignedMessage::Sign(decode_cose_sign(message, detached_payload)?))
+    }
+}
+
+/// Returns the layers of the message that may be countersigned, the message itself first.
+fn countersigned_layers(message: &SignedMessage) -> Vec<Countersigned<'_>> {
+    match *message {
+        SignedMessage::Sign(ref cose_sign) => {
+            let mut layers = vec![Countersigned {
+                target: CountersignatureTarget::Message,
+                headers: &cose_sign.headers,
+                payload: &cose_sign.payload,
+                other_fields: Vec::new(),
+            }];
+            for (index, cose_signature) in cose_sign.signatures.iter().enumerate() {
+                layers.push(Countersigned {
+                    target: CountersignatureTarget::Signature(index),
+                    headers: &cose_signature.headers,
+                    payload: &cose_signature.signature,
+                    other_fields: Vec::new(),
+                });
+            }
+            layers
+        }
+        SignedMessage::Sign1(ref cose_sign1) => {
+            vec![Countersigned {
+                target: CountersignatureTarget::Message,
+                headers: &cose_sign1.headers,
+                payload: &cose_sign1.payload,
+                other_fields: vec![cose_sign1.signature.clone()],
+            }]
+        }
+    }
+}
+
+/// Returns the serialized Countersign_structure that a countersigner signs to countersign the given
+/// layer of a COSE_Sign or COSE_Sign1. sign_protected is the protected bucket of a full
+/// countersignature (label 11), or None for an abbreviated one (label 12). It is an error for the
+/// message to not have the given layer.
+pub fn countersignature_to_be_signed(message: &[u8], detached_payload: Option<&[u8]>,
+                                     target: CountersignatureTarget,
+                                     sign_protected: Option<&[u8]>, external_aad: &[u8])
+                                     -> Result<Vec<u8>, VerifyError> {
+    let message = decode_signed_message(message, detached_payload)?;
+    match countersigned_layers(&message).iter().find(|layer| layer.target == target) {
+        Some(layer) => Ok(layer.to_be_signed(sign_protected, external_aad)),
+        None => Err(VerifyError::DecodingCOSEFailed),
+    }
+}
+
+/// Decodes the value of a countersignature header, which is a single COSE_Countersignature or an
+/// array of them.
+///
+/// COSE_Countersignature = [ protected: bstr, unprotected: map, signature: bstr ]
+fn decode_countersignatures(value: &CborType)
+                            -> Result<Vec<(Headers, Vec<u8>)>, VerifyError> {
+    let countersignatures = match *value {
+        CborType::Arr(ref elements) if elements.iter().all(|element| {
+            matches!(*element, CborType::Arr(_))
+        }) => elements.iter().collect(),
+        _ => vec![value],
+    };
+    if countersignatures.is_empty() {
+        return Err(VerifyError::DecodingCOSEFailed);
+    }
+    let mut decoded = Vec::with_capacity(countersignatures.len());
+    for countersignature in countersignatures {
+        let elements = match *countersignature {
+            CborType::Arr(ref elements) if elements.len() == 3 => elements,
+            _ => return Err(VerifyError::DecodingCOSEFailed),
+        };
+        let headers = Headers::decode(&elements[0], &elements[1])?;
+        match elements[2] {
+            CborType::BStr(ref signature) => decoded.push((headers, signature.clone())),
+            _ => return Err(VerifyError::DecodingCOSEFailed),
+        }
+    }
+    Ok(decoded)
+}
+
+/// Verifies the countersignatures on every layer of a COSE_Sign or COSE_Sign1 (with the payload
+/// supplied separately if it is detached) and reports the individual outcomes, layer by layer.
+/// Full countersignatures are verified with the trusted key their kid identifies, and abbreviated
+/// ones with the given key. As with verify_cose_sign, an error is returned only if the structure
+/// (including its countersignature headers) could not be decoded.
+pub fn verify_countersignatures(message: &[u8], detached_payload: Option<&[u8]>,
+                                external_aad: &[u8], trusted_keys: &[TrustedKey],
+                                abbreviated_countersigner: Option<&AbbreviatedCountersigner>)
+                                -> Result<Vec<CountersignatureReport>, VerifyError> {
+    let message = decode_signed_message(message, detached_payload)?;
+    let mut reports = Vec::new();
+    for layer in countersigned_layers(&message) {
+        if let Some(value) = layer.headers.get(HEADER_COUNTERSIGNATURE) {
+            for (headers, signature) in decode_countersignatures(value)? {
+                let to_verify = layer.to_be_signed(Some(&headers.protected_bytes), external_aad);
+                let result = headers.signature_algorithm().and_then(|algorithm| {
+                    let kid = headers.kid()?.ok_or(VerifyError::UnknownKeyId)?;
+                    let trusted_key = trusted_keys.iter()
+                                                  .find(|trusted_key| trusted_key.kid == kid)
+                                                  .ok_or(VerifyError::UnknownKeyId)?;
+                    verify_signature(algorithm, trusted_key.spki, &to_verify, &signature)
+                });
+                reports.push(CountersignatureReport {
+                    target: layer.target,
+                    abbreviated: false,
+                    signer: SignerReport {
+                        kid: headers.kid().unwrap_or(None).map(|kid| kid.to_vec()),
+                        algorithm: headers.signature_algorithm().ok(),
+                        result,
+                    },
+                });
+            }
+        }
+        let signature = match layer.headers.get(HEADER_COUNTERSIGNATURE0) {
+            Some(CborType::BStr(signature)) => signature,
+            Some(_) => return Err(VerifyError::DecodingCOSEFailed),
+            None => continue,
+        };
+        let result = match abbreviated_countersigner {
+            Some(countersigner) => {
+                verify_signature(countersigner.algorithm, countersigner.spki,
+                                 &layer.to_be_signed(None, external_aad), signature)
+            }
+            None => Err(VerifyError::UnknownKeyId),
+        };
+        reports.push(CountersignatureReport {
+            target: layer.target,
+            abbreviated: true,
+            signer: SignerReport {
+                kid: None,
+                algorithm: abbreviated_countersigner.map(|countersigner| countersigner.algorithm),
+                result,
+            },
+        });
+    }
+    Ok(reports)
+}
+
+#[test]
+fn test_countersignature_structure() {
+    assert_eq!(countersignature_structure(&[0xa1, 0x01, 0x26], None, &[], &[0x01], &[vec![0x02]]),
+               vec![0x85, 0x73, 0x43, 0x6f, 0x75, 0x6e, 0x74, 0x65, 0x72, 0x53, 0x69, 0x67, 0x6e,
+                    0x61, 0x74, 0x75, 0x72, 0x65, 0x30, 0x56, 0x32, 0x43, 0xa1, 0x01, 0x26, 0x40,
+                    0x41, 0x01, 0x81, 0x41, 0x02]);
+    let structure = countersignature_structure(&[], Some(&[0xa1, 0x01, 0x26]), &[], &[], &[]);
+    assert_eq!(&structure[..2], &[0x85, 0x72]);
+    assert_eq!(&structure[20..], &[0x40, 0x43, 0xa1, 0x01, 0x26, 0x40, 0x40]);
+}
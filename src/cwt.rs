@@ -0,0 +1,470 @@
+use std::collections::BTreeMap;
+use cose::{decode_cose_sign, decode_cose_sign1, label, sig_structure, strip_tag, Headers,
+           COSE_MAC0_TAG};
+use decode::{decode, CborError};
+use key::{verify_signature_with_cose_key, CoseKey, CoseKeySet, PointFormat};
+use mac::{create_cose_mac0, verify_cose_mac0, MacAlgorithm, SharedKey};
+use policy::{verify_cose_sign, verify_cose_sign1, TrustedKey, VerificationPolicy,
+             VerificationReport};
+use serialize::CborType;
+use sign::{create_cose_sign1, SigningKey};
+use verify::{SignatureAlgorithm, VerifyError};
+
+// See RFC 8392, Section 6 and the IANA "CBOR Tags" registry.
+pub const CWT_TAG: u64 = 61;
+
+// See RFC 8392, Section 4, RFC 8747, Section 3.1 and the IANA "CBOR Web Token (CWT) Claims"
+// registry.
+pub const CLAIM_ISS: u64 = 1;
+pub const CLAIM_SUB: u64 = 2;
+pub const CLAIM_AUD: u64 = 3;
+pub const CLAIM_EXP: u64 = 4;
+pub const CLAIM_NBF: u64 = 5;
+pub const CLAIM_IAT: u64 = 6;
+pub const CLAIM_CTI: u64 = 7;
+pub const CLAIM_CNF: u64 = 8;
+
+// See RFC 9597, Section 2 and the IANA "COSE Header Parameters" registry.
+pub const HEADER_CWT_CLAIMS: i64 = 15;
+
+// See RFC 8747, Section 3.1 and the IANA "CWT Confirmation Methods" registry.
+pub const CNF_COSE_KEY: u64 = 1;
+pub const CNF_ENCRYPTED_COSE_KEY: u64 = 2;
+pub const CNF_KID: u64 = 3;
+
+/// The claims of a CWT. Times (NumericDate values) are in seconds since the epoch. Claims other
+/// than the standard ones are kept by their key. Only NumericDates that are non-negative integers
+/// are supported: negative ones and those with fractional seconds, which are floating-point
+/// numbers the CBOR decoder can't represent, are rejected with DecodingCWTFailed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Claims {
+    pub iss: Option<String>,
+    pub sub: Option<String>,
+    pub aud: Option<String>,
+    pub exp: Option<u64>,
+    pub nbf: Option<u64>,
+    pub iat: Option<u64>,
+    pub cti: Option<Vec<u8>>,
+    /// The confirmation method map (RFC 8747).
+    pub cnf: Option<BTreeMap<CborType, CborType>>,
+    pub other: BTreeMap<CborType, CborType>,
+}
+
+/// What the claims of a CWT are validated against.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CwtValidation<'a> {
+    /// The current time in seconds since the epoch.
+    pub now: u64,
+    /// The audience the verifier identifies as. A CWT with an aud claim is only accepted if it
+    /// matches, so a verifier without an audience only accepts CWTs without one.
+    pub audience: Option<&'a str>,
+    /// The number of seconds the clocks of the issuer and the verifier may differ by.
+    pub leeway: u64,
+}
+
+/// The proof-of-possession key a CWT is bound to by its cnf claim (RFC 8747), which the presenter
+/// of the CWT must prove it holds.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfirmationKey {
+    CoseKey(CoseKey),
+    /// A key the recipient of the CWT already knows, identified by its kid.
+    KeyId(Vec<u8>),
+}
+
+/// The keys a CWT may be verified with, which determine whether it must be a COSE_Sign1 or a
+/// COSE_Mac0. Either way, the key is found by the kid of the COSE structure.
+pub enum CwtKeys<'a> {
+    Signature(&'a [TrustedKey<'a>]),
+    Mac(&'a [SharedKey<'a>]),
+}
+
+impl Claims {
+    /// Decodes a claims set, which must be a map.
+    pub fn decode(claims: &[u8]) -> Result<Claims, VerifyError> {
+        let item = decode(claims).map_err(|error| match error {
+            // Such as a floating-point NumericDate.
+            CborError::UnsupportedType => VerifyError::DecodingCWTFailed,
+            _ => VerifyError::DecodingCBORFailed,
+        })?;
+        Claims::from_cbor(&item)
+    }
+
+    /// Decodes a claims set map.
+    pub fn from_cbor(claims: &CborType) -> Result<Claims, VerifyError> {
+        let map = match *claims {
+            CborType::Map(ref map) => map.clone(),
+            _ => return Err(VerifyError::DecodingCWTFailed),
+        };
+        let mut decoded = Claims::default();
+        for (key, value) in map {
+            let key_value = match key {
+                CborType::UInt(key_value) => key_value,
+                _ => {
+                    decoded.other.insert(key, value);
+                    continue;
+                }
+            };
+            match (key_value, value) {
+                (CLAIM_ISS, CborType::TStr(iss)) => decoded.iss = Some(iss),
+                (CLAIM_SUB, CborType::TStr(sub)) => decoded.sub = Some(sub),
+                (CLAIM_AUD, CborType::TStr(aud)) => decoded.aud = Some(aud),
+                (CLAIM_EXP, CborType::UInt(exp)) => decoded.exp = Some(exp),
+                (CLAIM_NBF, CborType::UInt(nbf)) => decoded.nbf = Some(nbf),
+                (CLAIM_IAT, CborType::UInt(iat)) => decoded.iat = Some(iat),
+                (CLAIM_CTI, CborType::BStr(cti)) => decoded.cti = Some(cti),
+                (CLAIM_CNF, CborType::Map(cnf)) => decoded.cnf = Some(cnf),
+                (CLAIM_ISS..=CLAIM_CNF, _) => return Err(VerifyError::DecodingCWTFailed),
+                (_, value) => {
+                    decoded.other.insert(key, value);
+                }
+            }
+        }
+        Ok(decoded)
+    }
+
+    /// Encodes the claims set as a map. The encoding is deterministic, with the keys in canonical
+    /// order. The typed claims take precedence over any other claims with the same key.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut map = self.other.clone();
+        let mut insert = |key: u64, value: Option<CborType>| {
+            if let Some(value) = value {
+                map.insert(CborType::UInt(key), value);
+            }
+        };
+        insert(CLAIM_ISS, self.iss.clone().map(CborType::TStr));
+        insert(CLAIM_SUB, self.sub.clone().map(CborType::TStr));
+        insert(CLAIM_AUD, self.aud.clone().map(CborType::TStr));
+        insert(CLAIM_EXP, self.exp.map(CborType::UInt));
+        insert(CLAIM_NBF, self.nbf.map(CborType::UInt));
+        insert(CLAIM_IAT, self.iat.map(CborType::UInt));
+        insert(CLAIM_CTI, self.cti.clone().map(CborType::BStr));
+        insert(CLAIM_CNF, self.cnf.clone().map(CborType::Map));
+        CborType::Map(map).serialize()
+    }
+
+    /// Returns the confirmation key in the cnf claim, if there is one. The confirmation method map
+    /// must hold exactly one method. Encrypted COSE_Keys are not supported.
+    pub fn confirmation_key(&self) -> Result<Option<ConfirmationKey>, VerifyError> {
+        let cnf = match self.cnf {
+            Some(ref cnf) => cnf,
+            None => return Ok(None),
+        };
+        if cnf.len() != 1 {
+            return Err(VerifyError::DecodingCWTFailed);
+        }
+        match cnf.iter().next() {
+            Some((&CborType::UInt(CNF_COSE_KEY), cose_key)) => {
+                Ok(Some(ConfirmationKey::CoseKey(CoseKey::from_cbor(cose_key)?)))
+            }
+            Some((&CborType::UInt(CNF_KID), CborType::BStr(kid))) => {
+                Ok(Some(ConfirmationKey::KeyId(kid.clone())))
+            }
+            Some((&CborType::UInt(CNF_ENCRYPTED_COSE_KEY), _)) => Err(VerifyError::UnsupportedKey),
+            _ => Err(VerifyError::DecodingCWTFailed),
+        }
+    }
+
+    /// Checks that the claims are valid at the given time, allowing for the leeway, and that they
+    /// are for the given audience.
+    pub fn validate(&self, validation: &CwtValidation) -> Result<(), VerifyError> {
+        if self.exp.is_some_and(|exp| validation.now >= exp.saturating_add(validation.leeway)) {
+            return Err(VerifyError::TokenExpired);
+        }
+        if self.nbf.is_some_and(|nbf| validation.now.saturating_add(validation.leeway) < nbf) {
+            return Err(VerifyError::TokenNotYetValid);
+        }
+        match (self.aud.as_ref(), validation.audience) {
+            (Some(aud), Some(audience)) if aud == audience => Ok(()),
+            (None, _) => Ok(()),
+            _ => Err(VerifyError::AudienceMismatch),
+        }
+    }
+}
+
+/// Builds CWTs from a claims set and issues them as a COSE_Sign1 or a COSE_Mac0.
+#[derive(Clone, Debug, Default)]
+pub struct CwtBuilder {
+    claims: Claims,
+    tagged: bool,
+}
+
+impl CwtBuilder {
+    pub fn new() -> CwtBuilder {
+        CwtBuilder::default()
+    }
+
+    pub fn issuer(mut self, iss: &str) -> CwtBuilder {
+        self.claims.iss = Some(String::from(iss));
+        self
+    }
+
+    pub fn subject(mut self, sub: &str) -> CwtBuilder {
+        self.claims.sub = Some(String::from(sub));
+        self
+    }
+
+    pub fn audience(mut self, aud: &str) -> CwtBuilder {
+        self.claims.aud = Some(String::from(aud));
+        self
+    }
+
+    pub fn expiration(mut self, exp: u64) -> CwtBuilder {
+        self.claims.exp = Some(exp);
+        self
+    }
+
+    pub fn not_before(mut self, nbf: u64) -> CwtBuilder {
+        self.claims.nbf = Some(nbf);
+        self
+    }
+
+    pub fn issued_at(mut self, iat: u64) -> CwtBuilder {
+        self.claims.iat = Some(iat);
+        self
+    }
+
+    pub fn cwt_id(mut self, cti: &[u8]) -> CwtBuilder {
+        self.claims.cti = Some(cti.to_vec());
+        self
+    }
+
+    /// Binds the CWT to the given key, which goes in the cnf claim.
+    pub fn confirmation_key(mut self, key: &CoseKey) -> CwtBuilder {
+        let mut cnf = BTreeMap::new();
+        cnf.insert(CborType::UInt(CNF_COSE_KEY), key.to_cbor(PointFormat::Uncompressed));
+        self.claims.cnf = Some(cnf);
+        self
+    }
+
+    /// Binds the CWT to the key with the given kid, which the recipient already knows.
+    pub fn confirmation_kid(mut self, kid: &[u8]) -> CwtBuilder {
+        let mut cnf = BTreeMap::new();
+        cnf.insert(CborType::UInt(CNF_KID), CborType::BStr(kid.to_vec()));
+        self.claims.cnf = Some(cnf);
+        self
+    }
+
+    /// Adds a claim with an integer key. The standard claims are set with their own methods.
+    pub fn int_claim(mut self, key: i64, value: CborType) -> CwtBuilder {
+        self.claims.other.insert(label(key), value);
+        self
+    }
+
+    /// Adds a claim with a text key.
+    pub fn text_claim(mut self, key: &str, value: CborType) -> CwtBuilder {
+        self.claims.other.insert(CborType::TStr(String::from(key)), value);
+        self
+    }
+
+    /// Whether to wrap the COSE structure in the CWT tag. It is tagged with its own tag either way.
+    pub fn tagged(mut self, tagged: bool) -> CwtBuilder {
+        self.tagged = tagged;
+        self
+    }
+
+    pub fn claims(&self) -> &Claims {
+        &self.claims
+    }
+
+    fn wrap(&self, message: Vec<u8>) -> Result<Vec<u8>, VerifyError> {
+        if !self.tagged {
+            return Ok(message);
+        }
+        let message = decode(&message).map_err(|_| VerifyError::DecodingCBORFailed)?;
+        Ok(CborType::Tag(CWT_TAG, Box::new(message)).serialize())
+    }
+
+    /// Issues the CWT as a COSE_Sign1 signed with the given key, which the kid identifies.
+    pub fn sign(&self, algorithm: SignatureAlgorithm, key: &SigningKey, kid: &[u8])
+                -> Result<Vec<u8>, VerifyError> {
+        self.wrap(create_cose_sign1(algorithm, key, Some(kid), &self.claims.encode())?)
+    }
+
+    /// Issues the CWT as a COSE_Mac0 authenticated with the given key, which the kid identifies.
+    pub fn mac(&self, algorithm: MacAlgorithm, key: &[u8], kid: &[u8])
+               -> Result<Vec<u8>, VerifyError> {
+        self.wrap(create_cose_mac0(algorithm, key, Some(kid), &self.claims.encode(), &[])?)
+    }
+}
+
+/// Returns the kid of a COSE_Mac0, if it has one.
+fn mac0_kid(cose_mac0: &CborType) -> Result<Option<Vec<u8>>, VerifyError> {
+    let elements = match strip_tag(cose_mac0.clone(), COSE_MAC0_TAG)? {
+        CborType::Arr(elements) if elements.len() == 4 => elements,
+        _ => return Err(VerifyError::DecodingCOSEFailed),
+    };
+    let headers = Headers::decode(&elements[0], &elements[1])?;
+    Ok(headers.kid()?.map(|kid| kid.to_vec()))
+}
+
+/// Verifies a CWT (RFC 8392), which is a COSE_Sign1 or COSE_Mac0 optionally wrapped in the CWT
+/// tag, and validates its claims. Returns the claims.
+pub fn verify_cwt(cwt: &[u8], keys: &CwtKeys, validation: &CwtValidation)
+                  -> Result<Claims, VerifyError> {
+    let item = decode(cwt).map_err(|_| VerifyError::DecodingCBORFailed)?;
+    let message = match item {
+        CborType::Tag(CWT_TAG, message) => *message,
+        item => item,
+    };
+    let message_bytes = message.serialize();
+    let payload = match *keys {
+        CwtKeys::Signature(trusted_keys) => {
+            verify_cose_sign1(&message_bytes, None, trusted_keys)?
+        }
+        CwtKeys::Mac(shared_keys) => {
+            let kid = mac0_kid(&message)?;
+            let mut result = Err(VerifyError::UnknownKeyId);
+            for key in shared_keys {
+                if kid.as_ref().is_some_and(|kid| &kid[..] != key.kid) {
+                    continue;
+                }
+                result = verify_cose_mac0(&message_bytes, None, &[], key.key);
+                if result.is_ok() {
+                    break;
+                }
+            }
+            result?
+        }
+    };
+    let claims = Claims::decode(&payload)?;
+    claims.validate(validation)?;
+    Ok(claims)
+}
+
+/// Decodes the CWT Claims header parameter (RFC 9597). It is only accepted in the protected bucket,
+/// where the signature covers it.
+pub fn header_claims(headers: &Headers) -> Result<Option<Claims>, VerifyError> {
+    if headers.unprotected.contains_key(&label(HEADER_CWT_CLAIMS)) {
+        return Err(VerifyError::DecodingCOSEFailed);
+    }
+    match headers.protected.get(&label(HEADER_CWT_CLAIMS)) {
+        Some(claims) => Ok(Some(Claims::from_cbor(claims)?)),
+        None => Ok(None),
+    }
+}
+
+/// Verifies a COSE_Sign1 as verify_cose_sign1 does and validates the claims in its CWT Claims
+/// header, which it must have. Returns the claims and the payload.
+pub fn verify_cose_sign1_with_claims(cose_sign1: &[u8], detached_payload: Option<&[u8]>,
+                                     trusted_keys: &[TrustedKey], validation: &CwtValidation)
+                                     -> Result<(Claims, Vec<u8>), VerifyError> {
+    let payload = verify_cose_sign1(cose_sign1, detached_payload, trusted_keys)?;
+    let headers = decode_cose_sign1(cose_sign1, detached_payload)?.headers;
+    let claims = header_claims(&headers)?.ok_or(VerifyError::DecodingCWTFailed)?;
+    claims.validate(validation)?;
+    Ok((claims, payload))
+}
+
+/// Verifies a COSE_Sign as verify_cose_sign does and validates the claims in the CWT Claims header
+/// of its body, which it must have. As with verify_cose_sign, the claims may only be relied on if
+/// the report says the policy is satisfied.
+pub fn verify_cose_sign_with_claims(cose_sign: &[u8], detached_payload: Option<&[u8]>,
+                                    trusted_keys: &[TrustedKey], policy: VerificationPolicy,
+                                    validation: &CwtValidation)
+                                    -> Result<(VerificationReport, Claims), VerifyError> {
+    let report = verify_cose_sign(cose_sign, detached_payload, trusted_keys, policy)?;
+    let headers = decode_cose_sign(cose_sign, detached_payload)?.headers;
+    let claims = header_claims(&headers)?.ok_or(VerifyError::DecodingCWTFailed)?;
+    claims.validate(validation)?;
+    Ok((report, claims))
+}
+
+/// Verifies a COSE_Sign1 made by the presenter of a CWT (with the payload supplied separately if it
+/// is detached) with the confirmation key from the CWT's verified claims, and returns its payload.
+/// A confirmation kid is looked up in holder_keys. If both the COSE_Sign1 and the confirmation key
+/// have a kid, they must match.
+pub fn verify_proof_of_possession(claims: &Claims, proof: &[u8], detached_payload: Option<&[u8]>,
+                                  holder_keys: Option<&CoseKeySet>)
+                                  -> Result<Vec<u8>, VerifyError> {
+    let confirmation_key = claims.confirmation_key()?.ok_or(VerifyError::UnknownKeyId)?;
+    let key = match confirmation_key {
+        ConfirmationKey::CoseKey(ref key) => key,
+        ConfirmationKey::KeyId(ref kid) => {
+            holder_keys.and_then(|keys| keys.find_by_kid(kid)).ok_or(VerifyError::UnknownKeyId)?
+        }
+    };
+    let cose_sign1 = decode_cose_sign1(proof, detached_payload)?;
+    let algorithm = cose_sign1.headers.signature_algorithm()?;
+    if let (Some(kid), Some(key_kid)) = (cose_sign1.headers.kid()?, key.kid.as_ref()) {
+        if kid != &key_kid[..] {
+            return Err(VerifyError::UnknownKeyId);
+        }
+    }
+    let to_verify = sig_structure("Signature1", &cose_sign1.headers.protected_bytes, None, &[],
+                                  &cose_sign1.payload);
+    verify_signature_with_cose_key(algorithm, key, &to_verify, &cose_sign1.signature)?;
+    Ok(cose_sign1.payload)
+}
+
+#[test]
+fn test_validate() {
+    let claims = Claims {
+        aud: Some(String::from("light")),
+        exp: Some(200),
+        nbf: Some(100),
+        ..Default::default()
+    };
+    let validation = CwtValidation { now: 150, audience: Some("light"), leeway: 0 };
+    assert_eq!(claims.validate(&validation), Ok(()));
+    assert_eq!(claims.validate(&CwtValidation { now: 200, ..validation }),
+               Err(VerifyError::TokenExpired));
+    assert_eq!(claims.validate(&CwtValidation { now: 205, leeway: 10, ..validation }), Ok(()));
+    assert_eq!(claims.validate(&CwtValidation { now: 99, ..validation }),
+               Err(VerifyError::TokenNotYetValid));
+    assert_eq!(claims.validate(&CwtValidation { now: 95, leeway: 10, ..validation }), Ok(()));
+    assert_eq!(claims.validate(&CwtValidation { audience: Some("lock"), ..validation }),
+               Err(VerifyError::AudienceMismatch));
+    assert_eq!(claims.validate(&CwtValidation { audience: None, ..validation }),
+               Err(VerifyError::AudienceMismatch));
+    assert_eq!(Claims::default().validate(&CwtValidation { audience: None, ..validation }),
+               Ok(()));
+}
+
+#[test]
+fn test_decode_claims() {
+    // {1: "as", 4: 10, -1: h'00', "x": 1}
+    let claims = Claims::decode(&[0xa4, 0x01, 0x62, 0x61, 0x73, 0x04, 0x0a, 0x20, 0x41, 0x00,
+                                  0x61, 0x78, 0x01]).unwrap();
+    assert_eq!(claims.iss, Some(String::from("as")));
+    assert_eq!(claims.exp, Some(10));
+    assert_eq!(claims.other.len(), 2);
+    assert_eq!(claims.other.get(&CborType::NInt(-1)), Some(&CborType::BStr(vec![0])));
+    // The exp claim must be a NumericDate.
+    assert_eq!(Claims::decode(&[0xa1, 0x04, 0x41, 0x00]), Err(VerifyError::DecodingCWTFailed));
+    // Negative and floating-point NumericDates aren't supported: {4: -1} and {4: 1.5}.
+    assert_eq!(Claims::decode(&[0xa1, 0x04, 0x20]), Err(VerifyError::DecodingCWTFailed));
+    assert_eq!(Claims::decode(&[0xa1, 0x04, 0xfb, 0x3f, 0xf8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+               Err(VerifyError::DecodingCWTFailed));
+    assert_eq!(Claims::decode(&[0xa1, 0x04]), Err(VerifyError::DecodingCBORFailed));
+    assert_eq!(Claims::decode(&[0x80]), Err(VerifyError::DecodingCWTFailed));
+}
+
+#[test]
+fn test_encode_claims() {
+    let builder = CwtBuilder::new().text_claim("x", CborType::UInt(1))
+                                   .int_claim(-1, CborType::BStr(vec![0]))
+                                   .expiration(10)
+                                   .issuer("as");
+    let encoded = builder.claims().encode();
+    // {-1: h'00', 1: "as", 4: 10, "x": 1}
+    assert_eq!(encoded, vec![0xa4, 0x20, 0x41, 0x00, 0x01, 0x62, 0x61, 0x73, 0x04, 0x0a, 0x61,
+                             0x78, 0x01]);
+    assert_eq!(&Claims::decode(&encoded).unwrap(), builder.claims());
+    // A typed claim replaces another claim with the same key.
+    let builder = CwtBuilder::new().int_claim(4, CborType::Null).expiration(10);
+    assert_eq!(builder.claims().encode(), vec![0xa1, 0x04, 0x0a]);
+}
+
+#[test]
+fn test_confirmation_key() {
+    let claims = CwtBuilder::new().confirmation_kid(b"holder").claims().clone();
+    assert_eq!(claims.confirmation_key(), Ok(Some(ConfirmationKey::KeyId(b"holder".to_vec()))));
+    assert_eq!(Claims::default().confirmation_key(), Ok(None));
+    let mut cnf = BTreeMap::new();
+    cnf.insert(CborType::UInt(CNF_ENCRYPTED_COSE_KEY), CborType::Arr(Vec::new()));
+    let claims = Claims { cnf: Some(cnf.clone()), ..Default::default() };
+    assert_eq!(claims.confirmation_key(), Err(VerifyError::UnsupportedKey));
+    cnf.insert(CborType::UInt(CNF_KID), CborType::BStr(Vec::new()));
+    let claims = Claims { cnf: Some(cnf), ..Default::default() };
+    assert_eq!(claims.confirmation_key(), Err(VerifyError::DecodingCWTFailed));
+}
@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+use std::string::String;
+use serialize::CborType;
+
+/// Nested arrays, maps and tags beyond this depth are rejected rather than risking exhausting the
+/// stack on hostile input.
+const MAX_NESTING_DEPTH: usize = 64;
+
+/// An error type describing errors that may be encountered while decoding CBOR.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CborError {
+    DuplicateMapKey,
+    MalformedInput,
+    NestingTooDeep,
+    TrailingBytes,
+    TruncatedInput,
+    UnsupportedType,
+}
+
+struct DecoderCursor<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    depth: usize,
+}
+
+impl<'a> DecoderCursor<'a> {
+    fn read_byte(&mut self) -> Result<u8, CborError> {
+        match self.bytes.get(self.offset) {
+            Some(byte) => {
+                self.offset += 1;
+                Ok(*byte)
+            }
+            None => Err(CborError::TruncatedInput),
+        }
+    }
+
+    fn read_bytes(&mut self, len: u64) -> Result<&'a [u8], CborError> {
+        let remaining = (self.bytes.len() - self.offset) as u64;
+        if len > remaining {
+            return Err(CborError::TruncatedInput);
+        }
+        let start = self.offset;
+        self.offset += len as usize;
+        Ok(&self.bytes[start..self.offset])
+    }
+
+    /// The low 5 bits of the initial byte hold the "additional information". Values 0 through 23
+    /// are the argument itself. 24, 25, 26 and 27 mean that the argument follows in 1, 2, 4 or 8
+    /// bytes in network byte order. Indefinite lengths (31) and the reserved values are rejected.
+    fn read_argument(&mut self, additional_information: u8) -> Result<u64, CborError> {
+        let num_bytes = match additional_information {
+            0..=23 => return Ok(additional_information as u64),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            _ => return Err(CborError::MalformedInput),
+        };
+        let mut value: u64 = 0;
+        for byte in self.read_bytes(num_bytes)? {
+            value = (value << 8) | (*byte as u64);
+        }
+        Ok(value)
+    }
+
+    fn decode_item(&mut self) -> Result<CborType, CborError> {
+        let initial_byte = self.read_byte()?;
+        let major_type = initial_byte >> 5;
+        let additional_information = initial_byte & 0x1f;
+        if major_type == 7 {
+            return decode_simple(additional_information);
+        }
+        let argument = self.read_argument(additional_information)?;
+        match major_type {
+            0 => Ok(CborType::UInt(argument)),
+            1 => decode_negative(argument),
+            2 => Ok(CborType::BStr(self.read_bytes(argument)?.to_vec())),
+            3 => {
+                let utf8_bytes = self.read_bytes(argument)?;
+                match String::from_utf8(utf8_bytes.to_vec()) {
+                    Ok(tstr) => Ok(CborType::TStr(tstr)),
+                    Err(_) => Err(CborError::MalformedInput),
+                }
+            }
+            4 => self.decode_array(argument),
+            5 => self.decode_map(argument),
+            6 => {
+                self.enter()?;
+                let value = self.decode_item()?;
+                self.depth -= 1;
+                Ok(CborType::Tag(argument, Box::new(value)))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn enter(&mut self) -> Result<(), CborError> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return Err(CborError::NestingTooDeep);
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn decode_array(&mut self, num_items: u64) -> Result<CborType, CborError> {
+        self.enter()?;
+        // Each item takes at least one byte, so don't let the claimed length make us allocate more
+        // than the input could possibly hold.
+        if num_items > (self.bytes.len() - self.offset) as u64 {
+            return Err(CborError::TruncatedInput);
+        }
+        let mut array = Vec::with_capacity(num_items as usize);
+        for _ in 0..num_items {
+            array.push(self.decode_item()?);
+        }
+        self.depth -= 1;
+        Ok(CborType::Arr(array))
+    }
+
+    fn decode_map(&mut self, num_pairs: u64) -> Result<CborType, CborError> {
+        self.enter()?;
+        let mut map = BTreeMap::new();
+        for _ in 0..num_pairs {
+            let key = self.decode_item()?;
+            let value = self.decode_item()?;
+            if map.insert(key, value).is_some() {
+                return Err(CborError::DuplicateMapKey);
+            }
+        }
+        self.depth -= 1;
+        Ok(CborType::Map(map))
+    }
+}
+
+/// The major type is 1. The argument is -1 minus the value of the negative number. Only values
+/// representable as an i64 are supported.
+fn decode_negative(argument: u64) -> Result<CborType, CborError> {
+    if argument > i64::MAX as u64 {
+        return Err(CborError::UnsupportedType);
+    }
+    Ok(CborType::NInt(-1 - (argument as i64)))
+}
+
+/// The major type is 7. Only false (20), true (21) and null (22) are supported. In particular,
+/// floating-point numbers are not.
+fn decode_simple(additional_information: u8) -> Result<CborType, CborError> {
+    match additional_information {
+        20 => Ok(CborType::Bool(false)),
+        21 => Ok(CborType::Bool(true)),
+        22 => Ok(CborType::Null),
+        _ => Err(CborError::UnsupportedType),
+    }
+}
+
+/// Decodes the given bytes as exactly one CBOR data item. Trailing bytes after the item are an
+/// error.
+pub fn decode(bytes: &[u8]) -> Result<CborType, CborError> {
+    let mut cursor = DecoderCursor { bytes, offset: 0, depth: 0 };
+    let item = cursor.decode_item()?;
+    if cursor.offset != bytes.len() {
+        return Err(CborError::TrailingBytes);
+    }
+    Ok(item)
+}
+
+#[test]
+fn test_decode_integers() {
+    assert_eq!(Ok(CborType::UInt(0)), decode(&[0x00]));
+    assert_eq!(Ok(CborType::UInt(23)), decode(&[0x17]));
+    assert_eq!(Ok(CborType::UInt(1000)), decode(&[0x19, 0x03, 0xe8]));
+    assert_eq!(Ok(CborType::UInt(18446744073709551615)),
+               decode(&[0x1b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]));
+    assert_eq!(Ok(CborType::NInt(-1)), decode(&[0x20]));
+    assert_eq!(Ok(CborType::NInt(-1000)), decode(&[0x39, 0x03, 0xe7]));
+    assert_eq!(Err(CborError::UnsupportedType),
+               decode(&[0x3b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]));
+}
+
+#[test]
+fn test_decode_strings() {
+    assert_eq!(Ok(CborType::BStr(vec![])), decode(&[0x40]));
+    assert_eq!(Ok(CborType::BStr(vec![1, 2, 3, 4])), decode(&[0x44, 0x01, 0x02, 0x03, 0x04]));
+    assert_eq!(Ok(CborType::TStr(String::from("水"))), decode(&[0x63, 0xe6, 0xb0, 0xb4]));
+    assert_eq!(Err(CborError::MalformedInput), decode(&[0x62, 0xc3, 0x28]));
+    assert_eq!(Err(CborError::TruncatedInput), decode(&[0x44, 0x01, 0x02]));
+}
+
+#[test]
+fn test_decode_nested() {
+    let mut map = BTreeMap::new();
+    map.insert(CborType::UInt(1), CborType::NInt(-7));
+    map.insert(CborType::UInt(4), CborType::BStr(vec![0xaa]));
+    let expected = CborType::Tag(98, Box::new(CborType::Arr(vec![CborType::Map(map),
+                                                                 CborType::Null,
+                                                                 CborType::Bool(true)])));
+    let encoded = expected.serialize();
+    assert_eq!(vec![0xd8, 0x62, 0x83, 0xa2, 0x01, 0x26, 0x04, 0x41, 0xaa, 0xf6, 0xf5], encoded);
+    assert_eq!(Ok(expected), decode(&encoded));
+}
+
+#[test]
+fn test_decode_errors() {
+    assert_eq!(Err(CborError::TruncatedInput), decode(&[]));
+    assert_eq!(Err(CborError::TrailingBytes), decode(&[0x01, 0x02]));
+    assert_eq!(Err(CborError::DuplicateMapKey), decode(&[0xa2, 0x01, 0x02, 0x01, 0x03]));
+    assert_eq!(Err(CborError::MalformedInput), decode(&[0x9f, 0x01, 0xff]));
+    assert_eq!(Err(CborError::UnsupportedType), decode(&[0xf9, 0x3c, 0x00]));
+    assert_eq!(Err(CborError::TruncatedInput), decode(&[0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+                                                        0xff, 0xff]));
+    let deeply_nested = vec![0x81; MAX_NESTING_DEPTH + 1];
+    assert_eq!(Err(CborError::NestingTooDeep), decode(&deeply_nested));
+}
@@ -0,0 +1,139 @@
+// Just enough DER to build and parse subject public key infos and EC private keys. See X.690.
+pub const INTEGER: u8 = 0x02;
+pub const BIT_STRING: u8 = 0x03;
+pub const OCTET_STRING: u8 = 0x04;
+pub const NULL: u8 = 0x05;
+pub const OID: u8 = 0x06;
+pub const SEQUENCE: u8 = 0x30;
+pub const CONTEXT_SPECIFIC_1: u8 = 0xa1; // [1] EXPLICIT
+
+/// Encodes a tag-length-value triple with a definite length in the shortest form.
+pub fn encode(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![tag];
+    if contents.len() < 0x80 {
+        bytes.push(contents.len() as u8);
+    } else {
+        let length = (contents.len() as u64).to_be_bytes();
+        let length = &length[length.iter().take_while(|byte| **byte == 0).count()..];
+        bytes.push(0x80 | length.len() as u8);
+        bytes.extend_from_slice(length);
+    }
+    bytes.extend_from_slice(contents);
+    bytes
+}
+
+/// Encodes the given big-endian unsigned integer as an INTEGER, adding a leading zero if the most
+/// significant bit is set so that it is not interpreted as negative.
+pub fn encode_unsigned_integer(value: &[u8]) -> Vec<u8> {
+    let value = &value[value.iter().take_while(|byte| **byte == 0).count()..];
+    let mut contents = Vec::with_capacity(value.len() + 1);
+    if value.first().is_none_or(|byte| byte & 0x80 != 0) {
+        contents.push(0);
+    }
+    contents.extend_from_slice(value);
+    encode(INTEGER, &contents)
+}
+
+/// Encodes a BIT STRING with no unused bits.
+pub fn encode_bit_string(value: &[u8]) -> Vec<u8> {
+    let mut contents = Vec::with_capacity(value.len() + 1);
+    contents.push(0);
+    contents.extend_from_slice(value);
+    encode(BIT_STRING, &contents)
+}
+
+/// Encodes a SubjectPublicKeyInfo given the already-encoded AlgorithmIdentifier contents and the
+/// bytes of the subject public key.
+pub fn encode_spki(algorithm: &[u8], subject_public_key: &[u8]) -> Vec<u8> {
+    let mut contents = encode(SEQUENCE, algorithm);
+    contents.extend(encode_bit_string(subject_public_key));
+    encode(SEQUENCE, &contents)
+}
+
+/// Reads a tag-length-value triple with the given tag from the front of the input. Returns its
+/// contents and the remaining input, or None if the input doesn't start with a definite-length
+/// encoding of that tag in the shortest form.
+pub fn read(input: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    if input.len() < 2 || input[0] != tag {
+        return None;
+    }
+    let (length, header_length) = match input[1] {
+        length @ 0..=0x7f => (length as usize, 2),
+        0x81..=0x84 => {
+            let length_length = (input[1] & 0x7f) as usize;
+            let length_bytes = input.get(2..2 + length_length)?;
+            let length = length_bytes.iter().fold(0, |length, byte| (length << 8) | *byte as usize);
+            if length_bytes[0] == 0 || length < 0x80 {
+                return None;
+            }
+            (length, 2 + length_length)
+        }
+        _ => return None,
+    };
+    let contents = input.get(header_length..header_length + length)?;
+    Some((contents, &input[header_length + length..]))
+}
+
+/// Reads an INTEGER from the front of the input, which must be non-negative. Returns its minimal
+/// big-endian unsigned representation and the remaining input.
+pub fn read_unsigned_integer(input: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (contents, rest) = read(input, INTEGER)?;
+    match contents {
+        [] => None,
+        [first, ..] if first & 0x80 != 0 => None,
+        [0, second, ..] if second & 0x80 == 0 => None,
+        [0, value @ ..] if !value.is_empty() => Some((value, rest)),
+        value => Some((value, rest)),
+    }
+}
+
+/// Reads a BIT STRING with no unused bits from the front of the input.
+pub fn read_bit_string(input: &[u8]) -> Option<(&[u8], &[u8])> {
+    match read(input, BIT_STRING)? {
+        ([0, value @ ..], rest) => Some((value, rest)),
+        _ => None,
+    }
+}
+
+/// Splits a SubjectPublicKeyInfo into the algorithm OID, the remaining contents of the
+/// AlgorithmIdentifier (the algorithm parameters, if any), and the subject public key.
+pub fn read_spki(spki: &[u8]) -> Option<(&[u8], &[u8], &[u8])> {
+    let (contents, rest) = read(spki, SEQUENCE)?;
+    if !rest.is_empty() {
+        return None;
+    }
+    let (algorithm, contents) = read(contents, SEQUENCE)?;
+    let (oid, parameters) = read(algorithm, OID)?;
+    let (subject_public_key, rest) = read_bit_string(contents)?;
+    if !rest.is_empty() {
+        return None;
+    }
+    Some((oid, parameters, subject_public_key))
+}
+
+#[test]
+fn test_encode() {
+    assert_eq!(encode(NULL, &[]), vec![0x05, 0x00]);
+    assert_eq!(encode(OID, &[0x2b; 0x7f])[..2], [0x06, 0x7f]);
+    assert_eq!(encode(OID, &[0x2b; 0x80])[..3], [0x06, 0x81, 0x80]);
+    assert_eq!(encode(OID, &[0x2b; 0x100])[..4], [0x06, 0x82, 0x01, 0x00]);
+    assert_eq!(encode_unsigned_integer(&[0x01, 0x00, 0x01]), vec![0x02, 0x03, 0x01, 0x00, 0x01]);
+    assert_eq!(encode_unsigned_integer(&[0x00, 0x80]), vec![0x02, 0x02, 0x00, 0x80]);
+    assert_eq!(encode_unsigned_integer(&[]), vec![0x02, 0x01, 0x00]);
+}
+
+#[test]
+fn test_read() {
+    assert_eq!(read(&[0x05, 0x00, 0x01], NULL), Some((&[][..], &[0x01][..])));
+    assert_eq!(read(&[0x05, 0x00], OID), None);
+    assert_eq!(read(&[0x05, 0x01], NULL), None);
+    assert_eq!(read(&[0x05, 0x81, 0x01, 0x00], NULL), None);
+    assert_eq!(read(&[0x05, 0x80], NULL), None);
+    let long = encode(OID, &[0x2b; 0x100]);
+    assert_eq!(read(&long, OID), Some((&long[4..], &[][..])));
+    assert_eq!(read_unsigned_integer(&[0x02, 0x02, 0x00, 0x80]), Some((&[0x80][..], &[][..])));
+    assert_eq!(read_unsigned_integer(&[0x02, 0x01, 0x00]), Some((&[0x00][..], &[][..])));
+    assert_eq!(read_unsigned_integer(&[0x02, 0x02, 0x00, 0x7f]), None);
+    assert_eq!(read_unsigned_integer(&[0x02, 0x01, 0x80]), None);
+    assert_eq!(read_bit_string(&[0x03, 0x02, 0x01, 0xfe]), None);
+}
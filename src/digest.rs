@@ -0,0 +1,128 @@
+use std::io::{ErrorKind, Read};
+use std::os::raw;
+use nss::{SECOidTag, PR_TRUE, SEC_OID_SHA256, SEC_OID_SHA384, SEC_OID_SHA512, SEC_SUCCESS,
+          SHA256_LENGTH, SHA384_LENGTH, SHA512_LENGTH, PK11_CreateDigestContext,
+          PK11_DestroyContext, PK11_DigestBegin, PK11_DigestFinal, PK11_DigestOp, PK11_HashBuf};
+use verify::VerifyError;
+
+/// An enum identifying supported hash algorithms. SHA256_64 is SHA-256 truncated to its leftmost 64
+/// bits.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HashAlgorithm {
+    SHA256,
+    SHA256_64,
+    SHA384,
+    SHA512,
+}
+
+impl HashAlgorithm {
+    /// Returns the algorithm corresponding to the given value from the IANA "COSE Algorithms"
+    /// registry, if it is supported.
+    pub fn from_cose_id(id: i64) -> Option<HashAlgorithm> {
+        match id {
+            -16 => Some(HashAlgorithm::SHA256),
+            -15 => Some(HashAlgorithm::SHA256_64),
+            -43 => Some(HashAlgorithm::SHA384),
+            -44 => Some(HashAlgorithm::SHA512),
+            _ => None,
+        }
+    }
+
+    /// Returns the value identifying this algorithm in the IANA "COSE Algorithms" registry.
+    pub fn cose_id(&self) -> i64 {
+        match *self {
+            HashAlgorithm::SHA256 => -16,
+            HashAlgorithm::SHA256_64 => -15,
+            HashAlgorithm::SHA384 => -43,
+            HashAlgorithm::SHA512 => -44,
+        }
+    }
+
+    /// Returns the length of the digests this algorithm produces, in bytes.
+    pub fn output_length(&self) -> usize {
+        match *self {
+            HashAlgorithm::SHA256 => SHA256_LENGTH,
+            HashAlgorithm::SHA256_64 => 8,
+            HashAlgorithm::SHA384 => SHA384_LENGTH,
+            HashAlgorithm::SHA512 => SHA512_LENGTH,
+        }
+    }
+
+    fn oid_and_full_length(&self) -> (SECOidTag, usize) {
+        match *self {
+            HashAlgorithm::SHA256 | HashAlgorithm::SHA256_64 => (SEC_OID_SHA256, SHA256_LENGTH),
+            HashAlgorithm::SHA384 => (SEC_OID_SHA384, SHA384_LENGTH),
+            HashAlgorithm::SHA512 => (SEC_OID_SHA512, SHA512_LENGTH),
+        }
+    }
+}
+
+/// Hashes the given data with the given algorithm.
+pub fn digest(algorithm: HashAlgorithm, data: &[u8]) -> Result<Vec<u8>, VerifyError> {
+    if data.len() > raw::c_int::MAX as usize {
+        return Err(VerifyError::InputTooLarge);
+    }
+    let (oid, full_length) = algorithm.oid_and_full_length();
+    let mut hash_buf = vec![0; full_length];
+    let hash_result = unsafe {
+        PK11_HashBuf(oid, hash_buf.as_mut_ptr(), data.as_ptr(), data.len() as raw::c_int)
+    };
+    if hash_result != SEC_SUCCESS {
+        return Err(VerifyError::LibraryFailure);
+    }
+    hash_buf.truncate(algorithm.output_length());
+    Ok(hash_buf)
+}
+
+/// The size of the chunks digest_reader reads.
+const READ_CHUNK_LENGTH: usize = 64 * 1024;
+
+/// Hashes the given prefix followed by the next length bytes from the reader, which it must yield.
+/// The data is hashed as it is read, so it may be of any length.
+pub fn digest_reader<R: Read>(algorithm: HashAlgorithm, prefix: &[u8], reader: &mut R,
+                              length: u64) -> Result<Vec<u8>, VerifyError> {
+    if prefix.len() > raw::c_uint::MAX as usize {
+        return Err(VerifyError::InputTooLarge);
+    }
+    let (oid, full_length) = algorithm.oid_and_full_length();
+    let context = unsafe { PK11_CreateDigestContext(oid) };
+    if context.is_null() {
+        return Err(VerifyError::LibraryFailure);
+    }
+    defer!(unsafe { PK11_DestroyContext(context, PR_TRUE); });
+    if unsafe { PK11_DigestBegin(context) } != SEC_SUCCESS ||
+       unsafe { PK11_DigestOp(context, prefix.as_ptr(), prefix.len() as raw::c_uint) } !=
+           SEC_SUCCESS {
+        return Err(VerifyError::LibraryFailure);
+    }
+    let mut chunk = vec![0; READ_CHUNK_LENGTH];
+    let mut remaining = length;
+    while remaining > 0 {
+        let chunk_length = if remaining < chunk.len() as u64 {
+            remaining as usize
+        } else {
+            chunk.len()
+        };
+        let read = match reader.read(&mut chunk[..chunk_length]) {
+            Ok(0) => return Err(VerifyError::PayloadReadFailed),
+            Ok(read) => read,
+            Err(ref error) if error.kind() == ErrorKind::Interrupted => continue,
+            Err(_) => return Err(VerifyError::PayloadReadFailed),
+        };
+        if unsafe { PK11_DigestOp(context, chunk.as_ptr(), read as raw::c_uint) } != SEC_SUCCESS {
+            return Err(VerifyError::LibraryFailure);
+        }
+        remaining -= read as u64;
+    }
+    let mut hash_buf = vec![0; full_length];
+    let mut hash_length: raw::c_uint = 0;
+    let result = unsafe {
+        PK11_DigestFinal(context, hash_buf.as_mut_ptr(), &mut hash_length,
+                         full_length as raw::c_uint)
+    };
+    if result != SEC_SUCCESS || hash_length as usize != full_length {
+        return Err(VerifyError::LibraryFailure);
+    }
+    hash_buf.truncate(algorithm.output_length());
+    Ok(hash_buf)
+}
@@ -0,0 +1,364 @@
+use std::collections::BTreeMap;
+use cwt::{verify_cwt, Claims, CwtKeys, CwtValidation};
+use serialize::CborType;
+use verify::VerifyError;
+
+// See RFC 9711, Section 4 and the IANA "CBOR Web Token (CWT) Claims" registry.
+pub const CLAIM_NONCE: u64 = 10;
+pub const CLAIM_UEID: u64 = 256;
+pub const CLAIM_OEMID: u64 = 258;
+pub const CLAIM_HWMODEL: u64 = 259;
+pub const CLAIM_HWVERSION: u64 = 260;
+pub const CLAIM_UPTIME: u64 = 261;
+pub const CLAIM_DBGSTAT: u64 = 263;
+pub const CLAIM_EAT_PROFILE: u64 = 265;
+pub const CLAIM_SUBMODS: u64 = 266;
+pub const CLAIM_BOOTCOUNT: u64 = 267;
+pub const CLAIM_MEASUREMENTS: u64 = 273;
+
+// See RFC 9711, Section 4.2.1.
+const UEID_TYPE_RAND: u8 = 0x01;
+const UEID_TYPE_EUI: u8 = 0x02;
+const UEID_TYPE_IMEI: u8 = 0x03;
+
+/// The manufacturer of the entity (RFC 9711, Section 4.2.3).
+#[derive(Clone, Debug, PartialEq)]
+pub enum OemId {
+    /// A 16-byte random identifier or a 3-byte IEEE OUI.
+    Bytes(Vec<u8>),
+    /// An IANA Private Enterprise Number.
+    Pen(u64),
+}
+
+/// The state of debug facilities of the entity (RFC 9711, Section 4.2.9).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DebugStatus {
+    Enabled,
+    Disabled,
+    DisabledSinceBoot,
+    DisabledPermanently,
+    DisabledFullyAndPermanently,
+}
+
+impl DebugStatus {
+    fn from_value(value: u64) -> Option<DebugStatus> {
+        match value {
+            0 => Some(DebugStatus::Enabled),
+            1 => Some(DebugStatus::Disabled),
+            2 => Some(DebugStatus::DisabledSinceBoot),
+            3 => Some(DebugStatus::DisabledPermanently),
+            4 => Some(DebugStatus::DisabledFullyAndPermanently),
+            _ => None,
+        }
+    }
+}
+
+/// The profile a token conforms to (RFC 9711, Section 4.3.2).
+#[derive(Clone, Debug, PartialEq)]
+pub enum EatProfile {
+    Uri(String),
+    /// The content octets of a DER OID.
+    Oid(Vec<u8>),
+}
+
+/// A measurement of a software component, in the given CoAP Content-Format (RFC 9711, Section
+/// 4.2.16).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Measurement {
+    pub content_format: u64,
+    pub measurement: Vec<u8>,
+}
+
+/// A submodule of the entity (RFC 9711, Section 4.2.18).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Submodule {
+    /// A claims set that is covered by the signature of the token it is in.
+    Claims(Box<EatClaims>),
+    /// A nested CBOR-encoded token that is signed separately and has not been verified.
+    Token(Vec<u8>),
+    /// A nested token that has been verified, with its claims.
+    VerifiedToken(Box<EatClaims>),
+    /// A nested JSON-encoded token, which is not verified.
+    JsonToken(String),
+    /// The digest of a detached claims set or token, with the COSE algorithm of the digest.
+    DetachedDigest { algorithm: i64, digest: Vec<u8> },
+}
+
+/// The claims of an Entity Attestation Token. The EAT claims are decoded into their own fields and
+/// the remaining claims are left in claims.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EatClaims {
+    pub claims: Claims,
+    /// The nonces the token was requested with, if any.
+    pub nonce: Vec<Vec<u8>>,
+    /// The universal entity ID, including its type byte.
+    pub ueid: Option<Vec<u8>>,
+    pub oemid: Option<OemId>,
+    pub hwmodel: Option<Vec<u8>>,
+    pub hwversion: Option<String>,
+    /// The number of seconds since the entity booted.
+    pub uptime: Option<u64>,
+    pub bootcount: Option<u64>,
+    pub dbgstat: Option<DebugStatus>,
+    pub eat_profile: Option<EatProfile>,
+    pub measurements: Vec<Measurement>,
+    pub submods: BTreeMap<String, Submodule>,
+}
+
+/// What the EAT claims are validated against, in addition to the CWT claims.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EatValidation<'a> {
+    /// The nonce the token was requested with, which must be one of its nonces.
+    pub nonce: Option<&'a [u8]>,
+}
+
+/// Checks the length of a UEID against its type: 16, 24 or 32 random bytes, an EUI-48 or EUI-64,
+/// or an IMEI.
+fn ueid_is_valid(ueid: &[u8]) -> bool {
+    matches!((ueid.first(), ueid.len()),
+             (Some(&UEID_TYPE_RAND), 17) | (Some(&UEID_TYPE_RAND), 25) |
+             (Some(&UEID_TYPE_RAND), 33) | (Some(&UEID_TYPE_EUI), 7) |
+             (Some(&UEID_TYPE_EUI), 9) | (Some(&UEID_TYPE_IMEI), 15))
+}
+
+fn decode_nonce(value: CborType) -> Result<Vec<Vec<u8>>, VerifyError> {
+    let nonces = match value {
+        CborType::BStr(nonce) => vec![nonce],
+        CborType::Arr(elements) if !elements.is_empty() => {
+            let mut nonces = Vec::with_capacity(elements.len());
+            for element in elements {
+                match element {
+                    CborType::BStr(nonce) => nonces.push(nonce),
+                    _ => return Err(VerifyError::DecodingEATFailed),
+                }
+            }
+            nonces
+        }
+        _ => return Err(VerifyError::DecodingEATFailed),
+    };
+    if nonces.iter().any(|nonce| nonce.len() < 8 || nonce.len() > 64) {
+        return Err(VerifyError::DecodingEATFailed);
+    }
+    Ok(nonces)
+}
+
+/// Decodes hwversion = [ version: tstr, ? scheme: int ], keeping only the version.
+fn decode_hwversion(value: CborType) -> Result<String, VerifyError> {
+    let mut elements = match value {
+        CborType::Arr(elements) if elements.len() == 1 || elements.len() == 2 => elements,
+        _ => return Err(VerifyError::DecodingEATFailed),
+    };
+    if elements.len() == 2 && !matches!(elements[1], CborType::UInt(_) | CborType::NInt(_)) {
+        return Err(VerifyError::DecodingEATFailed);
+    }
+    match elements.swap_remove(0) {
+        CborType::TStr(version) => Ok(version),
+        _ => Err(VerifyError::DecodingEATFailed),
+    }
+}
+
+fn decode_measurements(value: CborType) -> Result<Vec<Measurement>, VerifyError> {
+    let elements = match value {
+        CborType::Arr(elements) if !elements.is_empty() => elements,
+        _ => return Err(VerifyError::DecodingEATFailed),
+    };
+    let mut measurements = Vec::with_capacity(elements.len());
+    for element in elements {
+        match element {
+            CborType::Arr(ref fields) if fields.len() == 2 => match (&fields[0], &fields[1]) {
+                (&CborType::UInt(content_format), CborType::BStr(measurement)) => {
+                    measurements.push(Measurement {
+                        content_format,
+                        measurement: measurement.clone(),
+                    });
+                }
+                _ => return Err(VerifyError::DecodingEATFailed),
+            },
+            _ => return Err(VerifyError::DecodingEATFailed),
+        }
+    }
+    Ok(measurements)
+}
+
+fn decode_submodule(value: CborType) -> Result<Submodule, VerifyError> {
+    match value {
+        CborType::Map(_) => {
+            let claims = Claims::from_cbor(&value)?;
+            Ok(Submodule::Claims(Box::new(EatClaims::from_claims(claims)?)))
+        }
+        CborType::BStr(token) => Ok(Submodule::Token(token)),
+        CborType::TStr(token) => Ok(Submodule::JsonToken(token)),
+        CborType::Arr(elements) if elements.len() == 2 => match (&elements[0], &elements[1]) {
+            (&CborType::UInt(algorithm), CborType::BStr(digest))
+                if algorithm <= i64::MAX as u64 => {
+                Ok(Submodule::DetachedDigest {
+                    algorithm: algorithm as i64,
+                    digest: digest.clone(),
+                })
+            }
+            (&CborType::NInt(algorithm), CborType::BStr(digest)) => {
+                Ok(Submodule::DetachedDigest { algorithm, digest: digest.clone() })
+            }
+            _ => Err(VerifyError::DecodingEATFailed),
+        },
+        _ => Err(VerifyError::DecodingEATFailed),
+    }
+}
+
+fn decode_submods(value: CborType) -> Result<BTreeMap<String, Submodule>, VerifyError> {
+    let map = match value {
+        CborType::Map(map) => map,
+        _ => return Err(VerifyError::DecodingEATFailed),
+    };
+    let mut submods = BTreeMap::new();
+    for (name, submodule) in map {
+        match name {
+            CborType::TStr(name) => {
+                submods.insert(name, decode_submodule(submodule)?);
+            }
+            _ => return Err(VerifyError::DecodingEATFailed),
+        }
+    }
+    Ok(submods)
+}
+
+impl EatClaims {
+    /// Decodes the EAT claims from the other claims of a CWT claims set. Submodules with claims
+    /// sets are decoded too, but nested tokens are not.
+    pub fn from_claims(mut claims: Claims) -> Result<EatClaims, VerifyError> {
+        let mut eat = EatClaims::default();
+        let mut remaining = BTreeMap::new();
+        for (key, value) in ::std::mem::take(&mut claims.other) {
+            let key_value = match key {
+                CborType::UInt(key_value) => key_value,
+                _ => {
+                    remaining.insert(key, value);
+                    continue;
+                }
+            };
+            match (key_value, value) {
+                (CLAIM_NONCE, value) => eat.nonce = decode_nonce(value)?,
+                (CLAIM_UEID, CborType::BStr(ueid)) if ueid_is_valid(&ueid) => {
+                    eat.ueid = Some(ueid)
+                }
+                (CLAIM_OEMID, CborType::BStr(oemid)) if oemid.len() == 3 || oemid.len() == 16 => {
+                    eat.oemid = Some(OemId::Bytes(oemid))
+                }
+                (CLAIM_OEMID, CborType::UInt(pen)) => eat.oemid = Some(OemId::Pen(pen)),
+                (CLAIM_HWMODEL, CborType::BStr(hwmodel)) if !hwmodel.is_empty() => {
+                    eat.hwmodel = Some(hwmodel)
+                }
+                (CLAIM_HWVERSION, value) => eat.hwversion = Some(decode_hwversion(value)?),
+                (CLAIM_UPTIME, CborType::UInt(uptime)) => eat.uptime = Some(uptime),
+                (CLAIM_BOOTCOUNT, CborType::UInt(bootcount)) => eat.bootcount = Some(bootcount),
+                (CLAIM_DBGSTAT, CborType::UInt(dbgstat)) => {
+                    eat.dbgstat = Some(DebugStatus::from_value(dbgstat)
+                                           .ok_or(VerifyError::DecodingEATFailed)?)
+                }
+                (CLAIM_EAT_PROFILE, CborType::TStr(uri)) => {
+                    eat.eat_profile = Some(EatProfile::Uri(uri))
+                }
+                (CLAIM_EAT_PROFILE, CborType::BStr(oid)) => {
+                    eat.eat_profile = Some(EatProfile::Oid(oid))
+                }
+                (CLAIM_MEASUREMENTS, value) => eat.measurements = decode_measurements(value)?,
+                (CLAIM_SUBMODS, value) => eat.submods = decode_submods(value)?,
+                (CLAIM_UEID, _) | (CLAIM_OEMID, _) | (CLAIM_HWMODEL, _) | (CLAIM_UPTIME, _) |
+                (CLAIM_BOOTCOUNT, _) | (CLAIM_DBGSTAT, _) | (CLAIM_EAT_PROFILE, _) => {
+                    return Err(VerifyError::DecodingEATFailed);
+                }
+                (_, value) => {
+                    remaining.insert(CborType::UInt(key_value), value);
+                }
+            }
+        }
+        claims.other = remaining;
+        eat.claims = claims;
+        Ok(eat)
+    }
+
+    /// Checks that the token was requested with the expected nonce, if there is one.
+    pub fn validate(&self, validation: &EatValidation) -> Result<(), VerifyError> {
+        match validation.nonce {
+            Some(nonce) if !self.nonce.iter().any(|token_nonce| &token_nonce[..] == nonce) => {
+                Err(VerifyError::NonceMismatch)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Verifies the nested CBOR-encoded tokens of the submodules, at any depth, and replaces them
+    /// with their claims.
+    fn verify_nested_tokens(&mut self, keys: &CwtKeys, validation: &CwtValidation)
+                            -> Result<(), VerifyError> {
+        for submodule in self.submods.values_mut() {
+            match *submodule {
+                Submodule::Claims(ref mut claims) => {
+                    claims.verify_nested_tokens(keys, validation)?;
+                }
+                Submodule::Token(ref token) => {
+                    let claims = EatClaims::from_claims(verify_cwt(token, keys, validation)?)?;
+                    let mut claims = Box::new(claims);
+                    claims.verify_nested_tokens(keys, validation)?;
+                    *submodule = Submodule::VerifiedToken(claims);
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Verifies an Entity Attestation Token (RFC 9711), which is a CWT, and decodes and validates its
+/// claims. Nested tokens in submodules are verified with the same keys and CWT validation. The
+/// nonce is only checked at the top level.
+pub fn verify_eat(eat: &[u8], keys: &CwtKeys, validation: &CwtValidation,
+                  eat_validation: &EatValidation) -> Result<EatClaims, VerifyError> {
+    let mut claims = EatClaims::from_claims(verify_cwt(eat, keys, validation)?)?;
+    claims.validate(eat_validation)?;
+    claims.verify_nested_tokens(keys, validation)?;
+    Ok(claims)
+}
+
+#[test]
+fn test_decode_eat_claims() {
+    let mut claims = Claims::default();
+    claims.other.insert(CborType::UInt(CLAIM_UEID), CborType::BStr(vec![0x02; 7]));
+    claims.other.insert(CborType::UInt(CLAIM_OEMID), CborType::UInt(76543));
+    claims.other.insert(CborType::UInt(CLAIM_DBGSTAT), CborType::UInt(3));
+    claims.other.insert(CborType::UInt(CLAIM_HWVERSION),
+                        CborType::Arr(vec![CborType::TStr(String::from("1.3.4")),
+                                           CborType::UInt(1)]));
+    claims.other.insert(CborType::UInt(1000), CborType::Null);
+    let eat = EatClaims::from_claims(claims.clone()).unwrap();
+    assert_eq!(eat.ueid, Some(vec![0x02; 7]));
+    assert_eq!(eat.oemid, Some(OemId::Pen(76543)));
+    assert_eq!(eat.dbgstat, Some(DebugStatus::DisabledPermanently));
+    assert_eq!(eat.hwversion, Some(String::from("1.3.4")));
+    assert_eq!(eat.claims.other.len(), 1);
+
+    let mut eui64_ueid = claims.clone();
+    eui64_ueid.other.insert(CborType::UInt(CLAIM_UEID), CborType::BStr(vec![0x02; 9]));
+    assert_eq!(EatClaims::from_claims(eui64_ueid).unwrap().ueid, Some(vec![0x02; 9]));
+    let mut bad_ueid = claims.clone();
+    bad_ueid.other.insert(CborType::UInt(CLAIM_UEID), CborType::BStr(vec![0x01; 7]));
+    assert_eq!(EatClaims::from_claims(bad_ueid), Err(VerifyError::DecodingEATFailed));
+    let mut bad_eui_ueid = claims.clone();
+    bad_eui_ueid.other.insert(CborType::UInt(CLAIM_UEID), CborType::BStr(vec![0x02; 8]));
+    assert_eq!(EatClaims::from_claims(bad_eui_ueid), Err(VerifyError::DecodingEATFailed));
+    let mut bad_dbgstat = claims.clone();
+    bad_dbgstat.other.insert(CborType::UInt(CLAIM_DBGSTAT), CborType::UInt(5));
+    assert_eq!(EatClaims::from_claims(bad_dbgstat), Err(VerifyError::DecodingEATFailed));
+    let mut short_nonce = claims;
+    short_nonce.other.insert(CborType::UInt(CLAIM_NONCE), CborType::BStr(vec![0; 7]));
+    assert_eq!(EatClaims::from_claims(short_nonce), Err(VerifyError::DecodingEATFailed));
+}
+
+#[test]
+fn test_validate_nonce() {
+    let eat = EatClaims { nonce: vec![vec![1; 8], vec![2; 8]], ..Default::default() };
+    assert_eq!(eat.validate(&EatValidation { nonce: Some(&[2; 8]) }), Ok(()));
+    assert_eq!(eat.validate(&EatValidation { nonce: None }), Ok(()));
+    assert_eq!(eat.validate(&EatValidation { nonce: Some(&[3; 8]) }),
+               Err(VerifyError::NonceMismatch));
+}
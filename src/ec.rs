@@ -0,0 +1,268 @@
+// Point decompression for the NIST prime curves. COSE_Key allows an EC2 key to give only the sign
+// of its y coordinate (RFC 9053, Section 7.1.1), and SPKIs may hold compressed points (RFC 5480,
+// Section 2.2), but NSS only imports uncompressed points, so the y coordinate is recovered here.
+// Whether the resulting point is valid is left to NSS, which checks it when the key is imported.
+
+use std::cmp::Ordering;
+
+/// A non-negative integer stored as little-endian 32-bit limbs, with just enough arithmetic to
+/// decompress points. None of this is constant-time; it is only ever applied to public keys.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Natural {
+    limbs: Vec<u32>,
+}
+
+impl Natural {
+    fn from_be_bytes(bytes: &[u8]) -> Natural {
+        let mut limbs = Vec::with_capacity(bytes.len() / 4 + 1);
+        for chunk in bytes.rchunks(4) {
+            limbs.push(chunk.iter().fold(0, |limb, byte| (limb << 8) | u32::from(*byte)));
+        }
+        Natural::normalized(limbs)
+    }
+
+    /// Returns the big-endian encoding of this number, left-padded with zeros to the given length.
+    /// The number must fit.
+    fn to_be_bytes(&self, length: usize) -> Vec<u8> {
+        let mut bytes = vec![0; length];
+        for (i, limb) in self.limbs.iter().enumerate() {
+            for j in 0..4 {
+                let byte = (limb >> (8 * j)) as u8;
+                if 4 * i + j < length {
+                    bytes[length - 1 - 4 * i - j] = byte;
+                }
+            }
+        }
+        bytes
+    }
+
+    fn normalized(mut limbs: Vec<u32>) -> Natural {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        Natural { limbs }
+    }
+
+    fn bits(&self) -> usize {
+        match self.limbs.last() {
+            Some(last) => 32 * self.limbs.len() - last.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    fn bit(&self, index: usize) -> bool {
+        self.limbs.get(index / 32).is_some_and(|limb| (limb >> (index % 32)) & 1 == 1)
+    }
+
+    fn is_odd(&self) -> bool {
+        self.bit(0)
+    }
+
+    fn add(&self, other: &Natural) -> Natural {
+        let mut limbs = Vec::with_capacity(self.limbs.len().max(other.limbs.len()) + 1);
+        let mut carry = 0u64;
+        for i in 0..self.limbs.len().max(other.limbs.len()) {
+            let sum = u64::from(*self.limbs.get(i).unwrap_or(&0)) +
+                      u64::from(*other.limbs.get(i).unwrap_or(&0)) + carry;
+            limbs.push(sum as u32);
+            carry = sum >> 32;
+        }
+        limbs.push(carry as u32);
+        Natural::normalized(limbs)
+    }
+
+    /// Computes self - other. other must not be greater than self.
+    fn sub(&self, other: &Natural) -> Natural {
+        let mut limbs = Vec::with_capacity(self.limbs.len());
+        let mut borrow = 0i64;
+        for i in 0..self.limbs.len() {
+            let mut difference = i64::from(self.limbs[i]) -
+                                 i64::from(*other.limbs.get(i).unwrap_or(&0)) - borrow;
+            borrow = 0;
+            if difference < 0 {
+                difference += 1 << 32;
+                borrow = 1;
+            }
+            limbs.push(difference as u32);
+        }
+        Natural::normalized(limbs)
+    }
+
+    fn mul(&self, other: &Natural) -> Natural {
+        let mut limbs = vec![0u32; self.limbs.len() + other.limbs.len()];
+        for (i, a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, b) in other.limbs.iter().enumerate() {
+                let product = u64::from(*a) * u64::from(*b) + u64::from(limbs[i + j]) + carry;
+                limbs[i + j] = product as u32;
+                carry = product >> 32;
+            }
+            limbs[i + other.limbs.len()] = carry as u32;
+        }
+        Natural::normalized(limbs)
+    }
+
+    fn shl1(&self) -> Natural {
+        let mut limbs = Vec::with_capacity(self.limbs.len() + 1);
+        let mut carry = 0;
+        for limb in &self.limbs {
+            limbs.push((limb << 1) | carry);
+            carry = limb >> 31;
+        }
+        limbs.push(carry);
+        Natural::normalized(limbs)
+    }
+
+    fn rem(&self, modulus: &Natural) -> Natural {
+        let mut remainder = Natural { limbs: Vec::new() };
+        for index in (0..self.bits()).rev() {
+            remainder = remainder.shl1();
+            if self.bit(index) {
+                remainder = remainder.add(&Natural { limbs: vec![1] });
+            }
+            if remainder >= *modulus {
+                remainder = remainder.sub(modulus);
+            }
+        }
+        remainder
+    }
+}
+
+impl PartialOrd for Natural {
+    fn partial_cmp(&self, other: &Natural) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Natural {
+    fn cmp(&self, other: &Natural) -> Ordering {
+        self.limbs.len().cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+/// The parameters of a short Weierstrass curve y^2 = x^3 - 3x + b over the prime field of order p,
+/// as the NIST curves are.
+pub struct PrimeCurve {
+    p: &'static [u8],
+    b: &'static [u8],
+    /// The length in bytes of an encoded field element.
+    pub coordinate_length: usize,
+}
+
+pub static P256: PrimeCurve = PrimeCurve {
+    p: &[0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+         0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+         0xff, 0xff],
+    b: &[0x5a, 0xc6, 0x35, 0xd8, 0xaa, 0x3a, 0x93, 0xe7, 0xb3, 0xeb, 0xbd, 0x55, 0x76, 0x98, 0x86,
+         0xbc, 0x65, 0x1d, 0x06, 0xb0, 0xcc, 0x53, 0xb0, 0xf6, 0x3b, 0xce, 0x3c, 0x3e, 0x27, 0xd2,
+         0x60, 0x4b],
+    coordinate_length: 32,
+};
+
+pub static P384: PrimeCurve = PrimeCurve {
+    p: &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+         0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+         0xff, 0xfe, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff,
+         0xff, 0xff, 0xff],
+    b: &[0xb3, 0x31, 0x2f, 0xa7, 0xe2, 0x3e, 0xe7, 0xe4, 0x98, 0x8e, 0x05, 0x6b, 0xe3, 0xf8, 0x2d,
+         0x19, 0x18, 0x1d, 0x9c, 0x6e, 0xfe, 0x81, 0x41, 0x12, 0x03, 0x14, 0x08, 0x8f, 0x50, 0x13,
+         0x87, 0x5a, 0xc6, 0x56, 0x39, 0x8d, 0x8a, 0x2e, 0xd1, 0x9d, 0x2a, 0x85, 0xc8, 0xed, 0xd3,
+         0xec, 0x2a, 0xef],
+    coordinate_length: 48,
+};
+
+pub static P521: PrimeCurve = PrimeCurve {
+    p: &[0x01, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+         0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+         0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+         0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+         0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+    b: &[0x00, 0x51, 0x95, 0x3e, 0xb9, 0x61, 0x8e, 0x1c, 0x9a, 0x1f, 0x92, 0x9a, 0x21, 0xa0, 0xb6,
+         0x85, 0x40, 0xee, 0xa2, 0xda, 0x72, 0x5b, 0x99, 0xb3, 0x15, 0xf3, 0xb8, 0xb4, 0x89, 0x91,
+         0x8e, 0xf1, 0x09, 0xe1, 0x56, 0x19, 0x39, 0x51, 0xec, 0x7e, 0x93, 0x7b, 0x16, 0x52, 0xc0,
+         0xbd, 0x3b, 0xb1, 0xbf, 0x07, 0x35, 0x73, 0xdf, 0x88, 0x3d, 0x2c, 0x34, 0xf1, 0xef, 0x45,
+         0x1f, 0xd4, 0x6b, 0x50, 0x3f, 0x00],
+    coordinate_length: 66,
+};
+
+impl PrimeCurve {
+    /// Computes x^3 - 3x + b (mod p). x must be reduced.
+    fn right_hand_side(&self, x: &Natural) -> Natural {
+        let p = Natural::from_be_bytes(self.p);
+        let b = Natural::from_be_bytes(self.b);
+        let x_cubed = x.mul(x).rem(&p).mul(x).rem(&p);
+        let three_x = x.add(x).add(x).rem(&p);
+        x_cubed.add(&p).sub(&three_x).add(&b).rem(&p)
+    }
+
+    /// Given the big-endian x coordinate of a point and the parity of its y coordinate, returns the
+    /// y coordinate, or None if there is no such point. Each of the NIST prime curves has p = 3
+    /// (mod 4), so a square root of a is a^((p + 1) / 4) (mod p).
+    pub fn decompress(&self, x: &[u8], y_is_odd: bool) -> Option<Vec<u8>> {
+        let p = Natural::from_be_bytes(self.p);
+        let x = Natural::from_be_bytes(x);
+        if x >= p {
+            return None;
+        }
+        let a = self.right_hand_side(&x);
+        // (p + 1) / 4, computed by shifting p + 1 right twice.
+        let p_plus_one = p.add(&Natural { limbs: vec![1] });
+        let mut y = Natural { limbs: vec![1] };
+        for index in (2..p_plus_one.bits()).rev() {
+            y = y.mul(&y).rem(&p);
+            if p_plus_one.bit(index) {
+                y = y.mul(&a).rem(&p);
+            }
+        }
+        if y.mul(&y).rem(&p) != a {
+            return None;
+        }
+        if y.is_odd() != y_is_odd && y != (Natural { limbs: Vec::new() }) {
+            y = p.sub(&y);
+        }
+        if y.is_odd() != y_is_odd {
+            return None;
+        }
+        Some(y.to_be_bytes(self.coordinate_length))
+    }
+}
+
+#[cfg(test)]
+fn hex(hex: &str) -> Vec<u8> {
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect()
+}
+
+#[test]
+fn test_p256_generator() {
+    let x = hex("6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296");
+    let y = hex("4fe342e2fe1a7f9b8ee7eb4a7c0f9e162bce33576b315ececbb6406837bf51f5");
+    assert_eq!(P256.decompress(&x, true), Some(y.clone()));
+    // The other square root is p - y.
+    let other_y = P256.decompress(&x, false).unwrap();
+    assert_eq!(Natural::from_be_bytes(&other_y).add(&Natural::from_be_bytes(&y)).to_be_bytes(32),
+               P256.p);
+}
+
+#[test]
+fn test_p384_and_p521_generators() {
+    let x = hex("aa87ca22be8b05378eb1c71ef320ad746e1d3b628ba79b9859f741e082542a385502f25dbf55296c3a\
+                 545e3872760ab7");
+    let y = hex("3617de4a96262c6f5d9e98bf9292dc29f8f41dbd289a147ce9da3113b5f0b8c00a60b1ce1d7e819d7a\
+                 431d7c90ea0e5f");
+    assert_eq!(P384.decompress(&x, true), Some(y));
+    let x = hex("00c6858e06b70404e9cd9e3ecb662395b4429c648139053fb521f828af606b4d3dbaa14b5e77efe759\
+                 28fe1dc127a2ffa8de3348b3c1856a429bf97e7e31c2e5bd66");
+    let y = hex("011839296a789a3bc0045c8a5fb42c7d1bd998f54449579b446817afbd17273e662c97ee72995ef426\
+                 40c550b9013fad0761353c7086a272c24088be94769fd16650");
+    assert_eq!(P521.decompress(&x, false), Some(y));
+}
+
+#[test]
+fn test_decompress_not_on_curve() {
+    // x^3 - 3x + b is not a square for x = 1 on P-256.
+    let mut x = [0; 32];
+    x[31] = 1;
+    assert_eq!(P256.decompress(&x, false), None);
+    assert_eq!(P256.decompress(P256.p, false), None);
+}
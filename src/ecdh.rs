@@ -0,0 +1,110 @@
+use std::ptr;
+use std::slice;
+use der;
+use key::{CoseKey, Curve, KeyParameters, OID_EC_PUBLIC_KEY};
+use nss::{with_private_key_info, with_spki_public_key, SECItem, SECKEYPrivateKey,
+          SECKEYPublicKey, CKA_DERIVE, CKD_NULL, CKM_EC_KEY_PAIR_GEN, CKM_ECDH1_DERIVE,
+          CKM_SHA512_HMAC, KU_KEY_AGREEMENT, PR_FALSE, SEC_SUCCESS, PK11_ExtractKeyValue,
+          PK11_FreeSlot, PK11_FreeSymKey, PK11_GenerateKeyPair, PK11_GetInternalSlot,
+          PK11_GetKeyData, PK11_PubDeriveWithKDF, SECKEY_DestroyPrivateKey,
+          SECKEY_DestroyPublicKey};
+use verify::VerifyError;
+
+/// Returns the curve of the given EC2 key. Only the NIST curves are supported.
+fn ec2_curve(key: &CoseKey) -> Result<Curve, VerifyError> {
+    match key.parameters {
+        KeyParameters::EC2 { curve, .. } => Ok(curve),
+        _ => Err(VerifyError::UnsupportedKey),
+    }
+}
+
+/// Computes the ECDH shared secret (the x coordinate of the shared point) between the given NSS
+/// private key, which is borrowed, and the peer's public key.
+fn shared_secret(private_key: *const SECKEYPrivateKey, peer: &CoseKey)
+                 -> Result<Vec<u8>, VerifyError> {
+    with_spki_public_key(&peer.to_spki(), |peer_public_key| {
+        // The target mechanism only needs to allow a key of any length to be extracted.
+        let sym_key = unsafe {
+            PK11_PubDeriveWithKDF(private_key, peer_public_key, PR_FALSE, ptr::null(),
+                                  ptr::null(), CKM_ECDH1_DERIVE, CKM_SHA512_HMAC, CKA_DERIVE, 0,
+                                  CKD_NULL, ptr::null(), ptr::null())
+        };
+        if sym_key.is_null() {
+            return Err(VerifyError::LibraryFailure);
+        }
+        defer!(unsafe { PK11_FreeSymKey(sym_key); });
+        if unsafe { PK11_ExtractKeyValue(sym_key) } != SEC_SUCCESS {
+            return Err(VerifyError::LibraryFailure);
+        }
+        let key_data = unsafe { PK11_GetKeyData(sym_key) };
+        if key_data.is_null() {
+            return Err(VerifyError::LibraryFailure);
+        }
+        Ok(unsafe { slice::from_raw_parts((*key_data).data, (*key_data).len as usize) }.to_vec())
+    })
+}
+
+/// Generates an ephemeral key pair on the curve of the peer's EC2 public key and computes the
+/// shared secret with it. Returns the ephemeral public key and the shared secret.
+pub fn agree_ephemeral(peer: &CoseKey) -> Result<(CoseKey, Vec<u8>), VerifyError> {
+    let curve = ec2_curve(peer)?;
+    let parameters = der::encode(der::OID, curve.oid());
+    let parameters_item = SECItem::maybe_new(&parameters)?;
+    let slot = unsafe { PK11_GetInternalSlot() };
+    if slot.is_null() {
+        return Err(VerifyError::LibraryFailure);
+    }
+    defer!(unsafe { PK11_FreeSlot(slot); });
+    let mut public_key: *const SECKEYPublicKey = ptr::null();
+    let private_key = unsafe {
+        PK11_GenerateKeyPair(slot, CKM_EC_KEY_PAIR_GEN,
+                             &parameters_item as *const SECItem as *const _, &mut public_key,
+                             PR_FALSE, PR_FALSE, ptr::null())
+    };
+    if private_key.is_null() || public_key.is_null() {
+        return Err(VerifyError::LibraryFailure);
+    }
+    defer!(unsafe {
+        SECKEY_DestroyPrivateKey(private_key);
+        SECKEY_DestroyPublicKey(public_key);
+    });
+    let ephemeral_key = CoseKey::from_nss_public_key(public_key)?;
+    Ok((ephemeral_key, shared_secret(private_key, peer)?))
+}
+
+/// Encodes an EC private key as a PKCS #8 PrivateKeyInfo (RFC 5208) containing an ECPrivateKey
+/// (RFC 5915) with the public key, which NSS needs to import it.
+pub fn encode_private_key_info(key: &CoseKey, d: &[u8]) -> Result<Vec<u8>, VerifyError> {
+    let (curve, x, y) = match key.parameters {
+        KeyParameters::EC2 { curve, ref x, ref y } => (curve, x, y),
+        _ => return Err(VerifyError::UnsupportedKey),
+    };
+    if d.len() != x.len() {
+        return Err(VerifyError::InvalidKey);
+    }
+    let mut point = vec![0x04];
+    point.extend_from_slice(x);
+    point.extend_from_slice(y);
+    let mut ec_private_key = der::encode_unsigned_integer(&[1]);
+    ec_private_key.extend(der::encode(der::OCTET_STRING, d));
+    ec_private_key.extend(der::encode(der::CONTEXT_SPECIFIC_1, &der::encode_bit_string(&point)));
+    let mut algorithm = der::encode(der::OID, OID_EC_PUBLIC_KEY);
+    algorithm.extend(der::encode(der::OID, curve.oid()));
+    let mut private_key_info = der::encode_unsigned_integer(&[0]);
+    private_key_info.extend(der::encode(der::SEQUENCE, &algorithm));
+    private_key_info.extend(der::encode(der::OCTET_STRING,
+                                        &der::encode(der::SEQUENCE, &ec_private_key)));
+    Ok(der::encode(der::SEQUENCE, &private_key_info))
+}
+
+/// Computes the shared secret between a static EC2 key pair, given as its public key and the
+/// big-endian private scalar d, and the peer's public key, which must be on the same curve.
+pub fn agree_static(key: &CoseKey, d: &[u8], peer: &CoseKey) -> Result<Vec<u8>, VerifyError> {
+    if ec2_curve(key)? != ec2_curve(peer)? {
+        return Err(VerifyError::InvalidKey);
+    }
+    let private_key_info = encode_private_key_info(key, d)?;
+    with_private_key_info(&private_key_info, KU_KEY_AGREEMENT, |private_key| {
+        shared_secret(private_key, peer)
+    })
+}
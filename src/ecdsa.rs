@@ -0,0 +1,87 @@
+use der;
+use key::Curve;
+use verify::VerifyError;
+
+/// Converts an ECDSA signature in the fixed-length r || s form COSE uses (RFC 9053, Section 2.1)
+/// into a DER Ecdsa-Sig-Value (RFC 3279, Section 2.2.3), as X.509 tooling and many HSMs expect.
+/// The signature must be twice the coordinate length of the curve.
+pub fn ecdsa_signature_to_der(curve: Curve, signature: &[u8]) -> Result<Vec<u8>, VerifyError> {
+    let coordinate_length = curve.coordinate_length().ok_or(VerifyError::UnsupportedAlgorithm)?;
+    if signature.len() != 2 * coordinate_length {
+        return Err(VerifyError::InvalidSignatureLength);
+    }
+    let (r, s) = signature.split_at(coordinate_length);
+    let mut contents = der::encode_unsigned_integer(r);
+    contents.extend(der::encode_unsigned_integer(s));
+    Ok(der::encode(der::SEQUENCE, &contents))
+}
+
+/// Converts a DER Ecdsa-Sig-Value into the fixed-length r || s form COSE uses, left-padding r and s
+/// to the coordinate length of the curve.
+pub fn ecdsa_signature_from_der(curve: Curve, signature: &[u8]) -> Result<Vec<u8>, VerifyError> {
+    let coordinate_length = curve.coordinate_length().ok_or(VerifyError::UnsupportedAlgorithm)?;
+    let (contents, rest) = der::read(signature, der::SEQUENCE)
+        .ok_or(VerifyError::DecodingSignatureFailed)?;
+    if !rest.is_empty() {
+        return Err(VerifyError::DecodingSignatureFailed);
+    }
+    let (r, contents) = der::read_unsigned_integer(contents)
+        .ok_or(VerifyError::DecodingSignatureFailed)?;
+    let (s, contents) = der::read_unsigned_integer(contents)
+        .ok_or(VerifyError::DecodingSignatureFailed)?;
+    if !contents.is_empty() {
+        return Err(VerifyError::DecodingSignatureFailed);
+    }
+    let mut raw = Vec::with_capacity(2 * coordinate_length);
+    for value in &[r, s] {
+        if value.len() > coordinate_length {
+            return Err(VerifyError::DecodingSignatureFailed);
+        }
+        raw.resize(raw.len() + coordinate_length - value.len(), 0);
+        raw.extend_from_slice(value);
+    }
+    Ok(raw)
+}
+
+#[test]
+fn test_ecdsa_signature_conversion() {
+    let mut raw = vec![0; 64];
+    raw[31] = 0x01;
+    raw[32] = 0x80;
+    raw[63] = 0x02;
+    let der = ecdsa_signature_to_der(Curve::P256, &raw).unwrap();
+    let mut expected = vec![0x30, 0x26, 0x02, 0x01, 0x01, 0x02, 0x21, 0x00, 0x80];
+    expected.extend_from_slice(&[0; 30]);
+    expected.push(0x02);
+    assert_eq!(der, expected);
+    assert_eq!(ecdsa_signature_from_der(Curve::P256, &der), Ok(raw.clone()));
+    assert_eq!(ecdsa_signature_to_der(Curve::P256, &raw[..63]),
+               Err(VerifyError::InvalidSignatureLength));
+    assert_eq!(ecdsa_signature_to_der(Curve::P384, &raw), Err(VerifyError::InvalidSignatureLength));
+    assert_eq!(ecdsa_signature_to_der(Curve::Ed25519, &raw),
+               Err(VerifyError::UnsupportedAlgorithm));
+
+    let raw_p521 = vec![0xff; 132];
+    let der_p521 = ecdsa_signature_to_der(Curve::P521, &raw_p521).unwrap();
+    assert_eq!(ecdsa_signature_from_der(Curve::P521, &der_p521), Ok(raw_p521));
+    assert_eq!(ecdsa_signature_from_der(Curve::P256, &der_p521),
+               Err(VerifyError::DecodingSignatureFailed));
+}
+
+#[test]
+fn test_ecdsa_signature_from_invalid_der() {
+    // r is negative.
+    assert_eq!(ecdsa_signature_from_der(Curve::P256, &[0x30, 0x06, 0x02, 0x01, 0x80, 0x02, 0x01,
+                                                       0x01]),
+               Err(VerifyError::DecodingSignatureFailed));
+    // s is missing.
+    assert_eq!(ecdsa_signature_from_der(Curve::P256, &[0x30, 0x03, 0x02, 0x01, 0x01]),
+               Err(VerifyError::DecodingSignatureFailed));
+    // There is trailing data.
+    assert_eq!(ecdsa_signature_from_der(Curve::P256, &[0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01,
+                                                       0x01, 0x00]),
+               Err(VerifyError::DecodingSignatureFailed));
+    // A raw signature isn't DER.
+    assert_eq!(ecdsa_signature_from_der(Curve::P256, &[0x01; 64]),
+               Err(VerifyError::DecodingSignatureFailed));
+}
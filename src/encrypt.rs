@@ -0,0 +1,363 @@
+use std::mem;
+use std::os::raw;
+use std::slice;
+use ccm::aes_ccm;
+use cose::{decode_payload, encode_protected_algorithm, label, strip_tag, unprotected_kid, Headers,
+           COSE_ENCRYPT0_TAG, COSE_ENCRYPT_TAG, HEADER_IV, HEADER_PARTIAL_IV};
+use decode::decode;
+use nss::{generate_random, with_sym_key, CkAttributeType, CkChaCha20Poly1305Params, CkGcmParams,
+          CkMechanismType, SECItem, CKA_DECRYPT, CKA_ENCRYPT, CKM_AES_GCM, CKM_CHACHA20_POLY1305,
+          SEC_SUCCESS, PK11_Decrypt, PK11_Encrypt};
+use recipient::{decode_recipients, direct_content_key, encode_recipient, with_content_key,
+                Recipient, RecipientKey};
+use serialize::CborType;
+use verify::VerifyError;
+
+/// An enum identifying supported content encryption algorithms (RFC 9053, Section 4). These are
+/// all authenticated encryption algorithms that append a tag to the ciphertext. The AES-CCM
+/// algorithms are named AesCcm{L}_{M}_{key size} as in the registry, where the length field of L
+/// bits determines the nonce length and limits the plaintext length, and M is the tag size in bits.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EncryptionAlgorithm {
+    A128GCM,
+    A192GCM,
+    A256GCM,
+    AesCcm16_64_128,
+    AesCcm16_64_256,
+    AesCcm64_64_128,
+    AesCcm64_64_256,
+    AesCcm16_128_128,
+    AesCcm16_128_256,
+    AesCcm64_128_128,
+    AesCcm64_128_256,
+    ChaCha20Poly1305,
+}
+
+impl EncryptionAlgorithm {
+    /// Returns the algorithm corresponding to the given value from the IANA "COSE Algorithms"
+    /// registry, if it is supported.
+    pub fn from_cose_id(id: i64) -> Option<EncryptionAlgorithm> {
+        match id {
+            1 => Some(EncryptionAlgorithm::A128GCM),
+            2 => Some(EncryptionAlgorithm::A192GCM),
+            3 => Some(EncryptionAlgorithm::A256GCM),
+            10 => Some(EncryptionAlgorithm::AesCcm16_64_128),
+            11 => Some(EncryptionAlgorithm::AesCcm16_64_256),
+            12 => Some(EncryptionAlgorithm::AesCcm64_64_128),
+            13 => Some(EncryptionAlgorithm::AesCcm64_64_256),
+            24 => Some(EncryptionAlgorithm::ChaCha20Poly1305),
+            30 => Some(EncryptionAlgorithm::AesCcm16_128_128),
+            31 => Some(EncryptionAlgorithm::AesCcm16_128_256),
+            32 => Some(EncryptionAlgorithm::AesCcm64_128_128),
+            33 => Some(EncryptionAlgorithm::AesCcm64_128_256),
+            _ => None,
+        }
+    }
+
+    /// Returns the value identifying this algorithm in the IANA "COSE Algorithms" registry.
+    pub fn cose_id(&self) -> i64 {
+        match *self {
+            EncryptionAlgorithm::A128GCM => 1,
+            EncryptionAlgorithm::A192GCM => 2,
+            EncryptionAlgorithm::A256GCM => 3,
+            EncryptionAlgorithm::AesCcm16_64_128 => 10,
+            EncryptionAlgorithm::AesCcm16_64_256 => 11,
+            EncryptionAlgorithm::AesCcm64_64_128 => 12,
+            EncryptionAlgorithm::AesCcm64_64_256 => 13,
+            EncryptionAlgorithm::ChaCha20Poly1305 => 24,
+            EncryptionAlgorithm::AesCcm16_128_128 => 30,
+            EncryptionAlgorithm::AesCcm16_128_256 => 31,
+            EncryptionAlgorithm::AesCcm64_128_128 => 32,
+            EncryptionAlgorithm::AesCcm64_128_256 => 33,
+        }
+    }
+
+    /// Returns the length of the key in bytes.
+    pub fn key_length(&self) -> usize {
+        match *self {
+            EncryptionAlgorithm::A128GCM |
+            EncryptionAlgorithm::AesCcm16_64_128 |
+            EncryptionAlgorithm::AesCcm64_64_128 |
+            EncryptionAlgorithm::AesCcm16_128_128 |
+            EncryptionAlgorithm::AesCcm64_128_128 => 16,
+            EncryptionAlgorithm::A192GCM => 24,
+            EncryptionAlgorithm::A256GCM |
+            EncryptionAlgorithm::AesCcm16_64_256 |
+            EncryptionAlgorithm::AesCcm64_64_256 |
+            EncryptionAlgorithm::AesCcm16_128_256 |
+            EncryptionAlgorithm::AesCcm64_128_256 |
+            EncryptionAlgorithm::ChaCha20Poly1305 => 32,
+        }
+    }
+
+    /// Returns the length of the IV (nonce) in bytes. For AES-CCM, this is 15 bytes less the size
+    /// of the length field.
+    pub fn iv_length(&self) -> usize {
+        match *self {
+            EncryptionAlgorithm::AesCcm16_64_128 |
+            EncryptionAlgorithm::AesCcm16_64_256 |
+            EncryptionAlgorithm::AesCcm16_128_128 |
+            EncryptionAlgorithm::AesCcm16_128_256 => 13,
+            EncryptionAlgorithm::AesCcm64_64_128 |
+            EncryptionAlgorithm::AesCcm64_64_256 |
+            EncryptionAlgorithm::AesCcm64_128_128 |
+            EncryptionAlgorithm::AesCcm64_128_256 => 7,
+            _ => 12,
+        }
+    }
+
+    /// Returns the length of the authentication tag in bytes.
+    pub fn tag_length(&self) -> usize {
+        match *self {
+            EncryptionAlgorithm::AesCcm16_64_128 |
+            EncryptionAlgorithm::AesCcm16_64_256 |
+            EncryptionAlgorithm::AesCcm64_64_128 |
+            EncryptionAlgorithm::AesCcm64_64_256 => 8,
+            _ => 16,
+        }
+    }
+
+    /// Returns the maximum length of the plaintext in bytes, if it is limited by the size of the
+    /// AES-CCM length field rather than by what the platform can address.
+    pub fn max_plaintext_length(&self) -> Option<u64> {
+        match self.mechanism() {
+            None => {
+                let length_field_bits = (15 - self.iv_length()) * 8;
+                Some(u64::MAX >> (64 - length_field_bits))
+            }
+            Some(_) => None,
+        }
+    }
+
+    /// Returns the NSS mechanism, or None for AES-CCM, which NSS does not implement.
+    fn mechanism(&self) -> Option<CkMechanismType> {
+        match *self {
+            EncryptionAlgorithm::A128GCM |
+            EncryptionAlgorithm::A192GCM |
+            EncryptionAlgorithm::A256GCM => Some(CKM_AES_GCM),
+            EncryptionAlgorithm::ChaCha20Poly1305 => Some(CKM_CHACHA20_POLY1305),
+            _ => None,
+        }
+    }
+}
+
+/// Encrypts (with CKA_ENCRYPT) or decrypts (with CKA_DECRYPT) the input with the given key, IV and
+/// additional authenticated data.
+fn aead(algorithm: EncryptionAlgorithm, operation: CkAttributeType, key: &[u8], iv: &[u8],
+        aad: &[u8], input: &[u8]) -> Result<Vec<u8>, VerifyError> {
+    if key.len() != algorithm.key_length() {
+        return Err(VerifyError::InvalidKey);
+    }
+    if input.len() > raw::c_uint::MAX as usize - algorithm.tag_length() ||
+       aad.len() > raw::c_uint::MAX as usize {
+        return Err(VerifyError::InputTooLarge);
+    }
+    let (plaintext_length, output_length) = if operation == CKA_ENCRYPT {
+        (input.len(), input.len() + algorithm.tag_length())
+    } else {
+        (input.len() - algorithm.tag_length(), input.len())
+    };
+    if algorithm.max_plaintext_length().is_some_and(|max| plaintext_length as u64 > max) {
+        return Err(VerifyError::InputTooLarge);
+    }
+    match algorithm.mechanism() {
+        Some(CKM_AES_GCM) => {
+            let params = CkGcmParams::new(iv, aad, algorithm.tag_length());
+            aead_with_params(CKM_AES_GCM, &params, operation, key, input, output_length)
+        }
+        Some(mechanism) => {
+            let params = CkChaCha20Poly1305Params::new(iv, aad);
+            aead_with_params(mechanism, &params, operation, key, input, output_length)
+        }
+        None => aes_ccm(operation, key, iv, aad, input, algorithm.tag_length()),
+    }
+}
+
+/// Performs the operation of aead with the given mechanism parameters, which are passed to NSS as
+/// the bytes of the PKCS #11 structure.
+fn aead_with_params<P>(mechanism: CkMechanismType, params: &P, operation: CkAttributeType,
+                       key: &[u8], input: &[u8], output_length: usize)
+                       -> Result<Vec<u8>, VerifyError> {
+    let params_ptr: *const P = params;
+    let params_bytes = unsafe {
+        slice::from_raw_parts(params_ptr as *const u8, mem::size_of::<P>())
+    };
+    let params_item = SECItem::maybe_new(params_bytes)?;
+    let mut output = vec![0; output_length];
+    let mut written: raw::c_uint = 0;
+    with_sym_key(mechanism, operation, key, |sym_key| {
+        let function = if operation == CKA_ENCRYPT { PK11_Encrypt } else { PK11_Decrypt };
+        let result = unsafe {
+            function(sym_key, mechanism, &params_item, output.as_mut_ptr(), &mut written,
+                     output_length as raw::c_uint, input.as_ptr(), input.len() as raw::c_uint)
+        };
+        match result {
+            SEC_SUCCESS => Ok(()),
+            _ if operation == CKA_DECRYPT => Err(VerifyError::DecryptionFailed),
+            _ => Err(VerifyError::LibraryFailure),
+        }
+    })?;
+    output.truncate(written as usize);
+    Ok(output)
+}
+
+/// Encrypts the plaintext with the given key and IV, authenticating the additional data. Returns
+/// the ciphertext with the tag appended.
+pub fn seal(algorithm: EncryptionAlgorithm, key: &[u8], iv: &[u8], aad: &[u8], plaintext: &[u8])
+            -> Result<Vec<u8>, VerifyError> {
+    aead(algorithm, CKA_ENCRYPT, key, iv, aad, plaintext)
+}
+
+/// Decrypts a ciphertext with an appended tag, checking that it and the additional data are
+/// authentic.
+pub fn open(algorithm: EncryptionAlgorithm, key: &[u8], iv: &[u8], aad: &[u8], ciphertext: &[u8])
+            -> Result<Vec<u8>, VerifyError> {
+    if ciphertext.len() < algorithm.tag_length() {
+        return Err(VerifyError::DecryptionFailed);
+    }
+    aead(algorithm, CKA_DECRYPT, key, iv, aad, ciphertext)
+}
+
+/// Builds the serialized Enc_structure (RFC 9052, Section 5.3) that is used as the additional
+/// authenticated data.
+pub fn enc_structure(context: &str, protected: &[u8], external_aad: &[u8]) -> Vec<u8> {
+    CborType::Arr(vec![CborType::TStr(String::from(context)),
+                       CborType::BStr(protected.to_vec()),
+                       CborType::BStr(external_aad.to_vec())]).serialize()
+}
+
+/// Returns the encryption algorithm identified by the alg header.
+pub fn encryption_algorithm(headers: &Headers) -> Result<EncryptionAlgorithm, VerifyError> {
+    let id = headers.algorithm()?;
+    EncryptionAlgorithm::from_cose_id(id).ok_or(VerifyError::UnsupportedAlgorithm)
+}
+
+/// Returns the IV header, which must have the length the algorithm requires. A Partial IV is only
+/// meaningful with a context IV that comes with the key, which is not supported, so it is an error
+/// for the IV header to be absent.
+pub fn decode_iv(headers: &Headers, algorithm: EncryptionAlgorithm)
+                 -> Result<&[u8], VerifyError> {
+    match (headers.get(HEADER_IV), headers.get(HEADER_PARTIAL_IV)) {
+        (Some(CborType::BStr(iv)), None) if iv.len() == algorithm.iv_length() => Ok(iv),
+        _ => Err(VerifyError::DecodingCOSEFailed),
+    }
+}
+
+/// Encrypts the plaintext into a tagged COSE_Encrypt0 with a random IV. The algorithm goes in the
+/// protected header and the IV and the kid, if given, in the unprotected header.
+pub fn encrypt_cose_encrypt0(algorithm: EncryptionAlgorithm, key: &[u8], kid: Option<&[u8]>,
+                             plaintext: &[u8], external_aad: &[u8])
+                             -> Result<Vec<u8>, VerifyError> {
+    let protected = encode_protected_algorithm(algorithm.cose_id());
+    let iv = generate_random(algorithm.iv_length())?;
+    let aad = enc_structure("Encrypt0", &protected, external_aad);
+    let ciphertext = seal(algorithm, key, &iv, &aad, plaintext)?;
+    let mut unprotected = unprotected_kid(kid);
+    unprotected.insert(label(HEADER_IV), CborType::BStr(iv));
+    let elements = vec![CborType::BStr(protected), CborType::Map(unprotected),
+                        CborType::BStr(ciphertext)];
+    Ok(CborType::Tag(COSE_ENCRYPT0_TAG, Box::new(CborType::Arr(elements))).serialize())
+}
+
+/// Decrypts a COSE_Encrypt0 with the given key and returns the plaintext. A detached ciphertext
+/// must be supplied by the caller.
+///
+/// COSE_Encrypt0 = [ protected: bstr, unprotected: map, ciphertext: bstr / nil ]
+pub fn decrypt_cose_encrypt0(cose_encrypt0: &[u8], detached_ciphertext: Option<&[u8]>,
+                             external_aad: &[u8], key: &[u8]) -> Result<Vec<u8>, VerifyError> {
+    let item = decode(cose_encrypt0).map_err(|_| VerifyError::DecodingCBORFailed)?;
+    let elements = match strip_tag(item, COSE_ENCRYPT0_TAG)? {
+        CborType::Arr(elements) if elements.len() == 3 => elements,
+        _ => return Err(VerifyError::DecodingCOSEFailed),
+    };
+    let headers = Headers::decode(&elements[0], &elements[1])?;
+    let ciphertext = decode_payload(&elements[2], detached_ciphertext)?;
+    let algorithm = encryption_algorithm(&headers)?;
+    let iv = decode_iv(&headers, algorithm)?;
+    let aad = enc_structure("Encrypt0", &headers.protected_bytes, external_aad);
+    open(algorithm, key, iv, &aad, &ciphertext)
+}
+
+/// Encrypts the plaintext into a tagged COSE_Encrypt with a random IV for the given recipients.
+/// A fresh content key is wrapped for each recipient, unless there is a single recipient using a
+/// direct algorithm, in which case the content key is the recipient's (derived) key.
+pub fn encrypt_cose_encrypt(algorithm: EncryptionAlgorithm, recipients: &[Recipient],
+                            plaintext: &[u8], external_aad: &[u8])
+                            -> Result<Vec<u8>, VerifyError> {
+    if recipients.is_empty() {
+        return Err(VerifyError::UnknownKeyId);
+    }
+    let (content_key, encoded_recipients) = match recipients {
+        [recipient] if recipient.algorithm.is_direct() => {
+            let (content_key, encoded_recipient) =
+                direct_content_key(recipient, algorithm.cose_id(), algorithm.key_length())?;
+            (content_key, vec![encoded_recipient])
+        }
+        _ => {
+            let content_key = generate_random(algorithm.key_length())?;
+            let mut encoded_recipients = Vec::with_capacity(recipients.len());
+            for recipient in recipients {
+                encoded_recipients.push(encode_recipient(recipient, &content_key)?);
+            }
+            (content_key, encoded_recipients)
+        }
+    };
+    let protected = encode_protected_algorithm(algorithm.cose_id());
+    let iv = generate_random(algorithm.iv_length())?;
+    let aad = enc_structure("Encrypt", &protected, external_aad);
+    let ciphertext = seal(algorithm, &content_key, &iv, &aad, plaintext)?;
+    let mut unprotected = unprotected_kid(None);
+    unprotected.insert(label(HEADER_IV), CborType::BStr(iv));
+    let elements = vec![CborType::BStr(protected), CborType::Map(unprotected),
+                        CborType::BStr(ciphertext), CborType::Arr(encoded_recipients)];
+    Ok(CborType::Tag(COSE_ENCRYPT_TAG, Box::new(CborType::Arr(elements))).serialize())
+}
+
+/// Decrypts a COSE_Encrypt with the first recipient whose content key can be recovered with one
+/// of the given keys and returns the plaintext. Recipients are matched to keys by kid. If no
+/// recipient is for one of the given keys, the error is VerifyError::UnknownKeyId.
+///
+/// COSE_Encrypt = [ protected: bstr, unprotected: map, ciphertext: bstr / nil,
+///                  recipients: [+ COSE_recipient] ]
+pub fn decrypt_cose_encrypt(cose_encrypt: &[u8], detached_ciphertext: Option<&[u8]>,
+                            external_aad: &[u8], keys: &[RecipientKey])
+                            -> Result<Vec<u8>, VerifyError> {
+    let item = decode(cose_encrypt).map_err(|_| VerifyError::DecodingCBORFailed)?;
+    let elements = match strip_tag(item, COSE_ENCRYPT_TAG)? {
+        CborType::Arr(elements) if elements.len() == 4 => elements,
+        _ => return Err(VerifyError::DecodingCOSEFailed),
+    };
+    let headers = Headers::decode(&elements[0], &elements[1])?;
+    let ciphertext = decode_payload(&elements[2], detached_ciphertext)?;
+    let recipients = decode_recipients(&elements[3])?;
+    let algorithm = encryption_algorithm(&headers)?;
+    let iv = decode_iv(&headers, algorithm)?;
+    let aad = enc_structure("Encrypt", &headers.protected_bytes, external_aad);
+    with_content_key(&recipients, keys, algorithm.cose_id(), algorithm.key_length(),
+                     |content_key| open(algorithm, content_key, iv, &aad, &ciphertext))
+}
+
+#[test]
+fn test_algorithms() {
+    for &id in &[1, 2, 3, 10, 11, 12, 13, 24, 30, 31, 32, 33] {
+        let algorithm = EncryptionAlgorithm::from_cose_id(id).unwrap();
+        assert_eq!(algorithm.cose_id(), id);
+    }
+    assert_eq!(EncryptionAlgorithm::from_cose_id(4), None);
+    let ccm = EncryptionAlgorithm::AesCcm16_64_128;
+    assert_eq!((ccm.key_length(), ccm.iv_length(), ccm.tag_length()), (16, 13, 8));
+    assert_eq!(ccm.max_plaintext_length(), Some(0xffff));
+    let ccm = EncryptionAlgorithm::AesCcm64_128_256;
+    assert_eq!((ccm.key_length(), ccm.iv_length(), ccm.tag_length()), (32, 7, 16));
+    assert_eq!(ccm.max_plaintext_length(), Some(u64::MAX));
+    let chacha = EncryptionAlgorithm::ChaCha20Poly1305;
+    assert_eq!((chacha.key_length(), chacha.iv_length(), chacha.tag_length()), (32, 12, 16));
+    assert_eq!(chacha.max_plaintext_length(), None);
+}
+
+#[test]
+fn test_enc_structure() {
+    assert_eq!(enc_structure("Encrypt0", &[0xa1, 0x01, 0x01], &[]),
+               vec![0x83, 0x68, 0x45, 0x6e, 0x63, 0x72, 0x79, 0x70, 0x74, 0x30, 0x43, 0xa1, 0x01,
+                    0x01, 0x40]);
+}
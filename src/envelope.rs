@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+use content::ContentType;
+use cose::{decode_cose_sign1, label, unprotected_kid, Headers, HEADER_ALG, HEADER_CONTENT_TYPE};
+use digest::{digest, HashAlgorithm};
+use nss::secure_compare;
+use policy::{verify_cose_sign1, TrustedKey};
+use serialize::CborType;
+use sign::{create_cose_sign1_with_headers, SigningKey};
+use verify::{SignatureAlgorithm, VerifyError};
+
+// See draft-ietf-cose-hash-envelope, Section 4 and the IANA "COSE Header Parameters" registry.
+pub const HEADER_PAYLOAD_HASH_ALG: i64 = 258;
+pub const HEADER_PREIMAGE_CONTENT_TYPE: i64 = 259;
+pub const HEADER_PAYLOAD_LOCATION: i64 = 260;
+
+/// The headers of a hash envelope, which describe the artifact whose hash is the payload.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HashEnvelopeHeaders {
+    pub hash_algorithm: HashAlgorithm,
+    /// The content type of the artifact.
+    pub preimage_content_type: Option<ContentType>,
+    /// Where the artifact can be found, such as a URI.
+    pub payload_location: Option<String>,
+}
+
+impl HashEnvelopeHeaders {
+    /// Decodes the hash envelope headers. The hash algorithm must be protected, and there may not
+    /// be a content type, since the payload is the hash rather than the artifact.
+    fn decode(headers: &Headers) -> Result<HashEnvelopeHeaders, VerifyError> {
+        if headers.get(HEADER_CONTENT_TYPE).is_some() ||
+           headers.unprotected.contains_key(&label(HEADER_PAYLOAD_HASH_ALG)) {
+            return Err(VerifyError::DecodingCOSEFailed);
+        }
+        let hash_algorithm = match headers.protected.get(&label(HEADER_PAYLOAD_HASH_ALG)) {
+            Some(&CborType::NInt(id)) => {
+                HashAlgorithm::from_cose_id(id).ok_or(VerifyError::UnsupportedAlgorithm)?
+            }
+            Some(&CborType::UInt(_)) => return Err(VerifyError::UnsupportedAlgorithm),
+            _ => return Err(VerifyError::DecodingCOSEFailed),
+        };
+        let preimage_content_type = match headers.get(HEADER_PREIMAGE_CONTENT_TYPE) {
+            Some(value) => Some(ContentType::from_cbor(value)?),
+            None => None,
+        };
+        let payload_location = match headers.get(HEADER_PAYLOAD_LOCATION) {
+            Some(CborType::TStr(location)) => Some(location.clone()),
+            Some(_) => return Err(VerifyError::DecodingCOSEFailed),
+            None => None,
+        };
+        Ok(HashEnvelopeHeaders {
+            hash_algorithm,
+            preimage_content_type,
+            payload_location,
+        })
+    }
+}
+
+/// Creates a tagged COSE_Sign1 hash envelope for the given artifact: the payload is the hash of the
+/// artifact, and the hash envelope headers go in the protected header along with the algorithm.
+pub fn create_hash_envelope(signature_algorithm: SignatureAlgorithm, key: &SigningKey,
+                            kid: Option<&[u8]>, envelope_headers: &HashEnvelopeHeaders,
+                            artifact: &[u8]) -> Result<Vec<u8>, VerifyError> {
+    let payload_hash = digest(envelope_headers.hash_algorithm, artifact)?;
+    let mut protected = BTreeMap::new();
+    protected.insert(label(HEADER_ALG), label(signature_algorithm.cose_id()));
+    protected.insert(label(HEADER_PAYLOAD_HASH_ALG),
+                     label(envelope_headers.hash_algorithm.cose_id()));
+    if let Some(ref content_type) = envelope_headers.preimage_content_type {
+        protected.insert(label(HEADER_PREIMAGE_CONTENT_TYPE), content_type.to_cbor());
+    }
+    if let Some(ref location) = envelope_headers.payload_location {
+        protected.insert(label(HEADER_PAYLOAD_LOCATION), CborType::TStr(location.clone()));
+    }
+    let headers = Headers::new(protected, unprotected_kid(kid));
+    create_cose_sign1_with_headers(signature_algorithm, key, &headers, &payload_hash)
+}
+
+/// Verifies a COSE_Sign1 hash envelope with the trusted key its kid identifies, hashes the given
+/// artifact with the algorithm in the envelope and checks that it matches the payload. Returns the
+/// hash envelope headers.
+pub fn verify_hash_envelope(cose_sign1: &[u8], trusted_keys: &[TrustedKey], artifact: &[u8])
+                            -> Result<HashEnvelopeHeaders, VerifyError> {
+    let payload_hash = verify_cose_sign1(cose_sign1, None, trusted_keys)?;
+    let headers = decode_cose_sign1(cose_sign1, None)?.headers;
+    let envelope_headers = HashEnvelopeHeaders::decode(&headers)?;
+    if payload_hash.len() != envelope_headers.hash_algorithm.output_length() {
+        return Err(VerifyError::DecodingCOSEFailed);
+    }
+    if !secure_compare(&digest(envelope_headers.hash_algorithm, artifact)?, &payload_hash) {
+        return Err(VerifyError::PayloadHashMismatch);
+    }
+    Ok(envelope_headers)
+}
+
+#[test]
+fn test_decode_hash_envelope_headers() {
+    let mut protected = BTreeMap::new();
+    protected.insert(label(HEADER_ALG), label(-7));
+    protected.insert(label(HEADER_PAYLOAD_HASH_ALG), label(-43));
+    let mut unprotected = BTreeMap::new();
+    unprotected.insert(label(HEADER_PREIMAGE_CONTENT_TYPE), CborType::UInt(42));
+    let headers = Headers::new(protected.clone(), unprotected.clone());
+    assert_eq!(HashEnvelopeHeaders::decode(&headers),
+               Ok(HashEnvelopeHeaders {
+                   hash_algorithm: HashAlgorithm::SHA384,
+                   preimage_content_type: Some(ContentType::CoapContentFormat(42)),
+                   payload_location: None,
+               }));
+    let mut with_content_type = unprotected.clone();
+    with_content_type.insert(label(HEADER_CONTENT_TYPE), CborType::UInt(42));
+    assert_eq!(HashEnvelopeHeaders::decode(&Headers::new(protected.clone(), with_content_type)),
+               Err(VerifyError::DecodingCOSEFailed));
+    protected.insert(label(HEADER_PAYLOAD_HASH_ALG), label(-1));
+    assert_eq!(HashEnvelopeHeaders::decode(&Headers::new(protected.clone(), unprotected.clone())),
+               Err(VerifyError::UnsupportedAlgorithm));
+    protected.remove(&label(HEADER_PAYLOAD_HASH_ALG));
+    unprotected.insert(label(HEADER_PAYLOAD_HASH_ALG), label(-16));
+    assert_eq!(HashEnvelopeHeaders::decode(&Headers::new(protected, unprotected)),
+               Err(VerifyError::DecodingCOSEFailed));
+}
@@ -0,0 +1,386 @@
+use std::char;
+use std::collections::BTreeMap;
+
+/// Nested arrays and objects beyond this depth are rejected, as with CBOR.
+const MAX_NESTING_DEPTH: usize = 64;
+
+/// Just enough JSON (RFC 8259) to read and write JWKs. Numbers are kept as their text, because
+/// nothing here needs their value. Object members are kept sorted by name, so serialization is
+/// deterministic.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(BTreeMap<String, JsonValue>),
+}
+
+/// An error type describing errors that may be encountered while parsing JSON.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonError {
+    DuplicateMember,
+    MalformedInput,
+    NestingTooDeep,
+    TrailingCharacters,
+    TruncatedInput,
+}
+
+struct ParserCursor<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    depth: usize,
+}
+
+impl<'a> ParserCursor<'a> {
+    fn peek(&self) -> Result<u8, JsonError> {
+        self.bytes.get(self.offset).cloned().ok_or(JsonError::TruncatedInput)
+    }
+
+    fn read_byte(&mut self) -> Result<u8, JsonError> {
+        let byte = self.peek()?;
+        self.offset += 1;
+        Ok(byte)
+    }
+
+    fn expect(&mut self, expected: &[u8]) -> Result<(), JsonError> {
+        for byte in expected {
+            if self.read_byte()? != *byte {
+                return Err(JsonError::MalformedInput);
+            }
+        }
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(&byte) = self.bytes.get(self.offset) {
+            if byte != b' ' && byte != b'\t' && byte != b'\n' && byte != b'\r' {
+                break;
+            }
+            self.offset += 1;
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue, JsonError> {
+        self.skip_whitespace();
+        let value = match self.peek()? {
+            b'n' => self.expect(b"null").map(|_| JsonValue::Null)?,
+            b't' => self.expect(b"true").map(|_| JsonValue::Bool(true))?,
+            b'f' => self.expect(b"false").map(|_| JsonValue::Bool(false))?,
+            b'"' => JsonValue::String(self.parse_string()?),
+            b'[' => self.parse_array()?,
+            b'{' => self.parse_object()?,
+            b'-' | b'0'..=b'9' => self.parse_number()?,
+            _ => return Err(JsonError::MalformedInput),
+        };
+        self.skip_whitespace();
+        Ok(value)
+    }
+
+    fn enter(&mut self) -> Result<(), JsonError> {
+        self.depth += 1;
+        if self.depth > MAX_NESTING_DEPTH {
+            return Err(JsonError::NestingTooDeep);
+        }
+        Ok(())
+    }
+
+    fn parse_array(&mut self) -> Result<JsonValue, JsonError> {
+        self.enter()?;
+        self.expect(b"[")?;
+        let mut elements = Vec::new();
+        self.skip_whitespace();
+        if self.peek()? == b']' {
+            self.offset += 1;
+        } else {
+            loop {
+                elements.push(self.parse_value()?);
+                match self.read_byte()? {
+                    b',' => continue,
+                    b']' => break,
+                    _ => return Err(JsonError::MalformedInput),
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(JsonValue::Array(elements))
+    }
+
+    fn parse_object(&mut self) -> Result<JsonValue, JsonError> {
+        self.enter()?;
+        self.expect(b"{")?;
+        let mut members = BTreeMap::new();
+        self.skip_whitespace();
+        if self.peek()? == b'}' {
+            self.offset += 1;
+        } else {
+            loop {
+                self.skip_whitespace();
+                let name = self.parse_string()?;
+                self.skip_whitespace();
+                self.expect(b":")?;
+                let value = self.parse_value()?;
+                if members.insert(name, value).is_some() {
+                    return Err(JsonError::DuplicateMember);
+                }
+                match self.read_byte()? {
+                    b',' => continue,
+                    b'}' => break,
+                    _ => return Err(JsonError::MalformedInput),
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(JsonValue::Object(members))
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, JsonError> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let digit = (self.read_byte()? as char).to_digit(16).ok_or(JsonError::MalformedInput)?;
+            value = (value << 4) | digit;
+        }
+        Ok(value)
+    }
+
+    fn parse_string(&mut self) -> Result<String, JsonError> {
+        self.expect(b"\"")?;
+        let mut bytes = Vec::new();
+        loop {
+            match self.read_byte()? {
+                b'"' => break,
+                b'\\' => {
+                    let unescaped = match self.read_byte()? {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.parse_unicode_escape()?,
+                        _ => return Err(JsonError::MalformedInput),
+                    };
+                    let mut buffer = [0; 4];
+                    bytes.extend_from_slice(unescaped.encode_utf8(&mut buffer).as_bytes());
+                }
+                byte if byte < 0x20 => return Err(JsonError::MalformedInput),
+                byte => bytes.push(byte),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| JsonError::MalformedInput)
+    }
+
+    /// Parses the XXXX of a \uXXXX escape, along with the low surrogate escape that must follow a
+    /// high surrogate.
+    fn parse_unicode_escape(&mut self) -> Result<char, JsonError> {
+        let code_unit = self.parse_hex4()?;
+        let code_point = match code_unit {
+            0xd800..=0xdbff => {
+                self.expect(b"\\u")?;
+                let low = self.parse_hex4()?;
+                if !(0xdc00..=0xdfff).contains(&low) {
+                    return Err(JsonError::MalformedInput);
+                }
+                0x10000 + ((code_unit - 0xd800) << 10) + (low - 0xdc00)
+            }
+            _ => code_unit,
+        };
+        char::from_u32(code_point).ok_or(JsonError::MalformedInput)
+    }
+
+    fn read_digits(&mut self) -> Result<usize, JsonError> {
+        let start = self.offset;
+        while let Some(b'0'..=b'9') = self.bytes.get(self.offset) {
+            self.offset += 1;
+        }
+        if self.offset == start {
+            return Err(JsonError::MalformedInput);
+        }
+        Ok(self.offset - start)
+    }
+
+    /// number = [ minus ] int [ frac ] [ exp ]
+    fn parse_number(&mut self) -> Result<JsonValue, JsonError> {
+        let start = self.offset;
+        if self.peek()? == b'-' {
+            self.offset += 1;
+        }
+        let int_start = self.offset;
+        if self.read_digits()? > 1 && self.bytes[int_start] == b'0' {
+            return Err(JsonError::MalformedInput);
+        }
+        if self.bytes.get(self.offset) == Some(&b'.') {
+            self.offset += 1;
+            self.read_digits()?;
+        }
+        if let Some(b'e') | Some(b'E') = self.bytes.get(self.offset) {
+            self.offset += 1;
+            if let Some(b'+') | Some(b'-') = self.bytes.get(self.offset) {
+                self.offset += 1;
+            }
+            self.read_digits()?;
+        }
+        // The bytes are all ASCII, so this can't fail.
+        let text = String::from_utf8(self.bytes[start..self.offset].to_vec())
+            .map_err(|_| JsonError::MalformedInput)?;
+        Ok(JsonValue::Number(text))
+    }
+}
+
+/// Parses the given text as exactly one JSON value, surrounded by optional whitespace.
+pub fn parse(text: &str) -> Result<JsonValue, JsonError> {
+    let mut cursor = ParserCursor { bytes: text.as_bytes(), offset: 0, depth: 0 };
+    let value = cursor.parse_value()?;
+    if cursor.offset != text.len() {
+        return Err(JsonError::TrailingCharacters);
+    }
+    Ok(value)
+}
+
+fn serialize_string(output: &mut String, string: &str) {
+    output.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+}
+
+impl JsonValue {
+    fn serialize_into(&self, output: &mut String) {
+        match *self {
+            JsonValue::Null => output.push_str("null"),
+            JsonValue::Bool(true) => output.push_str("true"),
+            JsonValue::Bool(false) => output.push_str("false"),
+            JsonValue::Number(ref text) => output.push_str(text),
+            JsonValue::String(ref string) => serialize_string(output, string),
+            JsonValue::Array(ref elements) => {
+                output.push('[');
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        output.push(',');
+                    }
+                    element.serialize_into(output);
+                }
+                output.push(']');
+            }
+            JsonValue::Object(ref members) => {
+                output.push('{');
+                for (i, (name, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        output.push(',');
+                    }
+                    serialize_string(output, name);
+                    output.push(':');
+                    value.serialize_into(output);
+                }
+                output.push('}');
+            }
+        }
+    }
+
+    /// Serializes this value without any insignificant whitespace.
+    pub fn serialize(&self) -> String {
+        let mut output = String::new();
+        self.serialize_into(&mut output);
+        output
+    }
+}
+
+const BASE64URL_ALPHABET: &[u8] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Encodes the given bytes as base64url without padding (RFC 7515, Section 2).
+pub fn base64url_encode(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity((bytes.len() * 4).div_ceil(3));
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate()
+            .fold(0u32, |group, (i, byte)| group | (u32::from(*byte) << (16 - 8 * i)));
+        for i in 0..chunk.len() + 1 {
+            output.push(BASE64URL_ALPHABET[((group >> (18 - 6 * i)) & 0x3f) as usize] as char);
+        }
+    }
+    output
+}
+
+/// Decodes base64url without padding. Padding, other characters, and encodings with non-zero
+/// unused bits are rejected, so each byte string has exactly one accepted encoding.
+pub fn base64url_decode(text: &str) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(text.len() * 3 / 4);
+    for chunk in text.as_bytes().chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let mut group = 0u32;
+        for (i, c) in chunk.iter().enumerate() {
+            let value = BASE64URL_ALPHABET.iter().position(|a| a == c)? as u32;
+            group |= value << (18 - 6 * i);
+        }
+        let byte_count = chunk.len() - 1;
+        if group & (0xff_ffff >> (8 * byte_count)) != 0 {
+            return None;
+        }
+        for i in 0..byte_count {
+            output.push((group >> (16 - 8 * i)) as u8);
+        }
+    }
+    Some(output)
+}
+
+#[test]
+fn test_parse() {
+    let text = " {\"b\": [1, -2.5e+3, true, false, null], \"a\": \"x\\u00e9\\ud83d\\ude00\"} ";
+    let value = parse(text).unwrap();
+    let mut members = BTreeMap::new();
+    members.insert(String::from("a"), JsonValue::String(String::from("x\u{e9}\u{1f600}")));
+    members.insert(String::from("b"),
+                   JsonValue::Array(vec![JsonValue::Number(String::from("1")),
+                                         JsonValue::Number(String::from("-2.5e+3")),
+                                         JsonValue::Bool(true),
+                                         JsonValue::Bool(false),
+                                         JsonValue::Null]));
+    assert_eq!(value, JsonValue::Object(members));
+    assert_eq!(value.serialize(),
+               "{\"a\":\"x\u{e9}\u{1f600}\",\"b\":[1,-2.5e+3,true,false,null]}");
+    assert_eq!(parse("[]"), Ok(JsonValue::Array(vec![])));
+    assert_eq!(JsonValue::String(String::from("\"\\\n\u{1}")).serialize(),
+               "\"\\\"\\\\\\n\\u0001\"");
+}
+
+#[test]
+fn test_parse_errors() {
+    assert_eq!(parse(""), Err(JsonError::TruncatedInput));
+    assert_eq!(parse("{\"a\":1,\"a\":2}"), Err(JsonError::DuplicateMember));
+    assert_eq!(parse("[1,]"), Err(JsonError::MalformedInput));
+    assert_eq!(parse("01"), Err(JsonError::MalformedInput));
+    assert_eq!(parse("1."), Err(JsonError::MalformedInput));
+    assert_eq!(parse("\"\\ud83d\""), Err(JsonError::MalformedInput));
+    assert_eq!(parse("\"\n\""), Err(JsonError::MalformedInput));
+    assert_eq!(parse("{} x"), Err(JsonError::TrailingCharacters));
+    assert_eq!(parse(&"[".repeat(65)), Err(JsonError::NestingTooDeep));
+}
+
+#[test]
+fn test_base64url() {
+    assert_eq!(base64url_encode(b""), "");
+    assert_eq!(base64url_encode(b"f"), "Zg");
+    assert_eq!(base64url_encode(b"fo"), "Zm8");
+    assert_eq!(base64url_encode(b"foo"), "Zm9v");
+    assert_eq!(base64url_encode(&[0xfb, 0xff]), "-_8");
+    assert_eq!(base64url_decode("Zm9vYg"), Some(b"foob".to_vec()));
+    assert_eq!(base64url_decode("-_8"), Some(vec![0xfb, 0xff]));
+    assert_eq!(base64url_decode("Zh"), None);
+    assert_eq!(base64url_decode("Zg=="), None);
+    assert_eq!(base64url_decode("Z"), None);
+    assert_eq!(base64url_decode("Zm+v"), None);
+}
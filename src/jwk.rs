@@ -0,0 +1,267 @@
+use std::collections::BTreeMap;
+use cose::label;
+use json::{base64url_decode, base64url_encode, parse, JsonValue};
+use key::{CoseKey, CoseKeySet, Curve, KeyOperation, KeyParameters, EC2_CRV, EC2_X, EC2_Y,
+          KEY_ALG, KEY_KEY_OPS, KEY_KID, KEY_KTY, KTY_EC2, KTY_OKP, KTY_RSA, RSA_E, RSA_N};
+use serialize::CborType;
+use verify::VerifyError;
+
+// The algorithms with both a COSE and a JOSE identifier, from the IANA "COSE Algorithms" and "JSON
+// Web Signature and Encryption Algorithms" registries.
+const ALGORITHMS: [(i64, &str); 13] = [
+    (-7, "ES256"),
+    (-35, "ES384"),
+    (-36, "ES512"),
+    (-8, "EdDSA"),
+    (-37, "PS256"),
+    (-38, "PS384"),
+    (-39, "PS512"),
+    (-257, "RS256"),
+    (-258, "RS384"),
+    (-259, "RS512"),
+    (-29, "ECDH-ES+A128KW"),
+    (-30, "ECDH-ES+A192KW"),
+    (-31, "ECDH-ES+A256KW"),
+];
+
+// See RFC 7518, Section 6.2.1.1 and RFC 8037, Section 2.
+const CURVES: [(Curve, &str); 7] = [
+    (Curve::P256, "P-256"),
+    (Curve::P384, "P-384"),
+    (Curve::P521, "P-521"),
+    (Curve::X25519, "X25519"),
+    (Curve::X448, "X448"),
+    (Curve::Ed25519, "Ed25519"),
+    (Curve::Ed448, "Ed448"),
+];
+
+// See RFC 7517, Section 4.3. JOSE has no equivalent of the MAC operations.
+const KEY_OPERATIONS: [(KeyOperation, &str); 8] = [
+    (KeyOperation::Sign, "sign"),
+    (KeyOperation::Verify, "verify"),
+    (KeyOperation::Encrypt, "encrypt"),
+    (KeyOperation::Decrypt, "decrypt"),
+    (KeyOperation::WrapKey, "wrapKey"),
+    (KeyOperation::UnwrapKey, "unwrapKey"),
+    (KeyOperation::DeriveKey, "deriveKey"),
+    (KeyOperation::DeriveBits, "deriveBits"),
+];
+
+fn get_string<'a>(jwk: &'a BTreeMap<String, JsonValue>, name: &str)
+                  -> Result<Option<&'a str>, VerifyError> {
+    match jwk.get(name) {
+        None => Ok(None),
+        Some(JsonValue::String(value)) => Ok(Some(value)),
+        Some(_) => Err(VerifyError::DecodingJWKFailed),
+    }
+}
+
+/// Returns the base64url-decoded value of a required member.
+fn get_bytes(jwk: &BTreeMap<String, JsonValue>, name: &str) -> Result<CborType, VerifyError> {
+    let value = get_string(jwk, name)?.ok_or(VerifyError::DecodingJWKFailed)?;
+    Ok(CborType::BStr(base64url_decode(value).ok_or(VerifyError::DecodingJWKFailed)?))
+}
+
+fn get_curve(jwk: &BTreeMap<String, JsonValue>) -> Result<CborType, VerifyError> {
+    let name = get_string(jwk, "crv")?.ok_or(VerifyError::DecodingJWKFailed)?;
+    let curve = CURVES.iter().find(|&&(_, curve_name)| curve_name == name)
+        .ok_or(VerifyError::UnsupportedKey)?;
+    Ok(label(curve.0.cose_id()))
+}
+
+fn decode_key_ops(value: &JsonValue) -> Result<CborType, VerifyError> {
+    let key_ops = match *value {
+        JsonValue::Array(ref key_ops) => key_ops,
+        _ => return Err(VerifyError::DecodingJWKFailed),
+    };
+    let mut decoded = Vec::with_capacity(key_ops.len());
+    for key_op in key_ops {
+        let key_op = match *key_op {
+            JsonValue::String(ref key_op) => key_op,
+            _ => return Err(VerifyError::DecodingJWKFailed),
+        };
+        let key_op = KEY_OPERATIONS.iter().find(|&&(_, name)| name == key_op)
+            .ok_or(VerifyError::UnsupportedKey)?;
+        decoded.push(label(key_op.0.cose_id()));
+    }
+    Ok(CborType::Arr(decoded))
+}
+
+impl CoseKey {
+    /// Converts a public JWK (RFC 7517) with a kty of "EC", "RSA" or "OKP". The key is validated
+    /// as any COSE_Key would be. A kid is taken to be the UTF-8 encoding of the JWK's "kid".
+    /// Private key and unrecognized members are ignored.
+    pub fn from_jwk(jwk: &str) -> Result<CoseKey, VerifyError> {
+        let jwk = parse(jwk).map_err(|_| VerifyError::DecodingJSONFailed)?;
+        CoseKey::from_json(&jwk)
+    }
+
+    fn from_json(jwk: &JsonValue) -> Result<CoseKey, VerifyError> {
+        let jwk = match *jwk {
+            JsonValue::Object(ref jwk) => jwk,
+            _ => return Err(VerifyError::DecodingJWKFailed),
+        };
+        let mut map = BTreeMap::new();
+        match get_string(jwk, "kty")? {
+            Some("EC") => {
+                map.insert(label(KEY_KTY), label(KTY_EC2));
+                map.insert(label(EC2_CRV), get_curve(jwk)?);
+                map.insert(label(EC2_X), get_bytes(jwk, "x")?);
+                map.insert(label(EC2_Y), get_bytes(jwk, "y")?);
+            }
+            Some("OKP") => {
+                map.insert(label(KEY_KTY), label(KTY_OKP));
+                map.insert(label(EC2_CRV), get_curve(jwk)?);
+                map.insert(label(EC2_X), get_bytes(jwk, "x")?);
+            }
+            Some("RSA") => {
+                map.insert(label(KEY_KTY), label(KTY_RSA));
+                map.insert(label(RSA_N), get_bytes(jwk, "n")?);
+                map.insert(label(RSA_E), get_bytes(jwk, "e")?);
+            }
+            Some(_) => return Err(VerifyError::UnsupportedKey),
+            None => return Err(VerifyError::DecodingJWKFailed),
+        }
+        if let Some(kid) = get_string(jwk, "kid")? {
+            map.insert(label(KEY_KID), CborType::BStr(kid.as_bytes().to_vec()));
+        }
+        if let Some(name) = get_string(jwk, "alg")? {
+            let alg = ALGORITHMS.iter().find(|&&(_, alg_name)| alg_name == name)
+                .ok_or(VerifyError::UnsupportedAlgorithm)?;
+            map.insert(label(KEY_ALG), label(alg.0));
+        }
+        if let Some(key_ops) = jwk.get("key_ops") {
+            map.insert(label(KEY_KEY_OPS), decode_key_ops(key_ops)?);
+        }
+        CoseKey::from_cbor(&CborType::Map(map))
+    }
+
+    /// Returns the JWK equivalent to this key. Fails if the kid is not valid UTF-8 or the alg or
+    /// one of the key_ops has no JOSE equivalent, as the conversion would not be lossless.
+    pub fn to_jwk(&self) -> Result<String, VerifyError> {
+        Ok(self.to_json()?.serialize())
+    }
+
+    fn to_json(&self) -> Result<JsonValue, VerifyError> {
+        let mut jwk = BTreeMap::new();
+        let string = |value: &str| JsonValue::String(String::from(value));
+        let bytes = |value: &[u8]| JsonValue::String(base64url_encode(value));
+        let curve_name = |curve: Curve| {
+            CURVES.iter().find(|&&(jwk_curve, _)| jwk_curve == curve).map(|&(_, name)| name)
+        };
+        match self.parameters {
+            KeyParameters::EC2 { curve, ref x, ref y } => {
+                jwk.insert(String::from("kty"), string("EC"));
+                jwk.insert(String::from("crv"), string(curve_name(curve).unwrap_or_default()));
+                jwk.insert(String::from("x"), bytes(x));
+                jwk.insert(String::from("y"), bytes(y));
+            }
+            KeyParameters::OKP { curve, ref x } => {
+                jwk.insert(String::from("kty"), string("OKP"));
+                jwk.insert(String::from("crv"), string(curve_name(curve).unwrap_or_default()));
+                jwk.insert(String::from("x"), bytes(x));
+            }
+            KeyParameters::RSA { ref n, ref e } => {
+                jwk.insert(String::from("kty"), string("RSA"));
+                jwk.insert(String::from("n"), bytes(n));
+                jwk.insert(String::from("e"), bytes(e));
+            }
+        }
+        if let Some(ref kid) = self.kid {
+            let kid = String::from_utf8(kid.clone()).map_err(|_| VerifyError::UnsupportedKey)?;
+            jwk.insert(String::from("kid"), JsonValue::String(kid));
+        }
+        if let Some(alg) = self.alg {
+            let name = ALGORITHMS.iter().find(|&&(cose_alg, _)| cose_alg == alg)
+                .ok_or(VerifyError::UnsupportedAlgorithm)?.1;
+            jwk.insert(String::from("alg"), string(name));
+        }
+        if !self.key_ops.is_empty() {
+            let mut key_ops = Vec::with_capacity(self.key_ops.len());
+            for key_op in &self.key_ops {
+                let name = KEY_OPERATIONS.iter().find(|&&(operation, _)| operation == *key_op)
+                    .ok_or(VerifyError::UnsupportedKey)?.1;
+                key_ops.push(string(name));
+            }
+            jwk.insert(String::from("key_ops"), JsonValue::Array(key_ops));
+        }
+        Ok(JsonValue::Object(jwk))
+    }
+}
+
+impl CoseKeySet {
+    /// Converts a JWK Set (RFC 7517, Section 5). As the RFC requires, keys of an unsupported type
+    /// or with an unsupported curve, algorithm or operation are skipped. Any other invalid key is
+    /// an error.
+    pub fn from_jwk_set(jwk_set: &str) -> Result<CoseKeySet, VerifyError> {
+        let jwk_set = parse(jwk_set).map_err(|_| VerifyError::DecodingJSONFailed)?;
+        let jwks = match jwk_set {
+            JsonValue::Object(ref members) => match members.get("keys") {
+                Some(JsonValue::Array(jwks)) => jwks,
+                _ => return Err(VerifyError::DecodingJWKFailed),
+            },
+            _ => return Err(VerifyError::DecodingJWKFailed),
+        };
+        let mut keys = Vec::with_capacity(jwks.len());
+        for jwk in jwks {
+            match CoseKey::from_json(jwk) {
+                Ok(key) => keys.push(key),
+                Err(VerifyError::UnsupportedKey) | Err(VerifyError::UnsupportedAlgorithm) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(CoseKeySet { keys })
+    }
+
+    /// Returns the JWK Set equivalent to this key set. Fails if any key can't be converted
+    /// losslessly.
+    pub fn to_jwk_set(&self) -> Result<String, VerifyError> {
+        let mut jwks = Vec::with_capacity(self.keys.len());
+        for key in &self.keys {
+            jwks.push(key.to_json()?);
+        }
+        let mut jwk_set = BTreeMap::new();
+        jwk_set.insert(String::from("keys"), JsonValue::Array(jwks));
+        Ok(JsonValue::Object(jwk_set).serialize())
+    }
+}
+
+#[test]
+fn test_jwk_round_trip() {
+    // The example public keys from RFC 7517, Appendix A.1, with the "use" members removed.
+    let ec = "{\"alg\":\"ES256\",\"crv\":\"P-256\",\"kid\":\"1\",\"kty\":\"EC\",\
+              \"x\":\"MKBCTNIcKUSDii11ySs3526iDZ8AiTo7Tu6KPAqv7D4\",\
+              \"y\":\"4Etl6SRW2YiLUrN5vfvVHuhp7x8PxltmWWlbbM4IFyM\"}";
+    let key = CoseKey::from_jwk(ec).unwrap();
+    assert_eq!(key.kid, Some(b"1".to_vec()));
+    assert_eq!(key.alg, Some(-7));
+    assert_eq!(key.to_jwk(), Ok(String::from(ec)));
+    let okp = "{\"crv\":\"Ed25519\",\"key_ops\":[\"verify\"],\"kty\":\"OKP\",\
+               \"x\":\"11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo\"}";
+    let key = CoseKey::from_jwk(okp).unwrap();
+    assert_eq!(key.key_ops, vec![KeyOperation::Verify]);
+    assert_eq!(key.to_jwk(), Ok(String::from(okp)));
+}
+
+#[test]
+fn test_jwk_errors() {
+    assert_eq!(CoseKey::from_jwk("{"), Err(VerifyError::DecodingJSONFailed));
+    assert_eq!(CoseKey::from_jwk("{\"kty\":\"oct\",\"k\":\"AA\"}"),
+               Err(VerifyError::UnsupportedKey));
+    assert_eq!(CoseKey::from_jwk("{\"kty\":\"OKP\",\"crv\":\"Ed25519\"}"),
+               Err(VerifyError::DecodingJWKFailed));
+    assert_eq!(CoseKey::from_jwk("{\"kty\":\"OKP\",\"crv\":\"Ed25519\",\"x\":\"AA\"}"),
+               Err(VerifyError::InvalidKey));
+    assert_eq!(CoseKey::from_jwk("{\"kty\":\"OKP\",\"crv\":\"Ed25519\",\"alg\":\"ES256\",\
+                                  \"x\":\"11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo\"}"),
+               Err(VerifyError::InvalidKey));
+    let key = CoseKey {
+        kid: Some(vec![0xff]),
+        alg: None,
+        key_ops: Vec::new(),
+        parameters: KeyParameters::OKP { curve: Curve::X25519, x: vec![0; 32] },
+    };
+    assert_eq!(key.to_jwk(), Err(VerifyError::UnsupportedKey));
+    let key = CoseKey { kid: None, alg: Some(-25), ..key };
+    assert_eq!(key.to_jwk(), Err(VerifyError::UnsupportedAlgorithm));
+}
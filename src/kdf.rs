@@ -0,0 +1,189 @@
+use cose::{label, Headers};
+use mac::{compute_tag, MacAlgorithm};
+use serialize::CborType;
+use verify::VerifyError;
+
+// See RFC 9053, Sections 5.1 and 6.3.1 and the IANA "COSE Header Algorithm Parameters" registry.
+pub const HEADER_EPHEMERAL_KEY: i64 = -1;
+pub const HEADER_STATIC_KEY: i64 = -2;
+pub const HEADER_STATIC_KEY_ID: i64 = -3;
+pub const HEADER_SALT: i64 = -20;
+pub const HEADER_PARTY_U_IDENTITY: i64 = -21;
+pub const HEADER_PARTY_U_NONCE: i64 = -22;
+pub const HEADER_PARTY_U_OTHER: i64 = -23;
+pub const HEADER_PARTY_V_IDENTITY: i64 = -24;
+pub const HEADER_PARTY_V_NONCE: i64 = -25;
+pub const HEADER_PARTY_V_OTHER: i64 = -26;
+
+/// The hash functions HKDF may be used with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HkdfHash {
+    SHA256,
+    SHA512,
+}
+
+/// HKDF (RFC 5869) with the given hash. An empty salt is equivalent to the absent salt, a string
+/// of zeros as long as the hash output.
+pub fn hkdf(hash: HkdfHash, salt: &[u8], ikm: &[u8], info: &[u8], length: usize)
+            -> Result<Vec<u8>, VerifyError> {
+    let algorithm = match hash {
+        HkdfHash::SHA256 => MacAlgorithm::HMAC256_256,
+        HkdfHash::SHA512 => MacAlgorithm::HMAC512_512,
+    };
+    let hash_length = algorithm.tag_length();
+    if length > 255 * hash_length {
+        return Err(VerifyError::InputTooLarge);
+    }
+    let zeros = vec![0; hash_length];
+    let prk = compute_tag(algorithm, if salt.is_empty() { &zeros } else { salt }, ikm)?;
+    let mut okm = Vec::with_capacity(length + hash_length);
+    let mut block = Vec::new();
+    // The length check above keeps this to at most 255 blocks.
+    let mut counter: u8 = 0;
+    while okm.len() < length {
+        counter += 1;
+        block.extend_from_slice(info);
+        block.push(counter);
+        block = compute_tag(algorithm, &prk, &block)?;
+        okm.extend_from_slice(&block);
+    }
+    okm.truncate(length);
+    Ok(okm)
+}
+
+/// The PartyUInfo or PartyVInfo of a COSE_KDF_Context. Each value is nil when absent. A nonce may
+/// be a bstr or an int, while the identity and other information are bstrs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PartyInfo {
+    pub identity: Option<Vec<u8>>,
+    pub nonce: Option<CborType>,
+    pub other: Option<Vec<u8>>,
+}
+
+impl PartyInfo {
+    /// Reads the party information from the headers with the given labels for the identity, nonce
+    /// and other information.
+    fn from_headers(headers: &Headers, labels: (i64, i64, i64)) -> Result<PartyInfo, VerifyError> {
+        let bstr = |header_label| match headers.get(header_label) {
+            Some(CborType::BStr(value)) => Ok(Some(value.clone())),
+            Some(_) => Err(VerifyError::DecodingCOSEFailed),
+            None => Ok(None),
+        };
+        let nonce = match headers.get(labels.1) {
+            Some(nonce @ CborType::BStr(_)) | Some(nonce @ CborType::UInt(_)) |
+            Some(nonce @ CborType::NInt(_)) => Some(nonce.clone()),
+            Some(_) => return Err(VerifyError::DecodingCOSEFailed),
+            None => None,
+        };
+        Ok(PartyInfo {
+            identity: bstr(labels.0)?,
+            nonce,
+            other: bstr(labels.2)?,
+        })
+    }
+
+    fn to_cbor(&self) -> CborType {
+        let bstr_or_nil = |value: &Option<Vec<u8>>| match *value {
+            Some(ref value) => CborType::BStr(value.clone()),
+            None => CborType::Null,
+        };
+        CborType::Arr(vec![bstr_or_nil(&self.identity),
+                           self.nonce.clone().unwrap_or(CborType::Null),
+                           bstr_or_nil(&self.other)])
+    }
+}
+
+/// A COSE_KDF_Context (RFC 9053, Section 5.2), which binds a derived key to the algorithm it is
+/// for, the parties, and the protected headers of the structure it is derived for.
+///
+/// COSE_KDF_Context = [ AlgorithmID: int / tstr, PartyUInfo, PartyVInfo,
+///                      SuppPubInfo: [ keyDataLength: uint, protected: bstr, ? other: bstr ],
+///                      ? SuppPrivInfo: bstr ]
+#[derive(Clone, Debug, PartialEq)]
+pub struct KdfContext {
+    pub algorithm_id: i64,
+    pub party_u: PartyInfo,
+    pub party_v: PartyInfo,
+    /// The length of the derived key in bits.
+    pub key_data_length: u64,
+    pub protected: Vec<u8>,
+    pub supp_pub_other: Option<Vec<u8>>,
+    pub supp_priv_info: Option<Vec<u8>>,
+}
+
+impl KdfContext {
+    /// Builds the context for deriving a key of the given length in bytes for the given algorithm,
+    /// taking the party information from the headers of the recipient structure.
+    pub fn from_headers(headers: &Headers, algorithm_id: i64, key_length: usize)
+                        -> Result<KdfContext, VerifyError> {
+        Ok(KdfContext {
+            algorithm_id,
+            party_u: PartyInfo::from_headers(headers, (HEADER_PARTY_U_IDENTITY,
+                                                       HEADER_PARTY_U_NONCE,
+                                                       HEADER_PARTY_U_OTHER))?,
+            party_v: PartyInfo::from_headers(headers, (HEADER_PARTY_V_IDENTITY,
+                                                       HEADER_PARTY_V_NONCE,
+                                                       HEADER_PARTY_V_OTHER))?,
+            key_data_length: (key_length * 8) as u64,
+            protected: headers.protected_bytes.clone(),
+            supp_pub_other: None,
+            supp_priv_info: None,
+        })
+    }
+
+    /// Returns the serialized context.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut supp_pub_info = vec![CborType::UInt(self.key_data_length),
+                                     CborType::BStr(self.protected.clone())];
+        if let Some(ref other) = self.supp_pub_other {
+            supp_pub_info.push(CborType::BStr(other.clone()));
+        }
+        let mut context = vec![label(self.algorithm_id), self.party_u.to_cbor(),
+                               self.party_v.to_cbor(), CborType::Arr(supp_pub_info)];
+        if let Some(ref supp_priv_info) = self.supp_priv_info {
+            context.push(CborType::BStr(supp_priv_info.clone()));
+        }
+        CborType::Arr(context).serialize()
+    }
+}
+
+/// Derives a key of the given length in bytes for the given algorithm from an ECDH shared secret
+/// with HKDF (RFC 9053, Section 5.1), using the salt and party information from the headers of the
+/// recipient structure.
+pub fn derive_key(hash: HkdfHash, shared_secret: &[u8], headers: &Headers, algorithm_id: i64,
+                  key_length: usize) -> Result<Vec<u8>, VerifyError> {
+    let salt = match headers.get(HEADER_SALT) {
+        Some(CborType::BStr(salt)) => &salt[..],
+        Some(_) => return Err(VerifyError::DecodingCOSEFailed),
+        None => &[],
+    };
+    let context = KdfContext::from_headers(headers, algorithm_id, key_length)?;
+    hkdf(hash, salt, shared_secret, &context.encode(), key_length)
+}
+
+#[test]
+fn test_kdf_context() {
+    let mut protected = ::std::collections::BTreeMap::new();
+    protected.insert(label(1), label(-29));
+    let headers = Headers::new(protected, Default::default());
+    let context = KdfContext::from_headers(&headers, -3, 16).unwrap();
+    // The context for the A128KW key of an ECDH-ES + A128KW recipient.
+    assert_eq!(context.encode(),
+               vec![0x84, 0x22, 0x83, 0xf6, 0xf6, 0xf6, 0x83, 0xf6, 0xf6, 0xf6, 0x82, 0x18, 0x80,
+                    0x44, 0xa1, 0x01, 0x38, 0x1c]);
+
+    let mut unprotected = ::std::collections::BTreeMap::new();
+    unprotected.insert(label(HEADER_PARTY_U_IDENTITY), CborType::BStr(b"U".to_vec()));
+    unprotected.insert(label(HEADER_PARTY_V_NONCE), CborType::UInt(7));
+    let headers = Headers::new(Default::default(), unprotected);
+    let mut context = KdfContext::from_headers(&headers, 1, 16).unwrap();
+    context.supp_priv_info = Some(vec![0xff]);
+    assert_eq!(context.encode(),
+               vec![0x85, 0x01, 0x83, 0x41, 0x55, 0xf6, 0xf6, 0x83, 0xf6, 0x07, 0xf6, 0x82, 0x18,
+                    0x80, 0x40, 0x41, 0xff]);
+
+    let mut unprotected = ::std::collections::BTreeMap::new();
+    unprotected.insert(label(HEADER_PARTY_U_NONCE), CborType::Bool(true));
+    let headers = Headers::new(Default::default(), unprotected);
+    assert_eq!(KdfContext::from_headers(&headers, 1, 16), Err(VerifyError::DecodingCOSEFailed));
+}
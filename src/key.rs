@@ -0,0 +1,666 @@
+use std::collections::BTreeMap;
+use certs::Certificate;
+use cose::label;
+use decode::decode;
+use der;
+use ec::{PrimeCurve, P256, P384, P521};
+use nss::{encode_spki, SECKEYPublicKey};
+use serialize::CborType;
+use verify::{PublicKey, SignatureAlgorithm, VerifyError};
+
+// See RFC 9052, Section 7 and the IANA "COSE Key Common Parameters" registry.
+pub const KEY_KTY: i64 = 1;
+pub const KEY_KID: i64 = 2;
+pub const KEY_ALG: i64 = 3;
+pub const KEY_KEY_OPS: i64 = 4;
+
+// See RFC 9053, Section 7 and the IANA "COSE Key Type Parameters" registry. The OKP parameters
+// are the same as the corresponding EC2 ones.
+pub const EC2_CRV: i64 = -1;
+pub const EC2_X: i64 = -2;
+pub const EC2_Y: i64 = -3;
+pub const RSA_N: i64 = -1;
+pub const RSA_E: i64 = -2;
+
+// See the IANA "COSE Key Types" registry.
+pub const KTY_OKP: i64 = 1;
+pub const KTY_EC2: i64 = 2;
+pub const KTY_RSA: i64 = 3;
+
+// DER encodings of the object identifiers used in subject public key infos.
+pub const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+
+/// The elliptic curves from the IANA "COSE Elliptic Curves" registry that keys may be on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Curve {
+    P256,
+    P384,
+    P521,
+    X25519,
+    X448,
+    Ed25519,
+    Ed448,
+}
+
+impl Curve {
+    /// Returns the curve corresponding to the given value from the IANA "COSE Elliptic Curves"
+    /// registry, if it is supported.
+    pub fn from_cose_id(id: i64) -> Option<Curve> {
+        match id {
+            1 => Some(Curve::P256),
+            2 => Some(Curve::P384),
+            3 => Some(Curve::P521),
+            4 => Some(Curve::X25519),
+            5 => Some(Curve::X448),
+            6 => Some(Curve::Ed25519),
+            7 => Some(Curve::Ed448),
+            _ => None,
+        }
+    }
+
+    /// Returns the value identifying this curve in the IANA "COSE Elliptic Curves" registry.
+    pub fn cose_id(&self) -> i64 {
+        match *self {
+            Curve::P256 => 1,
+            Curve::P384 => 2,
+            Curve::P521 => 3,
+            Curve::X25519 => 4,
+            Curve::X448 => 5,
+            Curve::Ed25519 => 6,
+            Curve::Ed448 => 7,
+        }
+    }
+
+    /// Returns the curve with the given DER-encoded object identifier, which is that of the curve
+    /// (for the NIST curves) or of the key algorithm (for the OKP curves).
+    pub fn from_oid(oid: &[u8]) -> Option<Curve> {
+        CURVES.iter().find(|curve| curve.oid() == oid).cloned()
+    }
+
+    /// Returns the length in bytes of an encoded coordinate if this is one of the NIST prime curves
+    /// used with EC2 keys, or None if it is an OKP curve.
+    pub fn coordinate_length(&self) -> Option<usize> {
+        self.prime_curve().map(|prime_curve| prime_curve.coordinate_length)
+    }
+
+    /// Returns the parameters of the curve if it is one of the NIST prime curves used with EC2
+    /// keys, or None if it is an OKP curve.
+    fn prime_curve(&self) -> Option<&'static PrimeCurve> {
+        match *self {
+            Curve::P256 => Some(&P256),
+            Curve::P384 => Some(&P384),
+            Curve::P521 => Some(&P521),
+            _ => None,
+        }
+    }
+
+    /// Returns the length of an encoded public key on this curve if it is an OKP curve.
+    fn okp_key_length(&self) -> Option<usize> {
+        match *self {
+            Curve::X25519 | Curve::Ed25519 => Some(32),
+            Curve::X448 => Some(56),
+            Curve::Ed448 => Some(57),
+            _ => None,
+        }
+    }
+
+    /// Returns the DER encoding of the object identifier of this curve (for the NIST curves) or of
+    /// the key algorithm (for the OKP curves, see RFC 8410).
+    pub fn oid(&self) -> &'static [u8] {
+        match *self {
+            Curve::P256 => &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07],
+            Curve::P384 => &[0x2b, 0x81, 0x04, 0x00, 0x22],
+            Curve::P521 => &[0x2b, 0x81, 0x04, 0x00, 0x23],
+            Curve::X25519 => &[0x2b, 0x65, 0x6e],
+            Curve::X448 => &[0x2b, 0x65, 0x6f],
+            Curve::Ed25519 => &[0x2b, 0x65, 0x70],
+            Curve::Ed448 => &[0x2b, 0x65, 0x71],
+        }
+    }
+}
+
+const CURVES: [Curve; 7] = [Curve::P256, Curve::P384, Curve::P521, Curve::X25519, Curve::X448,
+                            Curve::Ed25519, Curve::Ed448];
+
+/// How the point of an EC2 key is encoded: with both coordinates, or with just the x coordinate and
+/// the sign bit of the y coordinate (RFC 9053, Section 7.1.1).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PointFormat {
+    Uncompressed,
+    Compressed,
+}
+
+/// The permitted operations of a key. See RFC 9052, Table 5.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeyOperation {
+    Sign,
+    Verify,
+    Encrypt,
+    Decrypt,
+    WrapKey,
+    UnwrapKey,
+    DeriveKey,
+    DeriveBits,
+    MacCreate,
+    MacVerify,
+}
+
+impl KeyOperation {
+    pub fn from_cose_id(id: i64) -> Option<KeyOperation> {
+        match id {
+            1 => Some(KeyOperation::Sign),
+            2 => Some(KeyOperation::Verify),
+            3 => Some(KeyOperation::Encrypt),
+            4 => Some(KeyOperation::Decrypt),
+            5 => Some(KeyOperation::WrapKey),
+            6 => Some(KeyOperation::UnwrapKey),
+            7 => Some(KeyOperation::DeriveKey),
+            8 => Some(KeyOperation::DeriveBits),
+            9 => Some(KeyOperation::MacCreate),
+            10 => Some(KeyOperation::MacVerify),
+            _ => None,
+        }
+    }
+
+    pub fn cose_id(&self) -> i64 {
+        match *self {
+            KeyOperation::Sign => 1,
+            KeyOperation::Verify => 2,
+            KeyOperation::Encrypt => 3,
+            KeyOperation::Decrypt => 4,
+            KeyOperation::WrapKey => 5,
+            KeyOperation::UnwrapKey => 6,
+            KeyOperation::DeriveKey => 7,
+            KeyOperation::DeriveBits => 8,
+            KeyOperation::MacCreate => 9,
+            KeyOperation::MacVerify => 10,
+        }
+    }
+}
+
+/// The type-specific parameters of a public key. EC2 points are always held in uncompressed
+/// form, and all values are big-endian.
+#[derive(Clone, Debug, PartialEq)]
+pub enum KeyParameters {
+    EC2 { curve: Curve, x: Vec<u8>, y: Vec<u8> },
+    OKP { curve: Curve, x: Vec<u8> },
+    RSA { n: Vec<u8>, e: Vec<u8> },
+}
+
+/// A public key in the form of a COSE_Key (RFC 9052, Section 7). Private key parameters and
+/// parameters this library doesn't know about are ignored.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoseKey {
+    pub kid: Option<Vec<u8>>,
+    /// The algorithm the key is restricted to, from the IANA "COSE Algorithms" registry.
+    pub alg: Option<i64>,
+    /// The operations the key is restricted to. Empty if unrestricted.
+    pub key_ops: Vec<KeyOperation>,
+    pub parameters: KeyParameters,
+}
+
+fn get_int(map: &BTreeMap<CborType, CborType>, key_label: i64) -> Result<Option<i64>, VerifyError> {
+    match map.get(&label(key_label)) {
+        None => Ok(None),
+        Some(&CborType::UInt(value)) if value <= i64::MAX as u64 => Ok(Some(value as i64)),
+        Some(&CborType::NInt(value)) => Ok(Some(value)),
+        Some(_) => Err(VerifyError::DecodingCOSEFailed),
+    }
+}
+
+fn get_bstr(map: &BTreeMap<CborType, CborType>, key_label: i64)
+            -> Result<Option<Vec<u8>>, VerifyError> {
+    match map.get(&label(key_label)) {
+        None => Ok(None),
+        Some(CborType::BStr(value)) => Ok(Some(value.clone())),
+        Some(_) => Err(VerifyError::DecodingCOSEFailed),
+    }
+}
+
+fn require<T>(value: Option<T>) -> Result<T, VerifyError> {
+    value.ok_or(VerifyError::DecodingCOSEFailed)
+}
+
+fn decode_key_ops(value: Option<&CborType>) -> Result<Vec<KeyOperation>, VerifyError> {
+    let key_ops = match value {
+        None => return Ok(Vec::new()),
+        Some(CborType::Arr(key_ops)) if !key_ops.is_empty() => key_ops,
+        Some(_) => return Err(VerifyError::DecodingCOSEFailed),
+    };
+    let mut decoded = Vec::with_capacity(key_ops.len());
+    for key_op in key_ops {
+        let key_op = match *key_op {
+            CborType::UInt(id) => KeyOperation::from_cose_id(id as i64),
+            CborType::NInt(_) | CborType::TStr(_) => None,
+            _ => return Err(VerifyError::DecodingCOSEFailed),
+        };
+        decoded.push(key_op.ok_or(VerifyError::UnsupportedKey)?);
+    }
+    Ok(decoded)
+}
+
+fn decode_ec2(map: &BTreeMap<CborType, CborType>) -> Result<KeyParameters, VerifyError> {
+    let curve = Curve::from_cose_id(require(get_int(map, EC2_CRV)?)?)
+        .ok_or(VerifyError::UnsupportedKey)?;
+    let prime_curve = curve.prime_curve().ok_or(VerifyError::InvalidKey)?;
+    let x = require(get_bstr(map, EC2_X)?)?;
+    if x.len() != prime_curve.coordinate_length {
+        return Err(VerifyError::InvalidKey);
+    }
+    let y = match map.get(&label(EC2_Y)) {
+        Some(CborType::BStr(y)) if y.len() == prime_curve.coordinate_length => y.clone(),
+        Some(CborType::BStr(_)) => return Err(VerifyError::InvalidKey),
+        Some(&CborType::Bool(y_is_odd)) => {
+            prime_curve.decompress(&x, y_is_odd).ok_or(VerifyError::InvalidKey)?
+        }
+        _ => return Err(VerifyError::DecodingCOSEFailed),
+    };
+    Ok(KeyParameters::EC2 { curve, x, y })
+}
+
+fn decode_okp(map: &BTreeMap<CborType, CborType>) -> Result<KeyParameters, VerifyError> {
+    let curve = Curve::from_cose_id(require(get_int(map, EC2_CRV)?)?)
+        .ok_or(VerifyError::UnsupportedKey)?;
+    let key_length = curve.okp_key_length().ok_or(VerifyError::InvalidKey)?;
+    let x = require(get_bstr(map, EC2_X)?)?;
+    if x.len() != key_length {
+        return Err(VerifyError::InvalidKey);
+    }
+    Ok(KeyParameters::OKP { curve, x })
+}
+
+fn decode_rsa(map: &BTreeMap<CborType, CborType>) -> Result<KeyParameters, VerifyError> {
+    let n = require(get_bstr(map, RSA_N)?)?;
+    let e = require(get_bstr(map, RSA_E)?)?;
+    // Neither may be empty or have leading zeros.
+    if n.first().is_none_or(|byte| *byte == 0) || e.first().is_none_or(|byte| *byte == 0) {
+        return Err(VerifyError::InvalidKey);
+    }
+    Ok(KeyParameters::RSA { n, e })
+}
+
+/// Decodes an EC point (in either format) on the curve identified by the given ECParameters.
+fn decode_ec_point(parameters: &[u8], point: &[u8]) -> Result<KeyParameters, VerifyError> {
+    let (oid, rest) = der::read(parameters, der::OID).ok_or(VerifyError::DecodingSPKIFailed)?;
+    if !rest.is_empty() {
+        return Err(VerifyError::DecodingSPKIFailed);
+    }
+    let curve = *CURVES.iter().find(|curve| curve.oid() == oid && curve.prime_curve().is_some())
+        .ok_or(VerifyError::UnsupportedKey)?;
+    let prime_curve = curve.prime_curve().ok_or(VerifyError::UnsupportedKey)?;
+    let length = prime_curve.coordinate_length;
+    let (x, y) = match point.split_first() {
+        Some((&0x04, coordinates)) if coordinates.len() == 2 * length => {
+            (coordinates[..length].to_vec(), coordinates[length..].to_vec())
+        }
+        Some((&form, x)) if (form == 0x02 || form == 0x03) && x.len() == length => {
+            let y = prime_curve.decompress(x, form == 0x03).ok_or(VerifyError::InvalidKey)?;
+            (x.to_vec(), y)
+        }
+        _ => return Err(VerifyError::InvalidKey),
+    };
+    Ok(KeyParameters::EC2 { curve, x, y })
+}
+
+/// Decodes an RSAPublicKey. The AlgorithmIdentifier parameters must be NULL.
+fn decode_rsa_public_key(parameters: &[u8], rsa_public_key: &[u8])
+                         -> Result<KeyParameters, VerifyError> {
+    if parameters != [der::NULL, 0] {
+        return Err(VerifyError::DecodingSPKIFailed);
+    }
+    let decoded = der::read(rsa_public_key, der::SEQUENCE).and_then(|(contents, rest)| {
+        let (n, contents) = der::read_unsigned_integer(contents)?;
+        let (e, contents) = der::read_unsigned_integer(contents)?;
+        if !rest.is_empty() || !contents.is_empty() {
+            return None;
+        }
+        Some((n.to_vec(), e.to_vec()))
+    });
+    let (n, e) = decoded.ok_or(VerifyError::DecodingSPKIFailed)?;
+    if n == [0] || e == [0] {
+        return Err(VerifyError::InvalidKey);
+    }
+    Ok(KeyParameters::RSA { n, e })
+}
+
+/// Returns whether or not a key with the given parameters may be used with the given algorithm.
+/// Algorithms that this library doesn't know the key requirements of are assumed to be compatible.
+fn algorithm_is_consistent(alg: i64, parameters: &KeyParameters) -> bool {
+    match (alg, parameters) {
+        // ES256, ES384 and ES512.
+        (-7, &KeyParameters::EC2 { curve, .. }) => curve == Curve::P256,
+        (-35, &KeyParameters::EC2 { curve, .. }) => curve == Curve::P384,
+        (-36, &KeyParameters::EC2 { curve, .. }) => curve == Curve::P521,
+        (-7, _) | (-35, _) | (-36, _) => false,
+        // EdDSA.
+        (-8, &KeyParameters::OKP { curve, .. }) => {
+            curve == Curve::Ed25519 || curve == Curve::Ed448
+        }
+        (-8, _) => false,
+        // The ECDH-ES and ECDH-SS variants.
+        (-34..=-25, &KeyParameters::EC2 { .. }) => true,
+        (-34..=-25, &KeyParameters::OKP { curve, .. }) => {
+            curve == Curve::X25519 || curve == Curve::X448
+        }
+        (-34..=-25, _) => false,
+        // PS256, PS384, PS512, RS256, RS384 and RS512.
+        (-39..=-37, parameters) | (-259..=-257, parameters) => {
+            matches!(*parameters, KeyParameters::RSA { .. })
+        }
+        _ => true,
+    }
+}
+
+impl CoseKey {
+    /// Decodes the bytes of a COSE_Key.
+    pub fn decode(cose_key: &[u8]) -> Result<CoseKey, VerifyError> {
+        let item = decode(cose_key).map_err(|_| VerifyError::DecodingCBORFailed)?;
+        CoseKey::from_cbor(&item)
+    }
+
+    /// Decodes a COSE_Key map. The key type, curve and algorithm must be consistent with each
+    /// other. EC2 points are checked to be on their curve when the key is imported into NSS.
+    pub fn from_cbor(cose_key: &CborType) -> Result<CoseKey, VerifyError> {
+        let map = match *cose_key {
+            CborType::Map(ref map) => map,
+            _ => return Err(VerifyError::DecodingCOSEFailed),
+        };
+        let parameters = match map.get(&label(KEY_KTY)) {
+            Some(&CborType::UInt(kty)) if kty == KTY_EC2 as u64 => decode_ec2(map)?,
+            Some(&CborType::UInt(kty)) if kty == KTY_OKP as u64 => decode_okp(map)?,
+            Some(&CborType::UInt(kty)) if kty == KTY_RSA as u64 => decode_rsa(map)?,
+            Some(CborType::UInt(_)) | Some(CborType::NInt(_)) | Some(CborType::TStr(_)) => {
+                return Err(VerifyError::UnsupportedKey);
+            }
+            _ => return Err(VerifyError::DecodingCOSEFailed),
+        };
+        let alg = match map.get(&label(KEY_ALG)) {
+            Some(CborType::TStr(_)) => return Err(VerifyError::UnsupportedAlgorithm),
+            _ => get_int(map, KEY_ALG)?,
+        };
+        if let Some(alg) = alg {
+            if !algorithm_is_consistent(alg, &parameters) {
+                return Err(VerifyError::InvalidKey);
+            }
+        }
+        Ok(CoseKey {
+            kid: get_bstr(map, KEY_KID)?,
+            alg,
+            key_ops: decode_key_ops(map.get(&label(KEY_KEY_OPS)))?,
+            parameters,
+        })
+    }
+
+    /// Converts a DER subject public key info. EC points may be compressed or uncompressed. The
+    /// resulting key has no kid, alg or key_ops.
+    pub fn from_spki(spki: &[u8]) -> Result<CoseKey, VerifyError> {
+        let (oid, parameters, subject_public_key) =
+            der::read_spki(spki).ok_or(VerifyError::DecodingSPKIFailed)?;
+        let parameters = if oid == OID_EC_PUBLIC_KEY {
+            decode_ec_point(parameters, subject_public_key)?
+        } else if oid == OID_RSA_ENCRYPTION {
+            decode_rsa_public_key(parameters, subject_public_key)?
+        } else {
+            let curve = CURVES.iter()
+                .find(|curve| curve.oid() == oid && curve.okp_key_length().is_some())
+                .ok_or(VerifyError::UnsupportedKey)?;
+            // RFC 8410 forbids parameters.
+            if !parameters.is_empty() {
+                return Err(VerifyError::DecodingSPKIFailed);
+            }
+            if Some(subject_public_key.len()) != curve.okp_key_length() {
+                return Err(VerifyError::InvalidKey);
+            }
+            KeyParameters::OKP { curve: *curve, x: subject_public_key.to_vec() }
+        };
+        Ok(CoseKey {
+            kid: None,
+            alg: None,
+            key_ops: Vec::new(),
+            parameters,
+        })
+    }
+
+    /// Converts an NSS public key, which is borrowed.
+    pub(crate) fn from_nss_public_key(key: *const SECKEYPublicKey)
+                                      -> Result<CoseKey, VerifyError> {
+        CoseKey::from_spki(&encode_spki(key)?)
+    }
+
+    /// Converts the public key of the given DER certificate.
+    pub fn from_certificate(certificate: &[u8]) -> Result<CoseKey, VerifyError> {
+        Certificate::from_der(certificate)?.with_public_key(CoseKey::from_nss_public_key)
+    }
+
+    /// Returns the COSE_Key map for this key, with EC2 points in the given format.
+    pub fn to_cbor(&self, point_format: PointFormat) -> CborType {
+        let mut map = BTreeMap::new();
+        if let Some(ref kid) = self.kid {
+            map.insert(label(KEY_KID), CborType::BStr(kid.clone()));
+        }
+        if let Some(alg) = self.alg {
+            map.insert(label(KEY_ALG), label(alg));
+        }
+        if !self.key_ops.is_empty() {
+            let key_ops = self.key_ops.iter().map(|key_op| label(key_op.cose_id())).collect();
+            map.insert(label(KEY_KEY_OPS), CborType::Arr(key_ops));
+        }
+        match self.parameters {
+            KeyParameters::EC2 { curve, ref x, ref y } => {
+                map.insert(label(KEY_KTY), label(KTY_EC2));
+                map.insert(label(EC2_CRV), label(curve.cose_id()));
+                map.insert(label(EC2_X), CborType::BStr(x.clone()));
+                let y = match point_format {
+                    PointFormat::Uncompressed => CborType::BStr(y.clone()),
+                    PointFormat::Compressed => CborType::Bool(y.last().is_some_and(|y| y & 1 == 1)),
+                };
+                map.insert(label(EC2_Y), y);
+            }
+            KeyParameters::OKP { curve, ref x } => {
+                map.insert(label(KEY_KTY), label(KTY_OKP));
+                map.insert(label(EC2_CRV), label(curve.cose_id()));
+                map.insert(label(EC2_X), CborType::BStr(x.clone()));
+            }
+            KeyParameters::RSA { ref n, ref e } => {
+                map.insert(label(KEY_KTY), label(KTY_RSA));
+                map.insert(label(RSA_N), CborType::BStr(n.clone()));
+                map.insert(label(RSA_E), CborType::BStr(e.clone()));
+            }
+        }
+        CborType::Map(map)
+    }
+
+    /// Returns the canonical CBOR encoding of the COSE_Key for this key.
+    pub fn encode(&self, point_format: PointFormat) -> Vec<u8> {
+        self.to_cbor(point_format).serialize()
+    }
+
+    /// Returns the DER encoding of the subject public key info corresponding to this key, as
+    /// verify_signature and NSS expect.
+    pub fn to_spki(&self) -> Vec<u8> {
+        match self.parameters {
+            KeyParameters::EC2 { curve, ref x, ref y } => {
+                let mut algorithm = der::encode(der::OID, OID_EC_PUBLIC_KEY);
+                algorithm.extend(der::encode(der::OID, curve.oid()));
+                let mut point = vec![0x04];
+                point.extend_from_slice(x);
+                point.extend_from_slice(y);
+                der::encode_spki(&algorithm, &point)
+            }
+            KeyParameters::OKP { curve, ref x } => {
+                der::encode_spki(&der::encode(der::OID, curve.oid()), x)
+            }
+            KeyParameters::RSA { ref n, ref e } => {
+                let mut algorithm = der::encode(der::OID, OID_RSA_ENCRYPTION);
+                algorithm.extend(der::encode(der::NULL, &[]));
+                let mut rsa_public_key = der::encode_unsigned_integer(n);
+                rsa_public_key.extend(der::encode_unsigned_integer(e));
+                der::encode_spki(&algorithm, &der::encode(der::SEQUENCE, &rsa_public_key))
+            }
+        }
+    }
+}
+
+/// A COSE_KeySet (RFC 9052, Section 7): an array of COSE_Keys, such as a set of keys that are
+/// being rotated through.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoseKeySet {
+    pub keys: Vec<CoseKey>,
+}
+
+impl CoseKeySet {
+    /// Decodes the bytes of a COSE_KeySet. Every key must be valid.
+    pub fn decode(cose_key_set: &[u8]) -> Result<CoseKeySet, VerifyError> {
+        let item = decode(cose_key_set).map_err(|_| VerifyError::DecodingCBORFailed)?;
+        let cose_keys = match item {
+            CborType::Arr(cose_keys) if !cose_keys.is_empty() => cose_keys,
+            _ => return Err(VerifyError::DecodingCOSEFailed),
+        };
+        let mut keys = Vec::with_capacity(cose_keys.len());
+        for cose_key in &cose_keys {
+            keys.push(CoseKey::from_cbor(cose_key)?);
+        }
+        Ok(CoseKeySet { keys })
+    }
+
+    /// Returns the canonical CBOR encoding of this COSE_KeySet, with EC2 points in the given
+    /// format.
+    pub fn encode(&self, point_format: PointFormat) -> Vec<u8> {
+        CborType::Arr(self.keys.iter().map(|key| key.to_cbor(point_format)).collect()).serialize()
+    }
+
+    /// Returns the first key with the given kid.
+    pub fn find_by_kid(&self, kid: &[u8]) -> Option<&CoseKey> {
+        self.keys.iter().find(|key| key.kid.as_ref().is_some_and(|key_kid| key_kid == kid))
+    }
+
+    /// Returns the keys that may be used with the given algorithm, in order: those restricted to
+    /// it, and those not restricted to any algorithm but of a type and curve suitable for it.
+    /// Within each group, keys are in the order of the set.
+    pub fn find_by_algorithm(&self, alg: i64) -> Vec<&CoseKey> {
+        let restricted = self.keys.iter().filter(|key| key.alg == Some(alg));
+        let unrestricted = self.keys.iter().filter(|key| {
+            key.alg.is_none() && algorithm_is_consistent(alg, &key.parameters)
+        });
+        restricted.chain(unrestricted).collect()
+    }
+}
+
+/// Verifies a signature with a COSE_Key rather than a subject public key info. The key's alg and
+/// key_ops, if present, must permit verification with the given algorithm.
+pub fn verify_signature_with_cose_key(signature_algorithm: SignatureAlgorithm, key: &CoseKey,
+                                      payload: &[u8], signature: &[u8])
+                                      -> Result<(), VerifyError> {
+    PublicKey::from_cose_key(key)?.verify(signature_algorithm, payload, signature)
+}
+
+#[cfg(test)]
+fn ec2_key(crv: i64, x: Vec<u8>, y: CborType) -> CborType {
+    let mut map = BTreeMap::new();
+    map.insert(label(KEY_KTY), label(KTY_EC2));
+    map.insert(label(EC2_CRV), label(crv));
+    map.insert(label(EC2_X), CborType::BStr(x));
+    map.insert(label(EC2_Y), y);
+    CborType::Map(map)
+}
+
+#[test]
+fn test_decode_ec2_key() {
+    // The P-256 generator.
+    let x = vec![0x6b, 0x17, 0xd1, 0xf2, 0xe1, 0x2c, 0x42, 0x47, 0xf8, 0xbc, 0xe6, 0xe5, 0x63,
+                 0xa4, 0x40, 0xf2, 0x77, 0x03, 0x7d, 0x81, 0x2d, 0xeb, 0x33, 0xa0, 0xf4, 0xa1,
+                 0x39, 0x45, 0xd8, 0x98, 0xc2, 0x96];
+    let y = vec![0x4f, 0xe3, 0x42, 0xe2, 0xfe, 0x1a, 0x7f, 0x9b, 0x8e, 0xe7, 0xeb, 0x4a, 0x7c,
+                 0x0f, 0x9e, 0x16, 0x2b, 0xce, 0x33, 0x57, 0x6b, 0x31, 0x5e, 0xce, 0xcb, 0xb6,
+                 0x40, 0x68, 0x37, 0xbf, 0x51, 0xf5];
+    let expected = KeyParameters::EC2 { curve: Curve::P256, x: x.clone(), y: y.clone() };
+    let key = CoseKey::from_cbor(&ec2_key(1, x.clone(), CborType::BStr(y.clone()))).unwrap();
+    assert_eq!(key.parameters, expected);
+    assert_eq!(key.alg, None);
+    let key = CoseKey::from_cbor(&ec2_key(1, x.clone(), CborType::Bool(true))).unwrap();
+    assert_eq!(key.parameters, expected);
+    // Whether a point is on its curve is left to NSS, but a compressed point must decompress.
+    let mut not_x = vec![0; 32];
+    not_x[31] = 1;
+    assert_eq!(CoseKey::from_cbor(&ec2_key(1, not_x, CborType::Bool(false))),
+               Err(VerifyError::InvalidKey));
+    assert_eq!(CoseKey::from_cbor(&ec2_key(2, x.clone(), CborType::BStr(y.clone()))),
+               Err(VerifyError::InvalidKey));
+    assert_eq!(CoseKey::from_cbor(&ec2_key(6, x.clone(), CborType::BStr(y.clone()))),
+               Err(VerifyError::InvalidKey));
+    assert_eq!(CoseKey::from_cbor(&ec2_key(8, x.clone(), CborType::BStr(y.clone()))),
+               Err(VerifyError::UnsupportedKey));
+    let mut with_alg = ec2_key(1, x, CborType::BStr(y));
+    if let CborType::Map(ref mut map) = with_alg {
+        map.insert(label(KEY_ALG), label(-35));
+    }
+    assert_eq!(CoseKey::from_cbor(&with_alg), Err(VerifyError::InvalidKey));
+}
+
+#[test]
+fn test_decode_key_errors() {
+    let mut map = BTreeMap::new();
+    assert_eq!(CoseKey::from_cbor(&CborType::Map(map.clone())),
+               Err(VerifyError::DecodingCOSEFailed));
+    map.insert(label(KEY_KTY), label(4));
+    assert_eq!(CoseKey::from_cbor(&CborType::Map(map.clone())), Err(VerifyError::UnsupportedKey));
+    map.insert(label(KEY_KTY), label(KTY_OKP));
+    map.insert(label(EC2_CRV), label(6));
+    map.insert(label(EC2_X), CborType::BStr(vec![0; 31]));
+    assert_eq!(CoseKey::from_cbor(&CborType::Map(map.clone())), Err(VerifyError::InvalidKey));
+    map.insert(label(EC2_X), CborType::BStr(vec![0; 32]));
+    map.insert(label(KEY_KEY_OPS), CborType::Arr(vec![label(2), label(11)]));
+    assert_eq!(CoseKey::from_cbor(&CborType::Map(map.clone())), Err(VerifyError::UnsupportedKey));
+    map.insert(label(KEY_KEY_OPS), CborType::Arr(vec![label(2)]));
+    map.insert(label(KEY_ALG), label(-7));
+    assert_eq!(CoseKey::from_cbor(&CborType::Map(map.clone())), Err(VerifyError::InvalidKey));
+    map.insert(label(KEY_ALG), label(-8));
+    let key = CoseKey::from_cbor(&CborType::Map(map)).unwrap();
+    assert_eq!(key.key_ops, vec![KeyOperation::Verify]);
+    assert_eq!(key.to_spki()[..12],
+               [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00]);
+}
+
+#[test]
+fn test_from_spki_compressed_point() {
+    let key = CoseKey::from_cbor(&ec2_key(1, vec![0x6b, 0x17, 0xd1, 0xf2, 0xe1, 0x2c, 0x42, 0x47,
+                                                  0xf8, 0xbc, 0xe6, 0xe5, 0x63, 0xa4, 0x40, 0xf2,
+                                                  0x77, 0x03, 0x7d, 0x81, 0x2d, 0xeb, 0x33, 0xa0,
+                                                  0xf4, 0xa1, 0x39, 0x45, 0xd8, 0x98, 0xc2, 0x96],
+                                          CborType::Bool(true))).unwrap();
+    let x = match key.parameters {
+        KeyParameters::EC2 { ref x, .. } => x.clone(),
+        _ => panic!("expected an EC2 key"),
+    };
+    let mut algorithm = der::encode(der::OID, OID_EC_PUBLIC_KEY);
+    algorithm.extend(der::encode(der::OID, Curve::P256.oid()));
+    let mut point = vec![0x03];
+    point.extend(x);
+    assert_eq!(CoseKey::from_spki(&der::encode_spki(&algorithm, &point)), Ok(key.clone()));
+    point[0] = 0x02;
+    assert_ne!(CoseKey::from_spki(&der::encode_spki(&algorithm, &point)), Ok(key));
+}
+
+#[test]
+fn test_key_set_lookup() {
+    let okp = |kid: &[u8], alg: Option<i64>, curve: Curve| CoseKey {
+        kid: Some(kid.to_vec()),
+        alg,
+        key_ops: Vec::new(),
+        parameters: KeyParameters::OKP { curve, x: vec![0; 32] },
+    };
+    let key_set = CoseKeySet {
+        keys: vec![okp(b"a", None, Curve::Ed25519), okp(b"b", Some(-8), Curve::Ed25519),
+                   okp(b"b", None, Curve::X25519)],
+    };
+    assert_eq!(key_set.find_by_kid(b"b"), Some(&key_set.keys[1]));
+    assert_eq!(key_set.find_by_kid(b"c"), None);
+    // The key restricted to EdDSA comes before the unrestricted one, though it is later in the set.
+    assert_eq!(key_set.find_by_algorithm(-8), vec![&key_set.keys[1], &key_set.keys[0]]);
+    assert_eq!(key_set.find_by_algorithm(-25), vec![&key_set.keys[2]]);
+    assert_eq!(key_set.find_by_algorithm(-7), Vec::<&CoseKey>::new());
+    let encoded = key_set.encode(PointFormat::Uncompressed);
+    assert_eq!(CoseKeySet::decode(&encoded), Ok(key_set));
+    assert_eq!(CoseKeySet::decode(&[0x80]), Err(VerifyError::DecodingCOSEFailed));
+}
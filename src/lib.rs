@@ -1,6 +1,60 @@
 #[macro_use(defer)] extern crate scopeguard;
 
+mod nss;
+mod digest;
 mod verify;
 mod serialize;
+mod decode;
+mod cose;
+mod policy;
+mod certs;
+mod addon;
+mod x509;
+mod der;
+mod ec;
+mod key;
+mod json;
+mod jwk;
+mod mac;
+mod encrypt;
+mod kdf;
+mod ecdh;
+mod recipient;
+mod ccm;
+mod countersign;
+mod sign;
+mod cwt;
+mod eat;
+mod content;
+mod envelope;
+mod ecdsa;
 
-pub use self::verify::{verify_signature, SignatureAlgorithm, VerifyError};
+pub use self::verify::{verify_prehashed, verify_signature, PublicKey, SignatureAlgorithm,
+                       VerifyError};
+pub use self::serialize::CborType;
+pub use self::digest::HashAlgorithm;
+pub use self::policy::{verify_cose_sign, verify_cose_sign1, verify_cose_sign1_streaming,
+                       SignerReport, TrustedKey, VerificationPolicy, VerificationReport};
+pub use self::certs::{verify_certificate_chain, KeyPurpose};
+pub use self::addon::verify_addon_signature;
+pub use self::x509::verify_cose_sign_x509;
+pub use self::key::{verify_signature_with_cose_key, CoseKey, CoseKeySet, Curve, KeyOperation,
+                    KeyParameters, PointFormat};
+pub use self::mac::{create_cose_mac, create_cose_mac0, verify_cose_mac, verify_cose_mac0,
+                    MacAlgorithm, SharedKey};
+pub use self::encrypt::{decrypt_cose_encrypt, decrypt_cose_encrypt0, encrypt_cose_encrypt,
+                        encrypt_cose_encrypt0, EncryptionAlgorithm};
+pub use self::kdf::{hkdf, HkdfHash, KdfContext, PartyInfo};
+pub use self::recipient::{KeyDistributionAlgorithm, Recipient, RecipientKey, StaticKey};
+pub use self::countersign::{countersignature_structure, countersignature_to_be_signed,
+                            verify_countersignatures, AbbreviatedCountersigner,
+                            CountersignatureReport, CountersignatureTarget};
+pub use self::cwt::{verify_cose_sign1_with_claims, verify_cose_sign_with_claims, verify_cwt,
+                    verify_proof_of_possession, Claims, ConfirmationKey, CwtBuilder, CwtKeys,
+                    CwtValidation};
+pub use self::content::{verify_cose_sign1_decoded, ContentType, PayloadDecoders};
+pub use self::envelope::{create_hash_envelope, verify_hash_envelope, HashEnvelopeHeaders};
+pub use self::eat::{verify_eat, DebugStatus, EatClaims, EatProfile, EatValidation, Measurement,
+                    OemId, Submodule};
+pub use self::ecdsa::{ecdsa_signature_from_der, ecdsa_signature_to_der};
+pub use self::sign::{create_cose_sign1, sign, SigningKey};
@@ -0,0 +1,243 @@
+use std::collections::BTreeMap;
+use std::ptr;
+use cose::{decode_payload, encode_protected_algorithm, label, strip_tag, unprotected_kid, Headers,
+           COSE_MAC0_TAG, COSE_MAC_TAG, HEADER_ALG};
+use decode::decode;
+use nss::{secure_compare, with_sym_key, SECItem, CKA_SIGN, CKM_SHA256_HMAC, CKM_SHA384_HMAC,
+          CKM_SHA512_HMAC, SEC_SUCCESS, SHA256_LENGTH, SHA384_LENGTH, SHA512_LENGTH,
+          CkMechanismType, PK11_SignWithSymKey};
+use serialize::CborType;
+use verify::VerifyError;
+
+// See RFC 9053, Section 6.1.
+const ALG_DIRECT: i64 = -6;
+
+/// An enum identifying supported MAC algorithms (RFC 9053, Section 3.1). The name gives the hash
+/// function and then the length of the tag in bits.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MacAlgorithm {
+    HMAC256_64,
+    HMAC256_256,
+    HMAC384_384,
+    HMAC512_512,
+}
+
+impl MacAlgorithm {
+    /// Returns the algorithm corresponding to the given value from the IANA "COSE Algorithms"
+    /// registry, if it is supported.
+    pub fn from_cose_id(id: i64) -> Option<MacAlgorithm> {
+        match id {
+            4 => Some(MacAlgorithm::HMAC256_64),
+            5 => Some(MacAlgorithm::HMAC256_256),
+            6 => Some(MacAlgorithm::HMAC384_384),
+            7 => Some(MacAlgorithm::HMAC512_512),
+            _ => None,
+        }
+    }
+
+    /// Returns the value identifying this algorithm in the IANA "COSE Algorithms" registry.
+    pub fn cose_id(&self) -> i64 {
+        match *self {
+            MacAlgorithm::HMAC256_64 => 4,
+            MacAlgorithm::HMAC256_256 => 5,
+            MacAlgorithm::HMAC384_384 => 6,
+            MacAlgorithm::HMAC512_512 => 7,
+        }
+    }
+
+    /// Returns the length in bytes of the tag, which is the HMAC output truncated if necessary.
+    pub fn tag_length(&self) -> usize {
+        match *self {
+            MacAlgorithm::HMAC256_64 => 8,
+            MacAlgorithm::HMAC256_256 => SHA256_LENGTH,
+            MacAlgorithm::HMAC384_384 => SHA384_LENGTH,
+            MacAlgorithm::HMAC512_512 => SHA512_LENGTH,
+        }
+    }
+
+    fn mechanism_and_output_length(&self) -> (CkMechanismType, usize) {
+        match *self {
+            MacAlgorithm::HMAC256_64 | MacAlgorithm::HMAC256_256 => {
+                (CKM_SHA256_HMAC, SHA256_LENGTH)
+            }
+            MacAlgorithm::HMAC384_384 => (CKM_SHA384_HMAC, SHA384_LENGTH),
+            MacAlgorithm::HMAC512_512 => (CKM_SHA512_HMAC, SHA512_LENGTH),
+        }
+    }
+}
+
+/// A symmetric key that may be used to verify a COSE_Mac, along with its key identifier.
+pub struct SharedKey<'a> {
+    pub kid: &'a [u8],
+    pub key: &'a [u8],
+}
+
+/// Computes the (possibly truncated) HMAC of the given data.
+pub fn compute_tag(algorithm: MacAlgorithm, key: &[u8], data: &[u8])
+                   -> Result<Vec<u8>, VerifyError> {
+    let (mechanism, output_length) = algorithm.mechanism_and_output_length();
+    let data_item = SECItem::maybe_new(data)?;
+    let mut tag = vec![0; output_length];
+    with_sym_key(mechanism, CKA_SIGN, key, |sym_key| {
+        let mut tag_item = SECItem::maybe_new_output(&mut tag)?;
+        let result = unsafe {
+            PK11_SignWithSymKey(sym_key, mechanism, ptr::null(), &mut tag_item, &data_item)
+        };
+        if result != SEC_SUCCESS || tag_item.len as usize != output_length {
+            return Err(VerifyError::LibraryFailure);
+        }
+        Ok(())
+    })?;
+    tag.truncate(algorithm.tag_length());
+    Ok(tag)
+}
+
+/// Builds the serialized MAC_structure (RFC 9052, Section 6.3) that a tag is computed over.
+fn mac_structure(context: &str, protected: &[u8], external_aad: &[u8], payload: &[u8]) -> Vec<u8> {
+    CborType::Arr(vec![CborType::TStr(String::from(context)),
+                       CborType::BStr(protected.to_vec()),
+                       CborType::BStr(external_aad.to_vec()),
+                       CborType::BStr(payload.to_vec())]).serialize()
+}
+
+/// Checks the given tag against the one computed with the given key. The comparison takes the
+/// same time wherever the tags differ.
+fn check_tag(algorithm: MacAlgorithm, key: &[u8], to_verify: &[u8], tag: &[u8])
+             -> Result<(), VerifyError> {
+    let expected = compute_tag(algorithm, key, to_verify)?;
+    if !secure_compare(&expected, tag) {
+        return Err(VerifyError::MacVerificationFailed);
+    }
+    Ok(())
+}
+
+/// Decodes the common elements of a COSE_Mac0 or COSE_Mac: the headers, the payload, and the tag.
+fn decode_mac_elements(elements: &[CborType], detached_payload: Option<&[u8]>)
+                       -> Result<(Headers, Vec<u8>, Vec<u8>), VerifyError> {
+    let headers = Headers::decode(&elements[0], &elements[1])?;
+    let payload = decode_payload(&elements[2], detached_payload)?;
+    let tag = match elements[3] {
+        CborType::BStr(ref tag) => tag.clone(),
+        _ => return Err(VerifyError::DecodingCOSEFailed),
+    };
+    Ok((headers, payload, tag))
+}
+
+fn mac_algorithm(headers: &Headers) -> Result<MacAlgorithm, VerifyError> {
+    MacAlgorithm::from_cose_id(headers.algorithm()?).ok_or(VerifyError::UnsupportedAlgorithm)
+}
+
+/// Creates a tagged COSE_Mac0 over the given payload. The algorithm goes in the protected header
+/// and the kid, if given, in the unprotected header.
+pub fn create_cose_mac0(algorithm: MacAlgorithm, key: &[u8], kid: Option<&[u8]>, payload: &[u8],
+                        external_aad: &[u8]) -> Result<Vec<u8>, VerifyError> {
+    let protected = encode_protected_algorithm(algorithm.cose_id());
+    let to_mac = mac_structure("MAC0", &protected, external_aad, payload);
+    let tag = compute_tag(algorithm, key, &to_mac)?;
+    let elements = vec![CborType::BStr(protected), CborType::Map(unprotected_kid(kid)),
+                        CborType::BStr(payload.to_vec()), CborType::BStr(tag)];
+    Ok(CborType::Tag(COSE_MAC0_TAG, Box::new(CborType::Arr(elements))).serialize())
+}
+
+/// Verifies a COSE_Mac0 with the given key and returns its payload.
+///
+/// COSE_Mac0 = [ protected: bstr, unprotected: map, payload: bstr / nil, tag: bstr ]
+pub fn verify_cose_mac0(cose_mac0: &[u8], detached_payload: Option<&[u8]>, external_aad: &[u8],
+                        key: &[u8]) -> Result<Vec<u8>, VerifyError> {
+    let item = decode(cose_mac0).map_err(|_| VerifyError::DecodingCBORFailed)?;
+    let elements = match strip_tag(item, COSE_MAC0_TAG)? {
+        CborType::Arr(elements) if elements.len() == 4 => elements,
+        _ => return Err(VerifyError::DecodingCOSEFailed),
+    };
+    let (headers, payload, tag) = decode_mac_elements(&elements, detached_payload)?;
+    let algorithm = mac_algorithm(&headers)?;
+    let to_verify = mac_structure("MAC0", &headers.protected_bytes, external_aad, &payload);
+    check_tag(algorithm, key, &to_verify, &tag)?;
+    Ok(payload)
+}
+
+/// Creates a tagged COSE_Mac over the given payload with a single recipient that uses the key
+/// directly (RFC 9053, Section 6.1). The kid, if given, identifies the key in the recipient.
+pub fn create_cose_mac(algorithm: MacAlgorithm, key: &[u8], kid: Option<&[u8]>, payload: &[u8],
+                       external_aad: &[u8]) -> Result<Vec<u8>, VerifyError> {
+    let protected = encode_protected_algorithm(algorithm.cose_id());
+    let to_mac = mac_structure("MAC", &protected, external_aad, payload);
+    let tag = compute_tag(algorithm, key, &to_mac)?;
+    let mut recipient_unprotected = unprotected_kid(kid);
+    recipient_unprotected.insert(label(HEADER_ALG), label(ALG_DIRECT));
+    let recipient = CborType::Arr(vec![CborType::BStr(Vec::new()),
+                                       CborType::Map(recipient_unprotected),
+                                       CborType::BStr(Vec::new())]);
+    let elements = vec![CborType::BStr(protected), CborType::Map(BTreeMap::new()),
+                        CborType::BStr(payload.to_vec()), CborType::BStr(tag),
+                        CborType::Arr(vec![recipient])];
+    Ok(CborType::Tag(COSE_MAC_TAG, Box::new(CborType::Arr(elements))).serialize())
+}
+
+/// Verifies a COSE_Mac and returns its payload. Each recipient must use a key directly and is
+/// matched by kid against the given keys. The tag is valid if it verifies with the key of any
+/// recipient. If no recipient names a known key, the error is VerifyError::UnknownKeyId.
+///
+/// COSE_Mac = [ protected: bstr, unprotected: map, payload: bstr / nil, tag: bstr,
+///              recipients: [+ COSE_recipient] ]
+/// COSE_recipient = [ protected: bstr, unprotected: map, ciphertext: bstr / nil,
+///                    ? recipients: [+ COSE_recipient] ]
+pub fn verify_cose_mac(cose_mac: &[u8], detached_payload: Option<&[u8]>, external_aad: &[u8],
+                       keys: &[SharedKey]) -> Result<Vec<u8>, VerifyError> {
+    let item = decode(cose_mac).map_err(|_| VerifyError::DecodingCBORFailed)?;
+    let elements = match strip_tag(item, COSE_MAC_TAG)? {
+        CborType::Arr(elements) if elements.len() == 5 => elements,
+        _ => return Err(VerifyError::DecodingCOSEFailed),
+    };
+    let (headers, payload, tag) = decode_mac_elements(&elements, detached_payload)?;
+    let algorithm = mac_algorithm(&headers)?;
+    let recipients = match elements[4] {
+        CborType::Arr(ref recipients) if !recipients.is_empty() => recipients,
+        _ => return Err(VerifyError::DecodingCOSEFailed),
+    };
+    let to_verify = mac_structure("MAC", &headers.protected_bytes, external_aad, &payload);
+    let mut result = Err(VerifyError::UnknownKeyId);
+    for recipient in recipients {
+        let recipient_elements = match *recipient {
+            CborType::Arr(ref elements) if elements.len() == 3 || elements.len() == 4 => elements,
+            _ => return Err(VerifyError::DecodingCOSEFailed),
+        };
+        let recipient_headers = Headers::decode(&recipient_elements[0], &recipient_elements[1])?;
+        if recipient_headers.algorithm()? != ALG_DIRECT {
+            if result == Err(VerifyError::UnknownKeyId) {
+                result = Err(VerifyError::UnsupportedAlgorithm);
+            }
+            continue;
+        }
+        // A direct recipient has no encrypted key and no recipients of its own.
+        if recipient_elements[2] != CborType::BStr(Vec::new()) || recipient_elements.len() == 4 {
+            return Err(VerifyError::DecodingCOSEFailed);
+        }
+        let kid = recipient_headers.kid()?;
+        let key = match kid.and_then(|kid| keys.iter().find(|key| key.kid == kid)) {
+            Some(key) => key,
+            None => continue,
+        };
+        result = check_tag(algorithm, key.key, &to_verify, &tag);
+        if result.is_ok() {
+            break;
+        }
+    }
+    result.map(|_| payload)
+}
+
+#[test]
+fn test_algorithms() {
+    for id in 4..8 {
+        let algorithm = MacAlgorithm::from_cose_id(id).unwrap();
+        assert_eq!(algorithm.cose_id(), id);
+    }
+    assert_eq!(MacAlgorithm::from_cose_id(8), None);
+    assert_eq!(MacAlgorithm::HMAC256_64.tag_length(), 8);
+}
+
+#[test]
+fn test_mac_structure() {
+    assert_eq!(mac_structure("MAC0", &[0xa0], &[], &[0x01]),
+               vec![0x84, 0x64, 0x4d, 0x41, 0x43, 0x30, 0x41, 0xa0, 0x40, 0x41, 0x01]);
+}
@@ -0,0 +1,563 @@
+use std::os::raw;
+use std::ptr;
+use std::slice;
+use verify::VerifyError;
+
+pub type PRBool = raw::c_int;
+pub const PR_FALSE: PRBool = 0;
+pub const PR_TRUE: PRBool = 1;
+
+type SECItemType = raw::c_uint; // TODO: actually an enum - is this the right size?
+const SI_BUFFER: SECItemType = 0; // called siBuffer in NSS
+
+#[repr(C)]
+pub struct SECItem {
+    typ: SECItemType,
+    pub data: *const u8, // ugh it's not really const...
+    pub len: raw::c_uint,
+}
+
+impl SECItem {
+    pub fn maybe_new(data: &[u8]) -> Result<SECItem, VerifyError> {
+        if data.len() > u32::MAX as usize {
+            return Err(VerifyError::InputTooLarge);
+        }
+        Ok(SECItem { typ: SI_BUFFER, data: data.as_ptr(), len: data.len() as u32 })
+    }
+
+    /// Creates an item for NSS to write output into. NSS updates len to the length written.
+    pub fn maybe_new_output(buffer: &mut [u8]) -> Result<SECItem, VerifyError> {
+        if buffer.len() > u32::MAX as usize {
+            return Err(VerifyError::InputTooLarge);
+        }
+        Ok(SECItem { typ: SI_BUFFER, data: buffer.as_mut_ptr(), len: buffer.len() as u32 })
+    }
+}
+
+#[repr(C)]
+pub struct CkRsaPkcsPssParams { // Called CK_RSA_PKCS_PSS_PARAMS in NSS
+    hash_alg: CkMechanismType, // Called hashAlg in NSS
+    mgf: CkRsaPkcsMgfType,
+    s_len: raw::c_ulong, // Called sLen in NSS
+}
+
+impl CkRsaPkcsPssParams {
+    pub fn new() -> CkRsaPkcsPssParams {
+        CkRsaPkcsPssParams {
+            hash_alg: CKM_SHA256,
+            mgf: CKG_MGF1_SHA256,
+            s_len: 32,
+        }
+    }
+}
+
+#[repr(C)]
+pub struct CkGcmParams { // Called CK_GCM_PARAMS in NSS (the PKCS #11 v3.0 version)
+    iv: *const u8, // Called pIv in NSS
+    iv_len: raw::c_ulong, // Called ulIvLen in NSS
+    iv_bits: raw::c_ulong, // Called ulIvBits in NSS
+    aad: *const u8, // Called pAAD in NSS
+    aad_len: raw::c_ulong, // Called ulAADLen in NSS
+    tag_bits: raw::c_ulong, // Called ulTagBits in NSS
+}
+
+impl CkGcmParams {
+    /// The parameters borrow iv and aad, which must outlive them.
+    pub fn new(iv: &[u8], aad: &[u8], tag_length: usize) -> CkGcmParams {
+        CkGcmParams {
+            iv: iv.as_ptr(),
+            iv_len: iv.len() as raw::c_ulong,
+            iv_bits: (iv.len() * 8) as raw::c_ulong,
+            aad: aad.as_ptr(),
+            aad_len: aad.len() as raw::c_ulong,
+            tag_bits: (tag_length * 8) as raw::c_ulong,
+        }
+    }
+}
+
+#[repr(C)]
+pub struct CkAesCtrParams { // Called CK_AES_CTR_PARAMS in NSS
+    counter_bits: raw::c_ulong, // Called ulCounterBits in NSS
+    counter_block: [u8; 16], // Called cb in NSS
+}
+
+impl CkAesCtrParams {
+    /// The counter is the low counter_bits bits of the initial counter block.
+    pub fn new(counter_bits: usize, counter_block: [u8; 16]) -> CkAesCtrParams {
+        CkAesCtrParams {
+            counter_bits: counter_bits as raw::c_ulong,
+            counter_block,
+        }
+    }
+}
+
+#[repr(C)]
+pub struct CkChaCha20Poly1305Params { // Called CK_SALSA20_CHACHA20_POLY1305_PARAMS in NSS
+    nonce: *const u8, // Called pNonce in NSS
+    nonce_len: raw::c_ulong, // Called ulNonceLen in NSS
+    aad: *const u8, // Called pAAD in NSS
+    aad_len: raw::c_ulong, // Called ulAADLen in NSS
+}
+
+impl CkChaCha20Poly1305Params {
+    /// The parameters borrow nonce and aad, which must outlive them.
+    pub fn new(nonce: &[u8], aad: &[u8]) -> CkChaCha20Poly1305Params {
+        CkChaCha20Poly1305Params {
+            nonce: nonce.as_ptr(),
+            nonce_len: nonce.len() as raw::c_ulong,
+            aad: aad.as_ptr(),
+            aad_len: aad.len() as raw::c_ulong,
+        }
+    }
+}
+
+// TODO: link to NSS source where these are defined
+pub type SECOidTag = raw::c_uint; // TODO: actually an enum - is this the right size?
+pub const SEC_OID_SHA256: SECOidTag = 191;
+pub const SEC_OID_SHA384: SECOidTag = 192;
+pub const SEC_OID_SHA512: SECOidTag = 193;
+
+pub type CkMechanismType = raw::c_ulong; // called CK_MECHANISM_TYPE in NSS
+pub const CKM_ECDSA: CkMechanismType = 0x00001041;
+pub const CKM_RSA_PKCS_PSS: CkMechanismType = 0x0000000D;
+const CKM_SHA256: CkMechanismType = 0x00000250;
+pub const CKM_SHA256_HMAC: CkMechanismType = 0x00000251;
+pub const CKM_SHA384_HMAC: CkMechanismType = 0x00000261;
+pub const CKM_SHA512_HMAC: CkMechanismType = 0x00000271;
+pub const CKM_AES_CBC: CkMechanismType = 0x00001082;
+pub const CKM_AES_CTR: CkMechanismType = 0x00001086;
+pub const CKM_AES_GCM: CkMechanismType = 0x00001087;
+pub const CKM_CHACHA20_POLY1305: CkMechanismType = 0x00004021;
+pub const CKM_AES_KEY_WRAP: CkMechanismType = 0x00002109;
+pub const CKM_EC_KEY_PAIR_GEN: CkMechanismType = 0x00001040;
+pub const CKM_ECDH1_DERIVE: CkMechanismType = 0x00001050;
+
+pub type CkAttributeType = raw::c_ulong; // called CK_ATTRIBUTE_TYPE in NSS
+pub const CKA_ENCRYPT: CkAttributeType = 0x00000104;
+pub const CKA_DECRYPT: CkAttributeType = 0x00000105;
+pub const CKA_SIGN: CkAttributeType = 0x00000108;
+pub const CKA_DERIVE: CkAttributeType = 0x0000010C;
+pub const CKA_MODULUS: CkAttributeType = 0x00000120;
+pub const CKA_EC_PARAMS: CkAttributeType = 0x00000180;
+
+pub type CkFlags = raw::c_ulong; // called CK_FLAGS in NSS
+pub const CKF_VERIFY: CkFlags = 0x00002000;
+
+pub type CkObjectHandle = raw::c_ulong; // called CK_OBJECT_HANDLE in NSS
+pub const CK_INVALID_HANDLE: CkObjectHandle = 0;
+
+pub type CkEcKdfType = raw::c_ulong; // called CK_EC_KDF_TYPE in NSS
+pub const CKD_NULL: CkEcKdfType = 0x00000001;
+
+// See certt.h.
+pub const KU_DIGITAL_SIGNATURE: raw::c_uint = 0x80;
+pub const KU_KEY_AGREEMENT: raw::c_uint = 0x08;
+
+type PK11Origin = raw::c_uint; // TODO: actually an enum - is this the right size?
+const PK11_ORIGIN_UNWRAP: PK11Origin = 4; // Called PK11_OriginUnwrap in NSS
+
+type CkRsaPkcsMgfType = raw::c_ulong; // called CK_RSA_PKCS_MGF_TYPE in NSS
+const CKG_MGF1_SHA256: CkRsaPkcsMgfType = 0x00000002;
+
+pub type SECStatus = raw::c_int; // TODO: enum - right size?
+pub const SEC_SUCCESS: SECStatus = 0; // Called SECSuccess in NSS
+pub const SEC_FAILURE: SECStatus = -1; // Called SECFailure in NSS
+
+// See keythi.h.
+pub type KeyType = raw::c_int; // TODO: actually an enum - is this the right size?
+pub const RSA_KEY: KeyType = 1; // Called rsaKey in NSS
+pub const EC_KEY: KeyType = 6; // Called ecKey in NSS
+pub const RSA_PSS_KEY: KeyType = 7; // Called rsaPssKey in NSS
+
+pub type PK11ObjectType = raw::c_int; // TODO: actually an enum - is this the right size?
+pub const PK11_TYPE_PRIV_KEY: PK11ObjectType = 1; // Called PK11_TypePrivKey in NSS
+
+pub enum CERTSubjectPublicKeyInfo {}
+
+pub enum SECKEYPublicKey {}
+
+pub enum SECKEYPrivateKey {}
+
+pub enum CERTCertDBHandle {}
+
+pub enum CERTCertificate {}
+
+pub enum CERTCertList {}
+
+pub enum CERTRevocationFlags {}
+
+pub enum PK11SlotInfo {}
+
+pub enum PK11SymKey {}
+
+pub enum PK11Context {}
+
+pub const SHA256_LENGTH: usize = 32;
+pub const SHA384_LENGTH: usize = 48;
+pub const SHA512_LENGTH: usize = 64;
+
+// See certt.h. CERT_PKIXVerifyCert only accepts one usage at a time.
+pub type SECCertificateUsage = i64;
+pub const CERTIFICATE_USAGE_SSL_CLIENT: SECCertificateUsage = 0x0001;
+pub const CERTIFICATE_USAGE_SSL_SERVER: SECCertificateUsage = 0x0002;
+pub const CERTIFICATE_USAGE_EMAIL_SIGNER: SECCertificateUsage = 0x0010;
+pub const CERTIFICATE_USAGE_OBJECT_SIGNER: SECCertificateUsage = 0x0040;
+pub const CERTIFICATE_USAGE_STATUS_RESPONDER: SECCertificateUsage = 0x0400;
+
+pub type PRTime = i64; // Microseconds since the epoch.
+
+// Called CERTValParamInType in NSS. Only the parameters used here are listed.
+pub type CertValParamInType = raw::c_int;
+pub const CERT_PI_END: CertValParamInType = 0;
+pub const CERT_PI_DATE: CertValParamInType = 8;
+pub const CERT_PI_REVOCATION_FLAGS: CertValParamInType = 9;
+pub const CERT_PI_TRUST_ANCHORS: CertValParamInType = 11;
+
+/// Called CERTValParamInValue in NSS. The scalar, pointer and array members are unions in NSS. The
+/// only scalar used here is a PRTime, so that's how the scalar union is represented.
+#[repr(C)]
+pub struct CertValParamInValue {
+    scalar: i64,
+    pointer: *const raw::c_void,
+    array: *const raw::c_void,
+    array_size: raw::c_int,
+}
+
+/// Called CERTValInParam in NSS.
+#[repr(C)]
+pub struct CertValInParam {
+    typ: CertValParamInType,
+    value: CertValParamInValue,
+}
+
+impl CertValInParam {
+    pub fn new_pointer<T>(typ: CertValParamInType, pointer: *const T) -> CertValInParam {
+        CertValInParam {
+            typ,
+            value: CertValParamInValue {
+                scalar: 0,
+                pointer: pointer as *const raw::c_void,
+                array: ptr::null(),
+                array_size: 0,
+            },
+        }
+    }
+
+    pub fn new_time(typ: CertValParamInType, time: PRTime) -> CertValInParam {
+        CertValInParam {
+            typ,
+            value: CertValParamInValue {
+                scalar: time,
+                pointer: ptr::null(),
+                array: ptr::null(),
+                array_size: 0,
+            },
+        }
+    }
+
+    pub fn end() -> CertValInParam {
+        CertValInParam::new_pointer(CERT_PI_END, ptr::null::<raw::c_void>())
+    }
+}
+
+pub enum CertValOutParam {}
+
+// See secerr.h. These are the errors certificate verification is expected to produce.
+pub type PRErrorCode = raw::c_int;
+const SEC_ERROR_BASE: PRErrorCode = -0x2000;
+pub const SEC_ERROR_BAD_SIGNATURE: PRErrorCode = SEC_ERROR_BASE + 10;
+pub const SEC_ERROR_EXPIRED_CERTIFICATE: PRErrorCode = SEC_ERROR_BASE + 11;
+pub const SEC_ERROR_UNKNOWN_ISSUER: PRErrorCode = SEC_ERROR_BASE + 13;
+pub const SEC_ERROR_UNTRUSTED_ISSUER: PRErrorCode = SEC_ERROR_BASE + 20;
+pub const SEC_ERROR_UNTRUSTED_CERT: PRErrorCode = SEC_ERROR_BASE + 21;
+pub const SEC_ERROR_EXPIRED_ISSUER_CERTIFICATE: PRErrorCode = SEC_ERROR_BASE + 30;
+pub const SEC_ERROR_INADEQUATE_KEY_USAGE: PRErrorCode = SEC_ERROR_BASE + 90;
+pub const SEC_ERROR_INADEQUATE_CERT_TYPE: PRErrorCode = SEC_ERROR_BASE + 91;
+
+// TODO: ugh this will probably have a platform-specific name...
+#[link(name="nss3")]
+extern "C" {
+    pub fn PK11_HashBuf(hashAlg: SECOidTag,
+                        out: *mut u8,
+                        data_in: *const u8, // called "in" in NSS
+                        len: raw::c_int) -> SECStatus;
+    pub fn PK11_CreateDigestContext(hashAlg: SECOidTag) -> *const PK11Context;
+    pub fn PK11_DigestBegin(cx: *const PK11Context) -> SECStatus;
+    pub fn PK11_DigestOp(context: *const PK11Context,
+                         data_in: *const u8, // called "in" in NSS
+                         len: raw::c_uint) -> SECStatus;
+    pub fn PK11_DigestFinal(context: *const PK11Context,
+                            data: *mut u8,
+                            outLen: *mut raw::c_uint,
+                            length: raw::c_uint) -> SECStatus;
+    pub fn PK11_DestroyContext(context: *const PK11Context, freeit: PRBool);
+    pub fn PK11_VerifyWithMechanism(key: *const SECKEYPublicKey,
+                                    mechanism: CkMechanismType,
+                                    param: *const SECItem,
+                                    sig: *const SECItem,
+                                    hash: *const SECItem,
+                                    wincx: *const raw::c_void) -> SECStatus;
+    pub fn PK11_SignatureLen(key: *const SECKEYPrivateKey) -> raw::c_int;
+    pub fn PK11_SignWithMechanism(key: *const SECKEYPrivateKey,
+                                  mechanism: CkMechanismType,
+                                  param: *const SECItem,
+                                  sig: *mut SECItem,
+                                  hash: *const SECItem) -> SECStatus;
+
+    pub fn SECKEY_DecodeDERSubjectPublicKeyInfo(spkider: *const SECItem)
+       -> *const CERTSubjectPublicKeyInfo;
+    pub fn SECKEY_DestroySubjectPublicKeyInfo(spki: *const CERTSubjectPublicKeyInfo);
+    pub fn SECKEY_EncodeDERSubjectPublicKeyInfo(pubk: *const SECKEYPublicKey) -> *mut SECItem;
+
+    pub fn SECKEY_ExtractPublicKey(spki: *const CERTSubjectPublicKeyInfo)
+       -> *const SECKEYPublicKey;
+    pub fn SECKEY_DestroyPublicKey(pubk: *const SECKEYPublicKey);
+    pub fn SECKEY_GetPublicKeyType(pubKey: *const SECKEYPublicKey) -> KeyType;
+    pub fn SECKEY_PublicKeyStrengthInBits(pubk: *const SECKEYPublicKey) -> raw::c_uint;
+    pub fn SECKEY_DestroyPrivateKey(key: *const SECKEYPrivateKey);
+    pub fn SECKEY_GetPrivateKeyType(privKey: *const SECKEYPrivateKey) -> KeyType;
+    // The data of the item is allocated by NSS and must be freed with SECITEM_FreeItem.
+    pub fn PK11_ReadRawAttribute(typ: PK11ObjectType,
+                                 object: *const raw::c_void,
+                                 attr: CkAttributeType,
+                                 item: *mut SECItem) -> SECStatus;
+
+    pub fn CERT_GetDefaultCertDB() -> *const CERTCertDBHandle;
+    pub fn CERT_NewTempCertificate(handle: *const CERTCertDBHandle,
+                                   derCert: *const SECItem,
+                                   nickname: *const raw::c_char,
+                                   isperm: PRBool,
+                                   copyDER: PRBool) -> *const CERTCertificate;
+    pub fn CERT_DupCertificate(c: *const CERTCertificate) -> *const CERTCertificate;
+    pub fn CERT_DestroyCertificate(cert: *const CERTCertificate);
+    pub fn CERT_ExtractPublicKey(cert: *const CERTCertificate) -> *const SECKEYPublicKey;
+
+    pub fn CERT_NewCertList() -> *const CERTCertList;
+    // Takes ownership of the given reference to cert on success.
+    pub fn CERT_AddCertToListTail(certs: *const CERTCertList, cert: *const CERTCertificate)
+       -> SECStatus;
+    pub fn CERT_DestroyCertList(certs: *const CERTCertList);
+
+    pub fn CERT_GetClassicOCSPDisabledPolicy() -> *const CERTRevocationFlags;
+    pub fn CERT_PKIXVerifyCert(cert: *const CERTCertificate,
+                               usages: SECCertificateUsage,
+                               paramsIn: *const CertValInParam,
+                               paramsOut: *mut CertValOutParam,
+                               wincx: *const raw::c_void) -> SECStatus;
+
+    pub fn PK11_GetInternalSlot() -> *const PK11SlotInfo;
+    pub fn PK11_FreeSlot(slot: *const PK11SlotInfo);
+    pub fn PK11_GetBestSlotWithAttributes(typ: CkMechanismType, // called "type" in NSS
+                                          mechFlag: CkFlags,
+                                          keySize: raw::c_uint,
+                                          wincx: *const raw::c_void) -> *const PK11SlotInfo;
+    pub fn PK11_ImportPublicKey(slot: *const PK11SlotInfo,
+                                pubKey: *const SECKEYPublicKey,
+                                isToken: PRBool) -> CkObjectHandle;
+    pub fn PK11_ImportSymKey(slot: *const PK11SlotInfo,
+                             typ: CkMechanismType, // called "type" in NSS
+                             origin: PK11Origin,
+                             operation: CkAttributeType,
+                             key: *const SECItem,
+                             wincx: *const raw::c_void) -> *const PK11SymKey;
+    pub fn PK11_FreeSymKey(key: *const PK11SymKey);
+    pub fn PK11_SignWithSymKey(symKey: *const PK11SymKey,
+                               mechanism: CkMechanismType,
+                               param: *const SECItem,
+                               sig: *mut SECItem,
+                               data: *const SECItem) -> SECStatus;
+    pub fn PK11_Encrypt(symKey: *const PK11SymKey,
+                        mechanism: CkMechanismType,
+                        param: *const SECItem,
+                        out: *mut u8,
+                        outLen: *mut raw::c_uint,
+                        maxLen: raw::c_uint,
+                        data: *const u8,
+                        dataLen: raw::c_uint) -> SECStatus;
+    pub fn PK11_Decrypt(symkey: *const PK11SymKey,
+                        mechanism: CkMechanismType,
+                        param: *const SECItem,
+                        out: *mut u8,
+                        outLen: *mut raw::c_uint,
+                        maxLen: raw::c_uint,
+                        enc: *const u8,
+                        encLen: raw::c_uint) -> SECStatus;
+    pub fn PK11_GenerateRandom(data: *mut u8, len: raw::c_int) -> SECStatus;
+
+    pub fn PK11_GenerateKeyPair(slot: *const PK11SlotInfo,
+                                typ: CkMechanismType, // called "type" in NSS
+                                param: *const raw::c_void,
+                                pubk: *mut *const SECKEYPublicKey,
+                                isPerm: PRBool,
+                                isSensitive: PRBool,
+                                wincx: *const raw::c_void) -> *const SECKEYPrivateKey;
+    pub fn PK11_ImportDERPrivateKeyInfoAndReturnKey(slot: *const PK11SlotInfo,
+                                                    derPKI: *const SECItem,
+                                                    nickname: *const SECItem,
+                                                    publicValue: *const SECItem,
+                                                    isPerm: PRBool,
+                                                    isPrivate: PRBool,
+                                                    usage: raw::c_uint,
+                                                    privk: *mut *const SECKEYPrivateKey,
+                                                    wincx: *const raw::c_void) -> SECStatus;
+    pub fn PK11_PubDeriveWithKDF(privKey: *const SECKEYPrivateKey,
+                                 pubKey: *const SECKEYPublicKey,
+                                 isSender: PRBool,
+                                 randomA: *const SECItem,
+                                 randomB: *const SECItem,
+                                 derive: CkMechanismType,
+                                 target: CkMechanismType,
+                                 operation: CkAttributeType,
+                                 keySize: raw::c_int,
+                                 kdf: CkEcKdfType,
+                                 sharedData: *const SECItem,
+                                 wincx: *const raw::c_void) -> *const PK11SymKey;
+    pub fn PK11_ExtractKeyValue(symKey: *const PK11SymKey) -> SECStatus;
+    // The returned item is owned by the key.
+    pub fn PK11_GetKeyData(symKey: *const PK11SymKey) -> *const SECItem;
+}
+
+#[link(name="nssutil3")]
+extern "C" {
+    pub fn NSS_SecureMemcmp(a: *const raw::c_void, b: *const raw::c_void, n: usize) -> raw::c_int;
+    pub fn PORT_GetError() -> PRErrorCode;
+    pub fn SECITEM_FreeItem(zap: *mut SECItem, freeit: PRBool);
+}
+
+/// The leading members of SECKEYPublicKey (see keythi.h), through the first member of the union of
+/// key type specific structures. For EC keys, that is the DER encoding of the curve parameters.
+#[repr(C)]
+struct SECKEYPublicKeyPrefix {
+    arena: *const raw::c_void,
+    key_type: KeyType,
+    pkcs11_slot: *const PK11SlotInfo,
+    pkcs11_id: CkObjectHandle,
+    ec_params: SECItem,
+}
+
+/// Returns the DER encoding of the curve parameters of the given EC key, which is borrowed. Those
+/// of a named curve are its object identifier.
+pub fn ec_public_key_params(key: *const SECKEYPublicKey) -> Vec<u8> {
+    let params = unsafe { &(*(key as *const SECKEYPublicKeyPrefix)).ec_params };
+    if params.data.is_null() {
+        return Vec::new();
+    }
+    unsafe { slice::from_raw_parts(params.data, params.len as usize) }.to_vec()
+}
+
+/// Returns the value of the given attribute of the given private key, which is borrowed, or None
+/// if it can't be read.
+pub fn private_key_attribute(key: *const SECKEYPrivateKey, attribute: CkAttributeType)
+                             -> Option<Vec<u8>> {
+    let mut item = SECItem { typ: SI_BUFFER, data: ptr::null(), len: 0 };
+    let result = unsafe {
+        PK11_ReadRawAttribute(PK11_TYPE_PRIV_KEY, key as *const raw::c_void, attribute, &mut item)
+    };
+    if result != SEC_SUCCESS {
+        return None;
+    }
+    let value = if item.data.is_null() {
+        Vec::new()
+    } else {
+        unsafe { slice::from_raw_parts(item.data, item.len as usize) }.to_vec()
+    };
+    unsafe { SECITEM_FreeItem(&mut item, PR_FALSE); }
+    Some(value)
+}
+
+/// Returns the DER encoding of the subject public key info of the given key, which is borrowed.
+pub fn encode_spki(key: *const SECKEYPublicKey) -> Result<Vec<u8>, VerifyError> {
+    let spki_item = unsafe { SECKEY_EncodeDERSubjectPublicKeyInfo(key) };
+    if spki_item.is_null() {
+        return Err(VerifyError::LibraryFailure);
+    }
+    defer!(unsafe { SECITEM_FreeItem(spki_item, PR_TRUE); });
+    let spki = unsafe { slice::from_raw_parts((*spki_item).data, (*spki_item).len as usize) };
+    Ok(spki.to_vec())
+}
+
+/// Imports the given raw key into the internal slot for use with the given mechanism and
+/// operation and calls f with it. The key is only valid for the duration of the call.
+pub fn with_sym_key<T, F>(mechanism: CkMechanismType, operation: CkAttributeType, key: &[u8], f: F)
+                          -> Result<T, VerifyError>
+    where F: FnOnce(*const PK11SymKey) -> Result<T, VerifyError>
+{
+    let key_item = SECItem::maybe_new(key)?;
+    let slot = unsafe { PK11_GetInternalSlot() };
+    if slot.is_null() {
+        return Err(VerifyError::LibraryFailure);
+    }
+    defer!(unsafe { PK11_FreeSlot(slot); });
+    let sym_key = unsafe {
+        PK11_ImportSymKey(slot, mechanism, PK11_ORIGIN_UNWRAP, operation, &key_item, ptr::null())
+    };
+    if sym_key.is_null() {
+        return Err(VerifyError::InvalidKey);
+    }
+    defer!(unsafe { PK11_FreeSymKey(sym_key); });
+    f(sym_key)
+}
+
+/// Compares two byte strings in time that depends only on their lengths.
+pub fn secure_compare(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && unsafe {
+        NSS_SecureMemcmp(a.as_ptr() as *const raw::c_void, b.as_ptr() as *const raw::c_void,
+                         a.len()) == 0
+    }
+}
+
+/// Returns the given number of random bytes from the NSS random number generator.
+pub fn generate_random(length: usize) -> Result<Vec<u8>, VerifyError> {
+    if length > raw::c_int::MAX as usize {
+        return Err(VerifyError::InputTooLarge);
+    }
+    let mut bytes = vec![0; length];
+    if unsafe { PK11_GenerateRandom(bytes.as_mut_ptr(), length as raw::c_int) } != SEC_SUCCESS {
+        return Err(VerifyError::LibraryFailure);
+    }
+    Ok(bytes)
+}
+
+/// Decodes the given DER subject public key info and calls f with the public key. The key is only
+/// valid for the duration of the call.
+pub fn with_spki_public_key<T, F>(spki: &[u8], f: F) -> Result<T, VerifyError>
+    where F: FnOnce(*const SECKEYPublicKey) -> Result<T, VerifyError>
+{
+    let spki_item = SECItem::maybe_new(spki)?;
+    let spki = unsafe { SECKEY_DecodeDERSubjectPublicKeyInfo(&spki_item) };
+    if spki.is_null() {
+        return Err(VerifyError::DecodingSPKIFailed);
+    }
+    defer!(unsafe { SECKEY_DestroySubjectPublicKeyInfo(spki); });
+    let key = unsafe { SECKEY_ExtractPublicKey(spki) };
+    if key.is_null() {
+        return Err(VerifyError::LibraryFailure);
+    }
+    defer!(unsafe { SECKEY_DestroyPublicKey(key); });
+    f(key)
+}
+
+/// Imports the given DER PKCS #8 PrivateKeyInfo (RFC 5208) into the internal slot for the given key
+/// usage and calls f with the private key. The key is only valid for the duration of the call.
+pub fn with_private_key_info<T, F>(private_key_info: &[u8], usage: raw::c_uint, f: F)
+                                   -> Result<T, VerifyError>
+    where F: FnOnce(*const SECKEYPrivateKey) -> Result<T, VerifyError>
+{
+    let private_key_info_item = SECItem::maybe_new(private_key_info)?;
+    let slot = unsafe { PK11_GetInternalSlot() };
+    if slot.is_null() {
+        return Err(VerifyError::LibraryFailure);
+    }
+    defer!(unsafe { PK11_FreeSlot(slot); });
+    let mut private_key: *const SECKEYPrivateKey = ptr::null();
+    let result = unsafe {
+        PK11_ImportDERPrivateKeyInfoAndReturnKey(slot, &private_key_info_item, ptr::null(),
+                                                 ptr::null(), PR_FALSE, PR_TRUE, usage,
+                                                 &mut private_key, ptr::null())
+    };
+    if result != SEC_SUCCESS || private_key.is_null() {
+        return Err(VerifyError::InvalidKey);
+    }
+    defer!(unsafe { SECKEY_DestroyPrivateKey(private_key); });
+    f(private_key)
+}
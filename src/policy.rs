@@ -0,0 +1,138 @@
+use std::io::Read;
+use cose::{decode_cose_sign, decode_cose_sign1, sig_structure, CoseSign, CoseSignature};
+use digest::digest_reader;
+use serialize::bstr_head;
+use verify::{verify_prehashed, verify_signature, SignatureAlgorithm, VerifyError};
+
+/// A key that signatures may be verified with, identified by the kid the signer uses for it.
+pub struct TrustedKey<'a> {
+    pub kid: &'a [u8],
+    pub spki: &'a [u8],
+}
+
+/// Determines when a COSE_Sign with multiple signatures is considered valid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VerificationPolicy {
+    /// Every signature must verify with a trusted key.
+    All,
+    /// At least one signature must verify with a trusted key.
+    AtLeastOne,
+    /// Signatures from at least this many distinct trusted keys must verify. Signatures whose kids
+    /// or certificates resolve to the same key count once. A threshold of 0 is never satisfied.
+    Threshold(usize),
+}
+
+/// The outcome of verifying one COSE_Signature. kid and algorithm are None if the corresponding
+/// header was absent or could not be decoded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SignerReport {
+    pub kid: Option<Vec<u8>>,
+    pub algorithm: Option<SignatureAlgorithm>,
+    pub result: Result<(), VerifyError>,
+}
+
+/// The outcome of verifying a COSE_Sign: the individual result for each signature, in the order
+/// they appear in the structure, and whether or not they satisfy the requested policy.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerificationReport {
+    pub signers: Vec<SignerReport>,
+    pub policy_satisfied: bool,
+}
+
+fn find_trusted_key<'a, 'b>(trusted_keys: &'b [TrustedKey<'a>], kid: &[u8])
+                            -> Option<&'b TrustedKey<'a>> {
+    trusted_keys.iter().find(|trusted_key| trusted_key.kid == kid)
+}
+
+/// Verifies each signature of the given COSE_Sign with the subject public key info that
+/// signature_key returns for it, and evaluates the outcomes against the given policy. signature_key
+/// is only called for signatures with a supported algorithm. For the purposes of
+/// VerificationPolicy::Threshold, signers are distinguished by their keys.
+pub fn evaluate_cose_sign<F>(cose_sign: &CoseSign, policy: VerificationPolicy, mut signature_key: F)
+                             -> VerificationReport
+    where F: FnMut(&CoseSignature) -> Result<Vec<u8>, VerifyError>
+{
+    let mut signers = Vec::with_capacity(cose_sign.signatures.len());
+    let mut verified_keys: Vec<Vec<u8>> = Vec::new();
+    for cose_signature in &cose_sign.signatures {
+        let result = cose_signature.headers.signature_algorithm().and_then(|algorithm| {
+            let spki = signature_key(cose_signature)?;
+            verify_signature(algorithm, &spki, &cose_signature.to_verify,
+                             &cose_signature.signature)?;
+            if !verified_keys.contains(&spki) {
+                verified_keys.push(spki);
+            }
+            Ok(())
+        });
+        signers.push(SignerReport {
+            kid: cose_signature.headers.kid().unwrap_or(None).map(|kid| kid.to_vec()),
+            algorithm: cose_signature.headers.signature_algorithm().ok(),
+            result,
+        });
+    }
+    let policy_satisfied = match policy {
+        VerificationPolicy::All => signers.iter().all(|signer| signer.result.is_ok()),
+        VerificationPolicy::AtLeastOne => signers.iter().any(|signer| signer.result.is_ok()),
+        VerificationPolicy::Threshold(threshold) => {
+            threshold > 0 && verified_keys.len() >= threshold
+        }
+    };
+    VerificationReport {
+        signers,
+        policy_satisfied,
+    }
+}
+
+/// Given the bytes of a COSE_Sign (with the payload supplied separately if it is detached), a set of
+/// trusted keys, and a policy, verifies each signature with the trusted key its kid identifies and
+/// reports the individual outcomes. An error is returned only if the structure itself could not be
+/// decoded; failing signatures are recorded in the report instead.
+pub fn verify_cose_sign(cose_sign: &[u8], detached_payload: Option<&[u8]>,
+                        trusted_keys: &[TrustedKey], policy: VerificationPolicy)
+                        -> Result<VerificationReport, VerifyError> {
+    let cose_sign = decode_cose_sign(cose_sign, detached_payload)?;
+    Ok(evaluate_cose_sign(&cose_sign, policy, |cose_signature| {
+        let kid = cose_signature.headers.kid()?.ok_or(VerifyError::UnknownKeyId)?;
+        match find_trusted_key(trusted_keys, kid) {
+            Some(trusted_key) => Ok(trusted_key.spki.to_vec()),
+            None => Err(VerifyError::UnknownKeyId),
+        }
+    }))
+}
+
+/// Given the bytes of a COSE_Sign1 (with the payload supplied separately if it is detached) and a
+/// set of trusted keys, verifies the signature with the trusted key its kid identifies and returns
+/// the payload.
+///
+/// COSE_Sign1 = [ protected: bstr, unprotected: map, payload: bstr / nil, signature: bstr ]
+pub fn verify_cose_sign1(cose_sign1: &[u8], detached_payload: Option<&[u8]>,
+                         trusted_keys: &[TrustedKey]) -> Result<Vec<u8>, VerifyError> {
+    let cose_sign1 = decode_cose_sign1(cose_sign1, detached_payload)?;
+    let algorithm = cose_sign1.headers.signature_algorithm()?;
+    let kid = cose_sign1.headers.kid()?.ok_or(VerifyError::UnknownKeyId)?;
+    let trusted_key = find_trusted_key(trusted_keys, kid).ok_or(VerifyError::UnknownKeyId)?;
+    let to_verify = sig_structure("Signature1", &cose_sign1.headers.protected_bytes, None, &[],
+                                  &cose_sign1.payload);
+    verify_signature(algorithm, trusted_key.spki, &to_verify, &cose_sign1.signature)?;
+    Ok(cose_sign1.payload)
+}
+
+/// Like verify_cose_sign1, but for a COSE_Sign1 with a detached payload of the given length that
+/// is read from payload. The payload is hashed as it is read rather than held in memory, so it may
+/// be of any length.
+pub fn verify_cose_sign1_streaming<R: Read>(cose_sign1: &[u8], payload: &mut R,
+                                            payload_length: u64, trusted_keys: &[TrustedKey])
+                                            -> Result<(), VerifyError> {
+    let cose_sign1 = decode_cose_sign1(cose_sign1, Some(&[]))?;
+    let algorithm = cose_sign1.headers.signature_algorithm()?;
+    let kid = cose_sign1.headers.kid()?.ok_or(VerifyError::UnknownKeyId)?;
+    let trusted_key = find_trusted_key(trusted_keys, kid).ok_or(VerifyError::UnknownKeyId)?;
+    // The Sig_structure with an empty payload ends with the head of the payload bstr, which is
+    // replaced by the head for the actual length.
+    let mut prefix = sig_structure("Signature1", &cose_sign1.headers.protected_bytes, None, &[],
+                                   &[]);
+    prefix.pop();
+    prefix.extend(bstr_head(payload_length));
+    let hash = digest_reader(algorithm.hash_algorithm(), &prefix, payload, payload_length)?;
+    verify_prehashed(algorithm, trusted_key.spki, &hash, &cose_sign1.signature)
+}
@@ -0,0 +1,454 @@
+use std::collections::BTreeMap;
+use std::os::raw;
+use std::ptr;
+use cose::{label, unprotected_kid, Headers, HEADER_ALG};
+use ecdh::{agree_ephemeral, agree_static};
+use kdf::{derive_key, HkdfHash, HEADER_EPHEMERAL_KEY, HEADER_SALT, HEADER_STATIC_KEY,
+          HEADER_STATIC_KEY_ID};
+use key::{CoseKey, PointFormat};
+use mac::SharedKey;
+use nss::{generate_random, with_sym_key, CkAttributeType, CKA_DECRYPT, CKA_ENCRYPT,
+          CKM_AES_KEY_WRAP, SEC_SUCCESS, PK11_Decrypt, PK11_Encrypt};
+use serialize::CborType;
+use verify::VerifyError;
+
+/// An enum identifying supported ways of distributing the content key to a recipient (RFC 9053,
+/// Section 6). With Direct and the ECDH algorithms with HKDF alone the content key is the
+/// recipient's key (or derived from it), so a structure using them can have only that one
+/// recipient. The ECDH-ES algorithms use an ephemeral sender key and the ECDH-SS algorithms a
+/// static one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeyDistributionAlgorithm {
+    Direct,
+    A128KW,
+    A192KW,
+    A256KW,
+    EcdhEsHkdf256,
+    EcdhEsHkdf512,
+    EcdhSsHkdf256,
+    EcdhSsHkdf512,
+    EcdhEsA128KW,
+    EcdhEsA192KW,
+    EcdhEsA256KW,
+    EcdhSsA128KW,
+    EcdhSsA192KW,
+    EcdhSsA256KW,
+}
+
+impl KeyDistributionAlgorithm {
+    /// Returns the algorithm corresponding to the given value from the IANA "COSE Algorithms"
+    /// registry, if it is supported.
+    pub fn from_cose_id(id: i64) -> Option<KeyDistributionAlgorithm> {
+        match id {
+            -6 => Some(KeyDistributionAlgorithm::Direct),
+            -3 => Some(KeyDistributionAlgorithm::A128KW),
+            -4 => Some(KeyDistributionAlgorithm::A192KW),
+            -5 => Some(KeyDistributionAlgorithm::A256KW),
+            -25 => Some(KeyDistributionAlgorithm::EcdhEsHkdf256),
+            -26 => Some(KeyDistributionAlgorithm::EcdhEsHkdf512),
+            -27 => Some(KeyDistributionAlgorithm::EcdhSsHkdf256),
+            -28 => Some(KeyDistributionAlgorithm::EcdhSsHkdf512),
+            -29 => Some(KeyDistributionAlgorithm::EcdhEsA128KW),
+            -30 => Some(KeyDistributionAlgorithm::EcdhEsA192KW),
+            -31 => Some(KeyDistributionAlgorithm::EcdhEsA256KW),
+            -32 => Some(KeyDistributionAlgorithm::EcdhSsA128KW),
+            -33 => Some(KeyDistributionAlgorithm::EcdhSsA192KW),
+            -34 => Some(KeyDistributionAlgorithm::EcdhSsA256KW),
+            _ => None,
+        }
+    }
+
+    /// Returns the value identifying this algorithm in the IANA "COSE Algorithms" registry.
+    pub fn cose_id(&self) -> i64 {
+        match *self {
+            KeyDistributionAlgorithm::Direct => -6,
+            KeyDistributionAlgorithm::A128KW => -3,
+            KeyDistributionAlgorithm::A192KW => -4,
+            KeyDistributionAlgorithm::A256KW => -5,
+            KeyDistributionAlgorithm::EcdhEsHkdf256 => -25,
+            KeyDistributionAlgorithm::EcdhEsHkdf512 => -26,
+            KeyDistributionAlgorithm::EcdhSsHkdf256 => -27,
+            KeyDistributionAlgorithm::EcdhSsHkdf512 => -28,
+            KeyDistributionAlgorithm::EcdhEsA128KW => -29,
+            KeyDistributionAlgorithm::EcdhEsA192KW => -30,
+            KeyDistributionAlgorithm::EcdhEsA256KW => -31,
+            KeyDistributionAlgorithm::EcdhSsA128KW => -32,
+            KeyDistributionAlgorithm::EcdhSsA192KW => -33,
+            KeyDistributionAlgorithm::EcdhSsA256KW => -34,
+        }
+    }
+
+    /// Returns whether the content key is the recipient's key or is derived from it, rather than
+    /// being wrapped for the recipient.
+    pub fn is_direct(&self) -> bool {
+        self.key_wrap().is_none()
+    }
+
+    /// Returns whether the recipient's key is an EC2 key to agree on a key with.
+    fn is_key_agreement(&self) -> bool {
+        !matches!(*self, KeyDistributionAlgorithm::Direct | KeyDistributionAlgorithm::A128KW |
+                         KeyDistributionAlgorithm::A192KW | KeyDistributionAlgorithm::A256KW)
+    }
+
+    /// Returns whether the sender agrees on a key with a static rather than an ephemeral key.
+    pub fn is_static_static(&self) -> bool {
+        matches!(*self, KeyDistributionAlgorithm::EcdhSsHkdf256 |
+                        KeyDistributionAlgorithm::EcdhSsHkdf512 |
+                        KeyDistributionAlgorithm::EcdhSsA128KW |
+                        KeyDistributionAlgorithm::EcdhSsA192KW |
+                        KeyDistributionAlgorithm::EcdhSsA256KW)
+    }
+
+    /// Returns the hash HKDF is used with to derive a key from the shared secret of a key
+    /// agreement algorithm. Those with key wrap always use SHA-256.
+    fn hkdf_hash(&self) -> HkdfHash {
+        match *self {
+            KeyDistributionAlgorithm::EcdhEsHkdf512 |
+            KeyDistributionAlgorithm::EcdhSsHkdf512 => HkdfHash::SHA512,
+            _ => HkdfHash::SHA256,
+        }
+    }
+
+    /// Returns the AES key wrap algorithm and the length in bytes of its key, if the content key
+    /// is wrapped.
+    fn key_wrap(&self) -> Option<(i64, usize)> {
+        match *self {
+            KeyDistributionAlgorithm::A128KW |
+            KeyDistributionAlgorithm::EcdhEsA128KW |
+            KeyDistributionAlgorithm::EcdhSsA128KW => Some((-3, 16)),
+            KeyDistributionAlgorithm::A192KW |
+            KeyDistributionAlgorithm::EcdhEsA192KW |
+            KeyDistributionAlgorithm::EcdhSsA192KW => Some((-4, 24)),
+            KeyDistributionAlgorithm::A256KW |
+            KeyDistributionAlgorithm::EcdhEsA256KW |
+            KeyDistributionAlgorithm::EcdhSsA256KW => Some((-5, 32)),
+            _ => None,
+        }
+    }
+}
+
+/// A recipient's key. Shared keys are used with Direct and the AES key wrap algorithms, and EC2
+/// keys with the ECDH algorithms. The private key d of an EC2 key (the big-endian scalar) is
+/// only needed to decrypt. An EC2 key is identified by the kid of its COSE_Key. When decrypting,
+/// EC2 keys without d are the static keys of senders using ECDH-SS, looked up by their kid.
+pub enum RecipientKey<'a> {
+    Shared(SharedKey<'a>),
+    EC2 { key: &'a CoseKey, d: Option<&'a [u8]> },
+}
+
+impl<'a> RecipientKey<'a> {
+    fn kid(&self) -> Option<&[u8]> {
+        match *self {
+            RecipientKey::Shared(ref shared_key) => Some(shared_key.kid),
+            RecipientKey::EC2 { key, .. } => key.kid.as_ref().map(|kid| &kid[..]),
+        }
+    }
+}
+
+/// The static EC2 key pair of a sender using ECDH-SS, with the private key d as the big-endian
+/// scalar. The recipient finds the public key by its kid, or is sent it if it has none.
+pub struct StaticKey<'a> {
+    pub key: &'a CoseKey,
+    pub d: &'a [u8],
+}
+
+/// A recipient of a COSE_Encrypt and how the content key is distributed to it. The sender key is
+/// only used with the ECDH-SS algorithms.
+pub struct Recipient<'a> {
+    pub algorithm: KeyDistributionAlgorithm,
+    pub key: RecipientKey<'a>,
+    pub sender_key: Option<StaticKey<'a>>,
+}
+
+/// Wraps (with CKA_ENCRYPT) or unwraps (with CKA_DECRYPT) a key with AES key wrap (RFC 3394).
+fn aes_key_wrap(operation: CkAttributeType, kek: &[u8], input: &[u8])
+                -> Result<Vec<u8>, VerifyError> {
+    if input.len() % 8 != 0 || input.len() < 16 ||
+       input.len() > raw::c_uint::MAX as usize - 8 {
+        return Err(VerifyError::DecryptionFailed);
+    }
+    let output_length = input.len() + 8;
+    let mut output = vec![0; output_length];
+    let mut written: raw::c_uint = 0;
+    with_sym_key(CKM_AES_KEY_WRAP, operation, kek, |sym_key| {
+        let function = if operation == CKA_ENCRYPT { PK11_Encrypt } else { PK11_Decrypt };
+        let result = unsafe {
+            function(sym_key, CKM_AES_KEY_WRAP, ptr::null(), output.as_mut_ptr(), &mut written,
+                     output_length as raw::c_uint, input.as_ptr(), input.len() as raw::c_uint)
+        };
+        match result {
+            SEC_SUCCESS => Ok(()),
+            _ if operation == CKA_DECRYPT => Err(VerifyError::DecryptionFailed),
+            _ => Err(VerifyError::LibraryFailure),
+        }
+    })?;
+    output.truncate(written as usize);
+    Ok(output)
+}
+
+/// Returns the shared key of the given recipient key, which must be of the given length.
+fn shared_key<'a>(key: &RecipientKey<'a>, length: usize) -> Result<&'a [u8], VerifyError> {
+    match *key {
+        RecipientKey::Shared(SharedKey { key, .. }) if key.len() == length => Ok(key),
+        _ => Err(VerifyError::InvalidKey),
+    }
+}
+
+/// Checks that a recipient's EC2 key may be used with the given algorithm.
+fn check_ec2_key(key: &CoseKey, algorithm: KeyDistributionAlgorithm) -> Result<(), VerifyError> {
+    if key.alg.is_some_and(|alg| alg != algorithm.cose_id()) {
+        return Err(VerifyError::InvalidKey);
+    }
+    Ok(())
+}
+
+/// Builds the headers of the COSE_recipient for the given recipient. For the key agreement
+/// algorithms, this agrees on a key with the recipient's EC2 key and also returns the shared
+/// secret.
+fn sender_headers(recipient: &Recipient) -> Result<(Headers, Option<Vec<u8>>), VerifyError> {
+    let algorithm = recipient.algorithm;
+    let mut protected = BTreeMap::new();
+    let mut unprotected = unprotected_kid(recipient.key.kid());
+    // Key agreement algorithms protect the alg header, since it is part of the KDF context. The
+    // others must have an empty protected bucket (RFC 9053, Sections 6.1 and 6.2.1).
+    if !algorithm.is_key_agreement() {
+        unprotected.insert(label(HEADER_ALG), label(algorithm.cose_id()));
+        return Ok((Headers::new(protected, unprotected), None));
+    }
+    protected.insert(label(HEADER_ALG), label(algorithm.cose_id()));
+    let key = match recipient.key {
+        RecipientKey::EC2 { key, .. } => key,
+        _ => return Err(VerifyError::InvalidKey),
+    };
+    check_ec2_key(key, algorithm)?;
+    let shared_secret = if algorithm.is_static_static() {
+        let sender_key = match recipient.sender_key {
+            Some(ref sender_key) => sender_key,
+            None => return Err(VerifyError::InvalidKey),
+        };
+        match sender_key.key.kid {
+            Some(ref kid) => {
+                unprotected.insert(label(HEADER_STATIC_KEY_ID), CborType::BStr(kid.clone()))
+            }
+            None => {
+                unprotected.insert(label(HEADER_STATIC_KEY),
+                                   sender_key.key.to_cbor(PointFormat::Uncompressed))
+            }
+        };
+        // The static keys always give the same shared secret, so a random salt is what makes
+        // the derived key fresh (RFC 9053, Section 6.3.1).
+        unprotected.insert(label(HEADER_SALT), CborType::BStr(generate_random(32)?));
+        agree_static(sender_key.key, sender_key.d, key)?
+    } else {
+        let (ephemeral_key, shared_secret) = agree_ephemeral(key)?;
+        unprotected.insert(label(HEADER_EPHEMERAL_KEY),
+                           ephemeral_key.to_cbor(PointFormat::Uncompressed));
+        shared_secret
+    };
+    Ok((Headers::new(protected, unprotected), Some(shared_secret)))
+}
+
+fn encode(headers: Headers, ciphertext: Vec<u8>) -> CborType {
+    CborType::Arr(vec![CborType::BStr(headers.protected_bytes), CborType::Map(headers.unprotected),
+                       CborType::BStr(ciphertext)])
+}
+
+/// Builds the COSE_recipient that conveys the given content key to the given recipient by wrapping
+/// it. See direct_content_key for the direct algorithms.
+pub fn encode_recipient(recipient: &Recipient, content_key: &[u8])
+                        -> Result<CborType, VerifyError> {
+    let algorithm = recipient.algorithm;
+    let (kek_algorithm, kek_length) = match algorithm.key_wrap() {
+        Some(key_wrap) => key_wrap,
+        None => return Err(VerifyError::UnsupportedAlgorithm),
+    };
+    let (headers, shared_secret) = sender_headers(recipient)?;
+    let kek = match shared_secret {
+        Some(shared_secret) => {
+            derive_key(algorithm.hkdf_hash(), &shared_secret, &headers, kek_algorithm,
+                       kek_length)?
+        }
+        None => shared_key(&recipient.key, kek_length)?.to_vec(),
+    };
+    let ciphertext = aes_key_wrap(CKA_ENCRYPT, &kek, content_key)?;
+    Ok(encode(headers, ciphertext))
+}
+
+/// For a sole recipient using a direct algorithm, returns the content key for the content
+/// encryption algorithm with the given identifier and key length, along with the COSE_recipient.
+pub fn direct_content_key(recipient: &Recipient, content_algorithm_id: i64, key_length: usize)
+                          -> Result<(Vec<u8>, CborType), VerifyError> {
+    if !recipient.algorithm.is_direct() {
+        return Err(VerifyError::UnsupportedAlgorithm);
+    }
+    let (headers, shared_secret) = sender_headers(recipient)?;
+    let content_key = match shared_secret {
+        Some(shared_secret) => {
+            derive_key(recipient.algorithm.hkdf_hash(), &shared_secret, &headers,
+                       content_algorithm_id, key_length)?
+        }
+        None => shared_key(&recipient.key, key_length)?.to_vec(),
+    };
+    Ok((content_key, encode(headers, Vec::new())))
+}
+
+/// A decoded COSE_recipient. Nested recipients are not supported.
+///
+/// COSE_recipient = [ protected: bstr, unprotected: map, ciphertext: bstr / nil,
+///                    ? recipients: [+ COSE_recipient] ]
+pub struct DecodedRecipient {
+    pub headers: Headers,
+    pub ciphertext: Vec<u8>,
+}
+
+/// Decodes the recipients array of a COSE_Encrypt or COSE_Mac.
+pub fn decode_recipients(recipients: &CborType) -> Result<Vec<DecodedRecipient>, VerifyError> {
+    let recipients = match *recipients {
+        CborType::Arr(ref recipients) if !recipients.is_empty() => recipients,
+        _ => return Err(VerifyError::DecodingCOSEFailed),
+    };
+    let mut decoded = Vec::with_capacity(recipients.len());
+    for recipient in recipients {
+        let elements = match *recipient {
+            CborType::Arr(ref elements) if elements.len() == 3 => elements,
+            CborType::Arr(ref elements) if elements.len() == 4 => {
+                return Err(VerifyError::UnsupportedAlgorithm);
+            }
+            _ => return Err(VerifyError::DecodingCOSEFailed),
+        };
+        let headers = Headers::decode(&elements[0], &elements[1])?;
+        let ciphertext = match elements[2] {
+            CborType::BStr(ref ciphertext) => ciphertext.clone(),
+            _ => return Err(VerifyError::DecodingCOSEFailed),
+        };
+        decoded.push(DecodedRecipient { headers, ciphertext });
+    }
+    Ok(decoded)
+}
+
+/// Returns the sender's public key for a key agreement recipient: the ephemeral key for ECDH-ES,
+/// and for ECDH-SS the static key or the EC2 key among the given keys with the static key id.
+fn sender_public_key(headers: &Headers, algorithm: KeyDistributionAlgorithm,
+                     keys: &[RecipientKey]) -> Result<CoseKey, VerifyError> {
+    if !algorithm.is_static_static() {
+        return match headers.get(HEADER_EPHEMERAL_KEY) {
+            Some(ephemeral_key) => CoseKey::from_cbor(ephemeral_key),
+            None => Err(VerifyError::DecodingCOSEFailed),
+        };
+    }
+    if let Some(static_key) = headers.get(HEADER_STATIC_KEY) {
+        return CoseKey::from_cbor(static_key);
+    }
+    let kid = match headers.get(HEADER_STATIC_KEY_ID) {
+        Some(CborType::BStr(kid)) => kid,
+        _ => return Err(VerifyError::DecodingCOSEFailed),
+    };
+    keys.iter()
+        .filter_map(|key| match *key {
+            RecipientKey::EC2 { key, .. } if key.kid.as_ref() == Some(kid) => Some(key.clone()),
+            _ => None,
+        })
+        .next()
+        .ok_or(VerifyError::UnknownKeyId)
+}
+
+/// Recovers the content key, for the content encryption algorithm with the given identifier and
+/// key length, from the given recipient with the given key. The keys are those given to decrypt,
+/// which may include the sender's static key.
+fn recover_content_key(recipient: &DecodedRecipient, algorithm: KeyDistributionAlgorithm,
+                       key: &RecipientKey, keys: &[RecipientKey], content_algorithm_id: i64,
+                       key_length: usize) -> Result<Vec<u8>, VerifyError> {
+    if algorithm.is_direct() != recipient.ciphertext.is_empty() {
+        return Err(VerifyError::DecodingCOSEFailed);
+    }
+    if !algorithm.is_key_agreement() {
+        return match algorithm.key_wrap() {
+            None => Ok(shared_key(key, key_length)?.to_vec()),
+            Some((_, kek_length)) => {
+                aes_key_wrap(CKA_DECRYPT, shared_key(key, kek_length)?,
+                             &recipient.ciphertext)
+            }
+        };
+    }
+    let (public_key, d) = match *key {
+        RecipientKey::EC2 { key, d: Some(d) } => (key, d),
+        _ => return Err(VerifyError::InvalidKey),
+    };
+    check_ec2_key(public_key, algorithm)?;
+    let sender_key = sender_public_key(&recipient.headers, algorithm, keys)?;
+    let shared_secret = agree_static(public_key, d, &sender_key)?;
+    let headers = &recipient.headers;
+    match algorithm.key_wrap() {
+        None => {
+            derive_key(algorithm.hkdf_hash(), &shared_secret, headers, content_algorithm_id,
+                       key_length)
+        }
+        Some((kek_algorithm, kek_length)) => {
+            let kek = derive_key(algorithm.hkdf_hash(), &shared_secret, headers, kek_algorithm,
+                                 kek_length)?;
+            aes_key_wrap(CKA_DECRYPT, &kek, &recipient.ciphertext)
+        }
+    }
+}
+
+/// Tries each recipient that one of the given keys may be for and calls try_content_key with the
+/// content key recovered for it, until that succeeds. A recipient without a kid is tried with
+/// each key. Returns VerifyError::UnknownKeyId if no recipient is for one of the given keys.
+pub fn with_content_key<T, F>(recipients: &[DecodedRecipient], keys: &[RecipientKey],
+                              content_algorithm_id: i64, key_length: usize,
+                              mut try_content_key: F) -> Result<T, VerifyError>
+    where F: FnMut(&[u8]) -> Result<T, VerifyError>
+{
+    let mut result = Err(VerifyError::UnknownKeyId);
+    for recipient in recipients {
+        let id = recipient.headers.algorithm()?;
+        let algorithm = match KeyDistributionAlgorithm::from_cose_id(id) {
+            Some(algorithm) => algorithm,
+            None => {
+                if matches!(result, Err(VerifyError::UnknownKeyId)) {
+                    result = Err(VerifyError::UnsupportedAlgorithm);
+                }
+                continue;
+            }
+        };
+        if algorithm.is_direct() && recipients.len() != 1 {
+            return Err(VerifyError::DecodingCOSEFailed);
+        }
+        let kid = recipient.headers.kid()?;
+        for key in keys {
+            if kid.is_some_and(|kid| key.kid() != Some(kid)) {
+                continue;
+            }
+            if let RecipientKey::EC2 { d: None, .. } = *key {
+                continue;
+            }
+            result = recover_content_key(recipient, algorithm, key, keys, content_algorithm_id,
+                                         key_length)
+                .and_then(|content_key| try_content_key(&content_key));
+            if result.is_ok() {
+                return result;
+            }
+        }
+    }
+    result
+}
+
+#[test]
+fn test_algorithms() {
+    for id in -34..-24 {
+        let algorithm = KeyDistributionAlgorithm::from_cose_id(id).unwrap();
+        assert_eq!(algorithm.cose_id(), id);
+    }
+    for &id in &[-6, -3, -4, -5] {
+        let algorithm = KeyDistributionAlgorithm::from_cose_id(id).unwrap();
+        assert_eq!(algorithm.cose_id(), id);
+    }
+    assert_eq!(KeyDistributionAlgorithm::from_cose_id(-35), None);
+    assert!(KeyDistributionAlgorithm::EcdhEsHkdf256.is_direct());
+    assert!(KeyDistributionAlgorithm::EcdhSsHkdf512.is_direct());
+    assert!(!KeyDistributionAlgorithm::EcdhEsA128KW.is_direct());
+    assert!(KeyDistributionAlgorithm::EcdhSsA256KW.is_static_static());
+    assert!(!KeyDistributionAlgorithm::EcdhEsHkdf512.is_static_static());
+    assert_eq!(KeyDistributionAlgorithm::EcdhEsHkdf512.hkdf_hash(), HkdfHash::SHA512);
+    assert_eq!(KeyDistributionAlgorithm::EcdhSsA256KW.hkdf_hash(), HkdfHash::SHA256);
+}
@@ -1,13 +1,47 @@
+use std::cmp::Ordering;
 use std::string::String;
 use std::collections::BTreeMap;
 
-pub enum CborType<'a> {
+/// A CBOR data item. Integers, byte and text strings, arrays, maps, tags, booleans and null are
+/// supported. Floating-point values, undefined and other simple values are not.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CborType {
     UInt(u64),
     NInt(i64),
-    BStr(&'a [u8]),
-    TStr(&'a String),
-    Arr(&'a [CborType<'a>]),
-    Map(&'a BTreeMap<i64, CborType<'a>>), // TODO: find out what key value range we really have to support
+    BStr(Vec<u8>),
+    TStr(String),
+    Arr(Vec<CborType>),
+    Map(BTreeMap<CborType, CborType>),
+    Tag(u64, Box<CborType>),
+    Bool(bool),
+    Null,
+}
+
+/// Map keys are ordered such that all integers come first, sorted lowest value to highest.
+/// Everything else follows, sorted shortest encoding first and then lexicographically by encoding.
+impl Ord for CborType {
+    fn cmp(&self, other: &CborType) -> Ordering {
+        match (self, other) {
+            (&CborType::UInt(a), &CborType::UInt(b)) => a.cmp(&b),
+            (&CborType::NInt(a), &CborType::NInt(b)) => a.cmp(&b),
+            (&CborType::NInt(_), &CborType::UInt(_)) => Ordering::Less,
+            (&CborType::UInt(_), &CborType::NInt(_)) => Ordering::Greater,
+            (&CborType::UInt(_), _) | (&CborType::NInt(_), _) => Ordering::Less,
+            (_, &CborType::UInt(_)) | (_, &CborType::NInt(_)) => Ordering::Greater,
+            _ => {
+                let self_encoded = self.serialize();
+                let other_encoded = other.serialize();
+                self_encoded.len().cmp(&other_encoded.len())
+                    .then_with(|| self_encoded.cmp(&other_encoded))
+            }
+        }
+    }
+}
+
+impl PartialOrd for CborType {
+    fn partial_cmp(&self, other: &CborType) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 /// Given a vector of bytes to append to, a tag to use, and an unsigned value to encode, uses the
@@ -16,19 +50,19 @@ fn common_encode_unsigned(output: &mut Vec<u8>, tag: u8, value: u64) {
     assert!(tag < 8);
     let shifted_tag = tag << 5;
     match value {
-        0 ... 23 => {
+        0 ..= 23 => {
             output.push(shifted_tag | (value as u8));
         },
-        24 ... 255 => {
+        24 ..= 255 => {
             output.push(shifted_tag | 24);
             output.push(value as u8);
         },
-        256 ... 65535 => {
+        256 ..= 65535 => {
             output.push(shifted_tag | 25);
             output.push((value >> 8) as u8);
             output.push((value & 255) as u8);
         },
-        65536 ... 4294967295 => {
+        65536 ..= 4294967295 => {
             output.push(shifted_tag | 26);
             output.push((value >> 24) as u8);
             output.push(((value >> 16) & 255) as u8);
@@ -75,8 +109,15 @@ fn encode_bstr(output: &mut Vec<u8>, bstr: &[u8]) {
     }
 }
 
+/// Returns the head of a bstr of the given length, for when the data follows separately.
+pub fn bstr_head(length: u64) -> Vec<u8> {
+    let mut output = Vec::new();
+    common_encode_unsigned(&mut output, 2, length);
+    output
+}
+
 /// The major type is 3. The length is as with bstr. The UTF-8-encoded bytes of the string follow.
-fn encode_tstr(output: &mut Vec<u8>, tstr: &String) {
+fn encode_tstr(output: &mut Vec<u8>, tstr: &str) {
     let utf8_bytes = tstr.as_bytes();
     common_encode_unsigned(output, 3, utf8_bytes.len() as u64);
     for byte in utf8_bytes {
@@ -99,14 +140,10 @@ fn encode_array(output: &mut Vec<u8>, array: &[CborType]) {
 /// The major type is 5. The number of pairs is encoded as with positive integers. Then follows the
 /// encodings of each key, value pair. In Canonical CBOR, the keys must be sorted lowest value to
 /// highest.
-fn encode_map(output: &mut Vec<u8>, map: &BTreeMap<i64, CborType>) {
+fn encode_map(output: &mut Vec<u8>, map: &BTreeMap<CborType, CborType>) {
     common_encode_unsigned(output, 5, map.len() as u64);
     for (key, value) in map { // The implementation gives us this in sorted order already.
-        let key_encoded = if *key < 0 {
-            CborType::NInt(*key).serialize()
-        } else {
-            CborType::UInt(*key as u64).serialize()
-        };
+        let key_encoded = key.serialize();
         for byte in key_encoded {
             output.push(byte);
         }
@@ -117,16 +154,35 @@ fn encode_map(output: &mut Vec<u8>, map: &BTreeMap<i64, CborType>) {
     }
 }
 
-impl<'a> CborType<'a> {
+/// The major type is 6. The tag number is encoded as with positive integers. Then follows the
+/// encoding of the single tagged item.
+fn encode_tag(output: &mut Vec<u8>, tag: u64, value: &CborType) {
+    common_encode_unsigned(output, 6, tag);
+    let value_encoded = value.serialize();
+    for byte in value_encoded {
+        output.push(byte);
+    }
+}
+
+/// The major type is 7. false, true and null are the simple values 20, 21 and 22, respectively.
+fn encode_simple(output: &mut Vec<u8>, simple: u64) {
+    common_encode_unsigned(output, 7, simple);
+}
+
+impl CborType {
     pub fn serialize(&self) -> Vec<u8> {
         let mut bytes: Vec<u8> = Vec::new();
         match *self {
             CborType::UInt(unsigned) => encode_unsigned(&mut bytes, unsigned),
             CborType::NInt(negative) => encode_negative(&mut bytes, negative),
-            CborType::BStr(bstr) => encode_bstr(&mut bytes, bstr),
-            CborType::TStr(tstr) => encode_tstr(&mut bytes, tstr),
-            CborType::Arr(arr) => encode_array(&mut bytes, arr),
-            CborType::Map(map) => encode_map(&mut bytes, map),
+            CborType::BStr(ref bstr) => encode_bstr(&mut bytes, bstr),
+            CborType::TStr(ref tstr) => encode_tstr(&mut bytes, tstr),
+            CborType::Arr(ref arr) => encode_array(&mut bytes, arr),
+            CborType::Map(ref map) => encode_map(&mut bytes, map),
+            CborType::Tag(tag, ref value) => encode_tag(&mut bytes, tag, value),
+            CborType::Bool(false) => encode_simple(&mut bytes, 20),
+            CborType::Bool(true) => encode_simple(&mut bytes, 21),
+            CborType::Null => encode_simple(&mut bytes, 22),
         };
         bytes
     }
@@ -199,7 +255,7 @@ fn test_bstr() {
 
     ];
     for testcase in testcases {
-        let cbor = CborType::BStr(testcase.value.as_slice());
+        let cbor = CborType::BStr(testcase.value);
         assert_eq!(testcase.expected, cbor.serialize());
     }
 }
@@ -218,15 +274,15 @@ fn test_tstr() {
         Testcase { value: String::from("水"), expected: vec![0x63, 0xe6, 0xb0, 0xb4] },
     ];
     for testcase in testcases {
-        let cbor = CborType::TStr(&testcase.value);
+        let cbor = CborType::TStr(testcase.value);
         assert_eq!(testcase.expected, cbor.serialize());
     }
 }
 
 #[test]
 fn test_arr() {
-    struct Testcase<'a> {
-        value: Vec<CborType<'a>>,
+    struct Testcase {
+        value: Vec<CborType>,
         expected: Vec<u8>,
     }
     let nested_arr_1 = vec![CborType::UInt(2), CborType::UInt(3)];
@@ -236,8 +292,8 @@ fn test_arr() {
         Testcase { value: vec![CborType::UInt(1), CborType::UInt(2), CborType::UInt(3)],
                    expected: vec![0x83, 0x01, 0x02, 0x03] },
         Testcase { value: vec![CborType::UInt(1),
-                               CborType::Arr(nested_arr_1.as_slice()),
-                               CborType::Arr(nested_arr_2.as_slice())],
+                               CborType::Arr(nested_arr_1),
+                               CborType::Arr(nested_arr_2)],
                    expected: vec![0x83, 0x01, 0x82, 0x02, 0x03, 0x82, 0x04, 0x05] },
         Testcase { value: vec![CborType::UInt(1), CborType::UInt(2), CborType::UInt(3),
                                CborType::UInt(4), CborType::UInt(5), CborType::UInt(6),
@@ -253,35 +309,56 @@ fn test_arr() {
                                   0x15, 0x16, 0x17, 0x18, 0x18, 0x18, 0x19] },
     ];
     for testcase in testcases {
-        let cbor = CborType::Arr(&testcase.value.as_slice());
+        let cbor = CborType::Arr(testcase.value);
         assert_eq!(testcase.expected, cbor.serialize());
     }
 }
 
 #[test]
 fn test_map() {
-    let empty_map: BTreeMap<i64, CborType> = BTreeMap::new();
-    assert_eq!(vec![0xa0], CborType::Map(&empty_map).serialize());
+    let empty_map: BTreeMap<CborType, CborType> = BTreeMap::new();
+    assert_eq!(vec![0xa0], CborType::Map(empty_map).serialize());
 
-    let mut positive_map: BTreeMap<i64, CborType> = BTreeMap::new();
-    positive_map.insert(20, CborType::UInt(10));
-    positive_map.insert(10, CborType::UInt(20));
-    positive_map.insert(15, CborType::UInt(15));
+    let mut positive_map: BTreeMap<CborType, CborType> = BTreeMap::new();
+    positive_map.insert(CborType::UInt(20), CborType::UInt(10));
+    positive_map.insert(CborType::UInt(10), CborType::UInt(20));
+    positive_map.insert(CborType::UInt(15), CborType::UInt(15));
     assert_eq!(vec![0xa3, 0x0a, 0x14, 0x0f, 0x0f, 0x14, 0x0a],
-               CborType::Map(&positive_map).serialize());
+               CborType::Map(positive_map).serialize());
 
-    let mut negative_map: BTreeMap<i64, CborType> = BTreeMap::new();
-    negative_map.insert(-4, CborType::UInt(10));
-    negative_map.insert(-1, CborType::UInt(20));
-    negative_map.insert(-5, CborType::UInt(15));
-    negative_map.insert(-6, CborType::UInt(10));
+    let mut negative_map: BTreeMap<CborType, CborType> = BTreeMap::new();
+    negative_map.insert(CborType::NInt(-4), CborType::UInt(10));
+    negative_map.insert(CborType::NInt(-1), CborType::UInt(20));
+    negative_map.insert(CborType::NInt(-5), CborType::UInt(15));
+    negative_map.insert(CborType::NInt(-6), CborType::UInt(10));
     assert_eq!(vec![0xa4, 0x25, 0x0a, 0x24, 0x0f, 0x23, 0x0a, 0x20, 0x14],
-               CborType::Map(&negative_map).serialize());
+               CborType::Map(negative_map).serialize());
 
-    let mut mixed_map: BTreeMap<i64, CborType> = BTreeMap::new();
-    mixed_map.insert(0, CborType::UInt(10));
-    mixed_map.insert(-10, CborType::UInt(20));
-    mixed_map.insert(15, CborType::UInt(15));
+    let mut mixed_map: BTreeMap<CborType, CborType> = BTreeMap::new();
+    mixed_map.insert(CborType::UInt(0), CborType::UInt(10));
+    mixed_map.insert(CborType::NInt(-10), CborType::UInt(20));
+    mixed_map.insert(CborType::UInt(15), CborType::UInt(15));
     assert_eq!(vec![0xa3, 0x29, 0x14, 0x00, 0x0a, 0x0f, 0x0f],
-               CborType::Map(&mixed_map).serialize());
+               CborType::Map(mixed_map).serialize());
+}
+
+#[test]
+fn test_tag_and_simple() {
+    let tagged = CborType::Tag(98, Box::new(CborType::Arr(vec![CborType::UInt(1)])));
+    assert_eq!(vec![0xd8, 0x62, 0x81, 0x01], tagged.serialize());
+    assert_eq!(vec![0xf4], CborType::Bool(false).serialize());
+    assert_eq!(vec![0xf5], CborType::Bool(true).serialize());
+    assert_eq!(vec![0xf6], CborType::Null.serialize());
+}
+
+#[test]
+fn test_map_key_ordering() {
+    let mut map: BTreeMap<CborType, CborType> = BTreeMap::new();
+    map.insert(CborType::TStr(String::from("bb")), CborType::UInt(1));
+    map.insert(CborType::TStr(String::from("a")), CborType::UInt(2));
+    map.insert(CborType::UInt(1000), CborType::UInt(3));
+    map.insert(CborType::NInt(-1), CborType::UInt(4));
+    assert_eq!(vec![0xa4, 0x20, 0x04, 0x19, 0x03, 0xe8, 0x03, 0x61, 0x61, 0x02, 0x62, 0x62, 0x62,
+                    0x01],
+               CborType::Map(map).serialize());
 }
@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+use std::mem;
+use std::os::raw;
+use std::ptr;
+use std::slice;
+use cose::{label, sig_structure, unprotected_kid, Headers, COSE_SIGN1_TAG, HEADER_ALG};
+use ecdh::encode_private_key_info;
+use key::CoseKey;
+use nss::{with_private_key_info, CkRsaPkcsPssParams, SECItem, CKM_ECDSA, CKM_RSA_PKCS_PSS,
+          KU_DIGITAL_SIGNATURE, SEC_OID_SHA256, SEC_SUCCESS, SHA256_LENGTH, PK11_HashBuf,
+          PK11_SignWithMechanism, PK11_SignatureLen};
+use serialize::CborType;
+use verify::{KeyKind, SignatureAlgorithm, VerifyError};
+
+/// A private key to sign with.
+pub enum SigningKey<'a> {
+    /// An EC2 key pair, given as its public key and the big-endian private scalar d.
+    EC2 { key: &'a CoseKey, d: &'a [u8] },
+    /// A DER PKCS #8 PrivateKeyInfo (RFC 5208) holding an EC or an RSA key.
+    PrivateKeyInfo(&'a [u8]),
+}
+
+/// Signs the payload with the given private key, which must suit the algorithm as it must for
+/// verifying: ES256 needs a P-256 key and PS256 an RSA key of at least MIN_RSA_MODULUS_BITS bits.
+/// ES256 signatures are returned in the fixed-length r || s form COSE uses.
+pub fn sign(signature_algorithm: SignatureAlgorithm, key: &SigningKey, payload: &[u8])
+            -> Result<Vec<u8>, VerifyError> {
+    if payload.len() > raw::c_int::MAX as usize {
+        return Err(VerifyError::InputTooLarge);
+    }
+    let mut hash_buf = vec![0; SHA256_LENGTH];
+    let hash_result = unsafe {
+        PK11_HashBuf(SEC_OID_SHA256, hash_buf.as_mut_ptr(), payload.as_ptr(),
+                     payload.len() as raw::c_int)
+    };
+    if hash_result != SEC_SUCCESS {
+        return Err(VerifyError::LibraryFailure);
+    }
+    let hash_item = SECItem::maybe_new(&hash_buf)?;
+    let private_key_info = match *key {
+        SigningKey::EC2 { key, d } => encode_private_key_info(key, d)?,
+        SigningKey::PrivateKeyInfo(private_key_info) => private_key_info.to_vec(),
+    };
+    let rsa_pss_params = CkRsaPkcsPssParams::new();
+    let rsa_pss_params_bytes = unsafe {
+        slice::from_raw_parts(&rsa_pss_params as *const CkRsaPkcsPssParams as *const u8,
+                              mem::size_of::<CkRsaPkcsPssParams>())
+    };
+    let rsa_pss_params_item = SECItem::maybe_new(rsa_pss_params_bytes)?;
+    let (mechanism, params_item): (_, *const SECItem) = match signature_algorithm {
+        SignatureAlgorithm::ES256 => (CKM_ECDSA, ptr::null()),
+        SignatureAlgorithm::PS256 => (CKM_RSA_PKCS_PSS, &rsa_pss_params_item),
+    };
+    with_private_key_info(&private_key_info, KU_DIGITAL_SIGNATURE, |private_key| {
+        // NSS would sign ES256 with any EC key and PS256 with RSA keys of any size.
+        KeyKind::of_private_key(private_key).check_signature_algorithm(signature_algorithm)?;
+        let signature_len = unsafe { PK11_SignatureLen(private_key) };
+        if signature_len <= 0 {
+            return Err(VerifyError::InvalidKey);
+        }
+        let mut signature = vec![0; signature_len as usize];
+        let mut signature_item = SECItem::maybe_new_output(&mut signature)?;
+        let result = unsafe {
+            PK11_SignWithMechanism(private_key, mechanism, params_item, &mut signature_item,
+                                   &hash_item)
+        };
+        if result != SEC_SUCCESS {
+            return Err(VerifyError::InvalidKey);
+        }
+        let signature_len = signature_item.len as usize;
+        signature.truncate(signature_len);
+        Ok(signature)
+    })
+}
+
+/// Creates a tagged COSE_Sign1 over the given payload. The algorithm goes in the protected header
+/// and the kid, if given, in the unprotected header.
+pub fn create_cose_sign1(signature_algorithm: SignatureAlgorithm, key: &SigningKey,
+                         kid: Option<&[u8]>, payload: &[u8]) -> Result<Vec<u8>, VerifyError> {
+    let mut protected = BTreeMap::new();
+    protected.insert(label(HEADER_ALG), label(signature_algorithm.cose_id()));
+    let headers = Headers::new(protected, unprotected_kid(kid));
+    create_cose_sign1_with_headers(signature_algorithm, key, &headers, payload)
+}
+
+/// Creates a tagged COSE_Sign1 over the given payload with the given headers, which should
+/// identify the algorithm.
+pub fn create_cose_sign1_with_headers(signature_algorithm: SignatureAlgorithm, key: &SigningKey,
+                                      headers: &Headers, payload: &[u8])
+                                      -> Result<Vec<u8>, VerifyError> {
+    let to_sign = sig_structure("Signature1", &headers.protected_bytes, None, &[], payload);
+    let signature = sign(signature_algorithm, key, &to_sign)?;
+    let elements = vec![CborType::BStr(headers.protected_bytes.clone()),
+                        CborType::Map(headers.unprotected.clone()),
+                        CborType::BStr(payload.to_vec()), CborType::BStr(signature)];
+    Ok(CborType::Tag(COSE_SIGN1_TAG, Box::new(CborType::Arr(elements))).serialize())
+}
@@ -2,101 +2,96 @@ use std::os::raw;
 use std::ptr;
 use std::slice;
 use std::mem;
+use digest::{digest, HashAlgorithm};
+use der;
+use key::{CoseKey, Curve, KeyOperation};
+use nss::{ec_public_key_params, private_key_attribute, CkRsaPkcsPssParams, SECItem,
+          SECKEYPrivateKey, SECKEYPublicKey, CKA_EC_PARAMS, CKA_MODULUS, CKF_VERIFY, CKM_ECDSA,
+          CKM_RSA_PKCS_PSS, CK_INVALID_HANDLE, EC_KEY, PR_FALSE, RSA_KEY, RSA_PSS_KEY, SEC_FAILURE,
+          SEC_OID_SHA256, SEC_SUCCESS, SHA256_LENGTH, PK11_FreeSlot,
+          PK11_GetBestSlotWithAttributes, PK11_HashBuf, PK11_ImportPublicKey,
+          PK11_VerifyWithMechanism, SECKEY_DecodeDERSubjectPublicKeyInfo, SECKEY_DestroyPublicKey,
+          SECKEY_DestroySubjectPublicKeyInfo, SECKEY_ExtractPublicKey, SECKEY_GetPrivateKeyType,
+          SECKEY_GetPublicKeyType, SECKEY_PublicKeyStrengthInBits};
 
 /// An enum identifying supported signature algorithms. Currently only ECDSA with SHA256 (ES256) and
 /// RSASSA-PSS with SHA-256 (PS256) are supported. Note that with PS256, the salt length is defined
 /// to be 32 bytes.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum SignatureAlgorithm {
     ES256,
     PS256,
 }
 
-type SECItemType = raw::c_uint; // TODO: actually an enum - is this the right size?
-const SI_BUFFER: SECItemType = 0; // called siBuffer in NSS
-
-#[repr(C)]
-struct SECItem {
-    typ: SECItemType,
-    data: *const u8, // ugh it's not really const...
-    len: raw::c_uint,
-}
-
-impl SECItem {
-    fn maybe_new(data: &[u8]) -> Result<SECItem, VerifyError> {
-        if data.len() > u32::max_value() as usize {
-            return Err(VerifyError::InputTooLarge);
+impl SignatureAlgorithm {
+    /// Returns the algorithm corresponding to the given value from the IANA "COSE Algorithms"
+    /// registry, if it is supported.
+    pub fn from_cose_id(id: i64) -> Option<SignatureAlgorithm> {
+        match id {
+            -7 => Some(SignatureAlgorithm::ES256),
+            -37 => Some(SignatureAlgorithm::PS256),
+            _ => None,
         }
-        Ok(SECItem { typ: SI_BUFFER, data: data.as_ptr(), len: data.len() as u32 })
     }
-}
 
-#[repr(C)]
-struct CkRsaPkcsPssParams { // Called CK_RSA_PKCS_PSS_PARAMS in NSS
-    hash_alg: CkMechanismType, // Called hashAlg in NSS
-    mgf: CkRsaPkcsMgfType,
-    s_len: raw::c_ulong, // Called sLen in NSS
-}
-
-impl CkRsaPkcsPssParams {
-    fn new() -> CkRsaPkcsPssParams {
-        CkRsaPkcsPssParams {
-            hash_alg: CKM_SHA256,
-            mgf: CKG_MGF1_SHA256,
-            s_len: 32,
+    /// Returns the value identifying this algorithm in the IANA "COSE Algorithms" registry.
+    pub fn cose_id(&self) -> i64 {
+        match *self {
+            SignatureAlgorithm::ES256 => -7,
+            SignatureAlgorithm::PS256 => -37,
         }
     }
-}
-
-// TODO: link to NSS source where these are defined
-type SECOidTag = raw::c_uint; // TODO: actually an enum - is this the right size?
-const SEC_OID_SHA256: SECOidTag = 191;
-
-type CkMechanismType = raw::c_ulong; // called CK_MECHANISM_TYPE in NSS
-const CKM_ECDSA: CkMechanismType = 0x00001041;
-const CKM_RSA_PKCS_PSS: CkMechanismType = 0x0000000D;
-const CKM_SHA256: CkMechanismType = 0x00000250;
 
-type CkRsaPkcsMgfType = raw::c_ulong; // called CK_RSA_PKCS_MGF_TYPE in NSS
-const CKG_MGF1_SHA256: CkRsaPkcsMgfType = 0x00000002;
-
-type SECStatus = raw::c_int; // TODO: enum - right size?
-const SEC_SUCCESS: SECStatus = 0; // Called SECSuccess in NSS
-const SEC_FAILURE: SECStatus = -1; // Called SECFailure in NSS
-
-enum CERTSubjectPublicKeyInfo {}
-
-enum SECKEYPublicKey {}
-
-const SHA256_LENGTH: usize = 32;
-
-// TODO: ugh this will probably have a platform-specific name...
-#[link(name="nss3")]
-extern "C" {
-    fn PK11_HashBuf(hashAlg: SECOidTag,
-                    out: *mut u8,
-                    data_in: *const u8, // called "in" in NSS
-                    len: raw::c_int) -> SECStatus;
-    fn PK11_VerifyWithMechanism(key: *const SECKEYPublicKey,
-                                mechanism: CkMechanismType,
-                                param: *const SECItem,
-                                sig: *const SECItem,
-                                hash: *const SECItem,
-                                wincx: *const raw::c_void) -> SECStatus;
-
-    fn SECKEY_DecodeDERSubjectPublicKeyInfo(spkider: *const SECItem)
-       -> *const CERTSubjectPublicKeyInfo;
-    fn SECKEY_DestroySubjectPublicKeyInfo(spki: *const CERTSubjectPublicKeyInfo);
-
-    fn SECKEY_ExtractPublicKey(spki: *const CERTSubjectPublicKeyInfo) -> *const SECKEYPublicKey;
-    fn SECKEY_DestroyPublicKey(pubk: *const SECKEYPublicKey);
+    /// Returns the hash algorithm payloads are hashed with before signing.
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        match *self {
+            SignatureAlgorithm::ES256 | SignatureAlgorithm::PS256 => HashAlgorithm::SHA256,
+        }
+    }
 }
 
 /// An error type describing errors that may be encountered during verification.
+#[derive(Clone, Debug, PartialEq)]
 pub enum VerifyError {
+    AlgorithmMismatch,
+    AudienceMismatch,
+    CertificateChainInvalid,
+    CertificateExpired,
+    CertificateNotFound,
+    CertificateSignatureInvalid,
+    CertificateThumbprintMismatch,
+    CurveMismatch,
+    DecodingCBORFailed,
+    DecodingCOSEFailed,
+    DecodingCWTFailed,
+    DecodingEATFailed,
+    DecodingCertificateFailed,
+    DecodingJSONFailed,
+    DecodingJWKFailed,
     DecodingSPKIFailed,
+    DecodingSignatureFailed,
+    DecryptionFailed,
+    InadequateCertificateUsage,
     InputTooLarge,
+    InvalidDigestLength,
+    InvalidKey,
+    InvalidSignatureLength,
+    KeyTooSmall,
+    KeyTypeMismatch,
     LibraryFailure,
+    MacVerificationFailed,
+    MissingPayload,
+    NonceMismatch,
+    PayloadHashMismatch,
+    PayloadReadFailed,
     SignatureVerificationFailed,
+    TokenExpired,
+    TokenNotYetValid,
+    UnknownKeyId,
+    UnsupportedAlgorithm,
+    UnsupportedContentType,
+    UnsupportedKey,
+    UntrustedCertificate,
 }
 
 /// Main entrypoint for verification. Given a signature algorithm, the bytes of a subject public key
@@ -105,7 +100,7 @@ pub enum VerifyError {
 /// signed data.
 pub fn verify_signature(signature_algorithm: SignatureAlgorithm, spki: &[u8], payload: &[u8],
                         signature: &[u8]) -> Result<(), VerifyError> {
-    if payload.len() > raw::c_int::max_value() as usize {
+    if payload.len() > raw::c_int::MAX as usize {
         return Err(VerifyError::InputTooLarge);
     }
     let len: raw::c_int = payload.len() as raw::c_int;
@@ -116,50 +111,239 @@ pub fn verify_signature(signature_algorithm: SignatureAlgorithm, spki: &[u8], pa
     if hash_result != SEC_SUCCESS {
         return Err(VerifyError::LibraryFailure);
     }
-    let hash_item = SECItem::maybe_new(hash_buf.as_slice())?;
+    verify_prehashed(signature_algorithm, spki, &hash_buf, signature)
+}
 
-    let spki_item = SECItem::maybe_new(spki)?;
-    // TODO: helper/macro for pattern of "call unsafe function, check null, defer unsafe release"?
-    let spki_handle = unsafe {
-        SECKEY_DecodeDERSubjectPublicKeyInfo(&spki_item)
-    };
-    if spki_handle.is_null() {
-        return Err(VerifyError::DecodingSPKIFailed);
+/// Like verify_signature, but given the digest of the payload rather than the payload, for when it
+/// has already been computed. The digest must be of the length the algorithm's hash produces.
+/// ES256 signatures must be in the fixed-length r || s form; see ecdsa_signature_from_der for
+/// converting DER signatures.
+pub fn verify_prehashed(signature_algorithm: SignatureAlgorithm, spki: &[u8], digest: &[u8],
+                        signature: &[u8]) -> Result<(), VerifyError> {
+    PublicKey::from_spki(spki)?.verify_prehashed(signature_algorithm, digest, signature)
+}
+
+/// The smallest RSA modulus, in bits, that signatures are made or verified with.
+pub const MIN_RSA_MODULUS_BITS: usize = 2048;
+
+/// The type and size of a key, as far as they determine which signature algorithms it suits.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum KeyKind {
+    /// An EC key on the given curve, or None if it isn't one this library knows.
+    EC(Option<Curve>),
+    RSA { modulus_bits: usize },
+    Other,
+}
+
+impl KeyKind {
+    /// Inspects the given NSS public key, which is borrowed.
+    pub fn of_public_key(key: *const SECKEYPublicKey) -> KeyKind {
+        match unsafe { SECKEY_GetPublicKeyType(key) } {
+            EC_KEY => KeyKind::EC(curve_of_ec_params(&ec_public_key_params(key))),
+            RSA_KEY | RSA_PSS_KEY => {
+                let modulus_bits = unsafe { SECKEY_PublicKeyStrengthInBits(key) } as usize;
+                KeyKind::RSA { modulus_bits }
+            }
+            _ => KeyKind::Other,
+        }
     }
-    defer!(unsafe { SECKEY_DestroySubjectPublicKeyInfo(spki_handle); });
-    let key = unsafe {
-        SECKEY_ExtractPublicKey(spki_handle)
-    };
-    if key.is_null() {
-        // TODO: double-check that this can only fail if the library fails
-        return Err(VerifyError::LibraryFailure);
+
+    /// Inspects the given NSS private key, which is borrowed.
+    pub fn of_private_key(key: *const SECKEYPrivateKey) -> KeyKind {
+        match unsafe { SECKEY_GetPrivateKeyType(key) } {
+            EC_KEY => {
+                let params = private_key_attribute(key, CKA_EC_PARAMS).unwrap_or_default();
+                KeyKind::EC(curve_of_ec_params(&params))
+            }
+            RSA_KEY | RSA_PSS_KEY => match private_key_attribute(key, CKA_MODULUS) {
+                Some(modulus) => KeyKind::RSA { modulus_bits: bit_length(&modulus) },
+                None => KeyKind::Other,
+            },
+            _ => KeyKind::Other,
+        }
     }
-    defer!(unsafe { SECKEY_DestroyPublicKey(key); });
-    let signature_item = SECItem::maybe_new(signature)?;
-    let mechanism = match signature_algorithm {
-        SignatureAlgorithm::ES256 => CKM_ECDSA,
-        SignatureAlgorithm::PS256 => CKM_RSA_PKCS_PSS,
-    };
-    let rsa_pss_params = CkRsaPkcsPssParams::new();
-    // This isn't entirely NSS' fault, but it mostly is.
-    let rsa_pss_params_ptr: *const CkRsaPkcsPssParams = &rsa_pss_params;
-    let rsa_pss_params_ptr: *const u8 = rsa_pss_params_ptr as *const u8;
-    let rsa_pss_params_bytes = unsafe {
-        slice::from_raw_parts(rsa_pss_params_ptr, mem::size_of::<CkRsaPkcsPssParams>())
-    };
-    let rsa_pss_params_secitem = SECItem::maybe_new(rsa_pss_params_bytes)?;
-    let params_item: *const SECItem = match signature_algorithm {
-        SignatureAlgorithm::ES256 => ptr::null(),
-        SignatureAlgorithm::PS256 => &rsa_pss_params_secitem,
-    };
-    let null_cx_ptr: *const raw::c_void = ptr::null();
-    let result = unsafe {
-        PK11_VerifyWithMechanism(key, mechanism, params_item, &signature_item, &hash_item,
-                                 null_cx_ptr)
-    };
-    match result {
-        SEC_SUCCESS => Ok(()),
-        SEC_FAILURE => Err(VerifyError::SignatureVerificationFailed),
-        _ => Err(VerifyError::LibraryFailure),
+
+    /// Checks that a key of this kind can be used with the given algorithm: ES256 needs a P-256
+    /// key and PS256 an RSA key with a modulus of at least MIN_RSA_MODULUS_BITS bits.
+    pub fn check_signature_algorithm(&self, signature_algorithm: SignatureAlgorithm)
+                                     -> Result<(), VerifyError> {
+        match (signature_algorithm, *self) {
+            (SignatureAlgorithm::ES256, KeyKind::EC(Some(Curve::P256))) => Ok(()),
+            (SignatureAlgorithm::ES256, KeyKind::EC(_)) => Err(VerifyError::CurveMismatch),
+            (SignatureAlgorithm::PS256, KeyKind::RSA { modulus_bits }) => {
+                if modulus_bits < MIN_RSA_MODULUS_BITS {
+                    return Err(VerifyError::KeyTooSmall);
+                }
+                Ok(())
+            }
+            _ => Err(VerifyError::KeyTypeMismatch),
+        }
+    }
+}
+
+/// Returns the named curve the given DER EC parameters identify, if it is one this library knows.
+fn curve_of_ec_params(params: &[u8]) -> Option<Curve> {
+    match der::read(params, der::OID) {
+        Some((oid, [])) => Curve::from_oid(oid),
+        _ => None,
+    }
+}
+
+/// Returns the length in bits of the given big-endian unsigned integer.
+fn bit_length(value: &[u8]) -> usize {
+    match value.iter().position(|byte| *byte != 0) {
+        Some(index) => 8 * (value.len() - index) - value[index].leading_zeros() as usize,
+        None => 0,
+    }
+}
+
+/// A decoded public key, for verifying many signatures by the same signer without decoding its
+/// subject public key info each time. The NSS key is destroyed when this is dropped.
+pub struct PublicKey {
+    key: *const SECKEYPublicKey,
+    /// The key type and size, which are checked against the algorithm of each signature.
+    kind: KeyKind,
+    /// The alg of the COSE_Key this was parsed from, which restricts the key to that algorithm.
+    alg: Option<i64>,
+}
+
+// NSS keys aren't tied to the thread that created them. The only time verifying writes to a key is
+// when PK11_VerifyWithMechanism imports it into the best slot for the mechanism (with
+// PK11_GetBestSlotWithAttributes), recording the slot and object in the key. from_nss_public_key
+// does that import up front, into that same slot for the one mechanism the key can verify with, so
+// afterwards verifying only reads the key and it may be shared. Keys that suit no supported
+// algorithm are never passed to NSS to verify with.
+unsafe impl Send for PublicKey {}
+unsafe impl Sync for PublicKey {}
+
+impl PublicKey {
+    /// Decodes the given DER subject public key info.
+    pub fn from_spki(spki: &[u8]) -> Result<PublicKey, VerifyError> {
+        let spki_item = SECItem::maybe_new(spki)?;
+        let spki_handle = unsafe { SECKEY_DecodeDERSubjectPublicKeyInfo(&spki_item) };
+        if spki_handle.is_null() {
+            return Err(VerifyError::DecodingSPKIFailed);
+        }
+        defer!(unsafe { SECKEY_DestroySubjectPublicKeyInfo(spki_handle); });
+        let key = unsafe { SECKEY_ExtractPublicKey(spki_handle) };
+        if key.is_null() {
+            // TODO: double-check that this can only fail if the library fails
+            return Err(VerifyError::LibraryFailure);
+        }
+        PublicKey::from_nss_public_key(key)
+    }
+
+    /// Converts the given COSE_Key. Its alg, if present, is the only algorithm the key will verify
+    /// with, and its key_ops, if present, must include verify.
+    pub fn from_cose_key(key: &CoseKey) -> Result<PublicKey, VerifyError> {
+        if !key.key_ops.is_empty() && !key.key_ops.contains(&KeyOperation::Verify) {
+            return Err(VerifyError::InvalidKey);
+        }
+        let mut public_key = PublicKey::from_spki(&key.to_spki())?;
+        public_key.alg = key.alg;
+        Ok(public_key)
     }
+
+    /// Takes ownership of the given NSS public key and imports it into the slot NSS would pick for
+    /// verifying with it, where NSS would otherwise import it the first time it is used.
+    fn from_nss_public_key(key: *const SECKEYPublicKey) -> Result<PublicKey, VerifyError> {
+        let public_key = PublicKey { key, kind: KeyKind::of_public_key(key), alg: None };
+        let mechanism = match public_key.kind {
+            KeyKind::EC(_) => CKM_ECDSA,
+            KeyKind::RSA { .. } => CKM_RSA_PKCS_PSS,
+            KeyKind::Other => return Ok(public_key),
+        };
+        let slot = unsafe {
+            PK11_GetBestSlotWithAttributes(mechanism, CKF_VERIFY, 0, ptr::null())
+        };
+        if slot.is_null() {
+            return Err(VerifyError::LibraryFailure);
+        }
+        defer!(unsafe { PK11_FreeSlot(slot); });
+        // NSS validates the key as it imports it, rejecting EC points that aren't on their curve.
+        if unsafe { PK11_ImportPublicKey(slot, key, PR_FALSE) } == CK_INVALID_HANDLE {
+            return Err(VerifyError::InvalidKey);
+        }
+        Ok(public_key)
+    }
+
+    /// Verifies a signature over the given payload, as verify_signature does.
+    pub fn verify(&self, signature_algorithm: SignatureAlgorithm, payload: &[u8],
+                  signature: &[u8]) -> Result<(), VerifyError> {
+        let payload_digest = digest(signature_algorithm.hash_algorithm(), payload)?;
+        self.verify_prehashed(signature_algorithm, &payload_digest, signature)
+    }
+
+    /// Verifies a signature over the payload with the given digest, as verify_prehashed does.
+    pub fn verify_prehashed(&self, signature_algorithm: SignatureAlgorithm, digest: &[u8],
+                            signature: &[u8]) -> Result<(), VerifyError> {
+        if self.alg.is_some_and(|alg| alg != signature_algorithm.cose_id()) {
+            return Err(VerifyError::AlgorithmMismatch);
+        }
+        // NSS would fail these with a generic error, indistinguishable from a bad signature.
+        self.kind.check_signature_algorithm(signature_algorithm)?;
+        if digest.len() != signature_algorithm.hash_algorithm().output_length() {
+            return Err(VerifyError::InvalidDigestLength);
+        }
+        if signature_algorithm == SignatureAlgorithm::ES256 &&
+           Curve::P256.coordinate_length().map(|length| 2 * length) != Some(signature.len()) {
+            return Err(VerifyError::InvalidSignatureLength);
+        }
+        let hash_item = SECItem::maybe_new(digest)?;
+        let signature_item = SECItem::maybe_new(signature)?;
+        let mechanism = match signature_algorithm {
+            SignatureAlgorithm::ES256 => CKM_ECDSA,
+            SignatureAlgorithm::PS256 => CKM_RSA_PKCS_PSS,
+        };
+        let rsa_pss_params = CkRsaPkcsPssParams::new();
+        // This isn't entirely NSS' fault, but it mostly is.
+        let rsa_pss_params_ptr: *const CkRsaPkcsPssParams = &rsa_pss_params;
+        let rsa_pss_params_ptr: *const u8 = rsa_pss_params_ptr as *const u8;
+        let rsa_pss_params_bytes = unsafe {
+            slice::from_raw_parts(rsa_pss_params_ptr, mem::size_of::<CkRsaPkcsPssParams>())
+        };
+        let rsa_pss_params_secitem = SECItem::maybe_new(rsa_pss_params_bytes)?;
+        let params_item: *const SECItem = match signature_algorithm {
+            SignatureAlgorithm::ES256 => ptr::null(),
+            SignatureAlgorithm::PS256 => &rsa_pss_params_secitem,
+        };
+        let null_cx_ptr: *const raw::c_void = ptr::null();
+        let result = unsafe {
+            PK11_VerifyWithMechanism(self.key, mechanism, params_item, &signature_item,
+                                     &hash_item, null_cx_ptr)
+        };
+        match result {
+            SEC_SUCCESS => Ok(()),
+            SEC_FAILURE => Err(VerifyError::SignatureVerificationFailed),
+            _ => Err(VerifyError::LibraryFailure),
+        }
+    }
+}
+
+impl Drop for PublicKey {
+    fn drop(&mut self) {
+        unsafe { SECKEY_DestroyPublicKey(self.key); }
+    }
+}
+
+#[test]
+fn test_check_signature_algorithm() {
+    let rsa_2048 = KeyKind::RSA { modulus_bits: 2048 };
+    let rsa_2047 = KeyKind::RSA { modulus_bits: 2047 };
+    assert_eq!(KeyKind::EC(Some(Curve::P256)).check_signature_algorithm(SignatureAlgorithm::ES256),
+               Ok(()));
+    assert_eq!(KeyKind::EC(Some(Curve::P384)).check_signature_algorithm(SignatureAlgorithm::ES256),
+               Err(VerifyError::CurveMismatch));
+    assert_eq!(KeyKind::EC(None).check_signature_algorithm(SignatureAlgorithm::ES256),
+               Err(VerifyError::CurveMismatch));
+    assert_eq!(KeyKind::Other.check_signature_algorithm(SignatureAlgorithm::ES256),
+               Err(VerifyError::KeyTypeMismatch));
+    assert_eq!(rsa_2048.check_signature_algorithm(SignatureAlgorithm::ES256),
+               Err(VerifyError::KeyTypeMismatch));
+    assert_eq!(rsa_2048.check_signature_algorithm(SignatureAlgorithm::PS256), Ok(()));
+    assert_eq!(rsa_2047.check_signature_algorithm(SignatureAlgorithm::PS256),
+               Err(VerifyError::KeyTooSmall));
+    assert_eq!(KeyKind::EC(Some(Curve::P256)).check_signature_algorithm(SignatureAlgorithm::PS256),
+               Err(VerifyError::KeyTypeMismatch));
 }
@@ -0,0 +1,203 @@
+use certs::Certificate;
+use cose::{decode_cose_sign, Headers};
+use digest::{digest, HashAlgorithm};
+use policy::{evaluate_cose_sign, VerificationPolicy, VerificationReport};
+use serialize::CborType;
+use verify::VerifyError;
+
+// See RFC 9360, Section 2.
+pub const HEADER_X5BAG: i64 = 32;
+pub const HEADER_X5CHAIN: i64 = 33;
+pub const HEADER_X5T: i64 = 34;
+pub const HEADER_X5U: i64 = 35;
+
+/// The hash of a certificate, as carried in an x5t header.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CertificateThumbprint {
+    pub algorithm: HashAlgorithm,
+    pub hash: Vec<u8>,
+}
+
+impl CertificateThumbprint {
+    /// Returns whether or not this thumbprint identifies the given DER certificate.
+    pub fn matches(&self, certificate: &[u8]) -> Result<bool, VerifyError> {
+        Ok(digest(self.algorithm, certificate)? == self.hash)
+    }
+}
+
+/// The X.509 certificate headers from RFC 9360 found in the headers of a COSE structure.
+#[derive(Clone, Debug, PartialEq)]
+pub struct X509Headers {
+    /// An unordered bag of DER certificates that may help build a chain.
+    pub x5bag: Vec<Vec<u8>>,
+    /// An ordered chain of DER certificates, starting with the end-entity certificate.
+    pub x5chain: Vec<Vec<u8>>,
+    /// The hash of the end-entity certificate.
+    pub x5t: Option<CertificateThumbprint>,
+    /// A URI where the end-entity certificate (or a chain) can be found. It is not dereferenced.
+    pub x5u: Option<String>,
+}
+
+/// COSE_X509 = bstr / [ 2*certs: bstr ]
+///
+/// A single certificate must be a bare bstr, so an array of fewer than two is malformed.
+fn decode_certificates(value: Option<&CborType>) -> Result<Vec<Vec<u8>>, VerifyError> {
+    match value {
+        None => Ok(Vec::new()),
+        Some(CborType::BStr(certificate)) => Ok(vec![certificate.clone()]),
+        Some(CborType::Arr(certificates)) if certificates.len() >= 2 => {
+            let mut decoded = Vec::with_capacity(certificates.len());
+            for certificate in certificates {
+                match *certificate {
+                    CborType::BStr(ref certificate) => decoded.push(certificate.clone()),
+                    _ => return Err(VerifyError::DecodingCOSEFailed),
+                }
+            }
+            Ok(decoded)
+        }
+        Some(_) => Err(VerifyError::DecodingCOSEFailed),
+    }
+}
+
+/// COSE_CertHash = [ hashAlg: (int / tstr), hashValue: bstr ]
+fn decode_thumbprint(value: Option<&CborType>)
+                     -> Result<Option<CertificateThumbprint>, VerifyError> {
+    let elements = match value {
+        None => return Ok(None),
+        Some(CborType::Arr(elements)) if elements.len() == 2 => elements,
+        Some(_) => return Err(VerifyError::DecodingCOSEFailed),
+    };
+    let algorithm = match elements[0] {
+        CborType::NInt(id) => HashAlgorithm::from_cose_id(id),
+        CborType::UInt(_) | CborType::TStr(_) => None,
+        _ => return Err(VerifyError::DecodingCOSEFailed),
+    };
+    let algorithm = algorithm.ok_or(VerifyError::UnsupportedAlgorithm)?;
+    match elements[1] {
+        CborType::BStr(ref hash) if hash.len() == algorithm.output_length() => {
+            Ok(Some(CertificateThumbprint { algorithm, hash: hash.clone() }))
+        }
+        _ => Err(VerifyError::DecodingCOSEFailed),
+    }
+}
+
+impl X509Headers {
+    /// Decodes the X.509 certificate headers, if any, from either header bucket.
+    pub fn decode(headers: &Headers) -> Result<X509Headers, VerifyError> {
+        let x5u = match headers.get(HEADER_X5U) {
+            None => None,
+            Some(CborType::TStr(uri)) => Some(uri.clone()),
+            Some(_) => return Err(VerifyError::DecodingCOSEFailed),
+        };
+        Ok(X509Headers {
+            x5bag: decode_certificates(headers.get(HEADER_X5BAG))?,
+            x5chain: decode_certificates(headers.get(HEADER_X5CHAIN))?,
+            x5t: decode_thumbprint(headers.get(HEADER_X5T))?,
+            x5u,
+        })
+    }
+
+    /// Determines the end-entity certificate. If there is an x5chain, it is the first certificate
+    /// in it, and it must match the x5t, if present. Otherwise, it is whichever certificate from the
+    /// x5bag or the given additional certificates matches the x5t.
+    pub fn end_entity<'a>(&'a self, additional_certificates: &[&'a [u8]])
+                          -> Result<&'a [u8], VerifyError> {
+        if let Some(end_entity) = self.x5chain.first() {
+            if let Some(ref x5t) = self.x5t {
+                if !x5t.matches(end_entity)? {
+                    return Err(VerifyError::CertificateThumbprintMismatch);
+                }
+            }
+            return Ok(end_entity);
+        }
+        let x5t = match self.x5t {
+            Some(ref x5t) => x5t,
+            None => return Err(VerifyError::CertificateNotFound),
+        };
+        let candidates = self.x5bag.iter().map(|certificate| certificate.as_slice())
+            .chain(additional_certificates.iter().cloned());
+        for candidate in candidates {
+            if x5t.matches(candidate)? {
+                return Ok(candidate);
+            }
+        }
+        Err(VerifyError::CertificateNotFound)
+    }
+}
+
+/// Merges the X.509 headers of a COSE_Signature with those of the enclosing COSE_Sign body. Headers
+/// of the signature take precedence, except that the x5bags are combined.
+fn merge_headers(signature: X509Headers, body: &X509Headers) -> X509Headers {
+    let mut x5bag = signature.x5bag;
+    x5bag.extend(body.x5bag.iter().cloned());
+    X509Headers {
+        x5bag,
+        x5chain: if signature.x5chain.is_empty() {
+            body.x5chain.clone()
+        } else {
+            signature.x5chain
+        },
+        x5t: signature.x5t.or_else(|| body.x5t.clone()),
+        x5u: signature.x5u.or_else(|| body.x5u.clone()),
+    }
+}
+
+/// Verifies a COSE_Sign whose signers are identified by the X.509 certificate headers of RFC 9360
+/// rather than by kid. For each signature, the end-entity certificate is found from its x5chain or
+/// by matching its x5t against the certificates of any x5bag and the given additional certificates.
+/// The subject public key info of that certificate is then used to verify the signature. Note that
+/// this does not establish whether or not the certificates are trustworthy.
+pub fn verify_cose_sign_x509(cose_sign: &[u8], detached_payload: Option<&[u8]>,
+                             additional_certificates: &[&[u8]], policy: VerificationPolicy)
+                             -> Result<VerificationReport, VerifyError> {
+    let cose_sign = decode_cose_sign(cose_sign, detached_payload)?;
+    let body_x509_headers = X509Headers::decode(&cose_sign.headers)?;
+    Ok(evaluate_cose_sign(&cose_sign, policy, |cose_signature| {
+        let x509_headers = merge_headers(X509Headers::decode(&cose_signature.headers)?,
+                                         &body_x509_headers);
+        let end_entity = x509_headers.end_entity(additional_certificates)?;
+        Certificate::from_der(end_entity)?.spki()
+    }))
+}
+
+#[test]
+fn test_decode_x509_headers() {
+    use std::collections::BTreeMap;
+    use cose::label;
+
+    let mut protected = BTreeMap::new();
+    protected.insert(label(HEADER_X5CHAIN),
+                     CborType::Arr(vec![CborType::BStr(vec![1, 2]), CborType::BStr(vec![3])]));
+    protected.insert(label(HEADER_X5T),
+                     CborType::Arr(vec![CborType::NInt(-15), CborType::BStr(vec![0xab; 8])]));
+    let mut unprotected = BTreeMap::new();
+    unprotected.insert(label(HEADER_X5BAG), CborType::BStr(vec![4]));
+    unprotected.insert(label(HEADER_X5U), CborType::TStr(String::from("https://example.com/")));
+    let headers = Headers::decode(&CborType::BStr(CborType::Map(protected).serialize()),
+                                  &CborType::Map(unprotected)).unwrap();
+    assert_eq!(X509Headers::decode(&headers).unwrap(),
+               X509Headers {
+                   x5bag: vec![vec![4]],
+                   x5chain: vec![vec![1, 2], vec![3]],
+                   x5t: Some(CertificateThumbprint { algorithm: HashAlgorithm::SHA256_64,
+                                                     hash: vec![0xab; 8] }),
+                   x5u: Some(String::from("https://example.com/")),
+               });
+}
+
+#[test]
+fn test_decode_x509_headers_errors() {
+    assert_eq!(decode_certificates(Some(&CborType::Arr(vec![]))),
+               Err(VerifyError::DecodingCOSEFailed));
+    assert_eq!(decode_certificates(Some(&CborType::Arr(vec![CborType::BStr(vec![1])]))),
+               Err(VerifyError::DecodingCOSEFailed));
+    assert_eq!(decode_certificates(Some(&CborType::Arr(vec![CborType::BStr(vec![1]),
+                                                            CborType::UInt(1)]))),
+               Err(VerifyError::DecodingCOSEFailed));
+    assert_eq!(decode_thumbprint(Some(&CborType::Arr(vec![CborType::NInt(-16),
+                                                          CborType::BStr(vec![0; 8])]))),
+               Err(VerifyError::DecodingCOSEFailed));
+    assert_eq!(decode_thumbprint(Some(&CborType::Arr(vec![CborType::NInt(-14),
+                                                          CborType::BStr(vec![0; 20])]))),
+               Err(VerifyError::UnsupportedAlgorithm));
+}
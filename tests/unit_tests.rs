@@ -1,11 +1,15 @@
 extern crate cose;
 
+use std::collections::BTreeMap;
+use std::io::{self, Read};
 use std::os::raw;
 use std::ptr;
-use std::sync::{Once, ONCE_INIT};
+use std::sync::{Arc, Once};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use cose as verify;
 
-static START: Once = ONCE_INIT;
+static START: Once = Once::new();
 
 // curve: NIST P-256
 // public key: U = xG
@@ -16,7 +20,7 @@ static START: Once = ONCE_INIT;
 //     OID: 1.2.840.10045.2.1 (ecPublicKey)
 //     OID: 1.2.840.10045.3.1.7 (NIST P-256)
 //  BITSTRING (uncompressed EC point)
-static NIST_P256_TEST_SPKI: &'static [u8] =
+static NIST_P256_TEST_SPKI: &[u8] =
     &[0x30, 0x59,
             0x30, 0x13,
                   0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01,
@@ -35,7 +39,7 @@ type SECStatus = raw::c_int;
 const SEC_SUCCESS: SECStatus = 0;
 // TODO: ugh this will probably have a platform-specific name...
 #[link(name="nss3")]
-extern {
+extern "C" {
     fn NSS_NoDB_Init(configdir: *const u8) -> SECStatus;
 }
 
@@ -139,7 +143,7 @@ fn test_tampered_message_es256() {
 //    SEQUENCE
 //      INTEGER (n)
 //      INTEGER (e)
-static FIPS_RSA_3072_SPKI: &'static [u8] =
+static FIPS_RSA_3072_SPKI: &[u8] =
     &[0x30, 0x82, 0x01, 0xA2,
             0x30, 0x0d,
                   0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01,
@@ -236,3 +240,2194 @@ fn test_fips186_3_test_vector_1() {
     assert!(verify::verify_signature(verify::SignatureAlgorithm::PS256, FIPS_RSA_3072_SPKI,
                                      &payload, &signature).is_ok());
 }
+
+// The following keys are NIST P-256 keys with private keys 0x1111..., 0x2222... and 0x3333...,
+// respectively. They sign the COSE_Sign test vectors below as "signer1", "signer2" and "signer3".
+static SIGNER1_SPKI: &[u8] = &[
+    0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a,
+    0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00, 0x04, 0x02, 0x17, 0xe6, 0x17, 0xf0,
+    0xb6, 0x44, 0x39, 0x28, 0x27, 0x8f, 0x96, 0x99, 0x9e, 0x69, 0xa2, 0x3a, 0x4f, 0x2c, 0x15, 0x2b,
+    0xdf, 0x6d, 0x6c, 0xdf, 0x66, 0xe5, 0xb8, 0x02, 0x82, 0xd4, 0xed, 0x19, 0x4a, 0x7d, 0xeb, 0xcb,
+    0x97, 0x71, 0x2d, 0x2d, 0xda, 0x3c, 0xa8, 0x5a, 0xa8, 0x76, 0x5a, 0x56, 0xf4, 0x5f, 0xc7, 0x58,
+    0x59, 0x96, 0x52, 0xf2, 0x89, 0x7c, 0x65, 0x30, 0x6e, 0x57, 0x94];
+
+static SIGNER2_SPKI: &[u8] = &[
+    0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a,
+    0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00, 0x04, 0xd6, 0x5a, 0x93, 0x97, 0x7c,
+    0xaa, 0x3d, 0x1b, 0x08, 0x18, 0x52, 0xff, 0x57, 0xa7, 0x9e, 0x46, 0x5f, 0x16, 0x60, 0x57, 0x73,
+    0x04, 0xba, 0xea, 0xd5, 0x05, 0xdd, 0x3a, 0x48, 0x58, 0x9c, 0xf3, 0x50, 0x18, 0x5e, 0x89, 0x53,
+    0x72, 0xdf, 0x62, 0x21, 0xea, 0x3a, 0x13, 0x75, 0x57, 0xe4, 0x73, 0xfd, 0xdb, 0x67, 0x55, 0xf0,
+    0x5b, 0xd5, 0x07, 0xc3, 0xc5, 0x33, 0xfc, 0xe9, 0xc9, 0x12, 0x85];
+
+static SIGNER3_SPKI: &[u8] = &[
+    0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a,
+    0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00, 0x04, 0x51, 0xa7, 0x58, 0x08, 0x33,
+    0x89, 0x8e, 0xa1, 0xb1, 0x83, 0xcb, 0xd7, 0x35, 0x0a, 0x40, 0x99, 0x07, 0x8c, 0x6e, 0xf1, 0xc1,
+    0xe1, 0x8e, 0x97, 0x0c, 0xd7, 0x68, 0x30, 0x35, 0xf2, 0x5e, 0x7d, 0x01, 0x10, 0x52, 0x27, 0x12,
+    0xb0, 0xb5, 0xa7, 0xcf, 0xf0, 0x81, 0x68, 0x54, 0x86, 0x98, 0x4a, 0x94, 0xe6, 0x83, 0x1e, 0xda,
+    0xc4, 0x6e, 0x73, 0x60, 0xfa, 0x9d, 0x83, 0x4a, 0x7a, 0x81, 0xa1];
+
+// A COSE_Sign with payload "This is the content." and four ES256 signatures: valid ones from
+// signer1 and signer2, one from signer3 with a flipped bit, and an ES384 (unsupported) one from
+// signer4.
+static COSE_SIGN_MIXED: &[u8] = &[
+    0xd8, 0x62, 0x84, 0x40, 0xa0, 0x54, 0x54, 0x68, 0x69, 0x73, 0x20, 0x69, 0x73, 0x20, 0x74, 0x68,
+    0x65, 0x20, 0x63, 0x6f, 0x6e, 0x74, 0x65, 0x6e, 0x74, 0x2e, 0x84, 0x83, 0x43, 0xa1, 0x01, 0x26,
+    0xa1, 0x04, 0x47, 0x73, 0x69, 0x67, 0x6e, 0x65, 0x72, 0x31, 0x58, 0x40, 0xbc, 0x8d, 0x59, 0xf2,
+    0x79, 0x8d, 0x11, 0xc0, 0x56, 0x48, 0xd4, 0xff, 0x18, 0xa0, 0xec, 0xbc, 0xd0, 0xe6, 0x65, 0xe3,
+    0x1e, 0x18, 0xe1, 0x83, 0xed, 0xea, 0x6e, 0xdb, 0x72, 0xe6, 0x51, 0x44, 0xee, 0xaa, 0x27, 0x9a,
+    0x84, 0x01, 0xc3, 0x1e, 0x58, 0xfb, 0x02, 0x2d, 0x8f, 0xa8, 0x12, 0x0b, 0xcf, 0x93, 0xd9, 0xa5,
+    0x69, 0x8f, 0xa1, 0x6a, 0x33, 0xac, 0x1d, 0x06, 0x32, 0xd6, 0x58, 0x58, 0x83, 0x43, 0xa1, 0x01,
+    0x26, 0xa1, 0x04, 0x47, 0x73, 0x69, 0x67, 0x6e, 0x65, 0x72, 0x32, 0x58, 0x40, 0x78, 0x86, 0xc6,
+    0x6d, 0xe5, 0xc2, 0x66, 0xb6, 0x48, 0x58, 0x36, 0x26, 0xb3, 0xe1, 0xdd, 0x4d, 0x28, 0x72, 0x8f,
+    0x39, 0x8c, 0x3a, 0xef, 0xe8, 0x28, 0x1b, 0xc8, 0xbc, 0x68, 0x24, 0x7c, 0x1c, 0x58, 0x52, 0xc8,
+    0xba, 0x55, 0x65, 0x56, 0x65, 0xd0, 0x4b, 0xed, 0x4a, 0x63, 0x84, 0x04, 0x91, 0x9f, 0xb0, 0xff,
+    0x69, 0xfa, 0xaa, 0xb3, 0xb6, 0xab, 0x25, 0x96, 0xc5, 0x69, 0x20, 0x7b, 0x27, 0x83, 0x43, 0xa1,
+    0x01, 0x26, 0xa1, 0x04, 0x47, 0x73, 0x69, 0x67, 0x6e, 0x65, 0x72, 0x33, 0x58, 0x40, 0x2d, 0xc6,
+    0x52, 0x45, 0xed, 0xb5, 0x1a, 0xf3, 0x5a, 0x74, 0xca, 0x70, 0xad, 0x56, 0x08, 0x76, 0xc4, 0xf2,
+    0x11, 0xa6, 0x8a, 0x98, 0xd8, 0xf0, 0x19, 0x5d, 0xe3, 0x7c, 0x72, 0xf7, 0xfd, 0x46, 0x70, 0x56,
+    0x72, 0x50, 0xf5, 0x4d, 0xb3, 0x9a, 0xa9, 0x18, 0x31, 0x9a, 0xba, 0x30, 0x2a, 0xc0, 0x15, 0x8e,
+    0xe3, 0xc0, 0x22, 0x99, 0x14, 0x26, 0xc2, 0x6e, 0x59, 0x94, 0xbe, 0x5b, 0x68, 0xeb, 0x83, 0x44,
+    0xa1, 0x01, 0x38, 0x22, 0xa1, 0x04, 0x47, 0x73, 0x69, 0x67, 0x6e, 0x65, 0x72, 0x34, 0x58, 0x60,
+    0xe6, 0x80, 0xd0, 0x55, 0x0b, 0x0e, 0x2e, 0x0e, 0xcc, 0x18, 0xee, 0xf0, 0x38, 0xa9, 0x98, 0xac,
+    0x6f, 0xbc, 0x4e, 0xc2, 0xb8, 0xcb, 0x8c, 0xc4, 0xd2, 0x3f, 0xd9, 0x56, 0xc8, 0x38, 0x17, 0x88,
+    0xd0, 0x2b, 0xa9, 0x62, 0x9b, 0xe8, 0x30, 0x25, 0x27, 0xb7, 0x73, 0xbf, 0x3a, 0x81, 0x4d, 0x98,
+    0x50, 0x54, 0xc1, 0x18, 0xaf, 0x5a, 0x5f, 0xb8, 0xa0, 0x9b, 0x5b, 0x01, 0x14, 0x8e, 0xf6, 0x49,
+    0x57, 0xd9, 0x22, 0x43, 0x3f, 0x84, 0xc7, 0x5e, 0x35, 0x73, 0xa6, 0x44, 0x41, 0xa8, 0xd8, 0xe8,
+    0x0f, 0x7d, 0xfe, 0xe8, 0xc1, 0x63, 0x75, 0xe2, 0xf6, 0x02, 0x4f, 0x49, 0x2a, 0x81, 0xc3, 0x30];
+
+// A COSE_Sign with a detached payload "This is the content." and valid ES256 signatures from
+// signer1 and signer2.
+static COSE_SIGN_DETACHED: &[u8] = &[
+    0xd8, 0x62, 0x84, 0x40, 0xa0, 0xf6, 0x82, 0x83, 0x43, 0xa1, 0x01, 0x26, 0xa1, 0x04, 0x47, 0x73,
+    0x69, 0x67, 0x6e, 0x65, 0x72, 0x31, 0x58, 0x40, 0x0d, 0x8d, 0x9e, 0xc2, 0xa8, 0x69, 0xca, 0x84,
+    0xaa, 0xd2, 0x68, 0xdb, 0x95, 0xb5, 0x38, 0x3e, 0x32, 0x79, 0x6a, 0x66, 0xbb, 0xbb, 0x18, 0xac,
+    0xa0, 0xc2, 0x29, 0x1d, 0x13, 0xc4, 0x95, 0x4a, 0xac, 0xc6, 0xf0, 0x2d, 0x9d, 0x99, 0x29, 0x34,
+    0x5b, 0xb6, 0x7a, 0x50, 0x88, 0xdb, 0xe8, 0x82, 0x56, 0x87, 0x85, 0xf9, 0x9c, 0x8a, 0x10, 0xbd,
+    0x94, 0x24, 0xfc, 0x2b, 0x8c, 0x0a, 0x63, 0xbf, 0x83, 0x43, 0xa1, 0x01, 0x26, 0xa1, 0x04, 0x47,
+    0x73, 0x69, 0x67, 0x6e, 0x65, 0x72, 0x32, 0x58, 0x40, 0xf2, 0xca, 0xf9, 0x2b, 0x49, 0x4d, 0x88,
+    0x1d, 0x0d, 0xb8, 0x55, 0x3a, 0x1f, 0xab, 0x31, 0x06, 0xf5, 0x07, 0x3b, 0x16, 0x71, 0xa2, 0x22,
+    0x68, 0x62, 0x95, 0x9d, 0x0b, 0xcb, 0x83, 0x6e, 0x11, 0x22, 0xe4, 0xf1, 0xa9, 0x0a, 0x55, 0x60,
+    0x82, 0xaf, 0x18, 0x75, 0x29, 0x98, 0xd1, 0x27, 0x78, 0x99, 0x4d, 0xe6, 0xd9, 0x71, 0xab, 0xb9,
+    0x67, 0x0d, 0xc1, 0x01, 0xec, 0x75, 0x7b, 0x9d, 0xa5];
+
+fn all_trusted_keys() -> Vec<verify::TrustedKey<'static>> {
+    vec![verify::TrustedKey { kid: b"signer1", spki: SIGNER1_SPKI },
+         verify::TrustedKey { kid: b"signer2", spki: SIGNER2_SPKI },
+         verify::TrustedKey { kid: b"signer3", spki: SIGNER3_SPKI }]
+}
+
+#[test]
+fn test_cose_sign_report() {
+    setup();
+    let report = verify::verify_cose_sign(COSE_SIGN_MIXED, None, &all_trusted_keys(),
+                                          verify::VerificationPolicy::All).unwrap();
+    assert!(!report.policy_satisfied);
+    assert_eq!(report.signers,
+               vec![verify::SignerReport { kid: Some(b"signer1".to_vec()),
+                                           algorithm: Some(verify::SignatureAlgorithm::ES256),
+                                           result: Ok(()) },
+                    verify::SignerReport { kid: Some(b"signer2".to_vec()),
+                                           algorithm: Some(verify::SignatureAlgorithm::ES256),
+                                           result: Ok(()) },
+                    verify::SignerReport {
+                        kid: Some(b"signer3".to_vec()),
+                        algorithm: Some(verify::SignatureAlgorithm::ES256),
+                        result: Err(verify::VerifyError::SignatureVerificationFailed) },
+                    verify::SignerReport { kid: Some(b"signer4".to_vec()),
+                                           algorithm: None,
+                                           result: Err(verify::VerifyError::UnsupportedAlgorithm) }]);
+}
+
+#[test]
+fn test_cose_sign_policies() {
+    setup();
+    let trusted_keys = all_trusted_keys();
+    let satisfied = |policy| {
+        verify::verify_cose_sign(COSE_SIGN_MIXED, None, &trusted_keys, policy).unwrap()
+            .policy_satisfied
+    };
+    assert!(!satisfied(verify::VerificationPolicy::All));
+    assert!(satisfied(verify::VerificationPolicy::AtLeastOne));
+    assert!(satisfied(verify::VerificationPolicy::Threshold(2)));
+    assert!(!satisfied(verify::VerificationPolicy::Threshold(3)));
+    assert!(!satisfied(verify::VerificationPolicy::Threshold(0)));
+}
+
+#[test]
+fn test_cose_sign_threshold_untrusted_signer() {
+    setup();
+    let trusted_keys = vec![verify::TrustedKey { kid: b"signer1", spki: SIGNER1_SPKI }];
+    let report = verify::verify_cose_sign(COSE_SIGN_MIXED, None, &trusted_keys,
+                                          verify::VerificationPolicy::Threshold(2)).unwrap();
+    assert!(!report.policy_satisfied);
+    assert_eq!(report.signers[0].result, Ok(()));
+    assert_eq!(report.signers[1].result, Err(verify::VerifyError::UnknownKeyId));
+}
+
+// A COSE_Sign with payload "This is the content." and two valid ES256 signatures by the signer1
+// key, one with kid "signer1" and one with kid "signer1-backup".
+static COSE_SIGN_SAME_KEY: &[u8] = &[
+    0xd8, 0x62, 0x84, 0x40, 0xa0, 0x54, 0x54, 0x68, 0x69, 0x73, 0x20, 0x69, 0x73, 0x20, 0x74, 0x68,
+    0x65, 0x20, 0x63, 0x6f, 0x6e, 0x74, 0x65, 0x6e, 0x74, 0x2e, 0x82, 0x83, 0x43, 0xa1, 0x01, 0x26,
+    0xa1, 0x04, 0x47, 0x73, 0x69, 0x67, 0x6e, 0x65, 0x72, 0x31, 0x58, 0x40, 0x01, 0x92, 0xd6, 0x66,
+    0x08, 0x53, 0x8e, 0x1a, 0x3f, 0x0e, 0xb9, 0x5a, 0x97, 0xa4, 0x3e, 0x2a, 0x17, 0x0b, 0x20, 0xca,
+    0xd9, 0xd5, 0xe6, 0x81, 0xd3, 0xfa, 0x25, 0x19, 0x13, 0x35, 0x87, 0xde, 0x95, 0xc1, 0x40, 0x37,
+    0x7f, 0xae, 0x22, 0x60, 0x88, 0x82, 0xf4, 0x43, 0x1c, 0x35, 0x5e, 0x41, 0xa9, 0x05, 0x55, 0x90,
+    0x41, 0x9a, 0xea, 0x14, 0xea, 0xb4, 0x53, 0xd2, 0x0e, 0xd1, 0x4f, 0x49, 0x83, 0x43, 0xa1, 0x01,
+    0x26, 0xa1, 0x04, 0x4e, 0x73, 0x69, 0x67, 0x6e, 0x65, 0x72, 0x31, 0x2d, 0x62, 0x61, 0x63, 0x6b,
+    0x75, 0x70, 0x58, 0x40, 0xb7, 0xa6, 0xde, 0xbe, 0xca, 0xd4, 0xe5, 0xca, 0x62, 0x47, 0xc9, 0xb6,
+    0x93, 0x7b, 0x3d, 0x20, 0x34, 0x69, 0xee, 0x11, 0x54, 0xab, 0x1c, 0x1a, 0xdf, 0x21, 0xcf, 0x5a,
+    0x14, 0xe1, 0x53, 0xee, 0xe9, 0x19, 0x31, 0x34, 0x51, 0xd4, 0x7d, 0x98, 0x76, 0xb6, 0xa7, 0x29,
+    0xd3, 0xd2, 0x0c, 0x6c, 0x91, 0xeb, 0x56, 0x4a, 0x68, 0x96, 0x0d, 0x33, 0xb7, 0xaa, 0x15, 0x81,
+    0x92, 0xcc, 0x1f, 0x69];
+
+#[test]
+fn test_cose_sign_threshold_same_key() {
+    setup();
+    // Two kids for the same key are one signer as far as the threshold is concerned.
+    let trusted_keys = vec![verify::TrustedKey { kid: b"signer1", spki: SIGNER1_SPKI },
+                            verify::TrustedKey { kid: b"signer1-backup", spki: SIGNER1_SPKI }];
+    let report = verify::verify_cose_sign(COSE_SIGN_SAME_KEY, None, &trusted_keys,
+                                          verify::VerificationPolicy::Threshold(2)).unwrap();
+    assert_eq!(report.signers[0].result, Ok(()));
+    assert_eq!(report.signers[1].result, Ok(()));
+    assert!(!report.policy_satisfied);
+    let report = verify::verify_cose_sign(COSE_SIGN_SAME_KEY, None, &trusted_keys,
+                                          verify::VerificationPolicy::Threshold(1)).unwrap();
+    assert!(report.policy_satisfied);
+}
+
+#[test]
+fn test_cose_sign_detached_payload() {
+    setup();
+    let payload = b"This is the content.";
+    let report = verify::verify_cose_sign(COSE_SIGN_DETACHED, Some(payload), &all_trusted_keys(),
+                                          verify::VerificationPolicy::All).unwrap();
+    assert!(report.policy_satisfied);
+    let report = verify::verify_cose_sign(COSE_SIGN_DETACHED, Some(b"This is not the content."),
+                                          &all_trusted_keys(),
+                                          verify::VerificationPolicy::AtLeastOne).unwrap();
+    assert!(!report.policy_satisfied);
+    assert_eq!(verify::verify_cose_sign(COSE_SIGN_DETACHED, None, &all_trusted_keys(),
+                                        verify::VerificationPolicy::All),
+               Err(verify::VerifyError::MissingPayload));
+    assert_eq!(verify::verify_cose_sign(COSE_SIGN_MIXED, Some(payload), &all_trusted_keys(),
+                                        verify::VerificationPolicy::All),
+               Err(verify::VerifyError::DecodingCOSEFailed));
+}
+
+#[test]
+fn test_cose_sign_malformed() {
+    setup();
+    assert_eq!(verify::verify_cose_sign(&COSE_SIGN_MIXED[..100], None, &all_trusted_keys(),
+                                        verify::VerificationPolicy::All),
+               Err(verify::VerifyError::DecodingCBORFailed));
+    // A tagged COSE_Sign1 rather than a COSE_Sign.
+    assert_eq!(verify::verify_cose_sign(&[0xd2, 0x84, 0x40, 0xa0, 0xf6, 0x40], None,
+                                        &all_trusted_keys(), verify::VerificationPolicy::All),
+               Err(verify::VerifyError::DecodingCOSEFailed));
+}
+
+// A self-signed NIST P-256 root ("CN=Test Root", private key 0x4444...) that issues the
+// intermediate used by the add-on signature below. Like Mozilla's add-on signing hierarchy, every
+// certificate carries the id-kp-codeSigning extended key usage.
+static ADDON_ROOT: &[u8] = &[
+    0x30, 0x82, 0x01, 0x51, 0x30, 0x81, 0xf7, 0xa0, 0x03, 0x02, 0x01, 0x02, 0x02, 0x01, 0x01, 0x30,
+    0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x30, 0x14, 0x31, 0x12, 0x30,
+    0x10, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x09, 0x54, 0x65, 0x73, 0x74, 0x20, 0x52, 0x6f, 0x6f,
+    0x74, 0x30, 0x20, 0x17, 0x0d, 0x32, 0x30, 0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30, 0x30,
+    0x30, 0x5a, 0x18, 0x0f, 0x32, 0x30, 0x35, 0x30, 0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30,
+    0x30, 0x30, 0x5a, 0x30, 0x14, 0x31, 0x12, 0x30, 0x10, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x09,
+    0x54, 0x65, 0x73, 0x74, 0x20, 0x52, 0x6f, 0x6f, 0x74, 0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a,
+    0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07,
+    0x03, 0x42, 0x00, 0x04, 0x5b, 0x36, 0x89, 0x0d, 0xac, 0xbd, 0x7c, 0x9a, 0x96, 0xbb, 0x74, 0xa1,
+    0xee, 0x28, 0xb3, 0xd2, 0xd7, 0x5b, 0x72, 0xe0, 0x9a, 0x20, 0xef, 0x25, 0xcf, 0x8e, 0x6f, 0xd8,
+    0xa9, 0xf0, 0x35, 0x0d, 0x0e, 0x14, 0xbe, 0xd8, 0xd4, 0x68, 0x2a, 0x34, 0xd8, 0x35, 0x38, 0xbd,
+    0xff, 0x5b, 0x96, 0xe8, 0x9a, 0x66, 0x66, 0xec, 0x0d, 0xb5, 0x74, 0x5d, 0x02, 0xfa, 0x12, 0x10,
+    0x07, 0x2d, 0xf7, 0x5a, 0xa3, 0x38, 0x30, 0x36, 0x30, 0x0f, 0x06, 0x03, 0x55, 0x1d, 0x13, 0x01,
+    0x01, 0xff, 0x04, 0x05, 0x30, 0x03, 0x01, 0x01, 0xff, 0x30, 0x0e, 0x06, 0x03, 0x55, 0x1d, 0x0f,
+    0x01, 0x01, 0xff, 0x04, 0x04, 0x03, 0x02, 0x01, 0x06, 0x30, 0x13, 0x06, 0x03, 0x55, 0x1d, 0x25,
+    0x04, 0x0c, 0x30, 0x0a, 0x06, 0x08, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x03, 0x30, 0x0a,
+    0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x03, 0x49, 0x00, 0x30, 0x46, 0x02,
+    0x21, 0x00, 0xd9, 0xad, 0x7b, 0xf0, 0x10, 0x69, 0x3f, 0xee, 0x8f, 0xeb, 0xbc, 0xde, 0x15, 0x99,
+    0x07, 0xcb, 0x81, 0xb2, 0x6c, 0x3b, 0x66, 0xed, 0xac, 0xfd, 0xf6, 0xc7, 0x27, 0x06, 0xea, 0xcc,
+    0x8f, 0x6d, 0x02, 0x21, 0x00, 0xe0, 0xb0, 0xc0, 0x1e, 0x69, 0xb3, 0x8b, 0x6f, 0x89, 0xf7, 0x0c,
+    0x31, 0x09, 0xb9, 0x9f, 0x68, 0x6f, 0xf2, 0x6a, 0x92, 0x95, 0x79, 0x81, 0xe8, 0xb7, 0x39, 0x2a,
+    0xf8, 0x17, 0xe9, 0xbb, 0xce];
+
+// An unrelated self-signed root ("CN=Other Root").
+static ADDON_OTHER_ROOT: &[u8] = &[
+    0x30, 0x82, 0x01, 0x53, 0x30, 0x81, 0xf9, 0xa0, 0x03, 0x02, 0x01, 0x02, 0x02, 0x01, 0x06, 0x30,
+    0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x30, 0x15, 0x31, 0x13, 0x30,
+    0x11, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x0a, 0x4f, 0x74, 0x68, 0x65, 0x72, 0x20, 0x52, 0x6f,
+    0x6f, 0x74, 0x30, 0x20, 0x17, 0x0d, 0x32, 0x30, 0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30,
+    0x30, 0x30, 0x5a, 0x18, 0x0f, 0x32, 0x30, 0x35, 0x30, 0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30,
+    0x30, 0x30, 0x30, 0x5a, 0x30, 0x15, 0x31, 0x13, 0x30, 0x11, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c,
+    0x0a, 0x4f, 0x74, 0x68, 0x65, 0x72, 0x20, 0x52, 0x6f, 0x6f, 0x74, 0x30, 0x59, 0x30, 0x13, 0x06,
+    0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03,
+    0x01, 0x07, 0x03, 0x42, 0x00, 0x04, 0x0b, 0xbb, 0xc5, 0xe8, 0xbc, 0x84, 0xbd, 0x33, 0xd1, 0xd3,
+    0xce, 0x03, 0xff, 0xac, 0x9a, 0x74, 0x7f, 0x4c, 0x19, 0x93, 0xfd, 0xdb, 0x2e, 0xc9, 0x3a, 0x41,
+    0x16, 0xa8, 0x6f, 0x02, 0x2a, 0x77, 0xc3, 0xc1, 0x71, 0x91, 0x55, 0x9a, 0x4c, 0x2a, 0x1a, 0xa5,
+    0x7e, 0x79, 0xb8, 0xd1, 0x97, 0x7d, 0xa2, 0xc9, 0x59, 0x17, 0x2f, 0x47, 0x8e, 0x34, 0x1e, 0x27,
+    0x02, 0x8d, 0x69, 0xff, 0xfb, 0x7b, 0xa3, 0x38, 0x30, 0x36, 0x30, 0x0f, 0x06, 0x03, 0x55, 0x1d,
+    0x13, 0x01, 0x01, 0xff, 0x04, 0x05, 0x30, 0x03, 0x01, 0x01, 0xff, 0x30, 0x0e, 0x06, 0x03, 0x55,
+    0x1d, 0x0f, 0x01, 0x01, 0xff, 0x04, 0x04, 0x03, 0x02, 0x01, 0x06, 0x30, 0x13, 0x06, 0x03, 0x55,
+    0x1d, 0x25, 0x04, 0x0c, 0x30, 0x0a, 0x06, 0x08, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x03,
+    0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x03, 0x49, 0x00, 0x30,
+    0x46, 0x02, 0x21, 0x00, 0xbe, 0x24, 0x85, 0xfd, 0xc6, 0x5d, 0xb8, 0xd7, 0x4b, 0x6a, 0xbb, 0x5c,
+    0xa4, 0x65, 0x1e, 0xcd, 0x6d, 0xa4, 0x0e, 0xaa, 0xb5, 0xd4, 0x85, 0x7a, 0x24, 0xe1, 0xfa, 0x31,
+    0xf5, 0x5d, 0x9c, 0x92, 0x02, 0x21, 0x00, 0xec, 0x56, 0x9d, 0xf0, 0xc6, 0x9f, 0x64, 0xba, 0x22,
+    0x98, 0x17, 0x54, 0x19, 0xc1, 0x6c, 0x2b, 0x8d, 0xfe, 0x97, 0x4b, 0x79, 0xf8, 0x4d, 0x79, 0x88,
+    0x19, 0x03, 0xd4, 0x20, 0x7a, 0x56, 0xc5];
+
+// A COSE_Sign in the add-on signing profile over the detached payload "This is the content.". The
+// kid header of the body holds the intermediate "CN=Test Intermediate". It has two ES256
+// signatures by signer1's key: one whose kid is a currently-valid end-entity certificate
+// ("CN=Test Signer") and one whose kid is an end-entity certificate that expired in 2016
+// ("CN=Expired Signer").
+static ADDON_COSE_SIGN: &[u8] = &[
+    0xd8, 0x62, 0x84, 0x40, 0xa1, 0x04, 0x81, 0x59, 0x01, 0x5c, 0x30, 0x82, 0x01, 0x58, 0x30, 0x81,
+    0xff, 0xa0, 0x03, 0x02, 0x01, 0x02, 0x02, 0x01, 0x02, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48,
+    0xce, 0x3d, 0x04, 0x03, 0x02, 0x30, 0x14, 0x31, 0x12, 0x30, 0x10, 0x06, 0x03, 0x55, 0x04, 0x03,
+    0x0c, 0x09, 0x54, 0x65, 0x73, 0x74, 0x20, 0x52, 0x6f, 0x6f, 0x74, 0x30, 0x20, 0x17, 0x0d, 0x32,
+    0x30, 0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x5a, 0x18, 0x0f, 0x32, 0x30,
+    0x35, 0x30, 0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x5a, 0x30, 0x1c, 0x31,
+    0x1a, 0x30, 0x18, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x11, 0x54, 0x65, 0x73, 0x74, 0x20, 0x49,
+    0x6e, 0x74, 0x65, 0x72, 0x6d, 0x65, 0x64, 0x69, 0x61, 0x74, 0x65, 0x30, 0x59, 0x30, 0x13, 0x06,
+    0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03,
+    0x01, 0x07, 0x03, 0x42, 0x00, 0x04, 0x57, 0xe9, 0x77, 0xf6, 0xdb, 0x7e, 0x33, 0xc3, 0xfe, 0x7a,
+    0xcf, 0x28, 0x42, 0xed, 0x98, 0x70, 0x09, 0xca, 0xf5, 0x6d, 0x45, 0x86, 0x82, 0xfc, 0xa4, 0x47,
+    0xb7, 0xd3, 0xd7, 0x62, 0xab, 0x34, 0xc5, 0xab, 0x37, 0x70, 0xba, 0x57, 0x3b, 0xdf, 0xf5, 0x41,
+    0x40, 0x65, 0x64, 0x0f, 0xfb, 0x5b, 0x34, 0x6d, 0xfa, 0x84, 0xde, 0xc4, 0xdb, 0x4d, 0x68, 0xe5,
+    0xf5, 0x9c, 0xc4, 0x71, 0xc2, 0xec, 0xa3, 0x38, 0x30, 0x36, 0x30, 0x0f, 0x06, 0x03, 0x55, 0x1d,
+    0x13, 0x01, 0x01, 0xff, 0x04, 0x05, 0x30, 0x03, 0x01, 0x01, 0xff, 0x30, 0x0e, 0x06, 0x03, 0x55,
+    0x1d, 0x0f, 0x01, 0x01, 0xff, 0x04, 0x04, 0x03, 0x02, 0x01, 0x06, 0x30, 0x13, 0x06, 0x03, 0x55,
+    0x1d, 0x25, 0x04, 0x0c, 0x30, 0x0a, 0x06, 0x08, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x03,
+    0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x03, 0x48, 0x00, 0x30,
+    0x45, 0x02, 0x20, 0x74, 0x72, 0x20, 0x56, 0xa9, 0x59, 0x71, 0xc8, 0x11, 0x31, 0x90, 0xe2, 0x7a,
+    0x3e, 0x7e, 0x82, 0x5e, 0x5a, 0x2c, 0x1c, 0x48, 0x84, 0xab, 0xf5, 0x5d, 0x4d, 0x9b, 0xf5, 0x21,
+    0xba, 0xa6, 0x8d, 0x02, 0x21, 0x00, 0x93, 0x16, 0xb7, 0x34, 0xec, 0xfd, 0xe4, 0x8d, 0x24, 0xce,
+    0x1a, 0xaf, 0x09, 0x36, 0x1b, 0xfb, 0x28, 0xd1, 0x9e, 0x15, 0x44, 0x0c, 0xb9, 0x57, 0xcf, 0x2e,
+    0xf4, 0xc4, 0x97, 0xed, 0xca, 0xbc, 0xf6, 0x82, 0x83, 0x43, 0xa1, 0x01, 0x26, 0xa1, 0x04, 0x59,
+    0x01, 0x5b, 0x30, 0x82, 0x01, 0x57, 0x30, 0x81, 0xfe, 0xa0, 0x03, 0x02, 0x01, 0x02, 0x02, 0x01,
+    0x03, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x30, 0x1c, 0x31,
+    0x1a, 0x30, 0x18, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x11, 0x54, 0x65, 0x73, 0x74, 0x20, 0x49,
+    0x6e, 0x74, 0x65, 0x72, 0x6d, 0x65, 0x64, 0x69, 0x61, 0x74, 0x65, 0x30, 0x20, 0x17, 0x0d, 0x32,
+    0x30, 0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x5a, 0x18, 0x0f, 0x32, 0x30,
+    0x35, 0x30, 0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x5a, 0x30, 0x16, 0x31,
+    0x14, 0x30, 0x12, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x0b, 0x54, 0x65, 0x73, 0x74, 0x20, 0x53,
+    0x69, 0x67, 0x6e, 0x65, 0x72, 0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d,
+    0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00, 0x04,
+    0x02, 0x17, 0xe6, 0x17, 0xf0, 0xb6, 0x44, 0x39, 0x28, 0x27, 0x8f, 0x96, 0x99, 0x9e, 0x69, 0xa2,
+    0x3a, 0x4f, 0x2c, 0x15, 0x2b, 0xdf, 0x6d, 0x6c, 0xdf, 0x66, 0xe5, 0xb8, 0x02, 0x82, 0xd4, 0xed,
+    0x19, 0x4a, 0x7d, 0xeb, 0xcb, 0x97, 0x71, 0x2d, 0x2d, 0xda, 0x3c, 0xa8, 0x5a, 0xa8, 0x76, 0x5a,
+    0x56, 0xf4, 0x5f, 0xc7, 0x58, 0x59, 0x96, 0x52, 0xf2, 0x89, 0x7c, 0x65, 0x30, 0x6e, 0x57, 0x94,
+    0xa3, 0x35, 0x30, 0x33, 0x30, 0x0c, 0x06, 0x03, 0x55, 0x1d, 0x13, 0x01, 0x01, 0xff, 0x04, 0x02,
+    0x30, 0x00, 0x30, 0x0e, 0x06, 0x03, 0x55, 0x1d, 0x0f, 0x01, 0x01, 0xff, 0x04, 0x04, 0x03, 0x02,
+    0x07, 0x80, 0x30, 0x13, 0x06, 0x03, 0x55, 0x1d, 0x25, 0x04, 0x0c, 0x30, 0x0a, 0x06, 0x08, 0x2b,
+    0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x03, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d,
+    0x04, 0x03, 0x02, 0x03, 0x48, 0x00, 0x30, 0x45, 0x02, 0x21, 0x00, 0xb1, 0xd5, 0xd6, 0x60, 0xd2,
+    0x3c, 0x8f, 0x1d, 0x1d, 0x2b, 0x19, 0x64, 0xf3, 0x62, 0x52, 0xa6, 0x64, 0x7f, 0x75, 0x7c, 0xc6,
+    0xd5, 0xe2, 0x60, 0x93, 0xca, 0xd5, 0xc4, 0x52, 0x00, 0x0a, 0x0c, 0x02, 0x20, 0x26, 0x89, 0x8f,
+    0x0c, 0xdb, 0x74, 0x2d, 0x99, 0xe4, 0xb3, 0x84, 0x81, 0xf9, 0xe7, 0x80, 0x30, 0x7b, 0xb3, 0xd1,
+    0x70, 0x60, 0xba, 0xe0, 0xd3, 0x26, 0xc3, 0x07, 0xc0, 0xe8, 0x99, 0x7b, 0x7b, 0x58, 0x40, 0xcb,
+    0x1a, 0x70, 0x04, 0x8c, 0xfb, 0x5f, 0xc6, 0x2e, 0x76, 0x5a, 0x1c, 0xf5, 0xa3, 0x7f, 0x08, 0x8d,
+    0x4c, 0x6a, 0x8c, 0xad, 0x67, 0xbe, 0xc9, 0xd1, 0x04, 0xff, 0x6e, 0x54, 0x7a, 0xc2, 0x5b, 0x83,
+    0x45, 0xf3, 0x3c, 0xa6, 0xa2, 0xe1, 0xbc, 0x07, 0x28, 0xdd, 0x72, 0xb1, 0x30, 0x2e, 0xc0, 0x32,
+    0x78, 0x72, 0x78, 0x14, 0xd6, 0x60, 0x59, 0x51, 0x3b, 0xb3, 0x99, 0x61, 0x15, 0x2f, 0x77, 0x83,
+    0x43, 0xa1, 0x01, 0x26, 0xa1, 0x04, 0x59, 0x01, 0x5c, 0x30, 0x82, 0x01, 0x58, 0x30, 0x81, 0xff,
+    0xa0, 0x03, 0x02, 0x01, 0x02, 0x02, 0x01, 0x04, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce,
+    0x3d, 0x04, 0x03, 0x02, 0x30, 0x1c, 0x31, 0x1a, 0x30, 0x18, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c,
+    0x11, 0x54, 0x65, 0x73, 0x74, 0x20, 0x49, 0x6e, 0x74, 0x65, 0x72, 0x6d, 0x65, 0x64, 0x69, 0x61,
+    0x74, 0x65, 0x30, 0x1e, 0x17, 0x0d, 0x31, 0x35, 0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30,
+    0x30, 0x30, 0x5a, 0x17, 0x0d, 0x31, 0x36, 0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30, 0x30,
+    0x30, 0x5a, 0x30, 0x19, 0x31, 0x17, 0x30, 0x15, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x0e, 0x45,
+    0x78, 0x70, 0x69, 0x72, 0x65, 0x64, 0x20, 0x53, 0x69, 0x67, 0x6e, 0x65, 0x72, 0x30, 0x59, 0x30,
+    0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce,
+    0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00, 0x04, 0x02, 0x17, 0xe6, 0x17, 0xf0, 0xb6, 0x44, 0x39,
+    0x28, 0x27, 0x8f, 0x96, 0x99, 0x9e, 0x69, 0xa2, 0x3a, 0x4f, 0x2c, 0x15, 0x2b, 0xdf, 0x6d, 0x6c,
+    0xdf, 0x66, 0xe5, 0xb8, 0x02, 0x82, 0xd4, 0xed, 0x19, 0x4a, 0x7d, 0xeb, 0xcb, 0x97, 0x71, 0x2d,
+    0x2d, 0xda, 0x3c, 0xa8, 0x5a, 0xa8, 0x76, 0x5a, 0x56, 0xf4, 0x5f, 0xc7, 0x58, 0x59, 0x96, 0x52,
+    0xf2, 0x89, 0x7c, 0x65, 0x30, 0x6e, 0x57, 0x94, 0xa3, 0x35, 0x30, 0x33, 0x30, 0x0c, 0x06, 0x03,
+    0x55, 0x1d, 0x13, 0x01, 0x01, 0xff, 0x04, 0x02, 0x30, 0x00, 0x30, 0x0e, 0x06, 0x03, 0x55, 0x1d,
+    0x0f, 0x01, 0x01, 0xff, 0x04, 0x04, 0x03, 0x02, 0x07, 0x80, 0x30, 0x13, 0x06, 0x03, 0x55, 0x1d,
+    0x25, 0x04, 0x0c, 0x30, 0x0a, 0x06, 0x08, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x03, 0x30,
+    0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x03, 0x48, 0x00, 0x30, 0x45,
+    0x02, 0x20, 0x0e, 0x3e, 0xb3, 0xe1, 0x12, 0x72, 0xa3, 0xb3, 0xd1, 0x24, 0x48, 0x2f, 0x92, 0x6e,
+    0xec, 0xd7, 0xca, 0x78, 0x8c, 0x65, 0xd2, 0x0c, 0x81, 0xd3, 0xe6, 0x0a, 0x7c, 0xa2, 0x39, 0x3c,
+    0x6c, 0xd6, 0x02, 0x21, 0x00, 0xe8, 0xe4, 0xda, 0x92, 0x9e, 0x62, 0x33, 0xc3, 0x7f, 0xfd, 0x3c,
+    0x0d, 0x39, 0xfa, 0xdd, 0x3e, 0x79, 0x20, 0x56, 0x26, 0xd2, 0x31, 0x70, 0x59, 0x75, 0x09, 0x57,
+    0x3a, 0xc4, 0xe4, 0x63, 0x48, 0x58, 0x40, 0x93, 0x43, 0x57, 0x95, 0x55, 0xc5, 0x15, 0x14, 0x02,
+    0xee, 0xb7, 0xb3, 0x02, 0x52, 0x6d, 0x87, 0x64, 0x96, 0x71, 0x78, 0xdd, 0x01, 0xf7, 0x45, 0xb7,
+    0xb2, 0x35, 0xa8, 0x55, 0xef, 0x67, 0x7b, 0xf4, 0x01, 0xf7, 0x3c, 0x59, 0x90, 0x46, 0x12, 0xc7,
+    0x37, 0xd9, 0xf0, 0x71, 0x1d, 0xe4, 0xba, 0x73, 0xb8, 0x0a, 0xbb, 0xf9, 0xc8, 0xbc, 0x82, 0xe1,
+    0xd9, 0x43, 0xce, 0x46, 0x61, 0x10, 0xd9];
+
+#[test]
+fn test_addon_signature() {
+    setup();
+    let payload = b"This is the content.";
+    let report = verify::verify_addon_signature(ADDON_COSE_SIGN, Some(payload), ADDON_ROOT,
+                                                verify::VerificationPolicy::AtLeastOne).unwrap();
+    assert!(report.policy_satisfied);
+    assert_eq!(report.signers.len(), 2);
+    assert_eq!(report.signers[0].algorithm, Some(verify::SignatureAlgorithm::ES256));
+    assert_eq!(report.signers[0].result, Ok(()));
+    assert_eq!(report.signers[1].result, Err(verify::VerifyError::CertificateExpired));
+    let report = verify::verify_addon_signature(ADDON_COSE_SIGN, Some(payload), ADDON_ROOT,
+                                                verify::VerificationPolicy::All).unwrap();
+    assert!(!report.policy_satisfied);
+}
+
+#[test]
+fn test_addon_signature_untrusted_root() {
+    setup();
+    let payload = b"This is the content.";
+    let report = verify::verify_addon_signature(ADDON_COSE_SIGN, Some(payload), ADDON_OTHER_ROOT,
+                                                verify::VerificationPolicy::AtLeastOne).unwrap();
+    assert!(!report.policy_satisfied);
+    assert_eq!(report.signers[0].result, Err(verify::VerifyError::UntrustedCertificate));
+}
+
+#[test]
+fn test_addon_signature_tampered_payload() {
+    setup();
+    let payload = b"This is not the content.";
+    let report = verify::verify_addon_signature(ADDON_COSE_SIGN, Some(payload), ADDON_ROOT,
+                                                verify::VerificationPolicy::AtLeastOne).unwrap();
+    assert!(!report.policy_satisfied);
+    assert_eq!(report.signers[0].result, Err(verify::VerifyError::SignatureVerificationFailed));
+}
+
+#[test]
+fn test_addon_signature_bad_certificate() {
+    setup();
+    let payload = b"This is the content.";
+    // COSE_SIGN_DETACHED has no intermediates, and its kids aren't certificates.
+    let report = verify::verify_addon_signature(COSE_SIGN_DETACHED, Some(payload), ADDON_ROOT,
+                                                verify::VerificationPolicy::All).unwrap();
+    assert_eq!(report.signers[0].result, Err(verify::VerifyError::DecodingCertificateFailed));
+}
+
+// The end-entity certificate "CN=Test Signer" for signer1's key, issued by "CN=Test Intermediate".
+static TEST_SIGNER_CERT: &[u8] = &[
+    0x30, 0x82, 0x01, 0x57, 0x30, 0x81, 0xfe, 0xa0, 0x03, 0x02, 0x01, 0x02, 0x02, 0x01, 0x03, 0x30,
+    0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x30, 0x1c, 0x31, 0x1a, 0x30,
+    0x18, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x11, 0x54, 0x65, 0x73, 0x74, 0x20, 0x49, 0x6e, 0x74,
+    0x65, 0x72, 0x6d, 0x65, 0x64, 0x69, 0x61, 0x74, 0x65, 0x30, 0x20, 0x17, 0x0d, 0x32, 0x30, 0x30,
+    0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x5a, 0x18, 0x0f, 0x32, 0x30, 0x35, 0x30,
+    0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x5a, 0x30, 0x16, 0x31, 0x14, 0x30,
+    0x12, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x0b, 0x54, 0x65, 0x73, 0x74, 0x20, 0x53, 0x69, 0x67,
+    0x6e, 0x65, 0x72, 0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01,
+    0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00, 0x04, 0x02, 0x17,
+    0xe6, 0x17, 0xf0, 0xb6, 0x44, 0x39, 0x28, 0x27, 0x8f, 0x96, 0x99, 0x9e, 0x69, 0xa2, 0x3a, 0x4f,
+    0x2c, 0x15, 0x2b, 0xdf, 0x6d, 0x6c, 0xdf, 0x66, 0xe5, 0xb8, 0x02, 0x82, 0xd4, 0xed, 0x19, 0x4a,
+    0x7d, 0xeb, 0xcb, 0x97, 0x71, 0x2d, 0x2d, 0xda, 0x3c, 0xa8, 0x5a, 0xa8, 0x76, 0x5a, 0x56, 0xf4,
+    0x5f, 0xc7, 0x58, 0x59, 0x96, 0x52, 0xf2, 0x89, 0x7c, 0x65, 0x30, 0x6e, 0x57, 0x94, 0xa3, 0x35,
+    0x30, 0x33, 0x30, 0x0c, 0x06, 0x03, 0x55, 0x1d, 0x13, 0x01, 0x01, 0xff, 0x04, 0x02, 0x30, 0x00,
+    0x30, 0x0e, 0x06, 0x03, 0x55, 0x1d, 0x0f, 0x01, 0x01, 0xff, 0x04, 0x04, 0x03, 0x02, 0x07, 0x80,
+    0x30, 0x13, 0x06, 0x03, 0x55, 0x1d, 0x25, 0x04, 0x0c, 0x30, 0x0a, 0x06, 0x08, 0x2b, 0x06, 0x01,
+    0x05, 0x05, 0x07, 0x03, 0x03, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03,
+    0x02, 0x03, 0x48, 0x00, 0x30, 0x45, 0x02, 0x21, 0x00, 0xb1, 0xd5, 0xd6, 0x60, 0xd2, 0x3c, 0x8f,
+    0x1d, 0x1d, 0x2b, 0x19, 0x64, 0xf3, 0x62, 0x52, 0xa6, 0x64, 0x7f, 0x75, 0x7c, 0xc6, 0xd5, 0xe2,
+    0x60, 0x93, 0xca, 0xd5, 0xc4, 0x52, 0x00, 0x0a, 0x0c, 0x02, 0x20, 0x26, 0x89, 0x8f, 0x0c, 0xdb,
+    0x74, 0x2d, 0x99, 0xe4, 0xb3, 0x84, 0x81, 0xf9, 0xe7, 0x80, 0x30, 0x7b, 0xb3, 0xd1, 0x70, 0x60,
+    0xba, 0xe0, 0xd3, 0x26, 0xc3, 0x07, 0xc0, 0xe8, 0x99, 0x7b, 0x7b];
+
+// A COSE_Sign with payload "This is the content." whose body has an x5bag holding the end-entity
+// certificate "CN=Second Signer" for signer2's key. It has five ES256 signatures:
+// 1. by signer1, with an x5chain of "CN=Test Signer" and "CN=Test Intermediate"
+// 2. by signer2, with a SHA-256 x5t of "CN=Second Signer"
+// 3. by signer1, with an x5chain of "CN=Test Signer" but a SHA-256 x5t of "CN=Test Intermediate"
+// 4. by signer1, with only an x5u
+// 5. by signer1, with a SHA-384 x5t of "CN=Test Signer", which is not included in the message
+static COSE_SIGN_X509: &[u8] = &[
+    0xd8, 0x62, 0x84, 0x40, 0xa1, 0x18, 0x20, 0x59, 0x01, 0x5e, 0x30, 0x82, 0x01, 0x5a, 0x30, 0x82,
+    0x01, 0x00, 0xa0, 0x03, 0x02, 0x01, 0x02, 0x02, 0x01, 0x07, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86,
+    0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x30, 0x1c, 0x31, 0x1a, 0x30, 0x18, 0x06, 0x03, 0x55, 0x04,
+    0x03, 0x0c, 0x11, 0x54, 0x65, 0x73, 0x74, 0x20, 0x49, 0x6e, 0x74, 0x65, 0x72, 0x6d, 0x65, 0x64,
+    0x69, 0x61, 0x74, 0x65, 0x30, 0x20, 0x17, 0x0d, 0x32, 0x30, 0x30, 0x31, 0x30, 0x31, 0x30, 0x30,
+    0x30, 0x30, 0x30, 0x30, 0x5a, 0x18, 0x0f, 0x32, 0x30, 0x35, 0x30, 0x30, 0x31, 0x30, 0x31, 0x30,
+    0x30, 0x30, 0x30, 0x30, 0x30, 0x5a, 0x30, 0x18, 0x31, 0x16, 0x30, 0x14, 0x06, 0x03, 0x55, 0x04,
+    0x03, 0x0c, 0x0d, 0x53, 0x65, 0x63, 0x6f, 0x6e, 0x64, 0x20, 0x53, 0x69, 0x67, 0x6e, 0x65, 0x72,
+    0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a,
+    0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00, 0x04, 0xd6, 0x5a, 0x93, 0x97, 0x7c,
+    0xaa, 0x3d, 0x1b, 0x08, 0x18, 0x52, 0xff, 0x57, 0xa7, 0x9e, 0x46, 0x5f, 0x16, 0x60, 0x57, 0x73,
+    0x04, 0xba, 0xea, 0xd5, 0x05, 0xdd, 0x3a, 0x48, 0x58, 0x9c, 0xf3, 0x50, 0x18, 0x5e, 0x89, 0x53,
+    0x72, 0xdf, 0x62, 0x21, 0xea, 0x3a, 0x13, 0x75, 0x57, 0xe4, 0x73, 0xfd, 0xdb, 0x67, 0x55, 0xf0,
+    0x5b, 0xd5, 0x07, 0xc3, 0xc5, 0x33, 0xfc, 0xe9, 0xc9, 0x12, 0x85, 0xa3, 0x35, 0x30, 0x33, 0x30,
+    0x0c, 0x06, 0x03, 0x55, 0x1d, 0x13, 0x01, 0x01, 0xff, 0x04, 0x02, 0x30, 0x00, 0x30, 0x0e, 0x06,
+    0x03, 0x55, 0x1d, 0x0f, 0x01, 0x01, 0xff, 0x04, 0x04, 0x03, 0x02, 0x07, 0x80, 0x30, 0x13, 0x06,
+    0x03, 0x55, 0x1d, 0x25, 0x04, 0x0c, 0x30, 0x0a, 0x06, 0x08, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x07,
+    0x03, 0x03, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x03, 0x48,
+    0x00, 0x30, 0x45, 0x02, 0x21, 0x00, 0x9e, 0x59, 0x79, 0x6c, 0x36, 0x1e, 0x18, 0xbd, 0xe1, 0xed,
+    0x1a, 0xef, 0x65, 0xb3, 0xb5, 0x46, 0x02, 0xfb, 0x2a, 0x08, 0x35, 0xcd, 0x20, 0x4b, 0x6b, 0x96,
+    0x7c, 0x3b, 0xcf, 0x1d, 0x64, 0x26, 0x02, 0x20, 0x15, 0x6d, 0x76, 0x4e, 0x68, 0x9c, 0x46, 0xae,
+    0xf9, 0x37, 0xd1, 0xec, 0xab, 0x7e, 0x7f, 0x78, 0x80, 0x14, 0x18, 0x55, 0x36, 0x74, 0x9f, 0xb7,
+    0x3d, 0x53, 0x0b, 0x48, 0xc7, 0x70, 0x3f, 0xbf, 0x54, 0x54, 0x68, 0x69, 0x73, 0x20, 0x69, 0x73,
+    0x20, 0x74, 0x68, 0x65, 0x20, 0x63, 0x6f, 0x6e, 0x74, 0x65, 0x6e, 0x74, 0x2e, 0x85, 0x83, 0x59,
+    0x02, 0xc3, 0xa2, 0x01, 0x26, 0x18, 0x21, 0x82, 0x59, 0x01, 0x5b, 0x30, 0x82, 0x01, 0x57, 0x30,
+    0x81, 0xfe, 0xa0, 0x03, 0x02, 0x01, 0x02, 0x02, 0x01, 0x03, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86,
+    0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x30, 0x1c, 0x31, 0x1a, 0x30, 0x18, 0x06, 0x03, 0x55, 0x04,
+    0x03, 0x0c, 0x11, 0x54, 0x65, 0x73, 0x74, 0x20, 0x49, 0x6e, 0x74, 0x65, 0x72, 0x6d, 0x65, 0x64,
+    0x69, 0x61, 0x74, 0x65, 0x30, 0x20, 0x17, 0x0d, 0x32, 0x30, 0x30, 0x31, 0x30, 0x31, 0x30, 0x30,
+    0x30, 0x30, 0x30, 0x30, 0x5a, 0x18, 0x0f, 0x32, 0x30, 0x35, 0x30, 0x30, 0x31, 0x30, 0x31, 0x30,
+    0x30, 0x30, 0x30, 0x30, 0x30, 0x5a, 0x30, 0x16, 0x31, 0x14, 0x30, 0x12, 0x06, 0x03, 0x55, 0x04,
+    0x03, 0x0c, 0x0b, 0x54, 0x65, 0x73, 0x74, 0x20, 0x53, 0x69, 0x67, 0x6e, 0x65, 0x72, 0x30, 0x59,
+    0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48,
+    0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00, 0x04, 0x02, 0x17, 0xe6, 0x17, 0xf0, 0xb6, 0x44,
+    0x39, 0x28, 0x27, 0x8f, 0x96, 0x99, 0x9e, 0x69, 0xa2, 0x3a, 0x4f, 0x2c, 0x15, 0x2b, 0xdf, 0x6d,
+    0x6c, 0xdf, 0x66, 0xe5, 0xb8, 0x02, 0x82, 0xd4, 0xed, 0x19, 0x4a, 0x7d, 0xeb, 0xcb, 0x97, 0x71,
+    0x2d, 0x2d, 0xda, 0x3c, 0xa8, 0x5a, 0xa8, 0x76, 0x5a, 0x56, 0xf4, 0x5f, 0xc7, 0x58, 0x59, 0x96,
+    0x52, 0xf2, 0x89, 0x7c, 0x65, 0x30, 0x6e, 0x57, 0x94, 0xa3, 0x35, 0x30, 0x33, 0x30, 0x0c, 0x06,
+    0x03, 0x55, 0x1d, 0x13, 0x01, 0x01, 0xff, 0x04, 0x02, 0x30, 0x00, 0x30, 0x0e, 0x06, 0x03, 0x55,
+    0x1d, 0x0f, 0x01, 0x01, 0xff, 0x04, 0x04, 0x03, 0x02, 0x07, 0x80, 0x30, 0x13, 0x06, 0x03, 0x55,
+    0x1d, 0x25, 0x04, 0x0c, 0x30, 0x0a, 0x06, 0x08, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x03,
+    0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x03, 0x48, 0x00, 0x30,
+    0x45, 0x02, 0x21, 0x00, 0xb1, 0xd5, 0xd6, 0x60, 0xd2, 0x3c, 0x8f, 0x1d, 0x1d, 0x2b, 0x19, 0x64,
+    0xf3, 0x62, 0x52, 0xa6, 0x64, 0x7f, 0x75, 0x7c, 0xc6, 0xd5, 0xe2, 0x60, 0x93, 0xca, 0xd5, 0xc4,
+    0x52, 0x00, 0x0a, 0x0c, 0x02, 0x20, 0x26, 0x89, 0x8f, 0x0c, 0xdb, 0x74, 0x2d, 0x99, 0xe4, 0xb3,
+    0x84, 0x81, 0xf9, 0xe7, 0x80, 0x30, 0x7b, 0xb3, 0xd1, 0x70, 0x60, 0xba, 0xe0, 0xd3, 0x26, 0xc3,
+    0x07, 0xc0, 0xe8, 0x99, 0x7b, 0x7b, 0x59, 0x01, 0x5c, 0x30, 0x82, 0x01, 0x58, 0x30, 0x81, 0xff,
+    0xa0, 0x03, 0x02, 0x01, 0x02, 0x02, 0x01, 0x02, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce,
+    0x3d, 0x04, 0x03, 0x02, 0x30, 0x14, 0x31, 0x12, 0x30, 0x10, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c,
+    0x09, 0x54, 0x65, 0x73, 0x74, 0x20, 0x52, 0x6f, 0x6f, 0x74, 0x30, 0x20, 0x17, 0x0d, 0x32, 0x30,
+    0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x5a, 0x18, 0x0f, 0x32, 0x30, 0x35,
+    0x30, 0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x5a, 0x30, 0x1c, 0x31, 0x1a,
+    0x30, 0x18, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x11, 0x54, 0x65, 0x73, 0x74, 0x20, 0x49, 0x6e,
+    0x74, 0x65, 0x72, 0x6d, 0x65, 0x64, 0x69, 0x61, 0x74, 0x65, 0x30, 0x59, 0x30, 0x13, 0x06, 0x07,
+    0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01,
+    0x07, 0x03, 0x42, 0x00, 0x04, 0x57, 0xe9, 0x77, 0xf6, 0xdb, 0x7e, 0x33, 0xc3, 0xfe, 0x7a, 0xcf,
+    0x28, 0x42, 0xed, 0x98, 0x70, 0x09, 0xca, 0xf5, 0x6d, 0x45, 0x86, 0x82, 0xfc, 0xa4, 0x47, 0xb7,
+    0xd3, 0xd7, 0x62, 0xab, 0x34, 0xc5, 0xab, 0x37, 0x70, 0xba, 0x57, 0x3b, 0xdf, 0xf5, 0x41, 0x40,
+    0x65, 0x64, 0x0f, 0xfb, 0x5b, 0x34, 0x6d, 0xfa, 0x84, 0xde, 0xc4, 0xdb, 0x4d, 0x68, 0xe5, 0xf5,
+    0x9c, 0xc4, 0x71, 0xc2, 0xec, 0xa3, 0x38, 0x30, 0x36, 0x30, 0x0f, 0x06, 0x03, 0x55, 0x1d, 0x13,
+    0x01, 0x01, 0xff, 0x04, 0x05, 0x30, 0x03, 0x01, 0x01, 0xff, 0x30, 0x0e, 0x06, 0x03, 0x55, 0x1d,
+    0x0f, 0x01, 0x01, 0xff, 0x04, 0x04, 0x03, 0x02, 0x01, 0x06, 0x30, 0x13, 0x06, 0x03, 0x55, 0x1d,
+    0x25, 0x04, 0x0c, 0x30, 0x0a, 0x06, 0x08, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x03, 0x30,
+    0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x03, 0x48, 0x00, 0x30, 0x45,
+    0x02, 0x20, 0x74, 0x72, 0x20, 0x56, 0xa9, 0x59, 0x71, 0xc8, 0x11, 0x31, 0x90, 0xe2, 0x7a, 0x3e,
+    0x7e, 0x82, 0x5e, 0x5a, 0x2c, 0x1c, 0x48, 0x84, 0xab, 0xf5, 0x5d, 0x4d, 0x9b, 0xf5, 0x21, 0xba,
+    0xa6, 0x8d, 0x02, 0x21, 0x00, 0x93, 0x16, 0xb7, 0x34, 0xec, 0xfd, 0xe4, 0x8d, 0x24, 0xce, 0x1a,
+    0xaf, 0x09, 0x36, 0x1b, 0xfb, 0x28, 0xd1, 0x9e, 0x15, 0x44, 0x0c, 0xb9, 0x57, 0xcf, 0x2e, 0xf4,
+    0xc4, 0x97, 0xed, 0xca, 0xbc, 0xa0, 0x58, 0x40, 0x09, 0xb6, 0xc2, 0x01, 0x17, 0x28, 0x2c, 0x1e,
+    0x1e, 0xfe, 0x33, 0x6f, 0x3c, 0x68, 0xe1, 0xfd, 0x81, 0x73, 0x5a, 0xdf, 0x00, 0x2e, 0xb5, 0x8d,
+    0x55, 0x01, 0x28, 0x60, 0x72, 0xc6, 0x27, 0xeb, 0x79, 0xca, 0x7b, 0xc9, 0x9c, 0x58, 0x58, 0x12,
+    0x2b, 0xb9, 0x5c, 0xf6, 0x0f, 0x68, 0x06, 0xfb, 0xa5, 0x25, 0x76, 0xbb, 0x84, 0x2f, 0x17, 0xee,
+    0xab, 0x8d, 0x5b, 0xb0, 0x7a, 0x91, 0xe1, 0xca, 0x83, 0x58, 0x29, 0xa2, 0x01, 0x26, 0x18, 0x22,
+    0x82, 0x2f, 0x58, 0x20, 0x46, 0x49, 0xdf, 0x1e, 0x99, 0x66, 0x36, 0xfa, 0x88, 0xa2, 0x8d, 0x97,
+    0x92, 0xb8, 0x67, 0x1b, 0x81, 0xff, 0x36, 0x91, 0x06, 0x45, 0xf7, 0x45, 0xb4, 0xc4, 0xa1, 0x20,
+    0xa9, 0x3c, 0x33, 0x92, 0xa0, 0x58, 0x40, 0x1c, 0x19, 0x22, 0xb6, 0x7b, 0xdf, 0x6c, 0x9c, 0x02,
+    0x7d, 0xff, 0x3d, 0x67, 0x2b, 0x6a, 0x2a, 0x6e, 0x09, 0xde, 0xee, 0x20, 0x41, 0x10, 0xea, 0x1e,
+    0x35, 0x96, 0x58, 0x22, 0x82, 0x2e, 0xe0, 0x05, 0x64, 0x30, 0x13, 0x5a, 0xaf, 0x3f, 0xc1, 0x44,
+    0x4b, 0xae, 0x92, 0x86, 0xdd, 0x80, 0xbc, 0xdd, 0x8a, 0x6d, 0x99, 0x1a, 0x04, 0x82, 0x12, 0xd5,
+    0xc9, 0xc7, 0x57, 0x2b, 0x07, 0x5f, 0x76, 0x83, 0x59, 0x01, 0x89, 0xa3, 0x01, 0x26, 0x18, 0x21,
+    0x59, 0x01, 0x5b, 0x30, 0x82, 0x01, 0x57, 0x30, 0x81, 0xfe, 0xa0, 0x03, 0x02, 0x01, 0x02, 0x02,
+    0x01, 0x03, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x30, 0x1c,
+    0x31, 0x1a, 0x30, 0x18, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x11, 0x54, 0x65, 0x73, 0x74, 0x20,
+    0x49, 0x6e, 0x74, 0x65, 0x72, 0x6d, 0x65, 0x64, 0x69, 0x61, 0x74, 0x65, 0x30, 0x20, 0x17, 0x0d,
+    0x32, 0x30, 0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x5a, 0x18, 0x0f, 0x32,
+    0x30, 0x35, 0x30, 0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x5a, 0x30, 0x16,
+    0x31, 0x14, 0x30, 0x12, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x0b, 0x54, 0x65, 0x73, 0x74, 0x20,
+    0x53, 0x69, 0x67, 0x6e, 0x65, 0x72, 0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce,
+    0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00,
+    0x04, 0x02, 0x17, 0xe6, 0x17, 0xf0, 0xb6, 0x44, 0x39, 0x28, 0x27, 0x8f, 0x96, 0x99, 0x9e, 0x69,
+    0xa2, 0x3a, 0x4f, 0x2c, 0x15, 0x2b, 0xdf, 0x6d, 0x6c, 0xdf, 0x66, 0xe5, 0xb8, 0x02, 0x82, 0xd4,
+    0xed, 0x19, 0x4a, 0x7d, 0xeb, 0xcb, 0x97, 0x71, 0x2d, 0x2d, 0xda, 0x3c, 0xa8, 0x5a, 0xa8, 0x76,
+    0x5a, 0x56, 0xf4, 0x5f, 0xc7, 0x58, 0x59, 0x96, 0x52, 0xf2, 0x89, 0x7c, 0x65, 0x30, 0x6e, 0x57,
+    0x94, 0xa3, 0x35, 0x30, 0x33, 0x30, 0x0c, 0x06, 0x03, 0x55, 0x1d, 0x13, 0x01, 0x01, 0xff, 0x04,
+    0x02, 0x30, 0x00, 0x30, 0x0e, 0x06, 0x03, 0x55, 0x1d, 0x0f, 0x01, 0x01, 0xff, 0x04, 0x04, 0x03,
+    0x02, 0x07, 0x80, 0x30, 0x13, 0x06, 0x03, 0x55, 0x1d, 0x25, 0x04, 0x0c, 0x30, 0x0a, 0x06, 0x08,
+    0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x03, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce,
+    0x3d, 0x04, 0x03, 0x02, 0x03, 0x48, 0x00, 0x30, 0x45, 0x02, 0x21, 0x00, 0xb1, 0xd5, 0xd6, 0x60,
+    0xd2, 0x3c, 0x8f, 0x1d, 0x1d, 0x2b, 0x19, 0x64, 0xf3, 0x62, 0x52, 0xa6, 0x64, 0x7f, 0x75, 0x7c,
+    0xc6, 0xd5, 0xe2, 0x60, 0x93, 0xca, 0xd5, 0xc4, 0x52, 0x00, 0x0a, 0x0c, 0x02, 0x20, 0x26, 0x89,
+    0x8f, 0x0c, 0xdb, 0x74, 0x2d, 0x99, 0xe4, 0xb3, 0x84, 0x81, 0xf9, 0xe7, 0x80, 0x30, 0x7b, 0xb3,
+    0xd1, 0x70, 0x60, 0xba, 0xe0, 0xd3, 0x26, 0xc3, 0x07, 0xc0, 0xe8, 0x99, 0x7b, 0x7b, 0x18, 0x22,
+    0x82, 0x2f, 0x58, 0x20, 0xd0, 0xd8, 0xbe, 0x7f, 0xd9, 0x0d, 0x43, 0x1a, 0xb7, 0x15, 0x9d, 0x6c,
+    0x65, 0x4c, 0x4d, 0x2b, 0x5d, 0x93, 0xab, 0xdc, 0xce, 0xf8, 0xb7, 0x6a, 0xc2, 0x13, 0xde, 0x73,
+    0x5c, 0x4f, 0x07, 0xb9, 0xa0, 0x58, 0x40, 0x30, 0x41, 0x19, 0x78, 0x81, 0xb0, 0x8d, 0x47, 0xa6,
+    0x02, 0x44, 0xaf, 0x03, 0x75, 0x2e, 0xa3, 0x55, 0x48, 0x35, 0xfa, 0x71, 0x6c, 0x45, 0x80, 0x48,
+    0x68, 0x26, 0x94, 0x05, 0x9e, 0xeb, 0xbd, 0xf2, 0x02, 0x11, 0x50, 0x69, 0xd0, 0x36, 0xca, 0x44,
+    0x18, 0x73, 0xf1, 0xfc, 0x7a, 0xc7, 0x93, 0x6c, 0x60, 0x64, 0xc2, 0xc6, 0xaf, 0x63, 0x22, 0x03,
+    0x39, 0xd4, 0x3b, 0x29, 0xe4, 0xf7, 0xb1, 0x83, 0x58, 0x25, 0xa2, 0x01, 0x26, 0x18, 0x23, 0x78,
+    0x1e, 0x68, 0x74, 0x74, 0x70, 0x73, 0x3a, 0x2f, 0x2f, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65,
+    0x2e, 0x63, 0x6f, 0x6d, 0x2f, 0x73, 0x69, 0x67, 0x6e, 0x65, 0x72, 0x2e, 0x64, 0x65, 0x72, 0xa0,
+    0x58, 0x40, 0xb0, 0x25, 0xc7, 0x34, 0x3e, 0xe0, 0x03, 0xe1, 0x68, 0x28, 0x56, 0xda, 0xdd, 0x6d,
+    0xfa, 0x36, 0x94, 0x29, 0xd0, 0xbf, 0xa5, 0xc4, 0xa4, 0x28, 0x0e, 0x8f, 0xf3, 0xac, 0xe1, 0x7e,
+    0xe8, 0x0b, 0x3c, 0x4a, 0x6b, 0x59, 0xec, 0x6f, 0x42, 0x03, 0x65, 0x3a, 0xab, 0x8c, 0xf5, 0xbe,
+    0x7a, 0x23, 0xff, 0x5e, 0xd5, 0xac, 0x24, 0x5b, 0x37, 0xdc, 0x62, 0x52, 0x04, 0x62, 0x26, 0xee,
+    0x8c, 0xa3, 0x83, 0x58, 0x3a, 0xa2, 0x01, 0x26, 0x18, 0x22, 0x82, 0x38, 0x2a, 0x58, 0x30, 0x20,
+    0xba, 0xab, 0x2d, 0x15, 0xdf, 0x86, 0xf4, 0x60, 0x4b, 0x58, 0x7f, 0x3c, 0x7a, 0x16, 0xf9, 0xdb,
+    0x20, 0x5e, 0xa5, 0xfe, 0x0f, 0x38, 0xaa, 0xc7, 0x0d, 0xbe, 0xc2, 0x69, 0x2b, 0xd3, 0xef, 0x88,
+    0xb0, 0x1a, 0x70, 0x90, 0xdd, 0xfd, 0x1a, 0x18, 0x9a, 0xb2, 0xe8, 0x7c, 0xdc, 0x1f, 0xc7, 0xa0,
+    0x58, 0x40, 0xe4, 0xe5, 0x75, 0x97, 0xb7, 0x24, 0xeb, 0x7f, 0xf3, 0x22, 0x9e, 0x51, 0xc9, 0xf5,
+    0x52, 0x21, 0x41, 0x24, 0xcd, 0x80, 0x91, 0x6a, 0x48, 0xc0, 0x18, 0x20, 0x80, 0x44, 0x19, 0x33,
+    0xae, 0x9a, 0x6f, 0xae, 0x23, 0x68, 0x84, 0x4d, 0xd2, 0x95, 0x47, 0x59, 0x6e, 0x68, 0xbb, 0xa6,
+    0x57, 0xf8, 0x13, 0xc0, 0x18, 0x9a, 0x88, 0x64, 0x1e, 0xb7, 0x0d, 0x81, 0x95, 0x00, 0xd1, 0xdf,
+    0x6a, 0x11];
+
+#[test]
+fn test_cose_sign_x509() {
+    setup();
+    let report = verify::verify_cose_sign_x509(COSE_SIGN_X509, None, &[],
+                                               verify::VerificationPolicy::Threshold(2)).unwrap();
+    assert!(report.policy_satisfied);
+    let results: Vec<Result<(), verify::VerifyError>> =
+        report.signers.into_iter().map(|signer| signer.result).collect();
+    assert_eq!(results, vec![Ok(()),
+                             Ok(()),
+                             Err(verify::VerifyError::CertificateThumbprintMismatch),
+                             Err(verify::VerifyError::CertificateNotFound),
+                             Err(verify::VerifyError::CertificateNotFound)]);
+}
+
+#[test]
+fn test_cose_sign_x509_additional_certificates() {
+    setup();
+    let report = verify::verify_cose_sign_x509(COSE_SIGN_X509, None, &[TEST_SIGNER_CERT],
+                                               verify::VerificationPolicy::All).unwrap();
+    assert!(!report.policy_satisfied);
+    assert_eq!(report.signers[4].result, Ok(()));
+}
+
+static TEST_INTERMEDIATE_CERT: &[u8] = &[
+    0x30, 0x82, 0x01, 0x58, 0x30, 0x81, 0xff, 0xa0, 0x03, 0x02, 0x01, 0x02, 0x02, 0x01, 0x02, 0x30,
+    0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x30, 0x14, 0x31, 0x12, 0x30,
+    0x10, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x09, 0x54, 0x65, 0x73, 0x74, 0x20, 0x52, 0x6f, 0x6f,
+    0x74, 0x30, 0x20, 0x17, 0x0d, 0x32, 0x30, 0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30, 0x30,
+    0x30, 0x5a, 0x18, 0x0f, 0x32, 0x30, 0x35, 0x30, 0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30,
+    0x30, 0x30, 0x5a, 0x30, 0x1c, 0x31, 0x1a, 0x30, 0x18, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x11,
+    0x54, 0x65, 0x73, 0x74, 0x20, 0x49, 0x6e, 0x74, 0x65, 0x72, 0x6d, 0x65, 0x64, 0x69, 0x61, 0x74,
+    0x65, 0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08,
+    0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00, 0x04, 0x57, 0xe9, 0x77, 0xf6,
+    0xdb, 0x7e, 0x33, 0xc3, 0xfe, 0x7a, 0xcf, 0x28, 0x42, 0xed, 0x98, 0x70, 0x09, 0xca, 0xf5, 0x6d,
+    0x45, 0x86, 0x82, 0xfc, 0xa4, 0x47, 0xb7, 0xd3, 0xd7, 0x62, 0xab, 0x34, 0xc5, 0xab, 0x37, 0x70,
+    0xba, 0x57, 0x3b, 0xdf, 0xf5, 0x41, 0x40, 0x65, 0x64, 0x0f, 0xfb, 0x5b, 0x34, 0x6d, 0xfa, 0x84,
+    0xde, 0xc4, 0xdb, 0x4d, 0x68, 0xe5, 0xf5, 0x9c, 0xc4, 0x71, 0xc2, 0xec, 0xa3, 0x38, 0x30, 0x36,
+    0x30, 0x0f, 0x06, 0x03, 0x55, 0x1d, 0x13, 0x01, 0x01, 0xff, 0x04, 0x05, 0x30, 0x03, 0x01, 0x01,
+    0xff, 0x30, 0x0e, 0x06, 0x03, 0x55, 0x1d, 0x0f, 0x01, 0x01, 0xff, 0x04, 0x04, 0x03, 0x02, 0x01,
+    0x06, 0x30, 0x13, 0x06, 0x03, 0x55, 0x1d, 0x25, 0x04, 0x0c, 0x30, 0x0a, 0x06, 0x08, 0x2b, 0x06,
+    0x01, 0x05, 0x05, 0x07, 0x03, 0x03, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04,
+    0x03, 0x02, 0x03, 0x48, 0x00, 0x30, 0x45, 0x02, 0x20, 0x74, 0x72, 0x20, 0x56, 0xa9, 0x59, 0x71,
+    0xc8, 0x11, 0x31, 0x90, 0xe2, 0x7a, 0x3e, 0x7e, 0x82, 0x5e, 0x5a, 0x2c, 0x1c, 0x48, 0x84, 0xab,
+    0xf5, 0x5d, 0x4d, 0x9b, 0xf5, 0x21, 0xba, 0xa6, 0x8d, 0x02, 0x21, 0x00, 0x93, 0x16, 0xb7, 0x34,
+    0xec, 0xfd, 0xe4, 0x8d, 0x24, 0xce, 0x1a, 0xaf, 0x09, 0x36, 0x1b, 0xfb, 0x28, 0xd1, 0x9e, 0x15,
+    0x44, 0x0c, 0xb9, 0x57, 0xcf, 0x2e, 0xf4, 0xc4, 0x97, 0xed, 0xca, 0xbc];
+
+static EXPIRED_SIGNER_CERT: &[u8] = &[
+    0x30, 0x82, 0x01, 0x58, 0x30, 0x81, 0xff, 0xa0, 0x03, 0x02, 0x01, 0x02, 0x02, 0x01, 0x04, 0x30,
+    0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x30, 0x1c, 0x31, 0x1a, 0x30,
+    0x18, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x11, 0x54, 0x65, 0x73, 0x74, 0x20, 0x49, 0x6e, 0x74,
+    0x65, 0x72, 0x6d, 0x65, 0x64, 0x69, 0x61, 0x74, 0x65, 0x30, 0x1e, 0x17, 0x0d, 0x31, 0x35, 0x30,
+    0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x5a, 0x17, 0x0d, 0x31, 0x36, 0x30, 0x31,
+    0x30, 0x31, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x5a, 0x30, 0x19, 0x31, 0x17, 0x30, 0x15, 0x06,
+    0x03, 0x55, 0x04, 0x03, 0x0c, 0x0e, 0x45, 0x78, 0x70, 0x69, 0x72, 0x65, 0x64, 0x20, 0x53, 0x69,
+    0x67, 0x6e, 0x65, 0x72, 0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02,
+    0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00, 0x04, 0x02,
+    0x17, 0xe6, 0x17, 0xf0, 0xb6, 0x44, 0x39, 0x28, 0x27, 0x8f, 0x96, 0x99, 0x9e, 0x69, 0xa2, 0x3a,
+    0x4f, 0x2c, 0x15, 0x2b, 0xdf, 0x6d, 0x6c, 0xdf, 0x66, 0xe5, 0xb8, 0x02, 0x82, 0xd4, 0xed, 0x19,
+    0x4a, 0x7d, 0xeb, 0xcb, 0x97, 0x71, 0x2d, 0x2d, 0xda, 0x3c, 0xa8, 0x5a, 0xa8, 0x76, 0x5a, 0x56,
+    0xf4, 0x5f, 0xc7, 0x58, 0x59, 0x96, 0x52, 0xf2, 0x89, 0x7c, 0x65, 0x30, 0x6e, 0x57, 0x94, 0xa3,
+    0x35, 0x30, 0x33, 0x30, 0x0c, 0x06, 0x03, 0x55, 0x1d, 0x13, 0x01, 0x01, 0xff, 0x04, 0x02, 0x30,
+    0x00, 0x30, 0x0e, 0x06, 0x03, 0x55, 0x1d, 0x0f, 0x01, 0x01, 0xff, 0x04, 0x04, 0x03, 0x02, 0x07,
+    0x80, 0x30, 0x13, 0x06, 0x03, 0x55, 0x1d, 0x25, 0x04, 0x0c, 0x30, 0x0a, 0x06, 0x08, 0x2b, 0x06,
+    0x01, 0x05, 0x05, 0x07, 0x03, 0x03, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04,
+    0x03, 0x02, 0x03, 0x48, 0x00, 0x30, 0x45, 0x02, 0x20, 0x0e, 0x3e, 0xb3, 0xe1, 0x12, 0x72, 0xa3,
+    0xb3, 0xd1, 0x24, 0x48, 0x2f, 0x92, 0x6e, 0xec, 0xd7, 0xca, 0x78, 0x8c, 0x65, 0xd2, 0x0c, 0x81,
+    0xd3, 0xe6, 0x0a, 0x7c, 0xa2, 0x39, 0x3c, 0x6c, 0xd6, 0x02, 0x21, 0x00, 0xe8, 0xe4, 0xda, 0x92,
+    0x9e, 0x62, 0x33, 0xc3, 0x7f, 0xfd, 0x3c, 0x0d, 0x39, 0xfa, 0xdd, 0x3e, 0x79, 0x20, 0x56, 0x26,
+    0xd2, 0x31, 0x70, 0x59, 0x75, 0x09, 0x57, 0x3a, 0xc4, 0xe4, 0x63, 0x48];
+
+static SERVER_SIGNER_CERT: &[u8] = &[
+    0x30, 0x82, 0x01, 0x59, 0x30, 0x82, 0x01, 0x00, 0xa0, 0x03, 0x02, 0x01, 0x02, 0x02, 0x01, 0x05,
+    0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x30, 0x1c, 0x31, 0x1a,
+    0x30, 0x18, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x11, 0x54, 0x65, 0x73, 0x74, 0x20, 0x49, 0x6e,
+    0x74, 0x65, 0x72, 0x6d, 0x65, 0x64, 0x69, 0x61, 0x74, 0x65, 0x30, 0x20, 0x17, 0x0d, 0x32, 0x30,
+    0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x5a, 0x18, 0x0f, 0x32, 0x30, 0x35,
+    0x30, 0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x5a, 0x30, 0x18, 0x31, 0x16,
+    0x30, 0x14, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x0d, 0x53, 0x65, 0x72, 0x76, 0x65, 0x72, 0x20,
+    0x53, 0x69, 0x67, 0x6e, 0x65, 0x72, 0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce,
+    0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00,
+    0x04, 0x02, 0x17, 0xe6, 0x17, 0xf0, 0xb6, 0x44, 0x39, 0x28, 0x27, 0x8f, 0x96, 0x99, 0x9e, 0x69,
+    0xa2, 0x3a, 0x4f, 0x2c, 0x15, 0x2b, 0xdf, 0x6d, 0x6c, 0xdf, 0x66, 0xe5, 0xb8, 0x02, 0x82, 0xd4,
+    0xed, 0x19, 0x4a, 0x7d, 0xeb, 0xcb, 0x97, 0x71, 0x2d, 0x2d, 0xda, 0x3c, 0xa8, 0x5a, 0xa8, 0x76,
+    0x5a, 0x56, 0xf4, 0x5f, 0xc7, 0x58, 0x59, 0x96, 0x52, 0xf2, 0x89, 0x7c, 0x65, 0x30, 0x6e, 0x57,
+    0x94, 0xa3, 0x35, 0x30, 0x33, 0x30, 0x0c, 0x06, 0x03, 0x55, 0x1d, 0x13, 0x01, 0x01, 0xff, 0x04,
+    0x02, 0x30, 0x00, 0x30, 0x0e, 0x06, 0x03, 0x55, 0x1d, 0x0f, 0x01, 0x01, 0xff, 0x04, 0x04, 0x03,
+    0x02, 0x07, 0x80, 0x30, 0x13, 0x06, 0x03, 0x55, 0x1d, 0x25, 0x04, 0x0c, 0x30, 0x0a, 0x06, 0x08,
+    0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x01, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce,
+    0x3d, 0x04, 0x03, 0x02, 0x03, 0x47, 0x00, 0x30, 0x44, 0x02, 0x20, 0x46, 0x67, 0x08, 0xaa, 0xfd,
+    0x27, 0x73, 0x88, 0xd4, 0x48, 0x44, 0xb9, 0xec, 0xaf, 0xef, 0x5b, 0xe9, 0x8a, 0x0c, 0x58, 0x8e,
+    0xd6, 0x8a, 0xf0, 0x7d, 0x0e, 0x6f, 0x95, 0x39, 0x8e, 0x4d, 0x45, 0x02, 0x20, 0x6a, 0xa2, 0x33,
+    0xf0, 0x51, 0x5c, 0x49, 0xc2, 0x14, 0xbb, 0xdd, 0x8d, 0x7b, 0xdb, 0xff, 0xd1, 0xa9, 0x85, 0xdf,
+    0xf8, 0xdc, 0x08, 0x46, 0x2b, 0x4f, 0xa2, 0xc6, 0xa3, 0x98, 0xa0, 0x8a, 0xe4];
+
+// Claims to be issued by the test intermediate, but is signed with a different key.
+static FORGED_SIGNER_CERT: &[u8] = &[
+    0x30, 0x82, 0x01, 0x5a, 0x30, 0x82, 0x01, 0x00, 0xa0, 0x03, 0x02, 0x01, 0x02, 0x02, 0x01, 0x08,
+    0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x30, 0x1c, 0x31, 0x1a,
+    0x30, 0x18, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x11, 0x54, 0x65, 0x73, 0x74, 0x20, 0x49, 0x6e,
+    0x74, 0x65, 0x72, 0x6d, 0x65, 0x64, 0x69, 0x61, 0x74, 0x65, 0x30, 0x20, 0x17, 0x0d, 0x32, 0x30,
+    0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x5a, 0x18, 0x0f, 0x32, 0x30, 0x35,
+    0x30, 0x30, 0x31, 0x30, 0x31, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x5a, 0x30, 0x18, 0x31, 0x16,
+    0x30, 0x14, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x0d, 0x46, 0x6f, 0x72, 0x67, 0x65, 0x64, 0x20,
+    0x53, 0x69, 0x67, 0x6e, 0x65, 0x72, 0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce,
+    0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00,
+    0x04, 0x02, 0x17, 0xe6, 0x17, 0xf0, 0xb6, 0x44, 0x39, 0x28, 0x27, 0x8f, 0x96, 0x99, 0x9e, 0x69,
+    0xa2, 0x3a, 0x4f, 0x2c, 0x15, 0x2b, 0xdf, 0x6d, 0x6c, 0xdf, 0x66, 0xe5, 0xb8, 0x02, 0x82, 0xd4,
+    0xed, 0x19, 0x4a, 0x7d, 0xeb, 0xcb, 0x97, 0x71, 0x2d, 0x2d, 0xda, 0x3c, 0xa8, 0x5a, 0xa8, 0x76,
+    0x5a, 0x56, 0xf4, 0x5f, 0xc7, 0x58, 0x59, 0x96, 0x52, 0xf2, 0x89, 0x7c, 0x65, 0x30, 0x6e, 0x57,
+    0x94, 0xa3, 0x35, 0x30, 0x33, 0x30, 0x0c, 0x06, 0x03, 0x55, 0x1d, 0x13, 0x01, 0x01, 0xff, 0x04,
+    0x02, 0x30, 0x00, 0x30, 0x0e, 0x06, 0x03, 0x55, 0x1d, 0x0f, 0x01, 0x01, 0xff, 0x04, 0x04, 0x03,
+    0x02, 0x07, 0x80, 0x30, 0x13, 0x06, 0x03, 0x55, 0x1d, 0x25, 0x04, 0x0c, 0x30, 0x0a, 0x06, 0x08,
+    0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x03, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce,
+    0x3d, 0x04, 0x03, 0x02, 0x03, 0x48, 0x00, 0x30, 0x45, 0x02, 0x20, 0x6b, 0x05, 0x51, 0x6a, 0x44,
+    0x3f, 0x42, 0xbd, 0xaf, 0x89, 0x0a, 0x1d, 0x9a, 0x6a, 0x05, 0x5b, 0x20, 0xfa, 0xcb, 0x5f, 0xae,
+    0x7f, 0x65, 0x80, 0xc3, 0x92, 0xb3, 0x7c, 0xd3, 0x1e, 0x47, 0xac, 0x02, 0x21, 0x00, 0xf8, 0xd6,
+    0x86, 0xba, 0x6b, 0x0f, 0x5b, 0x68, 0x32, 0x56, 0x84, 0xb0, 0x1e, 0xed, 0x3e, 0xbb, 0xa4, 0x15,
+    0x94, 0xbc, 0x6e, 0x85, 0xf3, 0xfa, 0x23, 0x01, 0xb7, 0x63, 0x57, 0xe9, 0xf7, 0xd8];
+
+fn seconds_since_epoch(seconds: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(seconds)
+}
+
+#[test]
+fn test_certificate_chain() {
+    setup();
+    let spki = verify::verify_certificate_chain(TEST_SIGNER_CERT, &[TEST_INTERMEDIATE_CERT],
+                                                &[ADDON_OTHER_ROOT, ADDON_ROOT], SystemTime::now(),
+                                                verify::KeyPurpose::CodeSigning).unwrap();
+    assert_eq!(spki, SIGNER1_SPKI);
+}
+
+#[test]
+fn test_certificate_chain_untrusted() {
+    setup();
+    assert_eq!(verify::verify_certificate_chain(TEST_SIGNER_CERT, &[TEST_INTERMEDIATE_CERT],
+                                                &[ADDON_OTHER_ROOT], SystemTime::now(),
+                                                verify::KeyPurpose::CodeSigning),
+               Err(verify::VerifyError::UntrustedCertificate));
+}
+
+#[test]
+fn test_certificate_chain_expired() {
+    setup();
+    assert_eq!(verify::verify_certificate_chain(EXPIRED_SIGNER_CERT, &[TEST_INTERMEDIATE_CERT],
+                                                &[ADDON_ROOT], SystemTime::now(),
+                                                verify::KeyPurpose::CodeSigning),
+               Err(verify::VerifyError::CertificateExpired));
+    // 2051-01-01, after the chain has expired.
+    assert_eq!(verify::verify_certificate_chain(TEST_SIGNER_CERT, &[TEST_INTERMEDIATE_CERT],
+                                                &[ADDON_ROOT], seconds_since_epoch(2556144000),
+                                                verify::KeyPurpose::CodeSigning),
+               Err(verify::VerifyError::CertificateExpired));
+    // 2019-01-01, before the chain is valid.
+    assert_eq!(verify::verify_certificate_chain(TEST_SIGNER_CERT, &[TEST_INTERMEDIATE_CERT],
+                                                &[ADDON_ROOT], seconds_since_epoch(1546300800),
+                                                verify::KeyPurpose::CodeSigning),
+               Err(verify::VerifyError::CertificateExpired));
+    // 2049-01-01, still valid.
+    assert!(verify::verify_certificate_chain(TEST_SIGNER_CERT, &[TEST_INTERMEDIATE_CERT],
+                                             &[ADDON_ROOT], seconds_since_epoch(2493072000),
+                                             verify::KeyPurpose::CodeSigning).is_ok());
+}
+
+#[test]
+fn test_certificate_chain_bad_signature() {
+    setup();
+    assert_eq!(verify::verify_certificate_chain(FORGED_SIGNER_CERT, &[TEST_INTERMEDIATE_CERT],
+                                                &[ADDON_ROOT], SystemTime::now(),
+                                                verify::KeyPurpose::CodeSigning),
+               Err(verify::VerifyError::CertificateSignatureInvalid));
+}
+
+#[test]
+fn test_certificate_chain_inadequate_usage() {
+    setup();
+    assert_eq!(verify::verify_certificate_chain(SERVER_SIGNER_CERT, &[TEST_INTERMEDIATE_CERT],
+                                                &[ADDON_ROOT], SystemTime::now(),
+                                                verify::KeyPurpose::CodeSigning),
+               Err(verify::VerifyError::InadequateCertificateUsage));
+    assert_eq!(verify::verify_certificate_chain(TEST_SIGNER_CERT, &[TEST_INTERMEDIATE_CERT],
+                                                &[ADDON_ROOT], SystemTime::now(),
+                                                verify::KeyPurpose::ServerAuth),
+               Err(verify::VerifyError::InadequateCertificateUsage));
+}
+
+// The key of NIST_P256_TEST_SPKI as a COSE_Key:
+// { 1 (kty): 2 (EC2), 2 (kid): "rfc6979", 3 (alg): -7 (ES256), 4 (key_ops): [2 (verify)],
+//   -1 (crv): 1 (P-256), -2 (x): h'60FE...', -3 (y): h'7903...' }
+static NIST_P256_TEST_COSE_KEY: &[u8] = &[
+    0xa7, 0x22, 0x58, 0x20, 0x79, 0x03, 0xfe, 0x10, 0x08, 0xb8, 0xbc, 0x99, 0xa4, 0x1a, 0xe9, 0xe9,
+    0x56, 0x28, 0xbc, 0x64, 0xf2, 0xf1, 0xb2, 0x0c, 0x2d, 0x7e, 0x9f, 0x51, 0x77, 0xa3, 0xc2, 0x94,
+    0xd4, 0x46, 0x22, 0x99, 0x21, 0x58, 0x20, 0x60, 0xfe, 0xd4, 0xba, 0x25, 0x5a, 0x9d, 0x31, 0xc9,
+    0x61, 0xeb, 0x74, 0xc6, 0x35, 0x6d, 0x68, 0xc0, 0x49, 0xb8, 0x92, 0x3b, 0x61, 0xfa, 0x6c, 0xe6,
+    0x69, 0x62, 0x2e, 0x60, 0xf2, 0x9f, 0xb6, 0x20, 0x01, 0x01, 0x02, 0x02, 0x47, 0x72, 0x66, 0x63,
+    0x36, 0x39, 0x37, 0x39, 0x03, 0x26, 0x04, 0x81, 0x02];
+
+// The same key, with the y coordinate compressed to its sign bit and no other parameters.
+static NIST_P256_TEST_COSE_KEY_COMPRESSED: &[u8] = &[
+    0xa4, 0x22, 0xf5, 0x21, 0x58, 0x20, 0x60, 0xfe, 0xd4, 0xba, 0x25, 0x5a, 0x9d, 0x31, 0xc9, 0x61,
+    0xeb, 0x74, 0xc6, 0x35, 0x6d, 0x68, 0xc0, 0x49, 0xb8, 0x92, 0x3b, 0x61, 0xfa, 0x6c, 0xe6, 0x69,
+    0x62, 0x2e, 0x60, 0xf2, 0x9f, 0xb6, 0x20, 0x01, 0x01, 0x02];
+
+#[test]
+fn test_cose_key_to_spki() {
+    setup();
+    let key = verify::CoseKey::decode(NIST_P256_TEST_COSE_KEY).unwrap();
+    assert_eq!(key.kid, Some(b"rfc6979".to_vec()));
+    assert_eq!(key.alg, Some(-7));
+    assert_eq!(key.key_ops, vec![verify::KeyOperation::Verify]);
+    assert_eq!(key.to_spki(), NIST_P256_TEST_SPKI);
+    let compressed = verify::CoseKey::decode(NIST_P256_TEST_COSE_KEY_COMPRESSED).unwrap();
+    assert_eq!(compressed.parameters, key.parameters);
+    let rsa_key = verify::CoseKey {
+        kid: None,
+        alg: Some(-37),
+        key_ops: Vec::new(),
+        parameters: verify::KeyParameters::RSA {
+            n: FIPS_RSA_3072_SPKI[33..417].to_vec(),
+            e: FIPS_RSA_3072_SPKI[419..].to_vec(),
+        },
+    };
+    assert_eq!(rsa_key.to_spki(), FIPS_RSA_3072_SPKI);
+}
+
+#[test]
+fn test_verify_signature_with_cose_key() {
+    setup();
+    // RFC 6979, A.2.5, with SHA-256, message = "sample".
+    let signature =
+        vec![0xef, 0xd4, 0x8b, 0x2a, 0xac, 0xb6, 0xa8, 0xfd, 0x11, 0x40, 0xdd, 0x9c, 0xd4, 0x5e,
+             0x81, 0xd6, 0x9d, 0x2c, 0x87, 0x7b, 0x56, 0xaa, 0xf9, 0x91, 0xc3, 0x4d, 0x0e, 0xa8,
+             0x4e, 0xaf, 0x37, 0x16,
+             0xf7, 0xcb, 0x1c, 0x94, 0x2d, 0x65, 0x7c, 0x41, 0xd4, 0x36, 0xc7, 0xa1, 0xb6, 0xe2,
+             0x9f, 0x65, 0xf3, 0xe9, 0x00, 0xdb, 0xb9, 0xaf, 0xf4, 0x06, 0x4d, 0xc4, 0xab, 0x2f,
+             0x84, 0x3a, 0xcd, 0xa8];
+    let payload = b"sample";
+    let mut key = verify::CoseKey::decode(NIST_P256_TEST_COSE_KEY).unwrap();
+    assert!(verify::verify_signature_with_cose_key(verify::SignatureAlgorithm::ES256, &key,
+                                                   payload, &signature).is_ok());
+    assert_eq!(verify::verify_signature_with_cose_key(verify::SignatureAlgorithm::PS256, &key,
+                                                      payload, &signature),
+               Err(verify::VerifyError::AlgorithmMismatch));
+    key.alg = None;
+    assert_eq!(verify::verify_signature_with_cose_key(verify::SignatureAlgorithm::PS256, &key,
+                                                      payload, &signature),
+               Err(verify::VerifyError::KeyTypeMismatch));
+    key.key_ops = vec![verify::KeyOperation::Sign];
+    assert_eq!(verify::verify_signature_with_cose_key(verify::SignatureAlgorithm::ES256, &key,
+                                                      payload, &signature),
+               Err(verify::VerifyError::InvalidKey));
+}
+
+#[test]
+fn test_cose_key_from_spki() {
+    setup();
+    let mut key = verify::CoseKey::from_spki(NIST_P256_TEST_SPKI).unwrap();
+    assert_eq!(key.encode(verify::PointFormat::Compressed), NIST_P256_TEST_COSE_KEY_COMPRESSED);
+    key.kid = Some(b"rfc6979".to_vec());
+    key.alg = Some(-7);
+    key.key_ops = vec![verify::KeyOperation::Verify];
+    assert_eq!(key.encode(verify::PointFormat::Uncompressed), NIST_P256_TEST_COSE_KEY);
+    let rsa_key = verify::CoseKey::from_spki(FIPS_RSA_3072_SPKI).unwrap();
+    assert_eq!(rsa_key.to_spki(), FIPS_RSA_3072_SPKI);
+    let decoded = verify::CoseKey::decode(&rsa_key.encode(verify::PointFormat::Compressed));
+    assert_eq!(decoded, Ok(rsa_key));
+    let mut not_on_curve = NIST_P256_TEST_SPKI.to_vec();
+    let last = not_on_curve.len() - 1;
+    not_on_curve[last] ^= 1;
+    let not_on_curve_key = verify::CoseKey::from_spki(&not_on_curve).unwrap();
+    assert_eq!(verify::PublicKey::from_cose_key(&not_on_curve_key).err(),
+               Some(verify::VerifyError::InvalidKey));
+    assert_eq!(verify::PublicKey::from_spki(&not_on_curve).err(),
+               Some(verify::VerifyError::InvalidKey));
+    assert_eq!(verify::CoseKey::from_spki(&NIST_P256_TEST_SPKI[..last]),
+               Err(verify::VerifyError::DecodingSPKIFailed));
+}
+
+#[test]
+fn test_cose_key_from_certificate() {
+    setup();
+    let key = verify::CoseKey::from_certificate(TEST_SIGNER_CERT).unwrap();
+    assert_eq!(key.to_spki(), SIGNER1_SPKI);
+    assert_eq!(verify::CoseKey::from_certificate(&TEST_SIGNER_CERT[1..]),
+               Err(verify::VerifyError::DecodingCertificateFailed));
+}
+
+#[test]
+fn test_cose_key_set_jwk_set() {
+    setup();
+    let ec_key = verify::CoseKey::decode(NIST_P256_TEST_COSE_KEY).unwrap();
+    let mut rsa_key = verify::CoseKey::from_spki(FIPS_RSA_3072_SPKI).unwrap();
+    rsa_key.kid = Some(b"fips186-3".to_vec());
+    let key_set = verify::CoseKeySet { keys: vec![ec_key, rsa_key] };
+    let jwk_set = key_set.to_jwk_set().unwrap();
+    assert!(jwk_set.starts_with("{\"keys\":[{\"alg\":\"ES256\",\"crv\":\"P-256\",\
+                                 \"key_ops\":[\"verify\"],\"kid\":\"rfc6979\",\"kty\":\"EC\",\
+                                 \"x\":\"YP7UuiVanTHJYet0xjVtaMBJuJI7Yfps5mliLmDyn7Y\",\
+                                 \"y\":\"eQP-EAi4vJmkGunpVii8ZPLxsgwtfp9Rd6PClNRGIpk\"},\
+                                 {\"e\":\"FBWn\","));
+    assert_eq!(verify::CoseKeySet::from_jwk_set(&jwk_set), Ok(key_set.clone()));
+    let cose_key_set = verify::CoseKeySet::decode(&key_set.encode(verify::PointFormat::Compressed));
+    assert_eq!(cose_key_set.as_ref(), Ok(&key_set));
+    let found = key_set.find_by_kid(b"fips186-3").unwrap();
+    assert_eq!(found.to_spki(), FIPS_RSA_3072_SPKI);
+    assert_eq!(key_set.find_by_algorithm(-37), vec![found]);
+
+    // Symmetric keys are skipped rather than rejected.
+    let with_symmetric_key =
+        jwk_set.replacen("{\"keys\":[", "{\"keys\":[{\"kty\":\"oct\",\"k\":\"AA\"},", 1);
+    assert_eq!(verify::CoseKeySet::from_jwk_set(&with_symmetric_key), Ok(key_set));
+    assert_eq!(verify::CoseKeySet::from_jwk_set("{\"keys\":{}}"),
+               Err(verify::VerifyError::DecodingJWKFailed));
+}
+
+const MAC_TEST_KEY: &[u8] = &[
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,
+    0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1e, 0x1f
+];
+
+// A COSE_Mac0 over "This is the content." with HMAC 256/64, MAC_TEST_KEY, and kid "our-secret".
+const COSE_MAC0_HMAC_256_64: &[u8] = &[
+    0xd1, 0x84, 0x43, 0xa1, 0x01, 0x04, 0xa1, 0x04, 0x4a, 0x6f, 0x75, 0x72, 0x2d, 0x73, 0x65, 0x63,
+    0x72, 0x65, 0x74, 0x54, 0x54, 0x68, 0x69, 0x73, 0x20, 0x69, 0x73, 0x20, 0x74, 0x68, 0x65, 0x20,
+    0x63, 0x6f, 0x6e, 0x74, 0x65, 0x6e, 0x74, 0x2e, 0x48, 0x80, 0xca, 0xbe, 0x7f, 0xc1, 0x81, 0x55,
+    0x29
+];
+
+#[test]
+fn test_cose_mac0() {
+    setup();
+    assert_eq!(verify::verify_cose_mac0(COSE_MAC0_HMAC_256_64, None, &[], MAC_TEST_KEY),
+               Ok(b"This is the content.".to_vec()));
+    let created = verify::create_cose_mac0(verify::MacAlgorithm::HMAC256_64, MAC_TEST_KEY,
+                                           Some(b"our-secret"), b"This is the content.", &[]);
+    assert_eq!(created.as_ref().map(|created| &created[..]), Ok(COSE_MAC0_HMAC_256_64));
+    assert_eq!(verify::verify_cose_mac0(COSE_MAC0_HMAC_256_64, None, b"aad", MAC_TEST_KEY),
+               Err(verify::VerifyError::MacVerificationFailed));
+    assert_eq!(verify::verify_cose_mac0(COSE_MAC0_HMAC_256_64, None, &[], &MAC_TEST_KEY[1..]),
+               Err(verify::VerifyError::MacVerificationFailed));
+    let mut tampered = COSE_MAC0_HMAC_256_64.to_vec();
+    tampered[25] ^= 1;
+    assert_eq!(verify::verify_cose_mac0(&tampered, None, &[], MAC_TEST_KEY),
+               Err(verify::VerifyError::MacVerificationFailed));
+
+    for id in 4..8 {
+        let algorithm = verify::MacAlgorithm::from_cose_id(id).unwrap();
+        let cose_mac0 = verify::create_cose_mac0(algorithm, MAC_TEST_KEY, None, b"payload", b"aad")
+            .unwrap();
+        assert_eq!(verify::verify_cose_mac0(&cose_mac0, None, b"aad", MAC_TEST_KEY),
+                   Ok(b"payload".to_vec()));
+    }
+}
+
+#[test]
+fn test_cose_mac() {
+    setup();
+    let other_key = [0x55; 48];
+    let keys = [verify::SharedKey { kid: b"our-secret", key: MAC_TEST_KEY },
+                verify::SharedKey { kid: b"other", key: &other_key }];
+    let cose_mac = verify::create_cose_mac(verify::MacAlgorithm::HMAC384_384, &other_key,
+                                           Some(b"other"), b"payload", &[]).unwrap();
+    assert_eq!(verify::verify_cose_mac(&cose_mac, None, &[], &keys), Ok(b"payload".to_vec()));
+    assert_eq!(verify::verify_cose_mac(&cose_mac, None, &[], &keys[..1]),
+               Err(verify::VerifyError::UnknownKeyId));
+    let wrong_key = [verify::SharedKey { kid: b"other", key: MAC_TEST_KEY }];
+    assert_eq!(verify::verify_cose_mac(&cose_mac, None, &[], &wrong_key),
+               Err(verify::VerifyError::MacVerificationFailed));
+    // A COSE_Mac0 is not a COSE_Mac.
+    assert_eq!(verify::verify_cose_mac(COSE_MAC0_HMAC_256_64, None, &[], &keys),
+               Err(verify::VerifyError::DecodingCOSEFailed));
+}
+
+const AES_128_TEST_KEY: &[u8] = &[
+    0x84, 0x9b, 0x57, 0x21, 0x9d, 0xae, 0x48, 0xde, 0x64, 0x6d, 0x07, 0xdb, 0xb5, 0x33, 0x56, 0x6e
+];
+
+// A COSE_Encrypt0 of "This is the content." with A128GCM, AES_128_TEST_KEY, and kid "our-secret".
+const COSE_ENCRYPT0_A128GCM: &[u8] = &[
+    0xd0, 0x83, 0x43, 0xa1, 0x01, 0x01, 0xa2, 0x04, 0x4a, 0x6f, 0x75, 0x72, 0x2d, 0x73, 0x65, 0x63,
+    0x72, 0x65, 0x74, 0x05, 0x4c, 0x02, 0xd1, 0xf7, 0xe6, 0xf2, 0x6c, 0x43, 0xd4, 0x86, 0x8d, 0x87,
+    0xce, 0x58, 0x24, 0x60, 0x97, 0x3a, 0x94, 0xbb, 0x28, 0x98, 0x00, 0x9e, 0xe5, 0x2e, 0xcf, 0xd9,
+    0xab, 0x1d, 0xd2, 0x58, 0x67, 0x37, 0x4b, 0x16, 0x2e, 0x2c, 0x03, 0x56, 0x8b, 0x41, 0xf5, 0x7c,
+    0x3c, 0xc1, 0x6f, 0x91, 0x66, 0x25, 0x0a
+];
+
+#[test]
+fn test_cose_encrypt0() {
+    setup();
+    assert_eq!(verify::decrypt_cose_encrypt0(COSE_ENCRYPT0_A128GCM, None, &[], AES_128_TEST_KEY),
+               Ok(b"This is the content.".to_vec()));
+    assert_eq!(verify::decrypt_cose_encrypt0(COSE_ENCRYPT0_A128GCM, None, b"aad", AES_128_TEST_KEY),
+               Err(verify::VerifyError::DecryptionFailed));
+    assert_eq!(verify::decrypt_cose_encrypt0(COSE_ENCRYPT0_A128GCM, None, &[], &[0; 16]),
+               Err(verify::VerifyError::DecryptionFailed));
+    assert_eq!(verify::decrypt_cose_encrypt0(COSE_ENCRYPT0_A128GCM, None, &[], &[0; 32]),
+               Err(verify::VerifyError::InvalidKey));
+    let mut tampered = COSE_ENCRYPT0_A128GCM.to_vec();
+    tampered[40] ^= 1;
+    assert_eq!(verify::decrypt_cose_encrypt0(&tampered, None, &[], AES_128_TEST_KEY),
+               Err(verify::VerifyError::DecryptionFailed));
+    // The IV must be 12 bytes long.
+    let mut short_iv = COSE_ENCRYPT0_A128GCM.to_vec();
+    short_iv[20] = 0x4b;
+    short_iv.remove(21);
+    assert_eq!(verify::decrypt_cose_encrypt0(&short_iv, None, &[], AES_128_TEST_KEY),
+               Err(verify::VerifyError::DecodingCOSEFailed));
+
+    for id in 1..4 {
+        let algorithm = verify::EncryptionAlgorithm::from_cose_id(id).unwrap();
+        let key = vec![0x42; algorithm.key_length()];
+        let cose_encrypt0 =
+            verify::encrypt_cose_encrypt0(algorithm, &key, None, b"plaintext", b"aad").unwrap();
+        assert_eq!(verify::decrypt_cose_encrypt0(&cose_encrypt0, None, b"aad", &key),
+                   Ok(b"plaintext".to_vec()));
+    }
+}
+
+// A COSE_Encrypt0 of "This is the content." with AES-CCM-16-64-128, AES_128_TEST_KEY, and kid
+// "our-secret".
+const COSE_ENCRYPT0_AES_CCM_16_64_128: &[u8] = &[
+    0xd0, 0x83, 0x43, 0xa1, 0x01, 0x0a, 0xa2, 0x04, 0x4a, 0x6f, 0x75, 0x72, 0x2d, 0x73, 0x65, 0x63,
+    0x72, 0x65, 0x74, 0x05, 0x4d, 0x89, 0xf5, 0x2f, 0x65, 0xa1, 0xc5, 0x80, 0x93, 0x3b, 0x52, 0x61,
+    0xa7, 0x2f, 0x58, 0x1c, 0x68, 0x99, 0xda, 0x0a, 0x13, 0x2b, 0xd2, 0xd2, 0xb9, 0xb1, 0x09, 0x15,
+    0x74, 0x3e, 0xe1, 0xf7, 0xb9, 0x2a, 0x46, 0x80, 0xe7, 0xc5, 0x1b, 0xdb, 0xc1, 0xb3, 0x20, 0xea
+];
+
+// A COSE_Encrypt0 of "This is the content." with ChaCha20/Poly1305, the key consisting of the
+// bytes 0 to 31, and kid "sec-256".
+const COSE_ENCRYPT0_CHACHA20_POLY1305: &[u8] = &[
+    0xd0, 0x83, 0x44, 0xa1, 0x01, 0x18, 0x18, 0xa2, 0x04, 0x47, 0x73, 0x65, 0x63, 0x2d, 0x32, 0x35,
+    0x36, 0x05, 0x4c, 0x26, 0x68, 0x23, 0x06, 0xd4, 0xfb, 0x28, 0xca, 0x01, 0xb4, 0x3b, 0x80, 0x58,
+    0x24, 0xe4, 0xba, 0x39, 0xac, 0x7b, 0xb6, 0x3a, 0xb6, 0xa7, 0x54, 0x6b, 0x2f, 0xa0, 0xbd, 0x09,
+    0x14, 0x16, 0x7f, 0xdb, 0xdf, 0xec, 0x35, 0x18, 0x1b, 0x55, 0xfa, 0x12, 0xb7, 0x2b, 0x65, 0x6d,
+    0x7f, 0x18, 0x79, 0x14, 0xa4
+];
+
+#[test]
+fn test_cose_encrypt0_ccm_and_chacha20_poly1305() {
+    setup();
+    let content = Ok(b"This is the content.".to_vec());
+    assert_eq!(verify::decrypt_cose_encrypt0(COSE_ENCRYPT0_AES_CCM_16_64_128, None, &[],
+                                             AES_128_TEST_KEY),
+               content);
+    let chacha_key: Vec<u8> = (0..32).collect();
+    assert_eq!(verify::decrypt_cose_encrypt0(COSE_ENCRYPT0_CHACHA20_POLY1305, None, &[],
+                                             &chacha_key),
+               content);
+    // The tag of AES-CCM-16-64-128 is 8 bytes long.
+    let mut tampered = COSE_ENCRYPT0_AES_CCM_16_64_128.to_vec();
+    let last = tampered.len() - 1;
+    tampered[last] ^= 1;
+    assert_eq!(verify::decrypt_cose_encrypt0(&tampered, None, &[], AES_128_TEST_KEY),
+               Err(verify::VerifyError::DecryptionFailed));
+    assert_eq!(verify::decrypt_cose_encrypt0(COSE_ENCRYPT0_CHACHA20_POLY1305, None, b"aad",
+                                             &chacha_key),
+               Err(verify::VerifyError::DecryptionFailed));
+
+    for &id in &[10, 11, 12, 13, 24, 30, 31, 32, 33] {
+        let algorithm = verify::EncryptionAlgorithm::from_cose_id(id).unwrap();
+        let key = vec![0x42; algorithm.key_length()];
+        let cose_encrypt0 =
+            verify::encrypt_cose_encrypt0(algorithm, &key, None, b"plaintext", b"aad").unwrap();
+        assert_eq!(verify::decrypt_cose_encrypt0(&cose_encrypt0, None, b"aad", &key),
+                   Ok(b"plaintext".to_vec()));
+    }
+    // A 2-byte length field limits the plaintext to 65535 bytes.
+    let key = [0x42; 16];
+    assert_eq!(verify::encrypt_cose_encrypt0(verify::EncryptionAlgorithm::AesCcm16_64_128, &key,
+                                             None, &vec![0; 0x10000], &[]),
+               Err(verify::VerifyError::InputTooLarge));
+}
+
+// A P-256 recipient key with kid "meriadoc". Its private key d is 32 bytes of 0x44.
+const ECDH_RECIPIENT_COSE_KEY: &[u8] = &[
+    0xa5, 0x22, 0x58, 0x20, 0x0e, 0x14, 0xbe, 0xd8, 0xd4, 0x68, 0x2a, 0x34, 0xd8, 0x35, 0x38, 0xbd,
+    0xff, 0x5b, 0x96, 0xe8, 0x9a, 0x66, 0x66, 0xec, 0x0d, 0xb5, 0x74, 0x5d, 0x02, 0xfa, 0x12, 0x10,
+    0x07, 0x2d, 0xf7, 0x5a, 0x21, 0x58, 0x20, 0x5b, 0x36, 0x89, 0x0d, 0xac, 0xbd, 0x7c, 0x9a, 0x96,
+    0xbb, 0x74, 0xa1, 0xee, 0x28, 0xb3, 0xd2, 0xd7, 0x5b, 0x72, 0xe0, 0x9a, 0x20, 0xef, 0x25, 0xcf,
+    0x8e, 0x6f, 0xd8, 0xa9, 0xf0, 0x35, 0x0d, 0x20, 0x01, 0x01, 0x02, 0x02, 0x48, 0x6d, 0x65, 0x72,
+    0x69, 0x61, 0x64, 0x6f, 0x63
+];
+
+const ECDH_RECIPIENT_PRIVATE_KEY: &[u8] = &[0x44; 32];
+
+// A COSE_Encrypt of "This is the content." with A128GCM and two recipients: one using A128KW with
+// the key "kw-key" (16 bytes of 0xa5), and one using ECDH-ES + A128KW with ECDH_RECIPIENT_COSE_KEY.
+const COSE_ENCRYPT_TWO_RECIPIENTS: &[u8] = &[
+    0xd8, 0x60, 0x84, 0x43, 0xa1, 0x01, 0x01, 0xa1, 0x05, 0x4c, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05,
+    0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x58, 0x24, 0xa5, 0x1d, 0x7d, 0xa4, 0x60, 0x74, 0x87, 0xd2,
+    0xc6, 0x1c, 0x75, 0x77, 0xac, 0x63, 0x8f, 0x78, 0xde, 0x65, 0x2d, 0x91, 0x3a, 0x12, 0x55, 0xbc,
+    0xa3, 0x46, 0xa3, 0x54, 0x0a, 0x8d, 0x1f, 0x4d, 0x27, 0x18, 0x3c, 0x9b, 0x82, 0x83, 0x40, 0xa2,
+    0x01, 0x22, 0x04, 0x46, 0x6b, 0x77, 0x2d, 0x6b, 0x65, 0x79, 0x58, 0x18, 0x51, 0xc1, 0x7a, 0xcb,
+    0x43, 0x75, 0xd1, 0x27, 0x75, 0x3f, 0x1d, 0x71, 0xf3, 0x35, 0xf2, 0x53, 0x58, 0xf2, 0x3c, 0x29,
+    0xbe, 0xbe, 0xcc, 0xa4, 0x83, 0x44, 0xa1, 0x01, 0x38, 0x1c, 0xa2, 0x20, 0xa4, 0x22, 0x58, 0x20,
+    0xc5, 0xab, 0x37, 0x70, 0xba, 0x57, 0x3b, 0xdf, 0xf5, 0x41, 0x40, 0x65, 0x64, 0x0f, 0xfb, 0x5b,
+    0x34, 0x6d, 0xfa, 0x84, 0xde, 0xc4, 0xdb, 0x4d, 0x68, 0xe5, 0xf5, 0x9c, 0xc4, 0x71, 0xc2, 0xec,
+    0x21, 0x58, 0x20, 0x57, 0xe9, 0x77, 0xf6, 0xdb, 0x7e, 0x33, 0xc3, 0xfe, 0x7a, 0xcf, 0x28, 0x42,
+    0xed, 0x98, 0x70, 0x09, 0xca, 0xf5, 0x6d, 0x45, 0x86, 0x82, 0xfc, 0xa4, 0x47, 0xb7, 0xd3, 0xd7,
+    0x62, 0xab, 0x34, 0x20, 0x01, 0x01, 0x02, 0x04, 0x48, 0x6d, 0x65, 0x72, 0x69, 0x61, 0x64, 0x6f,
+    0x63, 0x58, 0x18, 0x6c, 0x7c, 0x0b, 0x81, 0x5f, 0x6d, 0xff, 0xfb, 0xe3, 0x5e, 0x0c, 0x4c, 0x9f,
+    0xb0, 0xde, 0x11, 0x51, 0x08, 0xef, 0xee, 0x8a, 0x1e, 0xd4, 0x53
+];
+
+#[test]
+fn test_cose_encrypt() {
+    setup();
+    let recipient_key = verify::CoseKey::decode(ECDH_RECIPIENT_COSE_KEY).unwrap();
+    let kek = [0xa5; 16];
+    let shared = || verify::RecipientKey::Shared(verify::SharedKey { kid: b"kw-key", key: &kek });
+    let ec2 = || verify::RecipientKey::EC2 {
+        key: &recipient_key,
+        d: Some(ECDH_RECIPIENT_PRIVATE_KEY),
+    };
+    let content = Ok(b"This is the content.".to_vec());
+    assert_eq!(verify::decrypt_cose_encrypt(COSE_ENCRYPT_TWO_RECIPIENTS, None, &[], &[shared()]),
+               content);
+    assert_eq!(verify::decrypt_cose_encrypt(COSE_ENCRYPT_TWO_RECIPIENTS, None, &[], &[ec2()]),
+               content);
+    let wrong_kek = [0x5a; 16];
+    let wrong = [verify::RecipientKey::Shared(verify::SharedKey {
+        kid: b"kw-key",
+        key: &wrong_kek,
+    })];
+    assert_eq!(verify::decrypt_cose_encrypt(COSE_ENCRYPT_TWO_RECIPIENTS, None, &[], &wrong),
+               Err(verify::VerifyError::DecryptionFailed));
+    let unknown = [verify::RecipientKey::Shared(verify::SharedKey { kid: b"other", key: &kek })];
+    assert_eq!(verify::decrypt_cose_encrypt(COSE_ENCRYPT_TWO_RECIPIENTS, None, &[], &unknown),
+               Err(verify::VerifyError::UnknownKeyId));
+
+    // Each kind of recipient, alone and together.
+    let direct_key = [0x42; 32];
+    let direct = verify::Recipient {
+        algorithm: verify::KeyDistributionAlgorithm::Direct,
+        key: verify::RecipientKey::Shared(verify::SharedKey { kid: b"direct", key: &direct_key }),
+        sender_key: None,
+    };
+    let key_wrap = verify::Recipient {
+        algorithm: verify::KeyDistributionAlgorithm::A128KW,
+        key: shared(),
+        sender_key: None,
+    };
+    let key_agreement = |algorithm| verify::Recipient {
+        algorithm,
+        key: verify::RecipientKey::EC2 { key: &recipient_key, d: None },
+        sender_key: None,
+    };
+    let direct_shared_key = verify::SharedKey { kid: b"direct", key: &direct_key };
+    let keys = [verify::RecipientKey::Shared(direct_shared_key), shared(), ec2()];
+    let recipient_lists = vec![
+        vec![direct],
+        vec![key_agreement(verify::KeyDistributionAlgorithm::EcdhEsHkdf256)],
+        vec![key_wrap, key_agreement(verify::KeyDistributionAlgorithm::EcdhEsA256KW)],
+    ];
+    for recipients in &recipient_lists {
+        let cose_encrypt = verify::encrypt_cose_encrypt(verify::EncryptionAlgorithm::A256GCM,
+                                                        recipients, b"plaintext", b"aad").unwrap();
+        assert_eq!(verify::decrypt_cose_encrypt(&cose_encrypt, None, b"aad", &keys),
+                   Ok(b"plaintext".to_vec()));
+    }
+}
+
+// The public key of an ECDH-SS sender, with the kid "peregrin" and the private key 0x55 repeated.
+const ECDH_SENDER_COSE_KEY: &[u8] = &[
+    0xa5, 0x22, 0x58, 0x20, 0xc5, 0xab, 0x37, 0x70, 0xba, 0x57, 0x3b, 0xdf, 0xf5, 0x41, 0x40, 0x65,
+    0x64, 0x0f, 0xfb, 0x5b, 0x34, 0x6d, 0xfa, 0x84, 0xde, 0xc4, 0xdb, 0x4d, 0x68, 0xe5, 0xf5, 0x9c,
+    0xc4, 0x71, 0xc2, 0xec, 0x21, 0x58, 0x20, 0x57, 0xe9, 0x77, 0xf6, 0xdb, 0x7e, 0x33, 0xc3, 0xfe,
+    0x7a, 0xcf, 0x28, 0x42, 0xed, 0x98, 0x70, 0x09, 0xca, 0xf5, 0x6d, 0x45, 0x86, 0x82, 0xfc, 0xa4,
+    0x47, 0xb7, 0xd3, 0xd7, 0x62, 0xab, 0x34, 0x20, 0x01, 0x01, 0x02, 0x02, 0x48, 0x70, 0x65, 0x72,
+    0x65, 0x67, 0x72, 0x69, 0x6e
+];
+
+const ECDH_SENDER_PRIVATE_KEY: &[u8] = &[0x55; 32];
+
+// A COSE_Encrypt of "This is the content." with A128GCM and a single ECDH-SS + HKDF-256 recipient
+// with ECDH_RECIPIENT_COSE_KEY, from ECDH_SENDER_COSE_KEY identified by its kid, with a salt.
+const COSE_ENCRYPT_ECDH_SS: &[u8] = &[
+    0xd8, 0x60, 0x84, 0x43, 0xa1, 0x01, 0x01, 0xa1, 0x05, 0x4c, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05,
+    0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x58, 0x24, 0x14, 0x1f, 0x6e, 0x79, 0xd2, 0xf9, 0x20, 0x93,
+    0xb9, 0xac, 0xec, 0xb9, 0x52, 0x48, 0x32, 0xa7, 0x2e, 0xfb, 0x76, 0x66, 0x03, 0xfd, 0xc4, 0xd5,
+    0xde, 0x57, 0x00, 0x51, 0x8d, 0x11, 0x97, 0x60, 0x50, 0x05, 0x84, 0xd4, 0x81, 0x83, 0x44, 0xa1,
+    0x01, 0x38, 0x1a, 0xa3, 0x33, 0x58, 0x20, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+    0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18,
+    0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1e, 0x1f, 0x22, 0x48, 0x70, 0x65, 0x72, 0x65, 0x67, 0x72, 0x69,
+    0x6e, 0x04, 0x48, 0x6d, 0x65, 0x72, 0x69, 0x61, 0x64, 0x6f, 0x63, 0x40
+];
+
+#[test]
+fn test_cose_encrypt_ecdh_ss() {
+    setup();
+    let recipient_key = verify::CoseKey::decode(ECDH_RECIPIENT_COSE_KEY).unwrap();
+    let sender_key = verify::CoseKey::decode(ECDH_SENDER_COSE_KEY).unwrap();
+    let ec2 = || verify::RecipientKey::EC2 {
+        key: &recipient_key,
+        d: Some(ECDH_RECIPIENT_PRIVATE_KEY),
+    };
+    let sender = || verify::RecipientKey::EC2 { key: &sender_key, d: None };
+    assert_eq!(verify::decrypt_cose_encrypt(COSE_ENCRYPT_ECDH_SS, None, &[], &[ec2(), sender()]),
+               Ok(b"This is the content.".to_vec()));
+    // The sender's static key is needed to find the shared secret.
+    assert_eq!(verify::decrypt_cose_encrypt(COSE_ENCRYPT_ECDH_SS, None, &[], &[ec2()]),
+               Err(verify::VerifyError::UnknownKeyId));
+
+    let mut anonymous_sender_key = sender_key.clone();
+    anonymous_sender_key.kid = None;
+    let recipient = |algorithm, sender: &'static [u8], key| verify::Recipient {
+        algorithm,
+        key: verify::RecipientKey::EC2 { key: &recipient_key, d: None },
+        sender_key: Some(verify::StaticKey { key, d: sender }),
+    };
+    let recipient_lists = vec![
+        vec![recipient(verify::KeyDistributionAlgorithm::EcdhSsHkdf256, ECDH_SENDER_PRIVATE_KEY,
+                       &sender_key)],
+        vec![recipient(verify::KeyDistributionAlgorithm::EcdhSsHkdf512, ECDH_SENDER_PRIVATE_KEY,
+                       &anonymous_sender_key)],
+        vec![recipient(verify::KeyDistributionAlgorithm::EcdhSsA192KW, ECDH_SENDER_PRIVATE_KEY,
+                       &sender_key)],
+        vec![verify::Recipient {
+            algorithm: verify::KeyDistributionAlgorithm::EcdhEsHkdf512,
+            key: verify::RecipientKey::EC2 { key: &recipient_key, d: None },
+            sender_key: None,
+        }],
+    ];
+    for recipients in &recipient_lists {
+        let cose_encrypt = verify::encrypt_cose_encrypt(verify::EncryptionAlgorithm::A128GCM,
+                                                        recipients, b"plaintext", b"aad").unwrap();
+        assert_eq!(verify::decrypt_cose_encrypt(&cose_encrypt, None, b"aad", &[ec2(), sender()]),
+                   Ok(b"plaintext".to_vec()));
+    }
+    // ECDH-SS needs the sender's static key.
+    let recipients = [verify::Recipient {
+        algorithm: verify::KeyDistributionAlgorithm::EcdhSsHkdf256,
+        key: verify::RecipientKey::EC2 { key: &recipient_key, d: None },
+        sender_key: None,
+    }];
+    assert_eq!(verify::encrypt_cose_encrypt(verify::EncryptionAlgorithm::A128GCM, &recipients,
+                                            b"plaintext", &[]),
+               Err(verify::VerifyError::InvalidKey));
+}
+
+#[test]
+fn test_hkdf() {
+    setup();
+    // RFC 5869, Appendix A.1.
+    let salt: Vec<u8> = (0x00..0x0d).collect();
+    let info: Vec<u8> = (0xf0..0xfa).collect();
+    assert_eq!(verify::hkdf(verify::HkdfHash::SHA256, &salt, &[0x0b; 22], &info, 42),
+               Ok(vec![0x3c, 0xb2, 0x5f, 0x25, 0xfa, 0xac, 0xd5, 0x7a, 0x90, 0x43, 0x4f, 0x64, 0xd0,
+                       0x36, 0x2f, 0x2a, 0x2d, 0x2d, 0x0a, 0x90, 0xcf, 0x1a, 0x5a, 0x4c, 0x5d, 0xb0,
+                       0x2d, 0x56, 0xec, 0xc4, 0xc5, 0xbf, 0x34, 0x00, 0x72, 0x08, 0xd5, 0xb8, 0x87,
+                       0x18, 0x58, 0x65]));
+
+    // The longest output takes 255 blocks, the most the one-byte block counter can number.
+    let okm = verify::hkdf(verify::HkdfHash::SHA256, &[], &[1], &[], 255 * 32).unwrap();
+    assert_eq!(okm.len(), 255 * 32);
+    assert_eq!(&okm[255 * 32 - 16..],
+               &[0x94, 0x1a, 0x62, 0x7b, 0xb8, 0x38, 0x9d, 0xb8, 0x77, 0xe8, 0x95, 0x02, 0x2c, 0xd2,
+                 0xbb, 0x92]);
+    assert_eq!(verify::hkdf(verify::HkdfHash::SHA256, &[], &[1], &[], 255 * 32 + 1),
+               Err(verify::VerifyError::InputTooLarge));
+    let okm = verify::hkdf(verify::HkdfHash::SHA512, &[], &[1], &[], 255 * 64).unwrap();
+    assert_eq!(okm.len(), 255 * 64);
+}
+
+// A COSE_Sign1 of "This is the content." signed by signer1 with a full countersignature (label 11)
+// by signer2 and an abbreviated countersignature (label 12) by signer3, all with ES256.
+static COSE_SIGN1_COUNTERSIGNED: &[u8] = &[
+    0xd2, 0x84, 0x43, 0xa1, 0x01, 0x26, 0xa3, 0x04, 0x47, 0x73, 0x69, 0x67, 0x6e, 0x65, 0x72, 0x31,
+    0x0b, 0x83, 0x43, 0xa1, 0x01, 0x26, 0xa1, 0x04, 0x47, 0x73, 0x69, 0x67, 0x6e, 0x65, 0x72, 0x32,
+    0x58, 0x40, 0xa9, 0x38, 0xf2, 0xa1, 0x31, 0x0e, 0xfd, 0x5e, 0x8e, 0x3d, 0xa1, 0xbd, 0xee, 0xe6,
+    0x38, 0xcb, 0xfb, 0x8d, 0xaa, 0xca, 0x06, 0xac, 0xb1, 0xb7, 0xc8, 0x86, 0x0d, 0x54, 0xf1, 0xca,
+    0xb1, 0x9c, 0xbc, 0xb9, 0xca, 0x3f, 0xfd, 0x89, 0xfa, 0xdb, 0x4b, 0xe3, 0x65, 0x80, 0x01, 0x55,
+    0xfa, 0xc9, 0xee, 0x18, 0xaa, 0xcb, 0x07, 0xb5, 0xcf, 0x8a, 0x97, 0xe9, 0x44, 0x0c, 0x2b, 0x44,
+    0x93, 0x2e, 0x0c, 0x58, 0x40, 0xe3, 0x02, 0x58, 0x35, 0xb9, 0x7d, 0x93, 0x7d, 0xea, 0xba, 0x6c,
+    0x10, 0x1c, 0x7a, 0x3e, 0xb6, 0x5f, 0x09, 0x45, 0xd6, 0xcb, 0x7d, 0x1b, 0x98, 0x6d, 0x0b, 0xa9,
+    0x17, 0x66, 0x1d, 0x13, 0xf8, 0xa8, 0xaa, 0x18, 0x6d, 0xe1, 0x3e, 0x98, 0xda, 0x96, 0x5e, 0x14,
+    0xed, 0x53, 0xa7, 0xe8, 0x97, 0x6e, 0xd2, 0xbb, 0x09, 0x0b, 0x55, 0xae, 0x26, 0x5e, 0x7d, 0x48,
+    0xa3, 0x27, 0x15, 0x45, 0xb9, 0x54, 0x54, 0x68, 0x69, 0x73, 0x20, 0x69, 0x73, 0x20, 0x74, 0x68,
+    0x65, 0x20, 0x63, 0x6f, 0x6e, 0x74, 0x65, 0x6e, 0x74, 0x2e, 0x58, 0x40, 0x93, 0xe7, 0x9d, 0x42,
+    0xc2, 0x94, 0x85, 0x1d, 0xef, 0x40, 0xd2, 0x53, 0x1b, 0x37, 0x0c, 0x88, 0x74, 0xdc, 0xf0, 0xf4,
+    0xa1, 0x04, 0x9b, 0x38, 0x01, 0x89, 0x9d, 0xeb, 0x13, 0x56, 0x27, 0x4d, 0x33, 0x7e, 0xd2, 0xa8,
+    0x45, 0xf9, 0x14, 0x65, 0x65, 0x7a, 0xd5, 0x32, 0x96, 0xf9, 0x66, 0xb4, 0x8e, 0xdd, 0xdd, 0xbf,
+    0x51, 0xf6, 0xc5, 0xc2, 0x7c, 0x1b, 0xb6, 0x8c, 0x75, 0x34, 0xae, 0xba
+];
+
+// A COSE_Sign of "This is the content." signed by signer1 and signer2. The message is countersigned
+// by signer3 and by an unknown signer4, and the signature of signer1 is countersigned by signer2.
+static COSE_SIGN_COUNTERSIGNED: &[u8] = &[
+    0xd8, 0x62, 0x84, 0x40, 0xa1, 0x0b, 0x82, 0x83, 0x43, 0xa1, 0x01, 0x26, 0xa1, 0x04, 0x47, 0x73,
+    0x69, 0x67, 0x6e, 0x65, 0x72, 0x33, 0x58, 0x40, 0x54, 0xf1, 0xb6, 0x1e, 0x9a, 0x9c, 0xff, 0x08,
+    0x40, 0xe6, 0xdd, 0x6b, 0x6a, 0xa4, 0x3d, 0x7d, 0x98, 0x85, 0xf3, 0xbf, 0xcc, 0xad, 0xac, 0xb7,
+    0x83, 0xb2, 0x57, 0xaf, 0x82, 0x40, 0x61, 0x8d, 0x64, 0x67, 0x9a, 0x8f, 0x75, 0x0a, 0x08, 0x82,
+    0xa6, 0x3a, 0x97, 0x45, 0x2d, 0x81, 0x58, 0x85, 0x02, 0x6c, 0xfa, 0xbb, 0xf5, 0x8f, 0x57, 0x33,
+    0x58, 0x8c, 0x6c, 0xca, 0xbe, 0x1c, 0x36, 0xa0, 0x83, 0x43, 0xa1, 0x01, 0x26, 0xa1, 0x04, 0x47,
+    0x73, 0x69, 0x67, 0x6e, 0x65, 0x72, 0x34, 0x58, 0x40, 0x4f, 0xbc, 0xe8, 0x45, 0x4c, 0x2b, 0x32,
+    0x76, 0xc3, 0x18, 0x8e, 0x80, 0x9d, 0xc4, 0xb0, 0xd5, 0x11, 0xf4, 0xca, 0x47, 0xd0, 0x31, 0x31,
+    0xbf, 0xaa, 0xe8, 0x96, 0x35, 0x49, 0x29, 0x75, 0xa6, 0xa2, 0xfe, 0x23, 0x7d, 0x59, 0xc4, 0xbe,
+    0x02, 0xe1, 0x59, 0xaa, 0x92, 0x29, 0x65, 0xd5, 0xb7, 0xff, 0x78, 0xab, 0x7b, 0x5a, 0x38, 0x63,
+    0x4c, 0x1e, 0x7e, 0x5a, 0x29, 0x3f, 0x3e, 0xfa, 0x4c, 0x54, 0x54, 0x68, 0x69, 0x73, 0x20, 0x69,
+    0x73, 0x20, 0x74, 0x68, 0x65, 0x20, 0x63, 0x6f, 0x6e, 0x74, 0x65, 0x6e, 0x74, 0x2e, 0x82, 0x83,
+    0x43, 0xa1, 0x01, 0x26, 0xa2, 0x04, 0x47, 0x73, 0x69, 0x67, 0x6e, 0x65, 0x72, 0x31, 0x0b, 0x83,
+    0x43, 0xa1, 0x01, 0x26, 0xa1, 0x04, 0x47, 0x73, 0x69, 0x67, 0x6e, 0x65, 0x72, 0x32, 0x58, 0x40,
+    0x2b, 0x71, 0xa6, 0xe9, 0xdd, 0xdb, 0xbb, 0x8f, 0xf3, 0x81, 0xeb, 0x18, 0xde, 0xa8, 0xce, 0x79,
+    0xa4, 0xe4, 0xf5, 0xbb, 0xd3, 0xda, 0x9f, 0xf3, 0x63, 0x29, 0x2d, 0xc5, 0x3f, 0x16, 0x56, 0x5a,
+    0x03, 0x50, 0x98, 0xb9, 0x49, 0xda, 0x13, 0x61, 0x1e, 0x9f, 0x30, 0x9f, 0x56, 0x3f, 0x3b, 0xc6,
+    0x02, 0xc8, 0x65, 0xa4, 0x80, 0x32, 0xe7, 0x56, 0xcc, 0xde, 0x0d, 0x42, 0x1e, 0x91, 0x46, 0xa0,
+    0x58, 0x40, 0x91, 0x7d, 0x40, 0x29, 0x2a, 0xf0, 0x6c, 0x3a, 0x97, 0x1f, 0xbd, 0xd3, 0x06, 0xe0,
+    0x83, 0x8e, 0x2d, 0x40, 0x1d, 0x84, 0x68, 0x48, 0x26, 0xce, 0xc8, 0xad, 0xac, 0x4c, 0x70, 0xb4,
+    0x11, 0x47, 0x36, 0xb0, 0x6b, 0xc4, 0x2f, 0xa8, 0x45, 0x5b, 0xf5, 0x27, 0x1e, 0xe1, 0xe4, 0x96,
+    0x7c, 0x8e, 0x0d, 0x2c, 0x7b, 0xc7, 0xfc, 0xcc, 0xa2, 0x11, 0x3b, 0xa1, 0xf2, 0x6b, 0xd3, 0x3b,
+    0x98, 0xc9, 0x83, 0x43, 0xa1, 0x01, 0x26, 0xa1, 0x04, 0x47, 0x73, 0x69, 0x67, 0x6e, 0x65, 0x72,
+    0x32, 0x58, 0x40, 0xe9, 0xf9, 0x43, 0xba, 0x78, 0x0c, 0xf6, 0x61, 0xae, 0x19, 0x86, 0x70, 0x62,
+    0x60, 0xdf, 0x59, 0x09, 0x21, 0x96, 0x86, 0xab, 0x49, 0x76, 0x7c, 0x9a, 0x54, 0x63, 0x20, 0x6d,
+    0x52, 0xec, 0x97, 0x51, 0x8f, 0x11, 0x18, 0x26, 0x4b, 0x05, 0xe8, 0xd7, 0x44, 0x7e, 0x3c, 0x59,
+    0xe3, 0xf7, 0x1f, 0x7a, 0xc1, 0x21, 0x2b, 0x25, 0x9e, 0xda, 0x7b, 0xc4, 0xf7, 0x2d, 0x15, 0x79,
+    0xeb, 0x3a, 0x37
+];
+
+#[test]
+fn test_countersignatures() {
+    setup();
+    let trusted_keys = [verify::TrustedKey { kid: b"signer2", spki: SIGNER2_SPKI },
+                        verify::TrustedKey { kid: b"signer3", spki: SIGNER3_SPKI }];
+    let abbreviated = verify::AbbreviatedCountersigner {
+        algorithm: verify::SignatureAlgorithm::ES256,
+        spki: SIGNER3_SPKI,
+    };
+    let report = |target, abbreviated, kid: Option<&[u8]>, algorithm, result| {
+        verify::CountersignatureReport {
+            target,
+            abbreviated,
+            signer: verify::SignerReport { kid: kid.map(|kid| kid.to_vec()), algorithm, result },
+        }
+    };
+    let es256 = Some(verify::SignatureAlgorithm::ES256);
+    let message = verify::CountersignatureTarget::Message;
+    assert_eq!(verify::verify_countersignatures(COSE_SIGN1_COUNTERSIGNED, None, &[],
+                                                &trusted_keys, Some(&abbreviated)),
+               Ok(vec![report(message, false, Some(b"signer2"), es256, Ok(())),
+                       report(message, true, None, es256, Ok(()))]));
+    assert_eq!(verify::verify_countersignatures(COSE_SIGN1_COUNTERSIGNED, None, b"aad",
+                                                &trusted_keys, None),
+               Ok(vec![report(message, false, Some(b"signer2"), es256,
+                              Err(verify::VerifyError::SignatureVerificationFailed)),
+                       report(message, true, None, None,
+                              Err(verify::VerifyError::UnknownKeyId))]));
+    let wrong_key = verify::AbbreviatedCountersigner {
+        algorithm: verify::SignatureAlgorithm::ES256,
+        spki: SIGNER2_SPKI,
+    };
+    let reports = verify::verify_countersignatures(COSE_SIGN1_COUNTERSIGNED, None, &[],
+                                                   &trusted_keys, Some(&wrong_key)).unwrap();
+    assert_eq!(reports[1].signer.result, Err(verify::VerifyError::SignatureVerificationFailed));
+
+    assert_eq!(verify::verify_countersignatures(COSE_SIGN_COUNTERSIGNED, None, &[],
+                                                &trusted_keys, Some(&abbreviated)),
+               Ok(vec![report(message, false, Some(b"signer3"), es256, Ok(())),
+                       report(message, false, Some(b"signer4"), es256,
+                              Err(verify::VerifyError::UnknownKeyId)),
+                       report(verify::CountersignatureTarget::Signature(0), false,
+                              Some(b"signer2"), es256, Ok(()))]));
+    // The countersignatures do not affect the signatures themselves.
+    let signer_keys = [verify::TrustedKey { kid: b"signer1", spki: SIGNER1_SPKI },
+                       verify::TrustedKey { kid: b"signer2", spki: SIGNER2_SPKI }];
+    let report = verify::verify_cose_sign(COSE_SIGN_COUNTERSIGNED, None, &signer_keys,
+                                          verify::VerificationPolicy::All).unwrap();
+    assert!(report.policy_satisfied);
+    // Signatures without countersignatures have none to report.
+    assert_eq!(verify::verify_countersignatures(COSE_SIGN_MIXED, None, &[], &trusted_keys, None),
+               Ok(Vec::new()));
+
+    let to_be_signed = verify::countersignature_to_be_signed(
+        COSE_SIGN_COUNTERSIGNED, None, verify::CountersignatureTarget::Signature(1),
+        Some(&[0xa1, 0x01, 0x26]), &[]).unwrap();
+    assert_eq!(&to_be_signed[..2], &[0x85, 0x72]);
+    assert_eq!(verify::countersignature_to_be_signed(COSE_SIGN1_COUNTERSIGNED, None,
+                                                     verify::CountersignatureTarget::Signature(0),
+                                                     None, &[]),
+               Err(verify::VerifyError::DecodingCOSEFailed));
+}
+
+// A CWT (tagged with 61) that is a COSE_Sign1 signed by signer1 with ES256, with the claims of the
+// example in RFC 8392, Appendix A.1.
+static CWT_SIGN1: &[u8] = &[
+    0xd8, 0x3d, 0xd2, 0x84, 0x43, 0xa1, 0x01, 0x26, 0xa1, 0x04, 0x47, 0x73, 0x69, 0x67, 0x6e, 0x65,
+    0x72, 0x31, 0x58, 0x50, 0xa7, 0x01, 0x75, 0x63, 0x6f, 0x61, 0x70, 0x3a, 0x2f, 0x2f, 0x61, 0x73,
+    0x2e, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x2e, 0x63, 0x6f, 0x6d, 0x02, 0x65, 0x65, 0x72,
+    0x69, 0x6b, 0x77, 0x03, 0x78, 0x18, 0x63, 0x6f, 0x61, 0x70, 0x3a, 0x2f, 0x2f, 0x6c, 0x69, 0x67,
+    0x68, 0x74, 0x2e, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x2e, 0x63, 0x6f, 0x6d, 0x04, 0x1a,
+    0x56, 0x12, 0xae, 0xb0, 0x05, 0x1a, 0x56, 0x10, 0xd9, 0xf0, 0x06, 0x1a, 0x56, 0x10, 0xd9, 0xf0,
+    0x07, 0x42, 0x0b, 0x71, 0x58, 0x40, 0x9c, 0xbb, 0x81, 0x38, 0xd1, 0xe6, 0x72, 0xdc, 0xc9, 0xf9,
+    0xe3, 0x51, 0xd3, 0x34, 0x60, 0xac, 0x63, 0x3f, 0xf6, 0x17, 0xb2, 0x58, 0xc1, 0xc9, 0xb0, 0x31,
+    0x11, 0xa9, 0xd8, 0x39, 0xbf, 0x78, 0x34, 0x67, 0xff, 0x5e, 0xd1, 0x2d, 0xc6, 0xf4, 0xda, 0x67,
+    0x9d, 0x5a, 0x3e, 0x98, 0x49, 0x43, 0x2a, 0x51, 0x2c, 0x4e, 0x3b, 0x97, 0x98, 0x05, 0xa1, 0xbe,
+    0x1e, 0x59, 0xb9, 0xb6, 0xcd, 0x3e
+];
+
+// An untagged CWT with the same claims that is a COSE_Mac0 with HMAC 256/64, MAC_TEST_KEY, and kid
+// "our-secret".
+static CWT_MAC0: &[u8] = &[
+    0xd1, 0x84, 0x43, 0xa1, 0x01, 0x04, 0xa1, 0x04, 0x4a, 0x6f, 0x75, 0x72, 0x2d, 0x73, 0x65, 0x63,
+    0x72, 0x65, 0x74, 0x58, 0x50, 0xa7, 0x01, 0x75, 0x63, 0x6f, 0x61, 0x70, 0x3a, 0x2f, 0x2f, 0x61,
+    0x73, 0x2e, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x2e, 0x63, 0x6f, 0x6d, 0x02, 0x65, 0x65,
+    0x72, 0x69, 0x6b, 0x77, 0x03, 0x78, 0x18, 0x63, 0x6f, 0x61, 0x70, 0x3a, 0x2f, 0x2f, 0x6c, 0x69,
+    0x67, 0x68, 0x74, 0x2e, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x2e, 0x63, 0x6f, 0x6d, 0x04,
+    0x1a, 0x56, 0x12, 0xae, 0xb0, 0x05, 0x1a, 0x56, 0x10, 0xd9, 0xf0, 0x06, 0x1a, 0x56, 0x10, 0xd9,
+    0xf0, 0x07, 0x42, 0x0b, 0x71, 0x48, 0x04, 0x6a, 0x26, 0xd9, 0x70, 0x82, 0x8a, 0xc9
+];
+
+#[test]
+fn test_cwt() {
+    setup();
+    let trusted_keys = [verify::TrustedKey { kid: b"signer1", spki: SIGNER1_SPKI }];
+    let validation = verify::CwtValidation {
+        now: 1443944944 + 60,
+        audience: Some("coap://light.example.com"),
+        leeway: 0,
+    };
+    let claims = verify::verify_cwt(CWT_SIGN1, &verify::CwtKeys::Signature(&trusted_keys),
+                                    &validation).unwrap();
+    assert_eq!(claims, verify::Claims {
+        iss: Some(String::from("coap://as.example.com")),
+        sub: Some(String::from("erikw")),
+        aud: Some(String::from("coap://light.example.com")),
+        exp: Some(1444064944),
+        nbf: Some(1443944944),
+        iat: Some(1443944944),
+        cti: Some(vec![0x0b, 0x71]),
+        ..Default::default()
+    });
+    let shared_keys = [verify::SharedKey { kid: b"our-secret", key: MAC_TEST_KEY }];
+    assert_eq!(verify::verify_cwt(CWT_MAC0, &verify::CwtKeys::Mac(&shared_keys), &validation),
+               Ok(claims));
+
+    let expired = verify::CwtValidation { now: 1444064944, ..validation };
+    assert_eq!(verify::verify_cwt(CWT_SIGN1, &verify::CwtKeys::Signature(&trusted_keys),
+                                  &expired),
+               Err(verify::VerifyError::TokenExpired));
+    let early = verify::CwtValidation { now: 1443944000, leeway: 600, ..validation };
+    assert_eq!(verify::verify_cwt(CWT_MAC0, &verify::CwtKeys::Mac(&shared_keys), &early),
+               Err(verify::VerifyError::TokenNotYetValid));
+    let other_audience = verify::CwtValidation { audience: Some("coap://lock"), ..validation };
+    assert_eq!(verify::verify_cwt(CWT_SIGN1, &verify::CwtKeys::Signature(&trusted_keys),
+                                  &other_audience),
+               Err(verify::VerifyError::AudienceMismatch));
+
+    // The keys determine which kind of COSE structure is expected.
+    assert_eq!(verify::verify_cwt(CWT_SIGN1, &verify::CwtKeys::Mac(&shared_keys), &validation),
+               Err(verify::VerifyError::DecodingCOSEFailed));
+    let wrong_key = [verify::SharedKey { kid: b"our-secret", key: &[0; 32] }];
+    assert_eq!(verify::verify_cwt(CWT_MAC0, &verify::CwtKeys::Mac(&wrong_key), &validation),
+               Err(verify::VerifyError::MacVerificationFailed));
+    let other_signer = [verify::TrustedKey { kid: b"signer1", spki: SIGNER2_SPKI }];
+    assert_eq!(verify::verify_cwt(CWT_SIGN1, &verify::CwtKeys::Signature(&other_signer),
+                                  &validation),
+               Err(verify::VerifyError::SignatureVerificationFailed));
+    // A COSE_Sign1 that isn't a CWT.
+    assert_eq!(verify::verify_cwt(COSE_SIGN_MIXED, &verify::CwtKeys::Signature(&trusted_keys),
+                                  &validation),
+               Err(verify::VerifyError::DecodingCOSEFailed));
+}
+
+static SIGNER2_PKCS8: &[u8] = &[
+    0x30, 0x81, 0x87, 0x02, 0x01, 0x00, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02,
+    0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x04, 0x6d, 0x30, 0x6b, 0x02,
+    0x01, 0x01, 0x04, 0x20, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22,
+    0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22,
+    0x22, 0x22, 0x22, 0x22, 0xa1, 0x44, 0x03, 0x42, 0x00, 0x04, 0xd6, 0x5a, 0x93, 0x97, 0x7c, 0xaa,
+    0x3d, 0x1b, 0x08, 0x18, 0x52, 0xff, 0x57, 0xa7, 0x9e, 0x46, 0x5f, 0x16, 0x60, 0x57, 0x73, 0x04,
+    0xba, 0xea, 0xd5, 0x05, 0xdd, 0x3a, 0x48, 0x58, 0x9c, 0xf3, 0x50, 0x18, 0x5e, 0x89, 0x53, 0x72,
+    0xdf, 0x62, 0x21, 0xea, 0x3a, 0x13, 0x75, 0x57, 0xe4, 0x73, 0xfd, 0xdb, 0x67, 0x55, 0xf0, 0x5b,
+    0xd5, 0x07, 0xc3, 0xc5, 0x33, 0xfc, 0xe9, 0xc9, 0x12, 0x85,
+];
+
+static RSA_2048_PKCS8: &[u8] = &[
+    0x30, 0x82, 0x04, 0xbe, 0x02, 0x01, 0x00, 0x30, 0x0d, 0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7,
+    0x0d, 0x01, 0x01, 0x01, 0x05, 0x00, 0x04, 0x82, 0x04, 0xa8, 0x30, 0x82, 0x04, 0xa4, 0x02, 0x01,
+    0x00, 0x02, 0x82, 0x01, 0x01, 0x00, 0xf9, 0x69, 0x42, 0x76, 0xf9, 0x2e, 0x33, 0xee, 0x9f, 0x4f,
+    0xd8, 0x8b, 0xd0, 0x7d, 0x2d, 0x3b, 0x37, 0x9e, 0xa1, 0x59, 0x0c, 0x53, 0x2a, 0xb0, 0x64, 0x92,
+    0x34, 0x44, 0xba, 0x2c, 0x17, 0x7d, 0x49, 0x33, 0x27, 0xd6, 0x62, 0x58, 0x1b, 0x7a, 0x1d, 0x87,
+    0x92, 0x72, 0x1b, 0xb6, 0x7a, 0x8b, 0x29, 0xe2, 0x02, 0x63, 0x84, 0x0d, 0x9d, 0xff, 0x58, 0x47,
+    0xcf, 0xae, 0x08, 0x19, 0x51, 0xdd, 0x43, 0x00, 0x1b, 0x58, 0x41, 0xd1, 0xc1, 0x3b, 0x01, 0xa8,
+    0xc2, 0xb3, 0xf7, 0x5a, 0xac, 0x93, 0xb4, 0x1a, 0x71, 0x5b, 0xf2, 0x41, 0xdc, 0x62, 0x6c, 0x55,
+    0xf4, 0x85, 0x56, 0x91, 0x9c, 0x37, 0x14, 0x46, 0x48, 0x9c, 0x73, 0x36, 0x66, 0xda, 0x03, 0x0c,
+    0xb6, 0x64, 0x74, 0xe5, 0x35, 0x95, 0xc1, 0xee, 0x89, 0x5c, 0x29, 0xeb, 0x3e, 0x01, 0x2b, 0xc0,
+    0x54, 0x6f, 0x6c, 0xa7, 0x93, 0x76, 0x18, 0xcf, 0xd7, 0xd4, 0x7d, 0xf4, 0xf3, 0x5a, 0x31, 0x0f,
+    0x9e, 0x67, 0x98, 0x19, 0x41, 0x32, 0x1f, 0x0a, 0xf3, 0x46, 0xdb, 0xb9, 0xb4, 0x7f, 0xcc, 0x60,
+    0x41, 0xe0, 0x23, 0x4f, 0x24, 0x57, 0x51, 0x69, 0x91, 0xbf, 0xf5, 0xb5, 0x78, 0x38, 0x83, 0xaf,
+    0x3a, 0xe0, 0xb5, 0xd6, 0x45, 0x25, 0xfc, 0x40, 0x96, 0x37, 0xa6, 0xe1, 0x96, 0x9e, 0x8b, 0x44,
+    0x8a, 0x8e, 0x82, 0x86, 0xc5, 0xba, 0x39, 0xa6, 0xbe, 0xce, 0xbc, 0xfe, 0x55, 0xef, 0x63, 0x05,
+    0x80, 0xae, 0x82, 0x97, 0x08, 0x0e, 0x78, 0x29, 0x23, 0xa7, 0x24, 0xa9, 0xc8, 0x7c, 0x9f, 0x1d,
+    0x3f, 0x1b, 0x82, 0x7b, 0x5b, 0x53, 0xa1, 0xe5, 0x39, 0x58, 0x28, 0xb9, 0x64, 0xab, 0x83, 0x1f,
+    0x0c, 0x26, 0x92, 0xf5, 0x7e, 0x65, 0x94, 0x71, 0x9e, 0x97, 0x46, 0x00, 0xd6, 0x75, 0x98, 0x0b,
+    0xfc, 0xf3, 0x26, 0x23, 0x6b, 0xbf, 0x02, 0x03, 0x01, 0x00, 0x01, 0x02, 0x82, 0x01, 0x00, 0x2d,
+    0x4b, 0x01, 0xc0, 0x74, 0x59, 0x01, 0x22, 0x54, 0xf9, 0x1d, 0x94, 0xaa, 0x6c, 0x89, 0x11, 0xc7,
+    0x74, 0x7d, 0xcd, 0x9b, 0x90, 0xe0, 0xe0, 0x3e, 0xab, 0x54, 0x65, 0xa4, 0xbf, 0x8c, 0x16, 0x7a,
+    0xfc, 0x18, 0xc0, 0x74, 0x84, 0x96, 0x9c, 0xd3, 0x0f, 0x03, 0x37, 0xc8, 0xb3, 0x97, 0x46, 0x7d,
+    0x79, 0x27, 0x40, 0xe6, 0x42, 0xc8, 0xce, 0x0d, 0xbc, 0xa0, 0xc8, 0xf4, 0x7c, 0x90, 0xa2, 0xd3,
+    0x5f, 0x8a, 0x17, 0xb9, 0x80, 0x3b, 0x8c, 0x99, 0x2d, 0x0a, 0x0a, 0xbf, 0xa1, 0x4e, 0x54, 0xd6,
+    0x70, 0xb4, 0xe4, 0x63, 0x36, 0xb5, 0xac, 0x84, 0x0b, 0x0a, 0x08, 0x4a, 0xe3, 0xcf, 0x90, 0x2b,
+    0x14, 0xe6, 0x6b, 0xce, 0xfa, 0x72, 0xac, 0x25, 0xc0, 0xa1, 0xc0, 0xd5, 0x7f, 0xa8, 0x7f, 0x05,
+    0x44, 0x69, 0xda, 0x7c, 0x5c, 0x55, 0x9a, 0xc5, 0xd4, 0xbc, 0x20, 0xae, 0x05, 0x32, 0xa0, 0x74,
+    0x71, 0x8d, 0x66, 0x1a, 0xf6, 0xd0, 0x3a, 0x9c, 0xae, 0xd4, 0x00, 0x25, 0xb0, 0xcf, 0x6c, 0xe6,
+    0x5c, 0x2f, 0xcf, 0xbc, 0x6d, 0xc6, 0x20, 0x29, 0xc0, 0x09, 0x31, 0x46, 0x53, 0x42, 0x2c, 0xb9,
+    0x43, 0x24, 0x1a, 0xf6, 0xd5, 0x33, 0x89, 0x7a, 0xa9, 0x41, 0xf5, 0x9c, 0xe5, 0xad, 0x81, 0xb5,
+    0x6d, 0x79, 0x29, 0xc7, 0xb3, 0xfe, 0x6d, 0x04, 0x99, 0x20, 0x62, 0x37, 0xf6, 0x0b, 0xeb, 0xc6,
+    0xe2, 0x8b, 0xf9, 0xdc, 0x80, 0x2d, 0x64, 0xa8, 0x31, 0x48, 0x23, 0xc8, 0x13, 0x2a, 0xf2, 0xb1,
+    0x09, 0xe8, 0x56, 0xcd, 0x8c, 0x71, 0x8d, 0x28, 0xab, 0x1b, 0xd8, 0xa7, 0x8e, 0xbc, 0x20, 0xdc,
+    0xf6, 0xd1, 0x5f, 0xed, 0x1a, 0xdf, 0x84, 0xcf, 0xe2, 0x15, 0x75, 0xab, 0xcf, 0xbf, 0xe6, 0x4f,
+    0x29, 0x42, 0xbb, 0xbf, 0x22, 0x1f, 0x82, 0x99, 0x0b, 0xa2, 0xd1, 0xbc, 0x36, 0x31, 0x8d, 0x02,
+    0x81, 0x81, 0x00, 0xff, 0xd8, 0x79, 0x8e, 0x82, 0xe3, 0xdb, 0x47, 0x70, 0x90, 0x1f, 0x54, 0x77,
+    0xf2, 0x31, 0xe8, 0xea, 0x02, 0xcc, 0x9d, 0xc6, 0x53, 0x0f, 0xfb, 0x43, 0x91, 0xd3, 0x4c, 0x08,
+    0x5d, 0x9d, 0xf9, 0xe7, 0xad, 0xa3, 0x04, 0xe8, 0x38, 0x4d, 0x3c, 0x72, 0x77, 0xf0, 0x4c, 0x33,
+    0x22, 0xd3, 0xd2, 0x6e, 0xb5, 0xa1, 0x00, 0x73, 0xd7, 0x66, 0x4f, 0x20, 0x89, 0x80, 0xe4, 0x5b,
+    0x81, 0x12, 0x93, 0x6b, 0x38, 0x1f, 0xda, 0xd3, 0x17, 0xfc, 0x86, 0x33, 0x07, 0xa7, 0x05, 0x5f,
+    0x95, 0x8e, 0x71, 0x15, 0xbb, 0xf2, 0x3e, 0x53, 0x22, 0x21, 0x6d, 0xcc, 0x38, 0xd6, 0xc5, 0x32,
+    0x47, 0xa5, 0x9c, 0x5f, 0xd3, 0x20, 0xf0, 0xab, 0x74, 0xe2, 0x05, 0x3d, 0x3e, 0x3a, 0xf0, 0x7e,
+    0x30, 0x96, 0x93, 0x2d, 0x65, 0x21, 0xe3, 0x6f, 0x81, 0x70, 0xbb, 0xa4, 0x6e, 0xef, 0x22, 0xf0,
+    0x77, 0x10, 0xf3, 0x02, 0x81, 0x81, 0x00, 0xf9, 0x8f, 0xca, 0x6e, 0xb6, 0x70, 0xf2, 0xdf, 0x9d,
+    0x33, 0x9b, 0x04, 0x3f, 0x55, 0x03, 0x5a, 0xad, 0x84, 0x84, 0x7d, 0x01, 0x31, 0x33, 0x3c, 0xb9,
+    0xee, 0x41, 0xcf, 0x4c, 0xd2, 0xfd, 0xd7, 0x8c, 0x2c, 0xa2, 0xf0, 0x34, 0x78, 0xd8, 0xe3, 0x64,
+    0x21, 0xab, 0xd9, 0xc3, 0xc5, 0xdb, 0x72, 0xa8, 0x59, 0xd9, 0x4d, 0x13, 0xeb, 0x7b, 0x06, 0x4b,
+    0x23, 0xe6, 0x1c, 0xb4, 0xea, 0xd6, 0x24, 0x27, 0x71, 0x9f, 0xe1, 0x33, 0x58, 0x31, 0x51, 0xec,
+    0x34, 0x59, 0x62, 0x94, 0x65, 0x71, 0x19, 0x92, 0x77, 0x7c, 0xf5, 0x5b, 0x14, 0x92, 0x8e, 0x88,
+    0xb8, 0x68, 0xa4, 0xb5, 0x4a, 0x45, 0x0b, 0x7f, 0xe5, 0xa0, 0x88, 0xfd, 0xcd, 0xe6, 0xf2, 0x37,
+    0x3b, 0x71, 0x19, 0x7d, 0x18, 0x06, 0xa7, 0xb1, 0x7e, 0x36, 0x52, 0x6c, 0x25, 0x10, 0x40, 0x86,
+    0xee, 0x02, 0x64, 0xf3, 0x2d, 0x4d, 0x05, 0x02, 0x81, 0x81, 0x00, 0x91, 0x2b, 0x01, 0x7e, 0x0d,
+    0x62, 0xc0, 0x8f, 0x3e, 0x9d, 0x68, 0x49, 0x83, 0x6d, 0x97, 0x48, 0x33, 0x4f, 0xb7, 0x4e, 0x66,
+    0xb3, 0xc6, 0xc3, 0xa9, 0x49, 0x5a, 0xfd, 0x2f, 0x59, 0xff, 0x43, 0x47, 0x63, 0xcc, 0xb6, 0x02,
+    0x15, 0xc0, 0x2d, 0x66, 0x3e, 0x0b, 0xc9, 0x10, 0xd2, 0xd8, 0x21, 0xf4, 0xd3, 0x76, 0x31, 0x4d,
+    0x89, 0x90, 0xf1, 0xa0, 0xe2, 0xa4, 0x91, 0xee, 0x21, 0xc4, 0xaa, 0x85, 0x11, 0xe9, 0x71, 0x3e,
+    0x89, 0xc3, 0xad, 0x59, 0xd3, 0x23, 0xbf, 0xf6, 0xc6, 0x63, 0x76, 0x63, 0x00, 0xab, 0xa9, 0x15,
+    0x7e, 0xae, 0x3d, 0x20, 0xbd, 0xb7, 0x8f, 0x58, 0xc2, 0x32, 0x84, 0x12, 0x13, 0x38, 0x03, 0x97,
+    0x25, 0xd1, 0x01, 0x6c, 0x08, 0x9d, 0x64, 0xcc, 0x15, 0x41, 0xba, 0xec, 0xed, 0x0f, 0x57, 0x7f,
+    0x73, 0x90, 0xc5, 0xc7, 0xbd, 0x72, 0x4e, 0xfe, 0xe6, 0x70, 0x65, 0x02, 0x81, 0x80, 0x65, 0x3a,
+    0x0c, 0xfb, 0x81, 0x67, 0x29, 0x2c, 0xc8, 0xe0, 0x00, 0xd5, 0xe5, 0x36, 0x7f, 0x88, 0xcd, 0x6f,
+    0x3e, 0x2a, 0x52, 0x4c, 0x79, 0xa2, 0xfd, 0x2c, 0x06, 0x67, 0xa6, 0x9a, 0xc2, 0xba, 0x26, 0xb2,
+    0x88, 0x58, 0xf4, 0x7f, 0xbc, 0x41, 0xe8, 0x00, 0xbc, 0x6b, 0x35, 0x3c, 0xd1, 0x39, 0x9d, 0xcd,
+    0xec, 0x15, 0x96, 0xa9, 0xe2, 0x99, 0xf7, 0xea, 0x94, 0xd1, 0x3a, 0x15, 0x33, 0x4d, 0x30, 0xee,
+    0xa6, 0xd2, 0x3a, 0xf8, 0x5f, 0xf1, 0x01, 0xe5, 0x6e, 0x08, 0x4d, 0x77, 0x24, 0x3c, 0x22, 0x2b,
+    0xf3, 0x74, 0xb6, 0x40, 0x0e, 0xfe, 0x5f, 0xb8, 0xa5, 0x41, 0x82, 0x03, 0xa1, 0x23, 0xfb, 0xf9,
+    0xd1, 0x77, 0x1f, 0xb3, 0xe0, 0x24, 0x89, 0x80, 0xc3, 0xec, 0x79, 0xd0, 0x40, 0x0a, 0xcf, 0x35,
+    0x09, 0xa5, 0xbf, 0x15, 0xfd, 0x94, 0x9e, 0xe6, 0x75, 0xf4, 0x2a, 0xab, 0x4a, 0x5d, 0x02, 0x81,
+    0x81, 0x00, 0xdc, 0x9b, 0xd4, 0x89, 0x5e, 0x60, 0x04, 0x9f, 0xd7, 0x83, 0x0f, 0xa7, 0x12, 0x30,
+    0x23, 0x35, 0x09, 0x54, 0x2e, 0xa7, 0xf0, 0xc6, 0xa4, 0x72, 0xb3, 0xd4, 0xc5, 0x8c, 0xa7, 0x0b,
+    0x00, 0x3a, 0x47, 0x12, 0xaf, 0xc2, 0x5b, 0x1f, 0x31, 0xb7, 0xbe, 0xc4, 0x17, 0x91, 0x7f, 0x65,
+    0x0c, 0x7c, 0xab, 0x4c, 0xff, 0x6f, 0x76, 0xda, 0x07, 0xf4, 0x8b, 0xf2, 0x6e, 0x7c, 0x15, 0x7a,
+    0x5e, 0x36, 0x9e, 0x92, 0xb3, 0x4b, 0x32, 0x7e, 0x61, 0x71, 0xec, 0xbf, 0xa6, 0xf1, 0xdb, 0x11,
+    0xf6, 0x5f, 0xaa, 0x4c, 0x70, 0x92, 0xbd, 0x86, 0x31, 0xec, 0x16, 0xb5, 0x3c, 0x4e, 0xb8, 0x42,
+    0x2e, 0x00, 0x0c, 0x21, 0x41, 0x16, 0x23, 0x16, 0x8a, 0x15, 0xbc, 0xad, 0x64, 0xfa, 0x2e, 0x1a,
+    0x7c, 0x73, 0x96, 0x1d, 0xb6, 0xc7, 0x85, 0xe1, 0x52, 0x6a, 0x0c, 0x7e, 0xab, 0x76, 0x60, 0xb1,
+    0xbb, 0x50,
+];
+
+static RSA_2048_SPKI: &[u8] = &[
+    0x30, 0x82, 0x01, 0x22, 0x30, 0x0d, 0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01,
+    0x01, 0x05, 0x00, 0x03, 0x82, 0x01, 0x0f, 0x00, 0x30, 0x82, 0x01, 0x0a, 0x02, 0x82, 0x01, 0x01,
+    0x00, 0xf9, 0x69, 0x42, 0x76, 0xf9, 0x2e, 0x33, 0xee, 0x9f, 0x4f, 0xd8, 0x8b, 0xd0, 0x7d, 0x2d,
+    0x3b, 0x37, 0x9e, 0xa1, 0x59, 0x0c, 0x53, 0x2a, 0xb0, 0x64, 0x92, 0x34, 0x44, 0xba, 0x2c, 0x17,
+    0x7d, 0x49, 0x33, 0x27, 0xd6, 0x62, 0x58, 0x1b, 0x7a, 0x1d, 0x87, 0x92, 0x72, 0x1b, 0xb6, 0x7a,
+    0x8b, 0x29, 0xe2, 0x02, 0x63, 0x84, 0x0d, 0x9d, 0xff, 0x58, 0x47, 0xcf, 0xae, 0x08, 0x19, 0x51,
+    0xdd, 0x43, 0x00, 0x1b, 0x58, 0x41, 0xd1, 0xc1, 0x3b, 0x01, 0xa8, 0xc2, 0xb3, 0xf7, 0x5a, 0xac,
+    0x93, 0xb4, 0x1a, 0x71, 0x5b, 0xf2, 0x41, 0xdc, 0x62, 0x6c, 0x55, 0xf4, 0x85, 0x56, 0x91, 0x9c,
+    0x37, 0x14, 0x46, 0x48, 0x9c, 0x73, 0x36, 0x66, 0xda, 0x03, 0x0c, 0xb6, 0x64, 0x74, 0xe5, 0x35,
+    0x95, 0xc1, 0xee, 0x89, 0x5c, 0x29, 0xeb, 0x3e, 0x01, 0x2b, 0xc0, 0x54, 0x6f, 0x6c, 0xa7, 0x93,
+    0x76, 0x18, 0xcf, 0xd7, 0xd4, 0x7d, 0xf4, 0xf3, 0x5a, 0x31, 0x0f, 0x9e, 0x67, 0x98, 0x19, 0x41,
+    0x32, 0x1f, 0x0a, 0xf3, 0x46, 0xdb, 0xb9, 0xb4, 0x7f, 0xcc, 0x60, 0x41, 0xe0, 0x23, 0x4f, 0x24,
+    0x57, 0x51, 0x69, 0x91, 0xbf, 0xf5, 0xb5, 0x78, 0x38, 0x83, 0xaf, 0x3a, 0xe0, 0xb5, 0xd6, 0x45,
+    0x25, 0xfc, 0x40, 0x96, 0x37, 0xa6, 0xe1, 0x96, 0x9e, 0x8b, 0x44, 0x8a, 0x8e, 0x82, 0x86, 0xc5,
+    0xba, 0x39, 0xa6, 0xbe, 0xce, 0xbc, 0xfe, 0x55, 0xef, 0x63, 0x05, 0x80, 0xae, 0x82, 0x97, 0x08,
+    0x0e, 0x78, 0x29, 0x23, 0xa7, 0x24, 0xa9, 0xc8, 0x7c, 0x9f, 0x1d, 0x3f, 0x1b, 0x82, 0x7b, 0x5b,
+    0x53, 0xa1, 0xe5, 0x39, 0x58, 0x28, 0xb9, 0x64, 0xab, 0x83, 0x1f, 0x0c, 0x26, 0x92, 0xf5, 0x7e,
+    0x65, 0x94, 0x71, 0x9e, 0x97, 0x46, 0x00, 0xd6, 0x75, 0x98, 0x0b, 0xfc, 0xf3, 0x26, 0x23, 0x6b,
+    0xbf, 0x02, 0x03, 0x01, 0x00, 0x01,
+];
+
+#[test]
+fn test_cwt_builder() {
+    setup();
+    let builder = verify::CwtBuilder::new().issuer("coap://as.example.com")
+                                           .subject("device-17")
+                                           .expiration(1444064944)
+                                           .issued_at(1443944944)
+                                           .int_claim(-70000, verify::CborType::UInt(3))
+                                           .text_claim("fw", verify::CborType::TStr(
+                                               String::from("1.2.0")));
+    let validation = verify::CwtValidation { now: 1443944944, audience: None, leeway: 0 };
+
+    let key = verify::CoseKey::from_spki(SIGNER1_SPKI).unwrap();
+    let signing_key = verify::SigningKey::EC2 { key: &key, d: &[0x11; 32] };
+    let cwt = builder.clone().tagged(true)
+                     .sign(verify::SignatureAlgorithm::ES256, &signing_key, b"signer1").unwrap();
+    assert_eq!(&cwt[..3], &[0xd8, 0x3d, 0xd2]);
+    let trusted_keys = [verify::TrustedKey { kid: b"signer1", spki: SIGNER1_SPKI }];
+    assert_eq!(verify::verify_cwt(&cwt, &verify::CwtKeys::Signature(&trusted_keys), &validation)
+                   .as_ref(),
+               Ok(builder.claims()));
+
+    let signing_key = verify::SigningKey::PrivateKeyInfo(SIGNER2_PKCS8);
+    let cwt = builder.sign(verify::SignatureAlgorithm::ES256, &signing_key, b"signer2").unwrap();
+    assert_eq!(cwt[0], 0xd2);
+    let trusted_keys = [verify::TrustedKey { kid: b"signer2", spki: SIGNER2_SPKI }];
+    assert_eq!(verify::verify_cwt(&cwt, &verify::CwtKeys::Signature(&trusted_keys), &validation)
+                   .as_ref(),
+               Ok(builder.claims()));
+
+    let signing_key = verify::SigningKey::PrivateKeyInfo(RSA_2048_PKCS8);
+    let cwt = builder.sign(verify::SignatureAlgorithm::PS256, &signing_key, b"rsa").unwrap();
+    let trusted_keys = [verify::TrustedKey { kid: b"rsa", spki: RSA_2048_SPKI }];
+    assert_eq!(verify::verify_cwt(&cwt, &verify::CwtKeys::Signature(&trusted_keys), &validation)
+                   .as_ref(),
+               Ok(builder.claims()));
+    assert_eq!(builder.sign(verify::SignatureAlgorithm::ES256, &signing_key, b"rsa"),
+               Err(verify::VerifyError::KeyTypeMismatch));
+
+    let cwt = builder.clone().tagged(true)
+                     .mac(verify::MacAlgorithm::HMAC256_256, MAC_TEST_KEY, b"our-secret")
+                     .unwrap();
+    assert_eq!(&cwt[..3], &[0xd8, 0x3d, 0xd1]);
+    let shared_keys = [verify::SharedKey { kid: b"our-secret", key: MAC_TEST_KEY }];
+    assert_eq!(verify::verify_cwt(&cwt, &verify::CwtKeys::Mac(&shared_keys), &validation)
+                   .as_ref(),
+               Ok(builder.claims()));
+    let expired = verify::CwtValidation { now: 1444064944, ..validation };
+    assert_eq!(verify::verify_cwt(&cwt, &verify::CwtKeys::Mac(&shared_keys), &expired),
+               Err(verify::VerifyError::TokenExpired));
+}
+
+#[test]
+fn test_cwt_proof_of_possession() {
+    setup();
+    let mut holder_key = verify::CoseKey::from_spki(SIGNER2_SPKI).unwrap();
+    holder_key.kid = Some(b"holder".to_vec());
+    let issuer_key = verify::CoseKey::from_spki(SIGNER1_SPKI).unwrap();
+    let issuer_signing_key = verify::SigningKey::EC2 { key: &issuer_key, d: &[0x11; 32] };
+    let trusted_keys = [verify::TrustedKey { kid: b"signer1", spki: SIGNER1_SPKI }];
+    let validation = verify::CwtValidation { now: 100, audience: None, leeway: 0 };
+    let holder_signing_key = verify::SigningKey::PrivateKeyInfo(SIGNER2_PKCS8);
+    let proof = verify::create_cose_sign1(verify::SignatureAlgorithm::ES256, &holder_signing_key,
+                                          Some(b"holder"), b"challenge").unwrap();
+
+    let cwt = verify::CwtBuilder::new().confirmation_key(&holder_key)
+                                       .sign(verify::SignatureAlgorithm::ES256,
+                                             &issuer_signing_key, b"signer1").unwrap();
+    let claims = verify::verify_cwt(&cwt, &verify::CwtKeys::Signature(&trusted_keys),
+                                    &validation).unwrap();
+    assert_eq!(claims.confirmation_key(),
+               Ok(Some(verify::ConfirmationKey::CoseKey(holder_key.clone()))));
+    assert_eq!(verify::verify_proof_of_possession(&claims, &proof, None, None),
+               Ok(b"challenge".to_vec()));
+    // A proof by someone else.
+    let other_proof = verify::create_cose_sign1(verify::SignatureAlgorithm::ES256,
+                                                &issuer_signing_key, None, b"challenge")
+                                               .unwrap();
+    assert_eq!(verify::verify_proof_of_possession(&claims, &other_proof, None, None),
+               Err(verify::VerifyError::SignatureVerificationFailed));
+    let other_kid_proof = verify::create_cose_sign1(verify::SignatureAlgorithm::ES256,
+                                                    &holder_signing_key, Some(b"other"),
+                                                    b"challenge").unwrap();
+    assert_eq!(verify::verify_proof_of_possession(&claims, &other_kid_proof, None, None),
+               Err(verify::VerifyError::UnknownKeyId));
+
+    let cwt = verify::CwtBuilder::new().confirmation_kid(b"holder")
+                                       .sign(verify::SignatureAlgorithm::ES256,
+                                             &issuer_signing_key, b"signer1").unwrap();
+    let claims = verify::verify_cwt(&cwt, &verify::CwtKeys::Signature(&trusted_keys),
+                                    &validation).unwrap();
+    let holder_keys = verify::CoseKeySet { keys: vec![holder_key] };
+    assert_eq!(verify::verify_proof_of_possession(&claims, &proof, None, Some(&holder_keys)),
+               Ok(b"challenge".to_vec()));
+    assert_eq!(verify::verify_proof_of_possession(&claims, &proof, None, None),
+               Err(verify::VerifyError::UnknownKeyId));
+    assert_eq!(verify::verify_proof_of_possession(&verify::Claims::default(), &proof, None,
+                                                  Some(&holder_keys)),
+               Err(verify::VerifyError::UnknownKeyId));
+}
+
+#[test]
+fn test_eat() {
+    setup();
+    let key = verify::CoseKey::from_spki(SIGNER1_SPKI).unwrap();
+    let signing_key = verify::SigningKey::EC2 { key: &key, d: &[0x11; 32] };
+    let trusted_keys = [verify::TrustedKey { kid: b"signer1", spki: SIGNER1_SPKI },
+                        verify::TrustedKey { kid: b"signer2", spki: SIGNER2_SPKI }];
+    let keys = verify::CwtKeys::Signature(&trusted_keys);
+    let validation = verify::CwtValidation { now: 1000, audience: None, leeway: 0 };
+    let nonce = [0x5a; 16];
+
+    // The secure element reports its own token, nested in the one of the device.
+    let nested = verify::CwtBuilder::new().int_claim(263, verify::CborType::UInt(4))
+                                          .tagged(true)
+                                          .sign(verify::SignatureAlgorithm::ES256,
+                                                &verify::SigningKey::PrivateKeyInfo(SIGNER2_PKCS8),
+                                                b"signer2").unwrap();
+    let mut radio = BTreeMap::new();
+    radio.insert(verify::CborType::UInt(259), verify::CborType::BStr(b"r-1".to_vec()));
+    let mut submods = BTreeMap::new();
+    submods.insert(verify::CborType::TStr(String::from("radio")), verify::CborType::Map(radio));
+    submods.insert(verify::CborType::TStr(String::from("se")), verify::CborType::BStr(nested));
+    submods.insert(verify::CborType::TStr(String::from("log")),
+                   verify::CborType::Arr(vec![verify::CborType::NInt(-16),
+                                              verify::CborType::BStr(vec![0xab; 32])]));
+    let mut ueid = vec![0x01];
+    ueid.extend_from_slice(&[0x42; 16]);
+    let measurement = verify::CborType::Arr(vec![verify::CborType::UInt(60),
+                                                 verify::CborType::BStr(vec![0xa0])]);
+    let eat = verify::CwtBuilder::new().issuer("device")
+                                       .int_claim(10, verify::CborType::BStr(nonce.to_vec()))
+                                       .int_claim(256, verify::CborType::BStr(ueid.clone()))
+                                       .int_claim(258, verify::CborType::BStr(vec![1, 2, 3]))
+                                       .int_claim(263, verify::CborType::UInt(1))
+                                       .int_claim(265, verify::CborType::TStr(
+                                           String::from("tag:example.com,2024:profile")))
+                                       .int_claim(273, verify::CborType::Arr(vec![measurement]))
+                                       .int_claim(266, verify::CborType::Map(submods))
+                                       .sign(verify::SignatureAlgorithm::ES256, &signing_key,
+                                             b"signer1").unwrap();
+
+    let claims = verify::verify_eat(&eat, &keys, &validation,
+                                    &verify::EatValidation { nonce: Some(&nonce) }).unwrap();
+    assert_eq!(claims.claims.iss, Some(String::from("device")));
+    assert!(claims.claims.other.is_empty());
+    assert_eq!(claims.nonce, vec![nonce.to_vec()]);
+    assert_eq!(claims.ueid, Some(ueid));
+    assert_eq!(claims.oemid, Some(verify::OemId::Bytes(vec![1, 2, 3])));
+    assert_eq!(claims.dbgstat, Some(verify::DebugStatus::Disabled));
+    assert_eq!(claims.eat_profile,
+               Some(verify::EatProfile::Uri(String::from("tag:example.com,2024:profile"))));
+    assert_eq!(claims.measurements,
+               vec![verify::Measurement { content_format: 60, measurement: vec![0xa0] }]);
+    assert_eq!(claims.submods.len(), 3);
+    match claims.submods["radio"] {
+        verify::Submodule::Claims(ref radio) => assert_eq!(radio.hwmodel, Some(b"r-1".to_vec())),
+        ref submodule => panic!("unexpected submodule {:?}", submodule),
+    }
+    match claims.submods["se"] {
+        verify::Submodule::VerifiedToken(ref se) => {
+            assert_eq!(se.dbgstat, Some(verify::DebugStatus::DisabledFullyAndPermanently))
+        }
+        ref submodule => panic!("unexpected submodule {:?}", submodule),
+    }
+    assert_eq!(claims.submods["log"],
+               verify::Submodule::DetachedDigest { algorithm: -16, digest: vec![0xab; 32] });
+
+    assert_eq!(verify::verify_eat(&eat, &keys, &validation,
+                                  &verify::EatValidation { nonce: Some(&[0; 16]) }),
+               Err(verify::VerifyError::NonceMismatch));
+    // The nested token must verify too.
+    assert_eq!(verify::verify_eat(&eat, &verify::CwtKeys::Signature(&trusted_keys[..1]),
+                                  &validation, &verify::EatValidation { nonce: None }),
+               Err(verify::VerifyError::UnknownKeyId));
+}
+
+static COSE_SIGN1_CWT_CLAIMS: &[u8] = &[
+    0xd2, 0x84, 0x58, 0x2e, 0xa2, 0x01, 0x26, 0x0f, 0xa4, 0x01, 0x75, 0x63, 0x6f, 0x61, 0x70, 0x3a,
+    0x2f, 0x2f, 0x61, 0x73, 0x2e, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x2e, 0x63, 0x6f, 0x6d,
+    0x02, 0x68, 0x66, 0x69, 0x72, 0x6d, 0x77, 0x61, 0x72, 0x65, 0x04, 0x19, 0x07, 0xd0, 0x05, 0x19,
+    0x03, 0xe8, 0xa1, 0x04, 0x47, 0x73, 0x69, 0x67, 0x6e, 0x65, 0x72, 0x31, 0x4e, 0x66, 0x69, 0x72,
+    0x6d, 0x77, 0x61, 0x72, 0x65, 0x20, 0x69, 0x6d, 0x61, 0x67, 0x65, 0x58, 0x40, 0x59, 0x5e, 0x7c,
+    0x6e, 0xdb, 0x35, 0x5a, 0x59, 0xff, 0xc5, 0x43, 0xf3, 0xce, 0xd3, 0xe8, 0x75, 0xc6, 0x15, 0x22,
+    0x23, 0x25, 0xf7, 0x40, 0x30, 0xe2, 0x2a, 0xfe, 0x12, 0x51, 0xf5, 0xdb, 0x5b, 0xe5, 0xa3, 0x59,
+    0x7d, 0x4b, 0xef, 0xf6, 0x44, 0xd4, 0x6f, 0x22, 0x20, 0x11, 0xf7, 0x18, 0x02, 0x0a, 0x10, 0xbd,
+    0x3a, 0xa6, 0x37, 0x2d, 0x05, 0xeb, 0x37, 0x89, 0xf2, 0x13, 0xc3, 0x03, 0x7d,
+];
+
+static COSE_SIGN1_UNPROTECTED_CWT_CLAIMS: &[u8] = &[
+    0xd2, 0x84, 0x43, 0xa1, 0x01, 0x26, 0xa2, 0x04, 0x47, 0x73, 0x69, 0x67, 0x6e, 0x65, 0x72, 0x31,
+    0x0f, 0xa4, 0x01, 0x75, 0x63, 0x6f, 0x61, 0x70, 0x3a, 0x2f, 0x2f, 0x61, 0x73, 0x2e, 0x65, 0x78,
+    0x61, 0x6d, 0x70, 0x6c, 0x65, 0x2e, 0x63, 0x6f, 0x6d, 0x02, 0x68, 0x66, 0x69, 0x72, 0x6d, 0x77,
+    0x61, 0x72, 0x65, 0x04, 0x19, 0x07, 0xd0, 0x05, 0x19, 0x03, 0xe8, 0x4e, 0x66, 0x69, 0x72, 0x6d,
+    0x77, 0x61, 0x72, 0x65, 0x20, 0x69, 0x6d, 0x61, 0x67, 0x65, 0x58, 0x40, 0x81, 0xbd, 0x86, 0x5d,
+    0x67, 0x7e, 0xe2, 0x96, 0x92, 0x09, 0xb0, 0x55, 0x22, 0xc4, 0x63, 0x10, 0x92, 0x24, 0x85, 0x5f,
+    0x44, 0x37, 0xd4, 0x11, 0x53, 0x73, 0x77, 0x3a, 0x87, 0x6e, 0x6e, 0xf6, 0x83, 0xed, 0x0c, 0x88,
+    0xcd, 0x72, 0x5c, 0x61, 0xfa, 0x05, 0xf5, 0xd7, 0x17, 0x1b, 0x34, 0xa2, 0x64, 0xc0, 0xa0, 0x7e,
+    0xed, 0x9a, 0xe0, 0x3a, 0x4c, 0xd0, 0x78, 0x78, 0x07, 0xda, 0x1f, 0x56,
+];
+
+static COSE_SIGN_CWT_CLAIMS: &[u8] = &[
+    0xd8, 0x62, 0x84, 0x58, 0x2c, 0xa1, 0x0f, 0xa4, 0x01, 0x75, 0x63, 0x6f, 0x61, 0x70, 0x3a, 0x2f,
+    0x2f, 0x61, 0x73, 0x2e, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x2e, 0x63, 0x6f, 0x6d, 0x02,
+    0x68, 0x66, 0x69, 0x72, 0x6d, 0x77, 0x61, 0x72, 0x65, 0x04, 0x19, 0x07, 0xd0, 0x05, 0x19, 0x03,
+    0xe8, 0xa0, 0x4e, 0x66, 0x69, 0x72, 0x6d, 0x77, 0x61, 0x72, 0x65, 0x20, 0x69, 0x6d, 0x61, 0x67,
+    0x65, 0x81, 0x83, 0x43, 0xa1, 0x01, 0x26, 0xa1, 0x04, 0x47, 0x73, 0x69, 0x67, 0x6e, 0x65, 0x72,
+    0x31, 0x58, 0x40, 0xe2, 0x76, 0xec, 0x1e, 0x3d, 0xf3, 0x6a, 0x96, 0xb0, 0x99, 0xe7, 0x79, 0x5f,
+    0xe3, 0xf5, 0x8c, 0x2d, 0x4a, 0x2a, 0xa7, 0xc8, 0xaa, 0x80, 0xa1, 0xaf, 0xba, 0xb4, 0xbd, 0x78,
+    0x8b, 0xc8, 0xc6, 0xe6, 0x02, 0x78, 0x45, 0xc0, 0xa3, 0x10, 0xe3, 0x8f, 0x29, 0x13, 0x4d, 0x3f,
+    0x93, 0xd5, 0x81, 0x00, 0x20, 0x16, 0x4c, 0x15, 0x69, 0x06, 0x90, 0xae, 0x51, 0x90, 0xc6, 0x94,
+    0x60, 0x2e, 0xcf,
+];
+
+#[test]
+fn test_cwt_claims_header() {
+    setup();
+    let trusted_keys = [verify::TrustedKey { kid: b"signer1", spki: SIGNER1_SPKI }];
+    let validation = verify::CwtValidation { now: 1500, audience: None, leeway: 0 };
+    let claims = verify::Claims {
+        iss: Some(String::from("coap://as.example.com")),
+        sub: Some(String::from("firmware")),
+        exp: Some(2000),
+        nbf: Some(1000),
+        ..Default::default()
+    };
+    assert_eq!(verify::verify_cose_sign1_with_claims(COSE_SIGN1_CWT_CLAIMS, None, &trusted_keys,
+                                                     &validation),
+               Ok((claims.clone(), b"firmware image".to_vec())));
+    let (report, sign_claims) =
+        verify::verify_cose_sign_with_claims(COSE_SIGN_CWT_CLAIMS, None, &trusted_keys,
+                                             verify::VerificationPolicy::All, &validation)
+               .unwrap();
+    assert!(report.policy_satisfied);
+    assert_eq!(sign_claims, claims);
+
+    let expired = verify::CwtValidation { now: 2000, ..validation };
+    assert_eq!(verify::verify_cose_sign1_with_claims(COSE_SIGN1_CWT_CLAIMS, None, &trusted_keys,
+                                                     &expired),
+               Err(verify::VerifyError::TokenExpired));
+    assert_eq!(verify::verify_cose_sign_with_claims(COSE_SIGN_CWT_CLAIMS, None, &trusted_keys,
+                                                    verify::VerificationPolicy::All, &expired),
+               Err(verify::VerifyError::TokenExpired));
+    // The claims must be protected.
+    assert_eq!(verify::verify_cose_sign1_with_claims(COSE_SIGN1_UNPROTECTED_CWT_CLAIMS, None,
+                                                     &trusted_keys, &validation),
+               Err(verify::VerifyError::DecodingCOSEFailed));
+    assert_eq!(verify::verify_cose_sign1(COSE_SIGN1_UNPROTECTED_CWT_CLAIMS, None, &trusted_keys),
+               Ok(b"firmware image".to_vec()));
+    // A COSE_Sign1 without claims.
+    let key = verify::CoseKey::from_spki(SIGNER1_SPKI).unwrap();
+    let signing_key = verify::SigningKey::EC2 { key: &key, d: &[0x11; 32] };
+    let cose_sign1 = verify::create_cose_sign1(verify::SignatureAlgorithm::ES256, &signing_key,
+                                               Some(b"signer1"), b"firmware image").unwrap();
+    assert_eq!(verify::verify_cose_sign1_with_claims(&cose_sign1, None, &trusted_keys,
+                                                     &validation),
+               Err(verify::VerifyError::DecodingCWTFailed));
+}
+
+static COSE_SIGN1_CBOR_CONTENT: &[u8] = &[
+    0xd2, 0x84, 0x46, 0xa2, 0x01, 0x26, 0x03, 0x18, 0x3c, 0xa1, 0x04, 0x47, 0x73, 0x69, 0x67, 0x6e,
+    0x65, 0x72, 0x31, 0x47, 0xa1, 0x64, 0x74, 0x65, 0x6d, 0x70, 0x15, 0x58, 0x40, 0x30, 0x4b, 0xc4,
+    0x52, 0x4f, 0xcf, 0x34, 0x5c, 0x55, 0x07, 0x35, 0x28, 0xc9, 0xba, 0x5f, 0xd8, 0x5e, 0x41, 0x9a,
+    0xd6, 0xfc, 0xbb, 0xad, 0xab, 0x63, 0xeb, 0xd9, 0x6b, 0xde, 0x39, 0xc4, 0x2c, 0x9a, 0x23, 0xaf,
+    0xa0, 0x09, 0x47, 0x10, 0x01, 0xc9, 0xb2, 0xfa, 0x16, 0x74, 0x0f, 0x6c, 0x90, 0xb1, 0x02, 0x53,
+    0x59, 0x6b, 0xb9, 0x58, 0x21, 0xae, 0xd8, 0x80, 0xbe, 0xe5, 0x92, 0xe0, 0x7d,
+];
+
+static COSE_SIGN1_TEXT_CONTENT: &[u8] = &[
+    0xd2, 0x84, 0x58, 0x1f, 0xa2, 0x01, 0x26, 0x03, 0x78, 0x19, 0x74, 0x65, 0x78, 0x74, 0x2f, 0x70,
+    0x6c, 0x61, 0x69, 0x6e, 0x3b, 0x20, 0x63, 0x68, 0x61, 0x72, 0x73, 0x65, 0x74, 0x3d, 0x75, 0x74,
+    0x66, 0x2d, 0x38, 0xa1, 0x04, 0x47, 0x73, 0x69, 0x67, 0x6e, 0x65, 0x72, 0x31, 0x45, 0x68, 0x65,
+    0x6c, 0x6c, 0x6f, 0x58, 0x40, 0xa8, 0x39, 0x85, 0x72, 0x7b, 0x1b, 0x34, 0x0b, 0xa6, 0xd5, 0x3c,
+    0x73, 0xa0, 0x81, 0xf4, 0xea, 0x10, 0xed, 0xe1, 0x65, 0xa8, 0x8a, 0x95, 0x1b, 0xd4, 0x1e, 0x5b,
+    0x85, 0x7e, 0xda, 0x48, 0x7b, 0xbc, 0xaa, 0x86, 0x60, 0x03, 0x71, 0x26, 0xa7, 0x9d, 0x6c, 0xb1,
+    0x1e, 0xdb, 0x2e, 0xce, 0xb9, 0x37, 0xfd, 0x0b, 0x5d, 0x43, 0x14, 0x5f, 0x71, 0xb4, 0xf0, 0xb2,
+    0x69, 0x5e, 0xa0, 0x96, 0xe1,
+];
+
+#[derive(Debug, PartialEq)]
+enum TestPayload {
+    Text(String),
+    Cbor(usize),
+}
+
+#[test]
+fn test_cose_sign1_content_type() {
+    setup();
+    let trusted_keys = [verify::TrustedKey { kid: b"signer1", spki: SIGNER1_SPKI }];
+    let mut decoders = verify::PayloadDecoders::new();
+    decoders.register(verify::ContentType::MediaType(String::from("application/cbor")),
+                      |payload: &[u8]| Ok(TestPayload::Cbor(payload.len())));
+    assert_eq!(verify::verify_cose_sign1_decoded(COSE_SIGN1_CBOR_CONTENT, None, &trusted_keys,
+                                                 &decoders),
+               Ok(TestPayload::Cbor(7)));
+    assert_eq!(verify::verify_cose_sign1_decoded(COSE_SIGN1_TEXT_CONTENT, None, &trusted_keys,
+                                                 &decoders),
+               Err(verify::VerifyError::UnsupportedContentType));
+    decoders.register(verify::ContentType::CoapContentFormat(0), |payload: &[u8]| {
+        String::from_utf8(payload.to_vec()).map(TestPayload::Text)
+                                           .map_err(|_| verify::VerifyError::DecodingCOSEFailed)
+    });
+    assert_eq!(verify::verify_cose_sign1_decoded(COSE_SIGN1_TEXT_CONTENT, None, &trusted_keys,
+                                                 &decoders),
+               Ok(TestPayload::Text(String::from("hello"))));
+    // The signature is verified first.
+    let other_signer = [verify::TrustedKey { kid: b"signer1", spki: SIGNER2_SPKI }];
+    assert_eq!(verify::verify_cose_sign1_decoded(COSE_SIGN1_TEXT_CONTENT, None, &other_signer,
+                                                 &decoders),
+               Err(verify::VerifyError::SignatureVerificationFailed));
+    // A COSE_Sign1 without a content type.
+    assert_eq!(verify::verify_cose_sign1_decoded(COSE_SIGN1_CWT_CLAIMS, None, &trusted_keys,
+                                                 &decoders),
+               Err(verify::VerifyError::UnsupportedContentType));
+}
+
+#[test]
+fn test_hash_envelope() {
+    setup();
+    let key = verify::CoseKey::from_spki(SIGNER1_SPKI).unwrap();
+    let signing_key = verify::SigningKey::EC2 { key: &key, d: &[0x11; 32] };
+    let trusted_keys = [verify::TrustedKey { kid: b"signer1", spki: SIGNER1_SPKI }];
+    let artifact = vec![0xfe; 100000];
+    let envelope_headers = verify::HashEnvelopeHeaders {
+        hash_algorithm: verify::HashAlgorithm::SHA384,
+        preimage_content_type: Some(verify::ContentType::MediaType(
+            String::from("application/vnd.example.firmware"))),
+        payload_location: Some(String::from("https://updates.example.com/fw-1.2.0.bin")),
+    };
+    let envelope = verify::create_hash_envelope(verify::SignatureAlgorithm::ES256, &signing_key,
+                                                Some(b"signer1"), &envelope_headers, &artifact)
+                          .unwrap();
+    assert_eq!(verify::verify_hash_envelope(&envelope, &trusted_keys, &artifact),
+               Ok(envelope_headers));
+    // The payload is the hash of the artifact.
+    assert_eq!(verify::verify_cose_sign1(&envelope, None, &trusted_keys).unwrap().len(), 48);
+    let mut tampered = artifact.clone();
+    tampered[50000] = 0;
+    assert_eq!(verify::verify_hash_envelope(&envelope, &trusted_keys, &tampered),
+               Err(verify::VerifyError::PayloadHashMismatch));
+    let other_signer = [verify::TrustedKey { kid: b"signer1", spki: SIGNER2_SPKI }];
+    assert_eq!(verify::verify_hash_envelope(&envelope, &other_signer, &artifact),
+               Err(verify::VerifyError::SignatureVerificationFailed));
+    // A COSE_Sign1 that isn't a hash envelope.
+    assert_eq!(verify::verify_hash_envelope(COSE_SIGN1_CBOR_CONTENT, &trusted_keys, &artifact),
+               Err(verify::VerifyError::DecodingCOSEFailed));
+}
+
+// A COSE_Sign1 with a detached payload of 3 MiB + 7 bytes, where byte i is i % 251.
+static COSE_SIGN1_LARGE_DETACHED: &[u8] = &[
+    0xd2, 0x84, 0x43, 0xa1, 0x01, 0x26, 0xa1, 0x04, 0x47, 0x73, 0x69, 0x67, 0x6e, 0x65, 0x72, 0x31,
+    0xf6, 0x58, 0x40, 0x70, 0xae, 0x1a, 0xf6, 0x0e, 0xf2, 0x8c, 0xd7, 0x2b, 0x14, 0x53, 0x16, 0x56,
+    0xd5, 0xea, 0x36, 0xa2, 0x55, 0x25, 0xcf, 0x29, 0x13, 0x9a, 0xe0, 0xfd, 0x95, 0x03, 0x63, 0x01,
+    0x89, 0xa1, 0xf9, 0xc5, 0x85, 0x89, 0x51, 0xc5, 0xe8, 0x57, 0xbf, 0x10, 0x3e, 0x0e, 0x00, 0xc2,
+    0x63, 0x17, 0xe7, 0x04, 0xd1, 0x0d, 0xcc, 0xd8, 0x23, 0x7b, 0xdc, 0xb9, 0x18, 0x42, 0xdc, 0xda,
+    0x85, 0x89, 0xe3,
+];
+
+/// Yields byte i % 251 at offset i, at most 1000 bytes at a time, until it reaches its length.
+struct PatternReader {
+    offset: usize,
+    length: usize,
+}
+
+impl Read for PatternReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = buf.len().min(1000).min(self.length - self.offset);
+        for byte in &mut buf[..read] {
+            *byte = (self.offset % 251) as u8;
+            self.offset += 1;
+        }
+        Ok(read)
+    }
+}
+
+#[test]
+fn test_cose_sign1_streaming() {
+    setup();
+    let trusted_keys = [verify::TrustedKey { kid: b"signer1", spki: SIGNER1_SPKI }];
+    let length = 3 * 1024 * 1024 + 7;
+    let mut reader = PatternReader { offset: 0, length };
+    assert_eq!(verify::verify_cose_sign1_streaming(COSE_SIGN1_LARGE_DETACHED, &mut reader,
+                                                   length as u64, &trusted_keys),
+               Ok(()));
+    assert_eq!(reader.offset, length);
+    let payload: Vec<u8> = (0..length).map(|i| (i % 251) as u8).collect();
+    assert_eq!(verify::verify_cose_sign1_streaming(COSE_SIGN1_LARGE_DETACHED, &mut &payload[..],
+                                                   length as u64, &trusted_keys),
+               Ok(()));
+    assert!(verify::verify_cose_sign1(COSE_SIGN1_LARGE_DETACHED, Some(&payload), &trusted_keys)
+                .is_ok());
+
+    let mut tampered = payload.clone();
+    tampered[length - 1] ^= 1;
+    assert_eq!(verify::verify_cose_sign1_streaming(COSE_SIGN1_LARGE_DETACHED, &mut &tampered[..],
+                                                   length as u64, &trusted_keys),
+               Err(verify::VerifyError::SignatureVerificationFailed));
+    // Only the given length is read.
+    assert_eq!(verify::verify_cose_sign1_streaming(COSE_SIGN1_LARGE_DETACHED, &mut &payload[..],
+                                                   length as u64 - 1, &trusted_keys),
+               Err(verify::VerifyError::SignatureVerificationFailed));
+    let mut short_reader = PatternReader { offset: 0, length: length - 1 };
+    assert_eq!(verify::verify_cose_sign1_streaming(COSE_SIGN1_LARGE_DETACHED, &mut short_reader,
+                                                   length as u64, &trusted_keys),
+               Err(verify::VerifyError::PayloadReadFailed));
+    // The payload must be detached.
+    assert_eq!(verify::verify_cose_sign1_streaming(COSE_SIGN1_CBOR_CONTENT, &mut &payload[..],
+                                                   7, &trusted_keys),
+               Err(verify::VerifyError::DecodingCOSEFailed));
+}
+
+#[test]
+fn test_verify_prehashed() {
+    setup();
+    // The SHA-256 digest of "build artifact".
+    let digest = [0xf9, 0xe3, 0x8a, 0xb2, 0xf6, 0xd5, 0x8d, 0xae, 0x2d, 0x3d, 0x83, 0x3d, 0x88,
+                  0xca, 0xac, 0x15, 0x46, 0x3e, 0xeb, 0x42, 0x2f, 0xea, 0x3a, 0xce, 0x03, 0xa4,
+                  0xc7, 0x05, 0x11, 0xe0, 0x3f, 0x69];
+    let key = verify::CoseKey::from_spki(SIGNER1_SPKI).unwrap();
+    let signature = verify::sign(verify::SignatureAlgorithm::ES256,
+                                 &verify::SigningKey::EC2 { key: &key, d: &[0x11; 32] },
+                                 b"build artifact").unwrap();
+    assert_eq!(verify::verify_prehashed(verify::SignatureAlgorithm::ES256, SIGNER1_SPKI, &digest,
+                                        &signature),
+               Ok(()));
+    assert_eq!(verify::verify_prehashed(verify::SignatureAlgorithm::ES256, SIGNER2_SPKI, &digest,
+                                        &signature),
+               Err(verify::VerifyError::SignatureVerificationFailed));
+    assert_eq!(verify::verify_prehashed(verify::SignatureAlgorithm::ES256, SIGNER1_SPKI,
+                                        &digest[..31], &signature),
+               Err(verify::VerifyError::InvalidDigestLength));
+
+    let signature = verify::sign(verify::SignatureAlgorithm::PS256,
+                                 &verify::SigningKey::PrivateKeyInfo(RSA_2048_PKCS8),
+                                 b"build artifact").unwrap();
+    assert_eq!(verify::verify_prehashed(verify::SignatureAlgorithm::PS256, RSA_2048_SPKI, &digest,
+                                        &signature),
+               Ok(()));
+    let mut other_digest = digest;
+    other_digest[0] ^= 1;
+    assert_eq!(verify::verify_prehashed(verify::SignatureAlgorithm::PS256, RSA_2048_SPKI,
+                                        &other_digest, &signature),
+               Err(verify::VerifyError::SignatureVerificationFailed));
+    assert_eq!(verify::verify_prehashed(verify::SignatureAlgorithm::PS256, RSA_2048_SPKI, &[0; 48],
+                                        &signature),
+               Err(verify::VerifyError::InvalidDigestLength));
+}
+
+#[test]
+fn test_ecdsa_signature_formats() {
+    setup();
+    let key = verify::CoseKey::from_spki(SIGNER1_SPKI).unwrap();
+    let signature = verify::sign(verify::SignatureAlgorithm::ES256,
+                                 &verify::SigningKey::EC2 { key: &key, d: &[0x11; 32] },
+                                 b"firmware image").unwrap();
+    assert_eq!(signature.len(), 64);
+    let der_signature = verify::ecdsa_signature_to_der(verify::Curve::P256, &signature).unwrap();
+    assert_eq!(der_signature[0], 0x30);
+    assert_eq!(verify::verify_signature(verify::SignatureAlgorithm::ES256, SIGNER1_SPKI,
+                                        b"firmware image", &der_signature),
+               Err(verify::VerifyError::InvalidSignatureLength));
+    let raw_signature = verify::ecdsa_signature_from_der(verify::Curve::P256, &der_signature)
+        .unwrap();
+    assert_eq!(raw_signature, signature);
+    assert_eq!(verify::verify_signature(verify::SignatureAlgorithm::ES256, SIGNER1_SPKI,
+                                        b"firmware image", &raw_signature),
+               Ok(()));
+    assert_eq!(verify::verify_signature(verify::SignatureAlgorithm::ES256, SIGNER1_SPKI,
+                                        b"firmware image", &raw_signature[..63]),
+               Err(verify::VerifyError::InvalidSignatureLength));
+}
+
+#[test]
+fn test_public_key() {
+    setup();
+    let key = verify::CoseKey::from_spki(SIGNER1_SPKI).unwrap();
+    let signing_key = verify::SigningKey::EC2 { key: &key, d: &[0x11; 32] };
+    let messages: Vec<Vec<u8>> = (0..8u8).map(|i| vec![i; 100]).collect();
+    let signatures: Vec<Vec<u8>> = messages.iter().map(|message| {
+        verify::sign(verify::SignatureAlgorithm::ES256, &signing_key, message).unwrap()
+    }).collect();
+
+    let public_key = Arc::new(verify::PublicKey::from_spki(SIGNER1_SPKI).unwrap());
+    let threads: Vec<_> = (0..4).map(|_| {
+        let public_key = public_key.clone();
+        let messages = messages.clone();
+        let signatures = signatures.clone();
+        thread::spawn(move || {
+            for (message, signature) in messages.iter().zip(signatures.iter()) {
+                assert_eq!(public_key.verify(verify::SignatureAlgorithm::ES256, message,
+                                             signature),
+                           Ok(()));
+            }
+        })
+    }).collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(public_key.verify(verify::SignatureAlgorithm::ES256, &messages[0], &signatures[1]),
+               Err(verify::VerifyError::SignatureVerificationFailed));
+
+    // RSA keys are imported into the best slot for RSA-PSS rather than for ECDSA.
+    let rsa_signature = verify::sign(verify::SignatureAlgorithm::PS256,
+                                     &verify::SigningKey::PrivateKeyInfo(RSA_2048_PKCS8),
+                                     &messages[0]).unwrap();
+    let rsa_public_key = Arc::new(verify::PublicKey::from_spki(RSA_2048_SPKI).unwrap());
+    let threads: Vec<_> = (0..4).map(|_| {
+        let rsa_public_key = rsa_public_key.clone();
+        let message = messages[0].clone();
+        let rsa_signature = rsa_signature.clone();
+        thread::spawn(move || {
+            for _ in 0..4 {
+                assert_eq!(rsa_public_key.verify(verify::SignatureAlgorithm::PS256, &message,
+                                                 &rsa_signature),
+                           Ok(()));
+            }
+        })
+    }).collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let mut restricted_key = key.clone();
+    restricted_key.alg = Some(-7);
+    let public_key = verify::PublicKey::from_cose_key(&restricted_key).unwrap();
+    assert_eq!(public_key.verify(verify::SignatureAlgorithm::ES256, &messages[0], &signatures[0]),
+               Ok(()));
+    assert_eq!(public_key.verify(verify::SignatureAlgorithm::PS256, &messages[0], &signatures[0]),
+               Err(verify::VerifyError::AlgorithmMismatch));
+    restricted_key.key_ops = vec![verify::KeyOperation::Sign];
+    assert_eq!(verify::PublicKey::from_cose_key(&restricted_key).err(),
+               Some(verify::VerifyError::InvalidKey));
+    assert_eq!(verify::PublicKey::from_spki(&[0x30, 0x00]).err(),
+               Some(verify::VerifyError::DecodingSPKIFailed));
+}
+
+static P384_SPKI: &[u8] = &[
+    0x30, 0x76, 0x30, 0x10, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x05, 0x2b,
+    0x81, 0x04, 0x00, 0x22, 0x03, 0x62, 0x00, 0x04, 0x1a, 0xee, 0x48, 0xc2, 0xf0, 0xf4, 0x67, 0x8b,
+    0x94, 0x2a, 0x66, 0xcc, 0xd2, 0x38, 0x5e, 0xdb, 0xd1, 0x83, 0x1e, 0x58, 0x72, 0x2e, 0x5d, 0x97,
+    0x4a, 0xa2, 0x56, 0x94, 0x89, 0xb3, 0xa7, 0x55, 0x99, 0x74, 0x10, 0x28, 0xcd, 0x11, 0x41, 0xc4,
+    0x26, 0x6e, 0x97, 0x78, 0xdf, 0x65, 0xdf, 0x0f, 0xd7, 0x08, 0xb8, 0x91, 0xd6, 0x89, 0x45, 0x3b,
+    0x9f, 0x0d, 0x45, 0xbf, 0x1d, 0xc6, 0x47, 0x13, 0x8b, 0x1f, 0x34, 0x92, 0x3b, 0x12, 0x9e, 0x3f,
+    0x47, 0x66, 0xb3, 0xb5, 0x87, 0x44, 0x40, 0xee, 0x54, 0x76, 0x7d, 0x26, 0xbd, 0xb9, 0x94, 0xc5,
+    0xfd, 0x0f, 0x88, 0x50, 0xa1, 0x0f, 0x65, 0x02
+];
+
+static RSA_1024_SPKI: &[u8] = &[
+    0x30, 0x81, 0x9f, 0x30, 0x0d, 0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01,
+    0x05, 0x00, 0x03, 0x81, 0x8d, 0x00, 0x30, 0x81, 0x89, 0x02, 0x81, 0x81, 0x00, 0xf4, 0x72, 0x8f,
+    0x8e, 0x23, 0x34, 0x75, 0xb0, 0x56, 0xd6, 0xd9, 0xa8, 0x93, 0x93, 0x82, 0xf8, 0x49, 0x88, 0x2a,
+    0x1b, 0xe0, 0x81, 0xa4, 0x31, 0x1c, 0x43, 0xed, 0x79, 0x5e, 0xa3, 0x17, 0x10, 0x47, 0x58, 0x78,
+    0x1e, 0xcc, 0x46, 0x8f, 0x82, 0x17, 0xd3, 0x53, 0x78, 0xa3, 0x08, 0xf8, 0x83, 0xcf, 0xa1, 0xbf,
+    0x87, 0x70, 0xf1, 0x0b, 0x71, 0x2d, 0x93, 0x09, 0x67, 0x6e, 0x94, 0xa7, 0xb8, 0xe8, 0x5a, 0x72,
+    0xfd, 0xfd, 0xb2, 0x34, 0x43, 0x68, 0xde, 0x7e, 0x0b, 0x5c, 0xd1, 0xfa, 0xca, 0x8b, 0x0d, 0x6f,
+    0x6a, 0xa9, 0xfa, 0x55, 0x5b, 0x63, 0x10, 0x3f, 0x0a, 0x84, 0xb7, 0x4b, 0xf6, 0xc2, 0xb5, 0xde,
+    0xbd, 0xc7, 0xe2, 0xcd, 0x32, 0xa1, 0xa7, 0xd2, 0xe0, 0x7f, 0x59, 0x7d, 0xcf, 0xec, 0x33, 0xe1,
+    0x01, 0xef, 0xca, 0x80, 0xe0, 0xd3, 0xe0, 0xd7, 0x40, 0xf8, 0xd5, 0x1c, 0x33, 0x02, 0x03, 0x01,
+    0x00, 0x01
+];
+
+#[test]
+fn test_key_algorithm_compatibility() {
+    setup();
+    let key = verify::CoseKey::from_spki(SIGNER1_SPKI).unwrap();
+    let es256_signature = verify::sign(verify::SignatureAlgorithm::ES256,
+                                       &verify::SigningKey::EC2 { key: &key, d: &[0x11; 32] },
+                                       b"payload").unwrap();
+    let ps256_signature = verify::sign(verify::SignatureAlgorithm::PS256,
+                                       &verify::SigningKey::PrivateKeyInfo(RSA_2048_PKCS8),
+                                       b"payload").unwrap();
+    assert_eq!(verify::verify_signature(verify::SignatureAlgorithm::ES256, RSA_2048_SPKI,
+                                        b"payload", &es256_signature),
+               Err(verify::VerifyError::KeyTypeMismatch));
+    assert_eq!(verify::verify_signature(verify::SignatureAlgorithm::PS256, SIGNER1_SPKI,
+                                        b"payload", &ps256_signature),
+               Err(verify::VerifyError::KeyTypeMismatch));
+    assert_eq!(verify::verify_signature(verify::SignatureAlgorithm::ES256, P384_SPKI, b"payload",
+                                        &es256_signature),
+               Err(verify::VerifyError::CurveMismatch));
+    assert_eq!(verify::verify_signature(verify::SignatureAlgorithm::PS256, RSA_1024_SPKI,
+                                        b"payload", &ps256_signature[..128]),
+               Err(verify::VerifyError::KeyTooSmall));
+    assert_eq!(verify::verify_signature(verify::SignatureAlgorithm::PS256, RSA_2048_SPKI,
+                                        b"payload", &ps256_signature),
+               Ok(()));
+
+    let public_key = verify::PublicKey::from_spki(RSA_1024_SPKI).unwrap();
+    assert_eq!(public_key.verify(verify::SignatureAlgorithm::PS256, b"payload",
+                                 &ps256_signature[..128]),
+               Err(verify::VerifyError::KeyTooSmall));
+    assert_eq!(public_key.verify(verify::SignatureAlgorithm::ES256, b"payload", &es256_signature),
+               Err(verify::VerifyError::KeyTypeMismatch));
+}
+
+// A different 1024-bit RSA key, for signing.
+static RSA_1024_PKCS8: &[u8] = &[
+    0x30, 0x82, 0x02, 0x76, 0x02, 0x01, 0x00, 0x30, 0x0d, 0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7,
+    0x0d, 0x01, 0x01, 0x01, 0x05, 0x00, 0x04, 0x82, 0x02, 0x60, 0x30, 0x82, 0x02, 0x5c, 0x02, 0x01,
+    0x00, 0x02, 0x81, 0x81, 0x00, 0xb4, 0x88, 0x5f, 0x35, 0x13, 0xb6, 0xa7, 0xaa, 0x2f, 0x3a, 0x21,
+    0x9b, 0x8d, 0xf6, 0xe5, 0x6a, 0x6e, 0x44, 0x7d, 0xc4, 0x21, 0x4c, 0x54, 0x22, 0xb3, 0xad, 0x21,
+    0x6b, 0x18, 0x9a, 0xf0, 0xb3, 0x7d, 0xd8, 0x9f, 0xb0, 0x63, 0x3c, 0x36, 0xd8, 0x59, 0x77, 0x1f,
+    0xf5, 0x1e, 0xdc, 0x4e, 0x88, 0x0c, 0x04, 0x99, 0xbd, 0x68, 0x2b, 0x80, 0x97, 0x2e, 0x78, 0x4d,
+    0x32, 0xd6, 0x0c, 0xa1, 0xa6, 0xdc, 0x43, 0x4b, 0x77, 0xda, 0x2d, 0xbf, 0x70, 0xc6, 0x73, 0x53,
+    0x32, 0xf0, 0xaf, 0xf2, 0x23, 0x11, 0x83, 0x0b, 0xf1, 0x16, 0x61, 0x14, 0x1f, 0x23, 0xfb, 0xb3,
+    0x69, 0x27, 0xd5, 0x79, 0x30, 0xc4, 0xcb, 0x5d, 0xea, 0xc6, 0x77, 0x1b, 0x18, 0x15, 0xa5, 0x26,
+    0xd1, 0x2b, 0x19, 0x13, 0x39, 0xf9, 0x0d, 0x13, 0x75, 0x51, 0x69, 0x0e, 0x3b, 0xb3, 0x8f, 0x71,
+    0xe6, 0x09, 0xcc, 0xc4, 0x3f, 0x02, 0x03, 0x01, 0x00, 0x01, 0x02, 0x81, 0x80, 0x7e, 0x87, 0xba,
+    0x1a, 0xec, 0x47, 0x2f, 0xba, 0x32, 0xc1, 0x8e, 0x7e, 0x85, 0x00, 0x8f, 0x88, 0x89, 0xe2, 0x4d,
+    0x37, 0x62, 0x93, 0x29, 0xc0, 0xec, 0xa7, 0xa2, 0x6d, 0x7a, 0x60, 0x55, 0xe5, 0xa7, 0x85, 0x65,
+    0x5e, 0xee, 0x1e, 0x6c, 0xde, 0x33, 0xce, 0x2f, 0x42, 0xd0, 0xaf, 0x5e, 0x07, 0x32, 0x4d, 0x23,
+    0x27, 0xc2, 0x54, 0x03, 0x1b, 0x72, 0xc1, 0x74, 0x71, 0xf5, 0xde, 0x02, 0x89, 0x4f, 0x3d, 0x62,
+    0x60, 0xd5, 0x7e, 0x55, 0xb2, 0xd2, 0x5b, 0xc3, 0x01, 0x88, 0x66, 0x6e, 0xd1, 0xda, 0x32, 0xbf,
+    0xc8, 0xf2, 0x39, 0x56, 0x31, 0x36, 0x06, 0x4d, 0x52, 0x10, 0x45, 0x69, 0xcd, 0xf6, 0x70, 0x83,
+    0x5f, 0xe2, 0xcd, 0x33, 0x8a, 0x44, 0x78, 0xac, 0xc9, 0xb2, 0xb4, 0x5e, 0x0c, 0x74, 0xf4, 0x6a,
+    0x23, 0x96, 0x45, 0x3d, 0xf2, 0x3d, 0x0d, 0x16, 0x02, 0x06, 0xa4, 0xd7, 0x31, 0x02, 0x41, 0x00,
+    0xe8, 0x8d, 0x84, 0x8c, 0x46, 0x79, 0xdc, 0x7c, 0xcd, 0xc0, 0x2d, 0x57, 0xa5, 0xe8, 0x97, 0x47,
+    0xe7, 0x19, 0x39, 0xf1, 0x0a, 0x3b, 0x6d, 0xd1, 0x53, 0xbf, 0xfa, 0x14, 0xc2, 0x44, 0xaf, 0xfd,
+    0x54, 0xd4, 0xf6, 0xf1, 0x5d, 0x6f, 0x53, 0xfc, 0x3f, 0x64, 0xfa, 0xbb, 0x0a, 0x92, 0x2b, 0x49,
+    0xc7, 0x07, 0x69, 0xe9, 0xe6, 0x39, 0xc6, 0xe7, 0x43, 0xa3, 0xc9, 0xa7, 0x36, 0xa0, 0xa6, 0x27,
+    0x02, 0x41, 0x00, 0xc6, 0xbc, 0x26, 0x44, 0x82, 0xea, 0x34, 0x04, 0x2c, 0x3f, 0xf9, 0x0e, 0x76,
+    0xd6, 0x0c, 0xf1, 0xe3, 0x58, 0x2b, 0x6e, 0x7e, 0x82, 0xa4, 0xdb, 0x7f, 0x5d, 0xee, 0x5f, 0xe8,
+    0x6f, 0x9b, 0x82, 0x35, 0xa2, 0x89, 0x25, 0xd1, 0x50, 0x04, 0x76, 0x85, 0x45, 0x3b, 0x3a, 0x23,
+    0xa2, 0xf3, 0xd0, 0xe6, 0xae, 0xb7, 0xbd, 0x68, 0xc2, 0xe0, 0x45, 0x47, 0x19, 0x80, 0xcd, 0x63,
+    0x1d, 0x98, 0x29, 0x02, 0x41, 0x00, 0xc5, 0x62, 0x68, 0xe0, 0xcf, 0x68, 0xdb, 0xb6, 0xaa, 0xc4,
+    0xb5, 0x37, 0xb1, 0xc0, 0x6b, 0xb7, 0x41, 0xf5, 0xed, 0x72, 0x23, 0xe7, 0x31, 0x5c, 0xfd, 0x22,
+    0xf3, 0x56, 0xef, 0x55, 0x71, 0xb3, 0x2a, 0x6f, 0xe0, 0x72, 0x19, 0x86, 0x46, 0xd8, 0xd1, 0xce,
+    0x19, 0xea, 0xec, 0x1c, 0x08, 0xd6, 0x04, 0x32, 0xcc, 0xb9, 0x15, 0x0a, 0x71, 0x43, 0x15, 0xec,
+    0x4d, 0x5a, 0xba, 0x49, 0x3c, 0xfd, 0x02, 0x40, 0x5d, 0x41, 0x72, 0xef, 0x99, 0x43, 0x0f, 0x4f,
+    0x99, 0xf8, 0x17, 0x05, 0xcb, 0x8b, 0xdc, 0x1d, 0x31, 0xa2, 0x71, 0x16, 0xf3, 0x74, 0x98, 0xa9,
+    0x8b, 0x55, 0xe7, 0x82, 0xc7, 0xce, 0x7f, 0x79, 0x95, 0x73, 0x84, 0xc9, 0xe3, 0x33, 0x9a, 0x34,
+    0x87, 0x8b, 0x1a, 0x24, 0xc9, 0x0e, 0x31, 0x3d, 0x28, 0xd2, 0x79, 0xd3, 0xfe, 0xb1, 0xdb, 0x8d,
+    0x86, 0xb4, 0x97, 0xf5, 0x48, 0xfa, 0x5d, 0x29, 0x02, 0x40, 0x2a, 0x29, 0x6c, 0xb3, 0xaf, 0xd8,
+    0x63, 0x43, 0x9e, 0x5a, 0x53, 0x52, 0xb8, 0xc7, 0xe7, 0x02, 0x5c, 0xf8, 0x79, 0x4e, 0x1b, 0x82,
+    0x02, 0x26, 0x40, 0x03, 0xef, 0xdf, 0x04, 0x1c, 0xc5, 0x57, 0xb6, 0x5b, 0x76, 0x82, 0x89, 0xac,
+    0x36, 0xa5, 0x11, 0x2d, 0xa8, 0x6d, 0x89, 0x3b, 0x93, 0xf4, 0x9d, 0xb2, 0x56, 0xc9, 0x7d, 0x33,
+    0x1a, 0xb1, 0x8b, 0x1f, 0xe7, 0x8d, 0x28, 0x8b, 0xcb, 0xde
+];
+
+#[test]
+fn test_sign_key_algorithm_compatibility() {
+    setup();
+    let p384_key = verify::CoseKey::from_spki(P384_SPKI).unwrap();
+    assert_eq!(verify::sign(verify::SignatureAlgorithm::ES256,
+                            &verify::SigningKey::EC2 { key: &p384_key, d: &[0x11; 48] },
+                            b"payload"),
+               Err(verify::VerifyError::CurveMismatch));
+    assert_eq!(verify::sign(verify::SignatureAlgorithm::PS256,
+                            &verify::SigningKey::PrivateKeyInfo(RSA_1024_PKCS8), b"payload"),
+               Err(verify::VerifyError::KeyTooSmall));
+    assert_eq!(verify::sign(verify::SignatureAlgorithm::ES256,
+                            &verify::SigningKey::PrivateKeyInfo(RSA_2048_PKCS8), b"payload"),
+               Err(verify::VerifyError::KeyTypeMismatch));
+    assert_eq!(verify::sign(verify::SignatureAlgorithm::PS256,
+                            &verify::SigningKey::PrivateKeyInfo(SIGNER2_PKCS8), b"payload"),
+               Err(verify::VerifyError::KeyTypeMismatch));
+}
+
+#[test]
+fn test_rsa_spki_forms() {
+    setup();
+    let signature = verify::sign(verify::SignatureAlgorithm::PS256,
+                                 &verify::SigningKey::PrivateKeyInfo(RSA_2048_PKCS8),
+                                 b"payload").unwrap();
+    // The same key with the id-RSASSA-PSS algorithm (RFC 4055) and no parameters, and with the
+    // rsaEncryption algorithm but no NULL parameters.
+    let subject_public_key = &RSA_2048_SPKI[19..];
+    let mut pss_spki = vec![0x30, 0x82, 0x01, 0x20, 0x30, 0x0b, 0x06, 0x09, 0x2a, 0x86, 0x48, 0x86,
+                            0xf7, 0x0d, 0x01, 0x01, 0x0a];
+    pss_spki.extend_from_slice(subject_public_key);
+    let mut no_null_spki = vec![0x30, 0x82, 0x01, 0x20, 0x30, 0x0b, 0x06, 0x09, 0x2a, 0x86, 0x48,
+                                0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+    no_null_spki.extend_from_slice(subject_public_key);
+    for spki in &[pss_spki, no_null_spki] {
+        assert_eq!(verify::verify_signature(verify::SignatureAlgorithm::PS256, spki, b"payload",
+                                            &signature),
+                   Ok(()));
+        assert_eq!(verify::verify_signature(verify::SignatureAlgorithm::PS256, spki, b"other",
+                                            &signature),
+                   Err(verify::VerifyError::SignatureVerificationFailed));
+        assert_eq!(verify::verify_signature(verify::SignatureAlgorithm::ES256, spki, b"payload",
+                                            &signature[..64]),
+                   Err(verify::VerifyError::KeyTypeMismatch));
+    }
+}